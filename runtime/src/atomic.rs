@@ -0,0 +1,209 @@
+//! Atomic integers for FORMA runtime
+//!
+//! A `FormaAtomicI64` may be shared between threads through its raw pointer,
+//! like [`crate::sync_map`], but needs no lock. Each atomic carries the memory
+//! ordering its operations use, `FORMA_ORDERING_SEQ_CST` unless created with
+//! [`forma_atomic_new_with_ordering`].
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// No ordering constraints, only atomicity
+pub const FORMA_ORDERING_RELAXED: i64 = 0;
+/// Loads acquire, stores release
+pub const FORMA_ORDERING_ACQ_REL: i64 = 1;
+/// Acquire/release plus a single total order of all operations (the default)
+pub const FORMA_ORDERING_SEQ_CST: i64 = 2;
+
+/// Internal representation of a FORMA atomic integer.
+pub struct FormaAtomicI64 {
+    value: AtomicI64,
+    ordering: Ordering,
+}
+
+impl FormaAtomicI64 {
+    /// Ordering for operations that only read
+    fn load_ordering(&self) -> Ordering {
+        match self.ordering {
+            Ordering::AcqRel => Ordering::Acquire,
+            ordering => ordering,
+        }
+    }
+
+    /// Ordering for operations that only write
+    fn store_ordering(&self) -> Ordering {
+        match self.ordering {
+            Ordering::AcqRel => Ordering::Release,
+            ordering => ordering,
+        }
+    }
+}
+
+/// Create an atomic integer holding `value`, with sequentially consistent
+/// operations.
+#[no_mangle]
+pub extern "C" fn forma_atomic_new(value: i64) -> *mut FormaAtomicI64 {
+    forma_atomic_new_with_ordering(value, FORMA_ORDERING_SEQ_CST)
+}
+
+/// Create an atomic integer holding `value` whose operations use `ordering`,
+/// one of the `FORMA_ORDERING_*` constants. Unknown orderings fall back to
+/// `FORMA_ORDERING_SEQ_CST`.
+#[no_mangle]
+pub extern "C" fn forma_atomic_new_with_ordering(value: i64, ordering: i64) -> *mut FormaAtomicI64 {
+    let ordering = match ordering {
+        FORMA_ORDERING_RELAXED => Ordering::Relaxed,
+        FORMA_ORDERING_ACQ_REL => Ordering::AcqRel,
+        _ => Ordering::SeqCst,
+    };
+    Box::into_raw(Box::new(FormaAtomicI64 {
+        value: AtomicI64::new(value),
+        ordering,
+    }))
+}
+
+/// Read the current value. Returns 0 if the atomic is null.
+#[no_mangle]
+pub extern "C" fn forma_atomic_load(a: *const FormaAtomicI64) -> i64 {
+    if a.is_null() {
+        return 0;
+    }
+    let a = unsafe { &*a };
+    a.value.load(a.load_ordering())
+}
+
+/// Replace the current value.
+#[no_mangle]
+pub extern "C" fn forma_atomic_store(a: *const FormaAtomicI64, value: i64) {
+    if a.is_null() {
+        return;
+    }
+    let a = unsafe { &*a };
+    a.value.store(value, a.store_ordering());
+}
+
+/// Add `delta`, wrapping on overflow, and return the previous value.
+/// Returns 0 if the atomic is null.
+#[no_mangle]
+pub extern "C" fn forma_atomic_fetch_add(a: *const FormaAtomicI64, delta: i64) -> i64 {
+    if a.is_null() {
+        return 0;
+    }
+    let a = unsafe { &*a };
+    a.value.fetch_add(delta, a.ordering)
+}
+
+/// Set the value to `new` if it is currently `expected`.
+///
+/// Returns true if the value was replaced. Unless `current` is null, the
+/// value found (equal to `expected` on success) is written to it. Returns
+/// false if the atomic is null.
+#[no_mangle]
+pub extern "C" fn forma_atomic_compare_exchange(
+    a: *const FormaAtomicI64,
+    expected: i64,
+    new: i64,
+    current: *mut i64,
+) -> bool {
+    if a.is_null() {
+        return false;
+    }
+    let a = unsafe { &*a };
+    let result = a
+        .value
+        .compare_exchange(expected, new, a.ordering, a.load_ordering());
+    if !current.is_null() {
+        unsafe { *current = result.unwrap_or_else(|found| found) };
+    }
+    result.is_ok()
+}
+
+/// Free the atomic. No other thread may still be using it.
+#[no_mangle]
+pub extern "C" fn forma_atomic_free(a: *mut FormaAtomicI64) {
+    if a.is_null() {
+        return;
+    }
+    unsafe {
+        drop(Box::from_raw(a));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_fetch_add() {
+        const THREADS: i64 = 8;
+        const PER_THREAD: i64 = 1000;
+
+        for ordering in [
+            FORMA_ORDERING_RELAXED,
+            FORMA_ORDERING_ACQ_REL,
+            FORMA_ORDERING_SEQ_CST,
+        ] {
+            let a = forma_atomic_new_with_ordering(0, ordering);
+            // Raw pointers are not Send; pass the address as the FFI caller would
+            let addr = a as usize;
+            let workers: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    thread::spawn(move || {
+                        for _ in 0..PER_THREAD {
+                            forma_atomic_fetch_add(addr as *const FormaAtomicI64, 1);
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            assert_eq!(forma_atomic_load(a), THREADS * PER_THREAD);
+            forma_atomic_free(a);
+        }
+    }
+
+    #[test]
+    fn test_compare_exchange() {
+        let a = forma_atomic_new(5);
+        let mut current = 0;
+
+        assert!(!forma_atomic_compare_exchange(a, 4, 10, &mut current));
+        assert_eq!(current, 5);
+        assert_eq!(forma_atomic_load(a), 5);
+
+        assert!(forma_atomic_compare_exchange(a, 5, 10, &mut current));
+        assert_eq!(current, 5);
+        assert_eq!(forma_atomic_load(a), 10);
+
+        assert!(forma_atomic_compare_exchange(a, 10, 11, ptr::null_mut()));
+        assert_eq!(forma_atomic_load(a), 11);
+        forma_atomic_free(a);
+    }
+
+    #[test]
+    fn test_load_store_and_wrapping_add() {
+        let a = forma_atomic_new(0);
+        forma_atomic_store(a, i64::MAX);
+        assert_eq!(forma_atomic_fetch_add(a, 1), i64::MAX);
+        assert_eq!(forma_atomic_load(a), i64::MIN);
+        forma_atomic_free(a);
+    }
+
+    #[test]
+    fn test_null_safety() {
+        assert_eq!(forma_atomic_load(ptr::null()), 0);
+        forma_atomic_store(ptr::null(), 1);
+        assert_eq!(forma_atomic_fetch_add(ptr::null(), 1), 0);
+        let mut current = 7;
+        assert!(!forma_atomic_compare_exchange(
+            ptr::null(),
+            0,
+            1,
+            &mut current
+        ));
+        assert_eq!(current, 7);
+        forma_atomic_free(ptr::null_mut()); // should not crash
+    }
+}
@@ -0,0 +1,103 @@
+//! Byte string runtime support for FORMA
+//!
+//! A `Bytes` value is a pointer to a length-prefixed buffer: an `i64` byte
+//! count followed by the bytes themselves. `b"..."` literals are emitted by the
+//! compiler as constant globals in this layout; [`forma_bytes_new`] builds the
+//! same layout on the heap.
+
+use std::alloc::{self, Layout};
+use std::mem;
+use std::ptr;
+
+/// Header of a FORMA byte string. The bytes follow `len` directly.
+#[repr(C)]
+pub struct FormaBytes {
+    len: i64,
+    data: [u8; 0],
+}
+
+impl FormaBytes {
+    fn layout_for(len: usize) -> Layout {
+        Layout::from_size_align(
+            mem::size_of::<FormaBytes>() + len,
+            mem::align_of::<FormaBytes>(),
+        )
+        .unwrap()
+    }
+}
+
+/// Copy `len` bytes from `data` into a new heap byte string.
+#[no_mangle]
+pub extern "C" fn forma_bytes_new(data: *const u8, len: i64) -> *mut FormaBytes {
+    let len = if data.is_null() {
+        0
+    } else {
+        len.max(0) as usize
+    };
+    let layout = FormaBytes::layout_for(len);
+    unsafe {
+        let b = alloc::alloc(layout) as *mut FormaBytes;
+        if b.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        (*b).len = len as i64;
+        if len > 0 {
+            ptr::copy_nonoverlapping(data, ptr::addr_of_mut!((*b).data) as *mut u8, len);
+        }
+        b
+    }
+}
+
+/// Return the number of bytes.
+#[no_mangle]
+pub extern "C" fn forma_bytes_len(b: *const FormaBytes) -> i64 {
+    if b.is_null() {
+        return 0;
+    }
+    unsafe { (*b).len }
+}
+
+/// Return the byte at `idx`, panicking if it is out of bounds.
+#[no_mangle]
+pub extern "C" fn forma_bytes_get(b: *const FormaBytes, idx: i64) -> u8 {
+    crate::forma_bounds_check(idx, forma_bytes_len(b));
+    unsafe { *(ptr::addr_of!((*b).data) as *const u8).add(idx as usize) }
+}
+
+/// Free a byte string created by [`forma_bytes_new`]. Literals are constant
+/// globals and must not be passed here.
+#[no_mangle]
+pub extern "C" fn forma_bytes_free(b: *mut FormaBytes) {
+    if b.is_null() {
+        return;
+    }
+    unsafe {
+        let layout = FormaBytes::layout_for((*b).len as usize);
+        alloc::dealloc(b as *mut u8, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_len_get() {
+        let raw = [0x00u8, 0x01, 0xFF];
+        let b = forma_bytes_new(raw.as_ptr(), raw.len() as i64);
+        assert_eq!(forma_bytes_len(b), 3);
+        assert_eq!(forma_bytes_get(b, 0), 0x00);
+        assert_eq!(forma_bytes_get(b, 1), 0x01);
+        assert_eq!(forma_bytes_get(b, 2), 0xFF);
+        forma_bytes_free(b);
+    }
+
+    #[test]
+    fn test_empty_and_null_safety() {
+        let b = forma_bytes_new(ptr::null(), 5);
+        assert_eq!(forma_bytes_len(b), 0);
+        forma_bytes_free(b);
+        assert_eq!(forma_bytes_len(ptr::null()), 0);
+        forma_bytes_free(ptr::null_mut()); // should not crash
+    }
+}
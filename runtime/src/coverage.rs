@@ -0,0 +1,73 @@
+//! Block coverage reporting for FORMA runtime
+//!
+//! Programs built with `forma build --coverage` keep one counter per MIR basic
+//! block and call [`forma_coverage_dump`] when `main` returns. The report has
+//! one `<function> bb_<N> <count>` line per block, the same format that
+//! `forma run --coverage` writes from the interpreter.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// Environment variable naming the report file.
+pub const COVERAGE_FILE_ENV: &str = "FORMA_COVERAGE_FILE";
+
+/// Report file used when `FORMA_COVERAGE_FILE` is unset.
+pub const DEFAULT_COVERAGE_FILE: &str = "forma.coverage";
+
+/// Render `count` counters and their block names as report lines.
+unsafe fn format_report(counters: *const i64, names: *const *const c_char, count: i64) -> String {
+    let mut out = String::new();
+    if counters.is_null() || names.is_null() {
+        return out;
+    }
+    for i in 0..count.max(0) as usize {
+        let name = *names.add(i);
+        if name.is_null() {
+            continue;
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+        out.push_str(&format!("{} {}\n", name, *counters.add(i)));
+    }
+    out
+}
+
+/// Write the block hit counts to `$FORMA_COVERAGE_FILE` (default
+/// `forma.coverage`). `names[i]` is the `"<function> bb_<N>"` label of
+/// `counters[i]`.
+#[no_mangle]
+pub unsafe extern "C" fn forma_coverage_dump(
+    counters: *const i64,
+    names: *const *const c_char,
+    count: i64,
+) {
+    let report = format_report(counters, names, count);
+    let path =
+        std::env::var(COVERAGE_FILE_ENV).unwrap_or_else(|_| DEFAULT_COVERAGE_FILE.to_string());
+    if let Err(e) = std::fs::write(&path, report) {
+        eprintln!("warning: could not write coverage to '{}': {}", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::ptr;
+
+    #[test]
+    fn test_format_report() {
+        let labels = [
+            CString::new("main bb_0").unwrap(),
+            CString::new("main bb_1").unwrap(),
+        ];
+        let names: Vec<*const c_char> = labels.iter().map(|s| s.as_ptr()).collect();
+        let counters = [3i64, 0];
+        let report = unsafe { format_report(counters.as_ptr(), names.as_ptr(), 2) };
+        assert_eq!(report, "main bb_0 3\nmain bb_1 0\n");
+    }
+
+    #[test]
+    fn test_format_report_null_safety() {
+        assert_eq!(unsafe { format_report(ptr::null(), ptr::null(), 4) }, "");
+    }
+}
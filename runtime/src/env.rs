@@ -1,6 +1,7 @@
 //! Environment and command-line argument access for FORMA runtime
 
-use std::ffi::{CStr, CString};
+use crate::string::into_owned_c_str;
+use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::ptr;
 
@@ -19,7 +20,7 @@ pub extern "C" fn forma_args_get(idx: i64) -> *mut c_char {
         return ptr::null_mut();
     }
     match std::env::args().nth(idx as usize) {
-        Some(arg) => CString::new(arg).unwrap_or_default().into_raw(),
+        Some(arg) => into_owned_c_str(&arg),
         None => ptr::null_mut(),
     }
 }
@@ -35,7 +36,7 @@ pub extern "C" fn forma_env_get(name: *const c_char) -> *mut c_char {
     unsafe {
         let name_str = CStr::from_ptr(name).to_string_lossy();
         match std::env::var(name_str.as_ref()) {
-            Ok(val) => CString::new(val).unwrap_or_default().into_raw(),
+            Ok(val) => into_owned_c_str(&val),
             Err(_) => ptr::null_mut(),
         }
     }
@@ -57,6 +58,7 @@ pub extern "C" fn forma_env_set(name: *const c_char, value: *const c_char) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ffi::CString;
 
     #[test]
     fn test_args_count_positive() {
@@ -77,4 +79,23 @@ mod tests {
     fn test_env_get_null_safety() {
         assert!(forma_env_get(ptr::null()).is_null());
     }
+
+    #[test]
+    fn test_env_set_then_get() {
+        let name = CString::new("FORMA_RUNTIME_TEST_VAR").unwrap();
+        let value = CString::new("forma").unwrap();
+        forma_env_set(name.as_ptr(), value.as_ptr());
+        let got = forma_env_get(name.as_ptr());
+        assert!(!got.is_null());
+        unsafe {
+            assert_eq!(CStr::from_ptr(got).to_str().unwrap(), "forma");
+            crate::string::forma_str_free(got);
+        }
+    }
+
+    #[test]
+    fn test_env_get_unset_is_null() {
+        let name = CString::new("FORMA_RUNTIME_TEST_UNSET_VAR").unwrap();
+        assert!(forma_env_get(name.as_ptr()).is_null());
+    }
 }
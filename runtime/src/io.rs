@@ -73,6 +73,95 @@ pub extern "C" fn forma_println_bool(b: bool) {
     println!("{}", b);
 }
 
+/// Argument tags for `forma_print_fmt`
+pub const FMT_ARG_INT: i64 = 0;
+pub const FMT_ARG_FLOAT: i64 = 1;
+pub const FMT_ARG_BOOL: i64 = 2;
+pub const FMT_ARG_STR: i64 = 3;
+
+/// The 64-bit payload of a `forma_print_fmt` argument
+/// Strings are passed as a pointer rather than an integer so they keep their provenance
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union FormaFmtValue {
+    /// Int and Bool values, or a Float's bit pattern
+    pub word: i64,
+    /// A string, for `FMT_ARG_STR`
+    pub ptr: *const c_char,
+}
+
+/// A single `forma_print_fmt` argument: a tag and its payload
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FormaFmtArg {
+    pub tag: i64,
+    pub value: FormaFmtValue,
+}
+
+/// Format a single argument according to its tag
+fn format_fmt_arg(arg: &FormaFmtArg) -> String {
+    unsafe {
+        match arg.tag {
+            FMT_ARG_INT => arg.value.word.to_string(),
+            FMT_ARG_FLOAT => f64::from_bits(arg.value.word as u64).to_string(),
+            FMT_ARG_BOOL => (arg.value.word != 0).to_string(),
+            FMT_ARG_STR => {
+                let ptr = arg.value.ptr;
+                if ptr.is_null() {
+                    return String::new();
+                }
+                CStr::from_ptr(ptr).to_string_lossy().into_owned()
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// Replace each `{}` in `fmt` with the next argument; `{{` and `}}` are literal braces
+fn format_with_args(fmt: &str, args: &[FormaFmtArg]) -> String {
+    let mut out = String::with_capacity(fmt.len());
+    let mut next = args.iter();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' if chars.peek() == Some(&'}') => {
+                chars.next();
+                if let Some(arg) = next.next() {
+                    out.push_str(&format_fmt_arg(arg));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Print a format string with its arguments to stdout with newline
+#[no_mangle]
+pub extern "C" fn forma_print_fmt(fmt: *const c_char, args: *const FormaFmtArg, count: size_t) {
+    if fmt.is_null() {
+        println!();
+        return;
+    }
+    unsafe {
+        let fmt = CStr::from_ptr(fmt).to_string_lossy();
+        let args = if args.is_null() || count == 0 {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(args, count)
+        };
+        println!("{}", format_with_args(&fmt, args));
+    }
+}
+
 /// Read a line from stdin
 /// Returns a heap-allocated null-terminated string that must be freed with forma_str_free
 #[no_mangle]
@@ -90,17 +179,7 @@ pub extern "C" fn forma_read_line() -> *mut c_char {
                 }
             }
 
-            // Allocate and copy to C string
-            let len = line.len();
-            unsafe {
-                let ptr = libc::malloc(len + 1) as *mut c_char;
-                if ptr.is_null() {
-                    return std::ptr::null_mut();
-                }
-                std::ptr::copy_nonoverlapping(line.as_ptr(), ptr as *mut u8, len);
-                *ptr.add(len) = 0; // null terminator
-                ptr
-            }
+            crate::string::into_owned_c_str(&line)
         }
         Err(_) => std::ptr::null_mut(),
     }
@@ -165,6 +244,27 @@ mod tests {
         forma_println_int(-1);
     }
 
+    #[test]
+    fn test_format_with_args() {
+        let name = std::ffi::CString::new("answer").unwrap();
+        let word = |tag, word| FormaFmtArg { tag, value: FormaFmtValue { word } };
+        let args = [
+            word(FMT_ARG_INT, 42),
+            FormaFmtArg { tag: FMT_ARG_STR, value: FormaFmtValue { ptr: name.as_ptr() } },
+            word(FMT_ARG_FLOAT, 1.5f64.to_bits() as i64),
+            word(FMT_ARG_BOOL, 1),
+        ];
+        assert_eq!(
+            format_with_args("{} is the {} ({}, {}) {{}}", &args),
+            "42 is the answer (1.5, true) {}"
+        );
+    }
+
+    #[test]
+    fn test_print_fmt_null_safety() {
+        forma_print_fmt(std::ptr::null(), std::ptr::null(), 0);
+    }
+
     #[test]
     fn test_write_stdout_null_safety() {
         assert_eq!(forma_write_stdout(std::ptr::null(), 10), -1);
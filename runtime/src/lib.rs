@@ -2,27 +2,42 @@
 //!
 //! This library provides the runtime functions needed by LLVM-compiled FORMA programs.
 //! All functions use the C ABI for compatibility with the generated LLVM IR.
+//!
+//! The unit tests are kept clean under Miri (`cargo +nightly miri test`); tests
+//! that need the real OS, such as reading the clock, are ignored there.
 
 #![allow(clippy::missing_safety_doc)]
 #![allow(clippy::not_unsafe_ptr_arg_deref)] // FFI exports intentionally take raw pointers
 
+pub mod atomic;
+pub mod bytes;
+pub mod coverage;
 pub mod env;
 pub mod io;
 pub mod map;
 pub mod math;
 pub mod memory;
+pub mod net;
 pub mod panic;
 pub mod string;
+pub mod sync_map;
+pub mod thread;
 pub mod time;
 pub mod vec;
 
 // Re-export all public functions at the crate root for convenience
+pub use atomic::*;
+pub use bytes::*;
+pub use coverage::*;
 pub use env::*;
 pub use io::*;
 pub use map::*;
 pub use math::*;
 pub use memory::*;
+pub use net::*;
 pub use panic::*;
 pub use string::*;
+pub use sync_map::*;
+pub use thread::*;
 pub use time::*;
 pub use vec::*;
@@ -1,7 +1,8 @@
 //! String-keyed map runtime support for FORMA
 
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use crate::string::into_owned_c_str;
+use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::ptr;
 
@@ -38,7 +39,7 @@ pub extern "C" fn forma_map_get(m: *const FormaMap, key: *const c_char) -> *mut
     unsafe {
         let key_str = CStr::from_ptr(key).to_string_lossy();
         match (*m).inner.get(key_str.as_ref()) {
-            Some(val) => CString::new(val.as_str()).unwrap_or_default().into_raw(),
+            Some(val) => into_owned_c_str(val),
             None => ptr::null_mut(),
         }
     }
@@ -81,6 +82,173 @@ pub extern "C" fn forma_map_remove(m: *mut FormaMap, key: *const c_char) -> bool
     }
 }
 
+/// Deep-copy the map into a new, independent map (caller must free with forma_map_free).
+/// Returns null if the map is null.
+#[no_mangle]
+pub extern "C" fn forma_map_clone(m: *const FormaMap) -> *mut FormaMap {
+    if m.is_null() {
+        return ptr::null_mut();
+    }
+    let inner = unsafe { (*m).inner.clone() };
+    Box::into_raw(Box::new(FormaMap { inner }))
+}
+
+/// Serialize the map to a JSON object string with keys in sorted order.
+/// Returns a newly allocated C string (caller must free with forma_str_free),
+/// or null if the map is null.
+#[no_mangle]
+pub extern "C" fn forma_map_to_json(m: *const FormaMap) -> *mut c_char {
+    if m.is_null() {
+        return ptr::null_mut();
+    }
+    let inner = unsafe { &(*m).inner };
+    let mut keys: Vec<&String> = inner.keys().collect();
+    keys.sort();
+
+    let mut json = String::from("{");
+    for (i, key) in keys.into_iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write_json_string(&mut json, key);
+        json.push(':');
+        write_json_string(&mut json, &inner[key]);
+    }
+    json.push('}');
+
+    into_owned_c_str(&json)
+}
+
+/// Parse a JSON object of string values into a new map.
+/// Returns null if the input is null or not a JSON object of strings.
+#[no_mangle]
+pub extern "C" fn forma_map_from_json(json: *const c_char) -> *mut FormaMap {
+    if json.is_null() {
+        return ptr::null_mut();
+    }
+    let text = match unsafe { CStr::from_ptr(json) }.to_str() {
+        Ok(t) => t,
+        Err(_) => return ptr::null_mut(),
+    };
+    match parse_json_object(text) {
+        Some(inner) => Box::into_raw(Box::new(FormaMap { inner })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Append `s` to `out` as a quoted, escaped JSON string.
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parse a complete JSON object whose values are all strings.
+fn parse_json_object(text: &str) -> Option<HashMap<String, String>> {
+    let mut chars = text.chars().peekable();
+    let mut map = HashMap::new();
+
+    skip_json_ws(&mut chars);
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip_json_ws(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_json_ws(&mut chars);
+            let key = parse_json_string(&mut chars)?;
+            skip_json_ws(&mut chars);
+            if chars.next()? != ':' {
+                return None;
+            }
+            skip_json_ws(&mut chars);
+            let value = parse_json_string(&mut chars)?;
+            map.insert(key, value);
+            skip_json_ws(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                '}' => break,
+                _ => return None,
+            }
+        }
+    }
+    skip_json_ws(&mut chars);
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(map)
+}
+
+fn skip_json_ws(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while matches!(chars.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+        chars.next();
+    }
+}
+
+/// Parse a quoted JSON string, decoding escapes (including surrogate pairs).
+fn parse_json_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => match chars.next()? {
+                '"' => s.push('"'),
+                '\\' => s.push('\\'),
+                '/' => s.push('/'),
+                'b' => s.push('\u{08}'),
+                'f' => s.push('\u{0C}'),
+                'n' => s.push('\n'),
+                'r' => s.push('\r'),
+                't' => s.push('\t'),
+                'u' => {
+                    let hi = parse_json_hex4(chars)?;
+                    let code = if (0xD800..0xDC00).contains(&hi) {
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let lo = parse_json_hex4(chars)?;
+                        if !(0xDC00..0xE000).contains(&lo) {
+                            return None;
+                        }
+                        0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)
+                    } else {
+                        hi
+                    };
+                    s.push(char::from_u32(code)?);
+                }
+                _ => return None,
+            },
+            c if (c as u32) < 0x20 => return None,
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_json_hex4(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(code)
+}
+
 /// Free the map and all its contents.
 #[no_mangle]
 pub extern "C" fn forma_map_free(m: *mut FormaMap) {
@@ -95,8 +263,112 @@ pub extern "C" fn forma_map_free(m: *mut FormaMap) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
     use std::ffi::CString;
 
+    /// System allocator that tracks live bytes per thread, so a test can
+    /// check for leaks without interference from tests on other threads
+    struct CountingAlloc;
+
+    thread_local! {
+        static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn track(delta: isize) {
+        let _ = LIVE_BYTES.try_with(|b| b.set(b.get() + delta));
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            track(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            track(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn test_get_then_free_does_not_leak() {
+        let m = forma_map_new();
+        let key = CString::new("name").unwrap();
+        let val = CString::new("forma").unwrap();
+        forma_map_set(m, key.as_ptr(), val.as_ptr());
+
+        // Warm up once so lazily created runtime state is not counted
+        crate::string::forma_str_free(forma_map_get(m, key.as_ptr()));
+
+        let before = LIVE_BYTES.with(|b| b.get());
+        for _ in 0..100 {
+            let got = forma_map_get(m, key.as_ptr());
+            assert!(!got.is_null());
+            crate::string::forma_str_free(got);
+        }
+        assert_eq!(LIVE_BYTES.with(|b| b.get()), before);
+        forma_map_free(m);
+    }
+
+    #[test]
+    fn test_set_get_remove_free_sequence() {
+        let m = forma_map_new();
+        let key = CString::new("lang").unwrap();
+        let first = CString::new("forma").unwrap();
+        let second = CString::new("FORMA").unwrap();
+
+        forma_map_set(m, key.as_ptr(), first.as_ptr());
+        let old = forma_map_get(m, key.as_ptr());
+        // Overwriting must not invalidate a value already handed out
+        forma_map_set(m, key.as_ptr(), second.as_ptr());
+        let new = forma_map_get(m, key.as_ptr());
+        assert!(forma_map_remove(m, key.as_ptr()));
+        assert!(forma_map_get(m, key.as_ptr()).is_null());
+        assert!(!forma_map_remove(m, key.as_ptr()));
+
+        // Returned strings are owned copies and outlive the map
+        forma_map_free(m);
+        unsafe {
+            assert_eq!(CStr::from_ptr(old).to_str().unwrap(), "forma");
+            assert_eq!(CStr::from_ptr(new).to_str().unwrap(), "FORMA");
+        }
+        crate::string::forma_str_free(old);
+        crate::string::forma_str_free(new);
+    }
+
+    #[test]
+    fn test_null_arguments_on_live_map() {
+        let m = forma_map_new();
+        let key = CString::new("k").unwrap();
+        forma_map_set(m, ptr::null(), key.as_ptr());
+        forma_map_set(m, key.as_ptr(), ptr::null());
+        assert_eq!(forma_map_len(m), 0);
+        assert!(forma_map_get(m, ptr::null()).is_null());
+        assert!(!forma_map_contains(m, ptr::null()));
+        assert!(!forma_map_remove(m, ptr::null()));
+        assert!(forma_map_clone(ptr::null()).is_null());
+        assert!(forma_map_to_json(ptr::null()).is_null());
+        assert!(forma_map_from_json(ptr::null()).is_null());
+        forma_map_free(m);
+    }
+
+    #[test]
+    fn test_double_free_is_a_no_op() {
+        let m = forma_map_new();
+        let key = CString::new("k").unwrap();
+        let val = CString::new("v").unwrap();
+        forma_map_set(m, key.as_ptr(), val.as_ptr());
+        let got = forma_map_get(m, key.as_ptr());
+        crate::string::forma_str_free(got);
+        crate::string::forma_str_free(got);
+        crate::string::forma_str_free(std::ptr::null_mut());
+        forma_map_free(m);
+    }
+
     #[test]
     fn test_new_and_len() {
         let m = forma_map_new();
@@ -117,7 +389,7 @@ mod tests {
         let got_str = unsafe { CStr::from_ptr(got).to_string_lossy().into_owned() };
         assert_eq!(got_str, "forma");
         // Free the returned string
-        unsafe { drop(CString::from_raw(got)); }
+        crate::string::forma_str_free(got);
         forma_map_free(m);
     }
 
@@ -135,6 +407,102 @@ mod tests {
         forma_map_free(m);
     }
 
+    #[test]
+    fn test_clone_is_independent() {
+        let m = forma_map_new();
+        let key = CString::new("k").unwrap();
+        let val = CString::new("original").unwrap();
+        forma_map_set(m, key.as_ptr(), val.as_ptr());
+
+        let c = forma_map_clone(m);
+        assert!(!c.is_null());
+        assert_eq!(forma_map_len(c), 1);
+
+        let changed = CString::new("changed").unwrap();
+        let extra = CString::new("extra").unwrap();
+        forma_map_set(c, key.as_ptr(), changed.as_ptr());
+        forma_map_set(c, extra.as_ptr(), changed.as_ptr());
+
+        assert_eq!(forma_map_len(m), 1);
+        assert!(!forma_map_contains(m, extra.as_ptr()));
+        let got = forma_map_get(m, key.as_ptr());
+        let got_str = unsafe { CStr::from_ptr(got).to_string_lossy().into_owned() };
+        assert_eq!(got_str, "original");
+        crate::string::forma_str_free(got);
+
+        forma_map_free(c);
+        forma_map_free(m);
+        assert!(forma_map_clone(ptr::null()).is_null());
+    }
+
+    fn to_json(m: *const FormaMap) -> String {
+        let json = forma_map_to_json(m);
+        assert!(!json.is_null());
+        let s = unsafe { CStr::from_ptr(json).to_string_lossy().into_owned() };
+        crate::string::forma_str_free(json);
+        s
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let m = forma_map_new();
+        let pairs = [
+            ("name", "forma"),
+            ("quote\"key", "back\\slash"),
+            ("line\nbreak", "tab\there"),
+            ("ctrl\u{01}", "unicode é ✓ 🎉"),
+            ("", "empty key"),
+        ];
+        for (k, v) in pairs {
+            let k = CString::new(k).unwrap();
+            let v = CString::new(v).unwrap();
+            forma_map_set(m, k.as_ptr(), v.as_ptr());
+        }
+
+        let json = to_json(m);
+        assert!(json.contains(r#""quote\"key":"back\\slash""#), "{}", json);
+        assert!(json.contains(r#""ctrl\u0001""#), "{}", json);
+
+        let json_c = CString::new(json).unwrap();
+        let back = forma_map_from_json(json_c.as_ptr());
+        assert!(!back.is_null());
+        assert_eq!(unsafe { &(*back).inner }, unsafe { &(*m).inner });
+        forma_map_free(back);
+        forma_map_free(m);
+    }
+
+    #[test]
+    fn test_json_empty_and_escapes() {
+        let m = forma_map_new();
+        assert_eq!(to_json(m), "{}");
+        forma_map_free(m);
+
+        let json = CString::new(r#" { "a" : "\u00e9\ud83c\udf89\/" } "#).unwrap();
+        let m = forma_map_from_json(json.as_ptr());
+        assert!(!m.is_null());
+        assert_eq!(unsafe { (*m).inner.get("a") }.map(String::as_str), Some("é🎉/"));
+        forma_map_free(m);
+    }
+
+    #[test]
+    fn test_json_invalid() {
+        for bad in [
+            "",
+            "[]",
+            "{",
+            r#"{"a":1}"#,
+            r#"{"a":"b",}"#,
+            r#"{"a" "b"}"#,
+            r#"{"a":"b"} x"#,
+            r#"{"a":"\q"}"#,
+        ] {
+            let c = CString::new(bad).unwrap();
+            assert!(forma_map_from_json(c.as_ptr()).is_null(), "accepted {:?}", bad);
+        }
+        assert!(forma_map_from_json(ptr::null()).is_null());
+        assert!(forma_map_to_json(ptr::null()).is_null());
+    }
+
     #[test]
     fn test_null_safety() {
         assert_eq!(forma_map_len(ptr::null()), 0);
@@ -0,0 +1,207 @@
+//! TCP sockets for FORMA runtime, gated by the network capability
+//!
+//! Nothing may connect until [`forma_net_allow`] grants access, either to
+//! every address or to a list of `host:port` pairs. Sockets are identified by
+//! positive integer handles; failures return negative error codes.
+
+use libc::c_char;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// Returned when the network capability does not cover the address
+pub const FORMA_NET_DENIED: i64 = -1;
+/// Returned when connecting, reading or writing fails
+pub const FORMA_NET_ERROR: i64 = -2;
+/// Returned for a handle that is not an open socket
+pub const FORMA_NET_BAD_HANDLE: i64 = -3;
+
+/// Addresses the program may connect to.
+enum NetPolicy {
+    Denied,
+    Any,
+    Hosts(Vec<String>),
+}
+
+impl NetPolicy {
+    fn allows(&self, addr: &str) -> bool {
+        match self {
+            NetPolicy::Denied => false,
+            NetPolicy::Any => true,
+            NetPolicy::Hosts(hosts) => hosts.iter().any(|h| h == addr),
+        }
+    }
+}
+
+static NET_POLICY: Mutex<NetPolicy> = Mutex::new(NetPolicy::Denied);
+
+/// Open sockets by handle, and the next handle to hand out
+static SOCKETS: LazyLock<Mutex<(HashMap<i64, TcpStream>, i64)>> =
+    LazyLock::new(|| Mutex::new((HashMap::new(), 1)));
+
+fn net_policy() -> MutexGuard<'static, NetPolicy> {
+    NET_POLICY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn sockets() -> MutexGuard<'static, (HashMap<i64, TcpStream>, i64)> {
+    SOCKETS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Grant the network capability.
+///
+/// A null or empty `addr` allows every address; otherwise `addr` is a
+/// `host:port` pair added to the allowed list. Granting every address wins
+/// over any list.
+#[no_mangle]
+pub extern "C" fn forma_net_allow(addr: *const c_char) {
+    let addr = if addr.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(addr).to_string_lossy().into_owned() }
+    };
+    let mut policy = net_policy();
+    if addr.is_empty() {
+        *policy = NetPolicy::Any;
+        return;
+    }
+    match &mut *policy {
+        NetPolicy::Any => {}
+        NetPolicy::Hosts(hosts) => hosts.push(addr),
+        NetPolicy::Denied => *policy = NetPolicy::Hosts(vec![addr]),
+    }
+}
+
+/// Connect to `host:port`.
+///
+/// Returns a positive socket handle, `FORMA_NET_DENIED` if the network
+/// capability does not cover the address (a message is printed to stderr),
+/// or `FORMA_NET_ERROR` if the connection fails.
+#[no_mangle]
+pub extern "C" fn forma_tcp_connect(host: *const c_char, port: i64) -> i64 {
+    if host.is_null() || !(0..=u16::MAX as i64).contains(&port) {
+        return FORMA_NET_ERROR;
+    }
+    let host = unsafe { CStr::from_ptr(host).to_string_lossy().into_owned() };
+    let addr = format!("{}:{}", host, port);
+    if !net_policy().allows(&addr) {
+        eprintln!(
+            "capability 'network' required for operation 'tcp_connect to {}'",
+            addr
+        );
+        return FORMA_NET_DENIED;
+    }
+    match TcpStream::connect(&addr) {
+        Ok(stream) => {
+            let mut sockets = sockets();
+            let handle = sockets.1;
+            sockets.1 += 1;
+            sockets.0.insert(handle, stream);
+            handle
+        }
+        Err(_) => FORMA_NET_ERROR,
+    }
+}
+
+/// Write `len` bytes from `data` to a socket.
+/// Returns the number of bytes written, or a negative error code.
+#[no_mangle]
+pub extern "C" fn forma_tcp_write(handle: i64, data: *const u8, len: i64) -> i64 {
+    if data.is_null() || len < 0 {
+        return FORMA_NET_ERROR;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data, len as usize) };
+    let mut sockets = sockets();
+    let Some(stream) = sockets.0.get_mut(&handle) else {
+        return FORMA_NET_BAD_HANDLE;
+    };
+    match stream.write_all(bytes) {
+        Ok(()) => len,
+        Err(_) => FORMA_NET_ERROR,
+    }
+}
+
+/// Read up to `cap` bytes from a socket into `buf`.
+/// Returns the number of bytes read (0 at end of stream), or a negative
+/// error code.
+#[no_mangle]
+pub extern "C" fn forma_tcp_read(handle: i64, buf: *mut u8, cap: i64) -> i64 {
+    if buf.is_null() || cap < 0 {
+        return FORMA_NET_ERROR;
+    }
+    let buf = unsafe { std::slice::from_raw_parts_mut(buf, cap as usize) };
+    // Reading can block, so read from a clone of the stream rather than
+    // holding the lock
+    let stream = match sockets().0.get(&handle) {
+        Some(stream) => stream.try_clone(),
+        None => return FORMA_NET_BAD_HANDLE,
+    };
+    match stream.and_then(|mut s| s.read(buf)) {
+        Ok(n) => n as i64,
+        Err(_) => FORMA_NET_ERROR,
+    }
+}
+
+/// Close a socket. Returns 0, or `FORMA_NET_BAD_HANDLE` if it was not open.
+#[no_mangle]
+pub extern "C" fn forma_tcp_close(handle: i64) -> i64 {
+    match sockets().0.remove(&handle) {
+        Some(_) => 0,
+        None => FORMA_NET_BAD_HANDLE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::net::TcpListener;
+
+    // The policy is process-wide, so the tests that change it run in one test
+    #[test]
+    #[cfg_attr(miri, ignore)] // needs real sockets
+    fn test_tcp_connect_policy_and_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port() as i64;
+        let host = CString::new("127.0.0.1").unwrap();
+
+        assert_eq!(forma_tcp_connect(host.as_ptr(), port), FORMA_NET_DENIED);
+
+        let other = CString::new(format!("127.0.0.1:{}", port + 1)).unwrap();
+        forma_net_allow(other.as_ptr());
+        assert_eq!(forma_tcp_connect(host.as_ptr(), port), FORMA_NET_DENIED);
+
+        let allowed = CString::new(format!("127.0.0.1:{}", port)).unwrap();
+        forma_net_allow(allowed.as_ptr());
+        let handle = forma_tcp_connect(host.as_ptr(), port);
+        assert!(handle > 0, "connect failed with {}", handle);
+
+        let (mut peer, _) = listener.accept().unwrap();
+        assert_eq!(forma_tcp_write(handle, b"ping".as_ptr(), 4), 4);
+        let mut got = [0u8; 4];
+        peer.read_exact(&mut got).unwrap();
+        assert_eq!(&got, b"ping");
+
+        peer.write_all(b"pong").unwrap();
+        let mut buf = [0u8; 16];
+        let n = forma_tcp_read(handle, buf.as_mut_ptr(), buf.len() as i64);
+        assert_eq!(&buf[..n as usize], b"pong");
+
+        assert_eq!(forma_tcp_close(handle), 0);
+        assert_eq!(forma_tcp_close(handle), FORMA_NET_BAD_HANDLE);
+
+        // A refused connection is an error code, not a panic
+        drop(listener);
+        forma_net_allow(std::ptr::null());
+        assert_eq!(forma_tcp_connect(host.as_ptr(), port), FORMA_NET_ERROR);
+    }
+
+    #[test]
+    fn test_tcp_null_safety() {
+        assert_eq!(forma_tcp_connect(std::ptr::null(), 80), FORMA_NET_ERROR);
+        assert_eq!(forma_tcp_write(0, std::ptr::null(), 1), FORMA_NET_ERROR);
+        assert_eq!(forma_tcp_read(0, std::ptr::null_mut(), 1), FORMA_NET_ERROR);
+        assert_eq!(forma_tcp_close(0), FORMA_NET_BAD_HANDLE);
+    }
+}
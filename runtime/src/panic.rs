@@ -4,22 +4,104 @@ use libc::c_char;
 use std::ffi::CStr;
 use std::process;
 
-/// Panic with a message
-/// This function never returns (calls exit)
-#[no_mangle]
-pub extern "C" fn forma_panic(msg: *const c_char) -> ! {
-    if msg.is_null() {
-        eprintln!("FORMA panic: (no message)");
+/// Lossily decode a C string for a diagnostic, or `None` if it is null.
+fn c_str_lossy(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
     } else {
-        unsafe {
-            let c_str = CStr::from_ptr(msg);
-            match c_str.to_str() {
-                Ok(s) => eprintln!("FORMA panic: {}", s),
-                Err(_) => eprintln!("FORMA panic: (invalid UTF-8 message)"),
+        unsafe { Some(CStr::from_ptr(ptr).to_string_lossy().into_owned()) }
+    }
+}
+
+/// Format the line printed by [`forma_panic`].
+fn panic_message(msg: *const c_char, file: *const c_char, line: i64) -> String {
+    let msg = c_str_lossy(msg).unwrap_or_else(|| "(no message)".to_string());
+    match c_str_lossy(file) {
+        Some(file) => format!("panic: {} at {}:{}", msg, file, line),
+        None => format!("panic: {}", msg),
+    }
+}
+
+/// Environment variable that turns on backtraces for [`forma_panic`].
+pub const BACKTRACE_ENV: &str = "FORMA_BACKTRACE";
+
+/// Recover a FORMA function name from its native symbol: `_F4math6helper`
+/// is `math::helper`. Returns `None` for symbols that are not FORMA-mangled.
+fn demangle_forma(symbol: &str) -> Option<String> {
+    let mut rest = symbol.strip_prefix("_F")?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + len)?;
+        if segment.is_empty() {
+            return None;
+        }
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    Some(segments.join("::"))
+}
+
+/// The FORMA functions on the current call stack, innermost first. Runtime
+/// and libc frames are skipped; `main` is kept as the outermost frame.
+fn forma_backtrace() -> Vec<String> {
+    let mut frames = Vec::new();
+    backtrace::trace(|frame| {
+        backtrace::resolve_frame(frame, |symbol| {
+            let Some(name) = symbol.name().and_then(|n| n.as_str()) else {
+                return;
+            };
+            let name = match demangle_forma(name) {
+                Some(name) => name,
+                None if name == "main" => name.to_string(),
+                None => return,
+            };
+            match (symbol.filename(), symbol.lineno()) {
+                (Some(file), Some(line)) => {
+                    frames.push(format!("{} at {}:{}", name, file.display(), line))
+                }
+                _ => frames.push(name),
             }
+        });
+        true
+    });
+    frames
+}
+
+/// Panic with a message and source location.
+///
+/// Prints `panic: <msg> at <file>:<line>` to stderr and aborts. This is the
+/// common trap path for compiled code: failed assertions and contracts,
+/// checked division, allocation failure and `panic(...)` all branch here.
+/// A null `file` omits the location. With `FORMA_BACKTRACE=1` the FORMA
+/// functions on the call stack are listed after the message.
+#[no_mangle]
+pub extern "C" fn forma_panic(msg: *const c_char, file: *const c_char, line: i64) -> ! {
+    eprintln!("{}", panic_message(msg, file, line));
+    if std::env::var_os(BACKTRACE_ENV).is_some_and(|v| v != "0") {
+        eprintln!("backtrace:");
+        for (i, frame) in forma_backtrace().iter().enumerate() {
+            eprintln!("{:4}: {}", i, frame);
         }
     }
-    process::exit(1);
+    process::abort();
+}
+
+/// Message reported by [`forma_panic_index`].
+fn index_message(index: i64, len: i64) -> String {
+    format!("index {} out of bounds for length {}", index, len)
+}
+
+/// Panic for an out-of-bounds index, as [`forma_panic`] does. Compiled code
+/// with `--bounds-checks` branches here when an index fails its check.
+#[no_mangle]
+pub extern "C" fn forma_panic_index(index: i64, len: i64, file: *const c_char, line: i64) -> ! {
+    let msg = std::ffi::CString::new(index_message(index, len)).expect("no NUL in message");
+    forma_panic(msg.as_ptr(), file, line)
 }
 
 /// Panic with a formatted integer message
@@ -43,7 +125,7 @@ pub extern "C" fn forma_panic_int(msg: *const c_char, value: i64) -> ! {
 #[no_mangle]
 pub extern "C" fn forma_assert(cond: bool, msg: *const c_char) {
     if !cond {
-        forma_panic(msg);
+        forma_panic(msg, std::ptr::null(), 0);
     }
 }
 
@@ -77,7 +159,10 @@ pub extern "C" fn forma_unreachable() -> ! {
 #[no_mangle]
 pub extern "C" fn forma_bounds_check(index: i64, len: i64) {
     if index < 0 || index >= len {
-        eprintln!("FORMA panic: index out of bounds: index {} len {}", index, len);
+        eprintln!(
+            "FORMA panic: index out of bounds: index {} len {}",
+            index, len
+        );
         process::exit(1);
     }
 }
@@ -128,7 +213,10 @@ pub extern "C" fn forma_sub_overflow_check(a: i64, b: i64) -> i64 {
     match a.checked_sub(b) {
         Some(result) => result,
         None => {
-            eprintln!("FORMA panic: integer overflow in subtraction: {} - {}", a, b);
+            eprintln!(
+                "FORMA panic: integer overflow in subtraction: {} - {}",
+                a, b
+            );
             process::exit(1);
         }
     }
@@ -140,7 +228,10 @@ pub extern "C" fn forma_mul_overflow_check(a: i64, b: i64) -> i64 {
     match a.checked_mul(b) {
         Some(result) => result,
         None => {
-            eprintln!("FORMA panic: integer overflow in multiplication: {} * {}", a, b);
+            eprintln!(
+                "FORMA panic: integer overflow in multiplication: {} * {}",
+                a, b
+            );
             process::exit(1);
         }
     }
@@ -150,6 +241,118 @@ pub extern "C" fn forma_mul_overflow_check(a: i64, b: i64) -> i64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_panic_message() {
+        let msg = std::ffi::CString::new("division by zero").unwrap();
+        let file = std::ffi::CString::new("main.forma").unwrap();
+        assert_eq!(
+            panic_message(msg.as_ptr(), file.as_ptr(), 3),
+            "panic: division by zero at main.forma:3"
+        );
+        assert_eq!(
+            panic_message(msg.as_ptr(), std::ptr::null(), 0),
+            "panic: division by zero"
+        );
+        assert_eq!(
+            panic_message(std::ptr::null(), std::ptr::null(), 0),
+            "panic: (no message)"
+        );
+    }
+
+    #[test]
+    fn test_index_message() {
+        assert_eq!(index_message(5, 3), "index 5 out of bounds for length 3");
+        assert_eq!(index_message(-1, 3), "index -1 out of bounds for length 3");
+    }
+
+    /// Re-runs this test in a child process, which calls `forma_panic` and
+    /// must die with the message on stderr.
+    #[test]
+    #[cfg_attr(miri, ignore)] // spawns a process
+    fn test_panic_aborts() {
+        if std::env::var_os("FORMA_PANIC_CHILD").is_some() {
+            let msg = std::ffi::CString::new("boom").unwrap();
+            let file = std::ffi::CString::new("main.forma").unwrap();
+            forma_panic(msg.as_ptr(), file.as_ptr(), 7);
+        }
+        let output = process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "panic::tests::test_panic_aborts", "--nocapture"])
+            .env("FORMA_PANIC_CHILD", "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("panic: boom at main.forma:7"),
+            "stderr: {}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_demangle_forma() {
+        assert_eq!(demangle_forma("_F6helper").as_deref(), Some("helper"));
+        assert_eq!(
+            demangle_forma("_F4math6helper").as_deref(),
+            Some("math::helper")
+        );
+        assert_eq!(demangle_forma("main"), None);
+        assert_eq!(demangle_forma("_F"), None);
+        assert_eq!(demangle_forma("_F9short"), None);
+        assert_eq!(demangle_forma("_Foo"), None);
+    }
+
+    // Stand-ins for compiled FORMA functions, under their mangled symbols
+    #[no_mangle]
+    #[inline(never)]
+    extern "C" fn _F5inner() {
+        let msg = std::ffi::CString::new("contract violated").unwrap();
+        forma_panic(msg.as_ptr(), std::ptr::null(), 0);
+    }
+
+    #[no_mangle]
+    #[inline(never)]
+    extern "C" fn _F5outer() {
+        _F5inner();
+    }
+
+    /// Like `test_panic_aborts`, but the child panics two FORMA frames deep
+    /// with and without `FORMA_BACKTRACE`.
+    #[test]
+    #[cfg_attr(miri, ignore)] // spawns a process
+    fn test_panic_backtrace() {
+        if std::env::var_os("FORMA_PANIC_CHILD").is_some() {
+            _F5outer();
+        }
+        let run = |backtrace: Option<&str>| {
+            let mut cmd = process::Command::new(std::env::current_exe().unwrap());
+            cmd.args([
+                "--exact",
+                "panic::tests::test_panic_backtrace",
+                "--nocapture",
+            ])
+            .env("FORMA_PANIC_CHILD", "1")
+            .env_remove(BACKTRACE_ENV);
+            if let Some(value) = backtrace {
+                cmd.env(BACKTRACE_ENV, value);
+            }
+            let output = cmd.output().unwrap();
+            assert!(!output.status.success());
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        };
+
+        let stderr = run(Some("1"));
+        let inner = stderr.find("inner").expect(&stderr);
+        let outer = stderr.find("outer").expect(&stderr);
+        assert!(inner < outer, "innermost frame first: {}", stderr);
+        assert!(stderr.contains("backtrace:"), "{}", stderr);
+
+        for terse in [run(None), run(Some("0"))] {
+            assert!(terse.contains("panic: contract violated"), "{}", terse);
+            assert!(!terse.contains("backtrace:"), "{}", terse);
+        }
+    }
+
     #[test]
     fn test_assert_passes() {
         forma_assert(true, std::ptr::null());
@@ -1,7 +1,33 @@
 //! String functions for FORMA runtime
 
 use libc::c_char;
-use std::ffi::CStr;
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// Strings handed out by the runtime and not yet freed, by address
+static LIVE_STRINGS: LazyLock<Mutex<HashSet<usize>>> =
+    LazyLock::new(|| Mutex::new(HashSet::with_capacity(64)));
+
+fn live_strings() -> MutexGuard<'static, HashSet<usize>> {
+    LIVE_STRINGS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Allocate an owned C string for returning across the FFI boundary
+/// The caller must free it with forma_str_free
+pub(crate) fn into_owned_c_str(s: &str) -> *mut c_char {
+    into_owned_c_str_bytes(s.as_bytes())
+}
+
+/// Like `into_owned_c_str`, for raw bytes; the string ends at the first interior NUL
+pub(crate) fn into_owned_c_str_bytes(bytes: &[u8]) -> *mut c_char {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let ptr = CString::new(&bytes[..end])
+        .expect("NUL bytes were stripped")
+        .into_raw();
+    live_strings().insert(ptr as usize);
+    ptr
+}
 
 /// Get the length of a string
 #[no_mangle]
@@ -15,6 +41,19 @@ pub extern "C" fn forma_str_len(s: *const c_char) -> i64 {
     }
 }
 
+/// Get the character at a character index, panicking if it is out of bounds
+#[no_mangle]
+pub extern "C" fn forma_str_index(s: *const c_char, idx: i64) -> u32 {
+    let text = if s.is_null() {
+        ""
+    } else {
+        unsafe { CStr::from_ptr(s).to_str().unwrap_or("") }
+    };
+    let len = text.chars().count() as i64;
+    crate::forma_bounds_check(idx, len);
+    text.chars().nth(idx as usize).map_or(0, |c| c as u32)
+}
+
 /// Concatenate two strings
 /// Returns a heap-allocated string that must be freed with forma_str_free
 #[no_mangle]
@@ -42,17 +81,7 @@ pub extern "C" fn forma_str_concat(a: *const c_char, b: *const c_char) -> *mut c
     };
 
     let result = format!("{}{}", str_a, str_b);
-    let len = result.len();
-
-    unsafe {
-        let ptr = libc::malloc(len + 1) as *mut c_char;
-        if ptr.is_null() {
-            return std::ptr::null_mut();
-        }
-        std::ptr::copy_nonoverlapping(result.as_ptr(), ptr as *mut u8, len);
-        *ptr.add(len) = 0; // null terminator
-        ptr
-    }
+    into_owned_c_str(&result)
 }
 
 /// Compare two strings for equality
@@ -102,17 +131,7 @@ pub extern "C" fn forma_str_cmp(a: *const c_char, b: *const c_char) -> i32 {
 #[no_mangle]
 pub extern "C" fn forma_int_to_str(n: i64) -> *mut c_char {
     let s = n.to_string();
-    let len = s.len();
-
-    unsafe {
-        let ptr = libc::malloc(len + 1) as *mut c_char;
-        if ptr.is_null() {
-            return std::ptr::null_mut();
-        }
-        std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, len);
-        *ptr.add(len) = 0; // null terminator
-        ptr
-    }
+    into_owned_c_str(&s)
 }
 
 /// Convert a float to a string
@@ -120,17 +139,7 @@ pub extern "C" fn forma_int_to_str(n: i64) -> *mut c_char {
 #[no_mangle]
 pub extern "C" fn forma_float_to_str(n: f64) -> *mut c_char {
     let s = format!("{}", n);
-    let len = s.len();
-
-    unsafe {
-        let ptr = libc::malloc(len + 1) as *mut c_char;
-        if ptr.is_null() {
-            return std::ptr::null_mut();
-        }
-        std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, len);
-        *ptr.add(len) = 0; // null terminator
-        ptr
-    }
+    into_owned_c_str(&s)
 }
 
 /// Convert a boolean to a string
@@ -138,17 +147,7 @@ pub extern "C" fn forma_float_to_str(n: f64) -> *mut c_char {
 #[no_mangle]
 pub extern "C" fn forma_bool_to_str(b: bool) -> *mut c_char {
     let s = if b { "true" } else { "false" };
-    let len = s.len();
-
-    unsafe {
-        let ptr = libc::malloc(len + 1) as *mut c_char;
-        if ptr.is_null() {
-            return std::ptr::null_mut();
-        }
-        std::ptr::copy_nonoverlapping(s.as_ptr(), ptr as *mut u8, len);
-        *ptr.add(len) = 0; // null terminator
-        ptr
-    }
+    into_owned_c_str(s)
 }
 
 /// Parse a string as an integer
@@ -224,11 +223,17 @@ pub extern "C" fn forma_str_to_float(s: *const c_char) -> f64 {
 }
 
 /// Free a string allocated by the runtime
+/// Every runtime function documented as returning a string that "must be freed
+/// with forma_str_free" allocates it through `into_owned_c_str`. Freeing null,
+/// a string the runtime did not allocate, or one that was already freed is a no-op.
 #[no_mangle]
 pub extern "C" fn forma_str_free(s: *mut c_char) {
-    if !s.is_null() {
+    if s.is_null() {
+        return;
+    }
+    if live_strings().remove(&(s as usize)) {
         unsafe {
-            libc::free(s as *mut libc::c_void);
+            drop(CString::from_raw(s));
         }
     }
 }
@@ -250,11 +255,7 @@ pub extern "C" fn forma_str_substr(s: *const c_char, start: i64, len: i64) -> *m
 
                 if start >= rust_str.len() {
                     // Return empty string
-                    let ptr = libc::malloc(1) as *mut c_char;
-                    if !ptr.is_null() {
-                        *ptr = 0;
-                    }
-                    return ptr;
+                    return into_owned_c_str("");
                 }
 
                 let end = std::cmp::min(start + len, rust_str.len());
@@ -263,28 +264,39 @@ pub extern "C" fn forma_str_substr(s: *const c_char, start: i64, len: i64) -> *m
                     Some(s) => s,
                     None => {
                         // Invalid byte boundary — return empty string (no crash)
-                        let ptr = libc::malloc(1) as *mut c_char;
-                        if !ptr.is_null() {
-                            *ptr = 0;
-                        }
-                        return ptr;
+                        return into_owned_c_str("");
                     }
                 };
-                let substr_len = substr.len();
-
-                let ptr = libc::malloc(substr_len + 1) as *mut c_char;
-                if ptr.is_null() {
-                    return std::ptr::null_mut();
-                }
-                std::ptr::copy_nonoverlapping(substr.as_ptr(), ptr as *mut u8, substr_len);
-                *ptr.add(substr_len) = 0;
-                ptr
+                into_owned_c_str(substr)
             }
             Err(_) => std::ptr::null_mut(),
         }
     }
 }
 
+/// Get a substring of `len` characters starting at character `start`
+/// The range is clamped to the string: a negative start counts as 0, and a
+/// range running past the end stops there. A null input yields an empty string.
+/// Returns a heap-allocated string that must be freed with forma_str_free
+#[no_mangle]
+pub extern "C" fn forma_str_substring(s: *const c_char, start: i64, len: i64) -> *mut c_char {
+    let rust_str = if s.is_null() {
+        ""
+    } else {
+        unsafe { CStr::from_ptr(s).to_str().unwrap_or("") }
+    };
+
+    let start = start.max(0) as usize;
+    let len = len.max(0) as usize;
+    let begin = rust_str
+        .char_indices()
+        .nth(start)
+        .map_or(rust_str.len(), |(i, _)| i);
+    let rest = &rust_str[begin..];
+    let end = rest.char_indices().nth(len).map_or(rest.len(), |(i, _)| i);
+    into_owned_c_str(&rest[..end])
+}
+
 /// Check if a string contains a substring
 #[no_mangle]
 pub extern "C" fn forma_str_contains(haystack: *const c_char, needle: *const c_char) -> bool {
@@ -336,24 +348,13 @@ pub extern "C" fn forma_str_dup(s: *const c_char) -> *mut c_char {
         return std::ptr::null_mut();
     }
 
-    unsafe {
-        let c_str = CStr::from_ptr(s);
-        let bytes = c_str.to_bytes_with_nul();
-        let len = bytes.len();
-
-        let ptr = libc::malloc(len) as *mut c_char;
-        if ptr.is_null() {
-            return std::ptr::null_mut();
-        }
-        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, len);
-        ptr
-    }
+    let bytes = unsafe { CStr::from_ptr(s).to_bytes() };
+    into_owned_c_str_bytes(bytes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::ffi::CString;
 
     /// Helper: create a C string from a Rust &str
     fn c(s: &str) -> CString {
@@ -370,6 +371,17 @@ mod tests {
         Some(s)
     }
 
+    #[test]
+    fn test_double_free_is_a_no_op() {
+        let s = into_owned_c_str("once");
+        forma_str_free(s);
+        forma_str_free(s);
+        // Pointers the runtime never handed out are left alone
+        let foreign = c("foreign");
+        forma_str_free(foreign.as_ptr() as *mut c_char);
+        assert_eq!(foreign.to_str().unwrap(), "foreign");
+    }
+
     #[test]
     fn test_substr_ascii() {
         let input = c("hello world");
@@ -411,4 +423,72 @@ mod tests {
         let result = forma_str_substr(input.as_ptr(), -1, 3);
         assert!(result.is_null());
     }
+
+    #[test]
+    fn test_str_len() {
+        let input = c("hello");
+        assert_eq!(forma_str_len(input.as_ptr()), 5);
+        assert_eq!(forma_str_len(std::ptr::null()), 0);
+    }
+
+    #[test]
+    fn test_str_index() {
+        let input = c("héllo");
+        assert_eq!(forma_str_index(input.as_ptr(), 0), 'h' as u32);
+        assert_eq!(forma_str_index(input.as_ptr(), 1), 'é' as u32);
+        assert_eq!(forma_str_index(input.as_ptr(), 4), 'o' as u32);
+    }
+
+    #[test]
+    fn test_str_concat() {
+        let a = c("foo");
+        let b = c("bar");
+        unsafe {
+            let result = forma_str_concat(a.as_ptr(), b.as_ptr());
+            assert_eq!(read_and_free(result), Some("foobar".to_string()));
+            let result = forma_str_concat(a.as_ptr(), std::ptr::null());
+            assert_eq!(read_and_free(result), Some("foo".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_substring_within_bounds() {
+        let input = c("hello world");
+        unsafe {
+            let result = forma_str_substring(input.as_ptr(), 6, 5);
+            assert_eq!(read_and_free(result), Some("world".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_substring_counts_chars() {
+        let input = c("héllo");
+        unsafe {
+            let result = forma_str_substring(input.as_ptr(), 1, 3);
+            assert_eq!(read_and_free(result), Some("éll".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_substring_clamps_out_of_range() {
+        let input = c("hello");
+        unsafe {
+            let result = forma_str_substring(input.as_ptr(), 3, i64::MAX);
+            assert_eq!(read_and_free(result), Some("lo".to_string()));
+            let result = forma_str_substring(input.as_ptr(), -2, 2);
+            assert_eq!(read_and_free(result), Some("he".to_string()));
+            let result = forma_str_substring(input.as_ptr(), 100, 5);
+            assert_eq!(read_and_free(result), Some("".to_string()));
+            let result = forma_str_substring(input.as_ptr(), 1, -1);
+            assert_eq!(read_and_free(result), Some("".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_substring_null_input() {
+        unsafe {
+            let result = forma_str_substring(std::ptr::null(), 0, 5);
+            assert_eq!(read_and_free(result), Some("".to_string()));
+        }
+    }
 }
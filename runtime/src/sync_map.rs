@@ -0,0 +1,187 @@
+//! Thread-safe string-keyed map runtime support for FORMA
+//!
+//! Same surface as [`crate::map`], but every operation takes the map's lock,
+//! so one map may be shared between threads through its raw pointer. Values
+//! are copied out under the lock, since a borrow could not outlive it.
+
+use crate::string::into_owned_c_str;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+/// Internal representation of a FORMA thread-safe string map.
+pub struct FormaSyncMap {
+    inner: Mutex<HashMap<String, String>>,
+}
+
+impl FormaSyncMap {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Create a new empty thread-safe map.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_new() -> *mut FormaSyncMap {
+    let m = Box::new(FormaSyncMap {
+        inner: Mutex::new(HashMap::new()),
+    });
+    Box::into_raw(m)
+}
+
+/// Return the number of entries in the map.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_len(m: *const FormaSyncMap) -> i64 {
+    if m.is_null() {
+        return 0;
+    }
+    unsafe { (*m).lock().len() as i64 }
+}
+
+/// Get the value for a key. Returns a newly allocated C string (caller must free with forma_str_free),
+/// or null if the key is not present.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_get(m: *const FormaSyncMap, key: *const c_char) -> *mut c_char {
+    if m.is_null() || key.is_null() {
+        return ptr::null_mut();
+    }
+    unsafe {
+        let key_str = CStr::from_ptr(key).to_string_lossy();
+        match (*m).lock().get(key_str.as_ref()) {
+            Some(val) => into_owned_c_str(val),
+            None => ptr::null_mut(),
+        }
+    }
+}
+
+/// Set a key-value pair in the map. Both key and value are C strings.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_set(
+    m: *const FormaSyncMap,
+    key: *const c_char,
+    value: *const c_char,
+) {
+    if m.is_null() || key.is_null() || value.is_null() {
+        return;
+    }
+    unsafe {
+        let key_str = CStr::from_ptr(key).to_string_lossy().into_owned();
+        let val_str = CStr::from_ptr(value).to_string_lossy().into_owned();
+        (*m).lock().insert(key_str, val_str);
+    }
+}
+
+/// Check whether the map contains a given key.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_contains(m: *const FormaSyncMap, key: *const c_char) -> bool {
+    if m.is_null() || key.is_null() {
+        return false;
+    }
+    unsafe {
+        let key_str = CStr::from_ptr(key).to_string_lossy();
+        (*m).lock().contains_key(key_str.as_ref())
+    }
+}
+
+/// Remove a key from the map. Returns true if the key was present.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_remove(m: *const FormaSyncMap, key: *const c_char) -> bool {
+    if m.is_null() || key.is_null() {
+        return false;
+    }
+    unsafe {
+        let key_str = CStr::from_ptr(key).to_string_lossy();
+        (*m).lock().remove(key_str.as_ref()).is_some()
+    }
+}
+
+/// Free the map and all its contents. No other thread may still be using it.
+#[no_mangle]
+pub extern "C" fn forma_sync_map_free(m: *mut FormaSyncMap) {
+    if m.is_null() {
+        return;
+    }
+    unsafe {
+        drop(Box::from_raw(m));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::thread;
+
+    #[test]
+    fn test_set_get_contains_remove() {
+        let m = forma_sync_map_new();
+        let key = CString::new("lang").unwrap();
+        let val = CString::new("forma").unwrap();
+        assert!(!forma_sync_map_contains(m, key.as_ptr()));
+        forma_sync_map_set(m, key.as_ptr(), val.as_ptr());
+        assert!(forma_sync_map_contains(m, key.as_ptr()));
+        assert_eq!(forma_sync_map_len(m), 1);
+
+        let got = forma_sync_map_get(m, key.as_ptr());
+        assert!(forma_sync_map_remove(m, key.as_ptr()));
+        assert!(!forma_sync_map_remove(m, key.as_ptr()));
+        assert!(forma_sync_map_get(m, key.as_ptr()).is_null());
+        assert_eq!(forma_sync_map_len(m), 0);
+
+        // The returned string is an owned copy and outlives the entry
+        forma_sync_map_free(m);
+        unsafe {
+            assert_eq!(CStr::from_ptr(got).to_str().unwrap(), "forma");
+        }
+        crate::string::forma_str_free(got);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 100;
+
+        let m = forma_sync_map_new();
+        // Raw pointers are not Send; pass the address as the FFI caller would
+        let addr = m as usize;
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                thread::spawn(move || {
+                    let m = addr as *const FormaSyncMap;
+                    for i in 0..PER_THREAD {
+                        let key = CString::new(format!("{}-{}", t, i)).unwrap();
+                        let val = CString::new(i.to_string()).unwrap();
+                        forma_sync_map_set(m, key.as_ptr(), val.as_ptr());
+                        assert!(forma_sync_map_contains(m, key.as_ptr()));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(forma_sync_map_len(m), (THREADS * PER_THREAD) as i64);
+        forma_sync_map_free(m);
+    }
+
+    #[test]
+    fn test_null_safety() {
+        let key = CString::new("k").unwrap();
+        assert_eq!(forma_sync_map_len(ptr::null()), 0);
+        assert!(forma_sync_map_get(ptr::null(), key.as_ptr()).is_null());
+        forma_sync_map_set(ptr::null(), key.as_ptr(), key.as_ptr());
+        assert!(!forma_sync_map_contains(ptr::null(), key.as_ptr()));
+        assert!(!forma_sync_map_remove(ptr::null(), key.as_ptr()));
+
+        let m = forma_sync_map_new();
+        forma_sync_map_set(m, ptr::null(), key.as_ptr());
+        forma_sync_map_set(m, key.as_ptr(), ptr::null());
+        assert_eq!(forma_sync_map_len(m), 0);
+        assert!(forma_sync_map_get(m, ptr::null()).is_null());
+        forma_sync_map_free(m);
+        forma_sync_map_free(ptr::null_mut()); // should not crash
+    }
+}
@@ -0,0 +1,157 @@
+//! OS threads for FORMA runtime, gated by the threads capability
+//!
+//! Nothing may be spawned until [`forma_thread_allow`] grants the capability.
+//! Threads are identified by positive integer handles; failures return
+//! negative error codes. Share data between threads with [`crate::sync_map`]
+//! or other synchronized runtime types passed through the `arg` pointer.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// Returned when the threads capability has not been granted
+pub const FORMA_THREAD_DENIED: i64 = -1;
+/// Returned when the thread cannot be started, or panicked
+pub const FORMA_THREAD_ERROR: i64 = -2;
+/// Returned for a handle that is not a running or unjoined thread
+pub const FORMA_THREAD_BAD_HANDLE: i64 = -3;
+
+/// Entry point of a spawned thread: called with the `arg` given to spawn,
+/// its return value is what join returns.
+pub type FormaThreadFn = extern "C" fn(*mut c_void) -> i64;
+
+static THREADS_ALLOWED: AtomicBool = AtomicBool::new(false);
+
+/// Unjoined threads by handle, and the next handle to hand out
+type ThreadTable = (HashMap<i64, JoinHandle<i64>>, i64);
+
+static THREADS: LazyLock<Mutex<ThreadTable>> = LazyLock::new(|| Mutex::new((HashMap::new(), 1)));
+
+fn threads() -> MutexGuard<'static, ThreadTable> {
+    THREADS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Grant the threads capability.
+#[no_mangle]
+pub extern "C" fn forma_thread_allow() {
+    THREADS_ALLOWED.store(true, Ordering::SeqCst);
+}
+
+/// Run `f(arg)` on a new OS thread.
+///
+/// Returns a positive thread handle, `FORMA_THREAD_DENIED` if the threads
+/// capability has not been granted (a message is printed to stderr), or
+/// `FORMA_THREAD_ERROR` if `f` is null or the thread cannot be started.
+/// Whatever `arg` points to must stay valid until the thread is joined.
+#[no_mangle]
+pub extern "C" fn forma_thread_spawn(f: Option<FormaThreadFn>, arg: *mut c_void) -> i64 {
+    if !THREADS_ALLOWED.load(Ordering::SeqCst) {
+        eprintln!("capability 'threads' required for operation 'thread_spawn'");
+        return FORMA_THREAD_DENIED;
+    }
+    let Some(f) = f else {
+        return FORMA_THREAD_ERROR;
+    };
+    // Raw pointers are not Send; the caller vouches for sharing `arg`
+    let arg = arg as usize;
+    match thread::Builder::new().spawn(move || f(arg as *mut c_void)) {
+        Ok(join) => {
+            let mut threads = threads();
+            let handle = threads.1;
+            threads.1 += 1;
+            threads.0.insert(handle, join);
+            handle
+        }
+        Err(_) => FORMA_THREAD_ERROR,
+    }
+}
+
+/// Wait for a thread to finish and return its function's result.
+///
+/// Returns `FORMA_THREAD_BAD_HANDLE` if the handle is unknown or was already
+/// joined, or `FORMA_THREAD_ERROR` if the thread panicked.
+#[no_mangle]
+pub extern "C" fn forma_thread_join(handle: i64) -> i64 {
+    // Joining blocks, so take the thread out rather than holding the lock
+    let Some(join) = threads().0.remove(&handle) else {
+        return FORMA_THREAD_BAD_HANDLE;
+    };
+    join.join().unwrap_or(FORMA_THREAD_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::forma_str_free;
+    use crate::sync_map::*;
+    use std::ffi::{CStr, CString};
+
+    const INCREMENTS: i64 = 1000;
+
+    /// Increment the "count" entry of the sync map `arg` points to.
+    ///
+    /// The map only locks single operations, so the read-modify-write is
+    /// serialized by `COUNTER_LOCK`.
+    extern "C" fn increment(arg: *mut c_void) -> i64 {
+        static COUNTER_LOCK: Mutex<()> = Mutex::new(());
+        let m = arg as *const FormaSyncMap;
+        let key = CString::new("count").unwrap();
+        for _ in 0..INCREMENTS {
+            let _guard = COUNTER_LOCK.lock().unwrap();
+            let current = forma_sync_map_get(m, key.as_ptr());
+            let n: i64 = unsafe { CStr::from_ptr(current) }
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            forma_str_free(current);
+            let next = CString::new((n + 1).to_string()).unwrap();
+            forma_sync_map_set(m, key.as_ptr(), next.as_ptr());
+        }
+        INCREMENTS
+    }
+
+    // The capability is process-wide, so the tests that change it run in one test
+    #[test]
+    #[cfg_attr(miri, ignore)] // spawns OS threads through FFI function pointers
+    fn test_spawn_denied_then_join_shared_counter() {
+        let m = forma_sync_map_new();
+        let key = CString::new("count").unwrap();
+        let zero = CString::new("0").unwrap();
+        forma_sync_map_set(m, key.as_ptr(), zero.as_ptr());
+
+        assert_eq!(
+            forma_thread_spawn(Some(increment), m.cast()),
+            FORMA_THREAD_DENIED
+        );
+
+        forma_thread_allow();
+        let a = forma_thread_spawn(Some(increment), m.cast());
+        let b = forma_thread_spawn(Some(increment), m.cast());
+        assert!(a > 0 && b > 0 && a != b, "spawn failed: {} {}", a, b);
+        assert_eq!(forma_thread_join(a), INCREMENTS);
+        assert_eq!(forma_thread_join(b), INCREMENTS);
+        assert_eq!(forma_thread_join(a), FORMA_THREAD_BAD_HANDLE);
+
+        let total = forma_sync_map_get(m, key.as_ptr());
+        assert_eq!(
+            unsafe { CStr::from_ptr(total) }.to_str().unwrap(),
+            (2 * INCREMENTS).to_string()
+        );
+        forma_str_free(total);
+        forma_sync_map_free(m);
+
+        assert_eq!(
+            forma_thread_spawn(None, std::ptr::null_mut()),
+            FORMA_THREAD_ERROR
+        );
+    }
+
+    #[test]
+    fn test_join_unknown_handle() {
+        assert_eq!(forma_thread_join(0), FORMA_THREAD_BAD_HANDLE);
+        assert_eq!(forma_thread_join(-5), FORMA_THREAD_BAD_HANDLE);
+    }
+}
@@ -24,6 +24,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg_attr(miri, ignore)] // reads the real-time clock, which Miri isolates
     fn test_time_now_ms_positive() {
         let now = forma_time_now_ms();
         assert!(now > 0, "time_now_ms should return positive value");
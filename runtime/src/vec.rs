@@ -18,7 +18,11 @@ impl FormaVec {
     }
 
     fn grow(&mut self) {
-        let new_cap = if self.capacity == 0 { 4 } else { self.capacity * 2 };
+        let new_cap = if self.capacity == 0 {
+            4
+        } else {
+            self.capacity * 2
+        };
         let new_layout = Self::layout_for(self.elem_size, new_cap);
         let new_data = if self.capacity == 0 {
             unsafe { alloc::alloc(new_layout) }
@@ -109,6 +113,27 @@ pub extern "C" fn forma_vec_set(v: *mut FormaVec, idx: i64, elem: *const u8) {
     }
 }
 
+/// Remove the last element, copying its `elem_size` bytes to `out`.
+/// Returns false, leaving `out` untouched, if the vector is empty.
+#[no_mangle]
+pub extern "C" fn forma_vec_pop(v: *mut FormaVec, out: *mut u8) -> bool {
+    if v.is_null() {
+        return false;
+    }
+    unsafe {
+        let vec = &mut *v;
+        if vec.len == 0 {
+            return false;
+        }
+        vec.len -= 1;
+        if !out.is_null() {
+            let src = vec.data.add(vec.len * vec.elem_size);
+            ptr::copy_nonoverlapping(src, out, vec.elem_size);
+        }
+        true
+    }
+}
+
 /// Free the vector and its backing storage.
 #[no_mangle]
 pub extern "C" fn forma_vec_free(v: *mut FormaVec) {
@@ -161,6 +186,30 @@ mod tests {
         forma_vec_free(v);
     }
 
+    #[test]
+    fn test_pop() {
+        let v = forma_vec_new(std::mem::size_of::<i64>());
+        for val in [1i64, 2, 3] {
+            forma_vec_push(v, &val as *const i64 as *const u8);
+        }
+        let mut out: i64 = 0;
+        assert!(forma_vec_pop(v, &mut out as *mut i64 as *mut u8));
+        assert_eq!(out, 3);
+        assert_eq!(forma_vec_len(v), 2);
+        assert!(forma_vec_get(v, 2).is_null());
+        forma_vec_free(v);
+    }
+
+    #[test]
+    fn test_pop_empty() {
+        let v = forma_vec_new(std::mem::size_of::<i64>());
+        let mut out: i64 = -7;
+        assert!(!forma_vec_pop(v, &mut out as *mut i64 as *mut u8));
+        assert_eq!(out, -7);
+        assert_eq!(forma_vec_len(v), 0);
+        forma_vec_free(v);
+    }
+
     #[test]
     fn test_out_of_bounds() {
         let v = forma_vec_new(std::mem::size_of::<i64>());
@@ -174,6 +223,7 @@ mod tests {
         assert_eq!(forma_vec_len(ptr::null()), 0);
         forma_vec_push(ptr::null_mut(), ptr::null());
         assert!(forma_vec_get(ptr::null(), 0).is_null());
+        assert!(!forma_vec_pop(ptr::null_mut(), ptr::null_mut()));
         forma_vec_free(ptr::null_mut()); // should not crash
     }
 }
@@ -510,15 +510,21 @@ impl BorrowChecker {
                 }
             }
 
-            ExprKind::For(_label, pattern, iter, body) => {
+            ExprKind::For(_label, pattern, iter, body, invariants) => {
                 self.check_expr(iter);
                 self.push_scope();
                 self.bind_pattern_for_match(pattern);
+                for invariant in invariants {
+                    self.check_expr(&invariant.condition);
+                }
                 self.check_block(body);
                 self.pop_scope();
             }
 
-            ExprKind::While(_label, cond, body) => {
+            ExprKind::While(_label, cond, body, invariants) => {
+                for invariant in invariants {
+                    self.check_expr(&invariant.condition);
+                }
                 self.check_expr(cond);
                 self.check_block(body);
             }
@@ -0,0 +1,162 @@
+//! Object cache for incremental builds.
+//!
+//! `forma build --incremental <dir>` keeps the object file compiled for each
+//! input under `<dir>/<input>/<key>.o`, where `<input>` identifies the input
+//! file by path and `<key>` is a hash of everything the object depends on:
+//! the source text, imported modules and build flags. A build whose key
+//! already has an object skips code generation and links the cached one.
+//!
+//! Objects are written to a temporary file in the entry directory and then
+//! renamed into place, so parallel builds sharing a cache never see a partly
+//! written object.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sha2::{Digest, Sha256};
+
+/// Distinguishes temporary files written by different stores in one process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Hex-encoded SHA-256 of `parts`, each length-prefixed so that moving bytes
+/// between neighbouring parts changes the hash.
+pub fn content_hash<I, P>(parts: I) -> String
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    let mut hasher = Sha256::new();
+    for part in parts {
+        let part = part.as_ref();
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The cached objects of one input file.
+#[derive(Debug, Clone)]
+pub struct ObjectCache {
+    entry_dir: PathBuf,
+}
+
+impl ObjectCache {
+    /// Open the entry for `input` in the cache directory `dir`, creating the
+    /// directories as needed.
+    pub fn open(dir: &Path, input: &Path) -> io::Result<Self> {
+        let input = fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf());
+        let name = input
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let path_hash = content_hash([input.to_string_lossy().as_bytes()]);
+        let entry_dir = dir.join(format!("{}-{}", name, &path_hash[..16]));
+        fs::create_dir_all(&entry_dir)?;
+        Ok(Self { entry_dir })
+    }
+
+    /// Path the object for `key` is stored at.
+    pub fn object_path(&self, key: &str) -> PathBuf {
+        self.entry_dir.join(format!("{}.o", key))
+    }
+
+    /// The cached object for `key`, if there is one.
+    pub fn lookup(&self, key: &str) -> Option<PathBuf> {
+        let path = self.object_path(key);
+        path.is_file().then_some(path)
+    }
+
+    /// Copy `object` into the cache under `key` and drop the objects cached
+    /// for earlier versions of the input. Returns the cached path.
+    pub fn store(&self, key: &str, object: &Path) -> io::Result<PathBuf> {
+        let target = self.object_path(key);
+        let temp = self.entry_dir.join(format!(
+            ".{}.{}.{}.tmp",
+            key,
+            process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(e) = fs::copy(object, &temp).and_then(|_| fs::rename(&temp, &target)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        for entry in fs::read_dir(&self.entry_dir)?.flatten() {
+            let path = entry.path();
+            if path != target && path.extension().is_some_and(|ext| ext == "o") {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash_separates_parts() {
+        assert_eq!(content_hash(["ab", "c"]), content_hash(["ab", "c"]));
+        assert_ne!(content_hash(["ab", "c"]), content_hash(["a", "bc"]));
+        assert_eq!(content_hash(["x"]).len(), 64);
+    }
+
+    #[test]
+    fn test_store_lookup_and_invalidate() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.forma");
+        fs::write(&input, "f main() -> Int = 0\n").unwrap();
+        let cache = ObjectCache::open(&dir.path().join("cache"), &input).unwrap();
+
+        let old_key = content_hash(["f main() -> Int = 0\n"]);
+        assert_eq!(cache.lookup(&old_key), None);
+
+        let object = dir.path().join("main.o");
+        fs::write(&object, b"first").unwrap();
+        let cached = cache.store(&old_key, &object).unwrap();
+        assert_eq!(cache.lookup(&old_key), Some(cached.clone()));
+        assert_eq!(fs::read(&cached).unwrap(), b"first");
+
+        // Storing a new version replaces the old entry
+        let new_key = content_hash(["f main() -> Int = 1\n"]);
+        fs::write(&object, b"second").unwrap();
+        cache.store(&new_key, &object).unwrap();
+        assert_eq!(cache.lookup(&old_key), None);
+        assert_eq!(
+            fs::read(cache.lookup(&new_key).unwrap()).unwrap(),
+            b"second"
+        );
+
+        // No temporary files are left behind
+        let leftovers: Vec<_> = fs::read_dir(cached.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_entries_are_per_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let a = ObjectCache::open(&cache_dir, &dir.path().join("a.forma")).unwrap();
+        let b = ObjectCache::open(&cache_dir, &dir.path().join("b.forma")).unwrap();
+
+        let object = dir.path().join("x.o");
+        fs::write(&object, b"obj").unwrap();
+        a.store("k1", &object).unwrap();
+        b.store("k2", &object).unwrap();
+        assert!(a.lookup("k1").is_some());
+        assert!(b.lookup("k2").is_some());
+        assert!(a.lookup("k2").is_none());
+    }
+}
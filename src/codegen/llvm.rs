@@ -20,41 +20,155 @@
 //! ```
 
 use inkwell::OptimizationLevel;
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
+use inkwell::debug_info::{
+    AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DIScope, DWARFEmissionKind,
+    DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::passes::PassBuilderOptions;
 use inkwell::targets::{
-    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+    TargetTriple,
 };
-use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, StructType};
 use inkwell::values::BasicMetadataValueEnum;
 use inkwell::values::{
-    BasicValueEnum, FloatValue, FunctionValue, IntValue, PointerValue, StructValue,
+    BasicValueEnum, FloatValue, FunctionValue, GlobalValue, IntValue, PointerValue, StructValue,
 };
-use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
-use std::collections::HashMap;
+use inkwell::{AddressSpace, DLLStorageClass, FloatPredicate, GlobalVisibility, IntPredicate};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
+use crate::lexer::Span;
+use crate::mir::optimize::find_uninitialized_uses;
 use crate::mir::{
-    BasicBlock, BinOp, Constant, Function, Operand, Program, Rvalue, Statement, StatementKind,
-    Terminator, UnOp,
+    BasicBlock, BinOp, CallConv, Constant, ExternFunction, Function, Global, Operand, Program,
+    Rvalue, Statement, StatementKind, Terminator, UnOp,
 };
 use crate::types::Ty;
 
+pub use crate::mir::mangle_symbol;
+
+/// Aggregates larger than this are returned through a hidden `sret` pointer
+/// rather than by value (two registers on common 64-bit ABIs).
+const SRET_THRESHOLD_BYTES: u64 = 16;
+
 /// Error during LLVM code generation.
 #[derive(Debug)]
-pub struct CodegenError {
-    pub message: String,
+pub enum CodegenError {
+    /// A call or function value names a function that was never declared
+    UnknownFunction(String),
+    /// An rvalue this backend cannot compile yet
+    UnsupportedRvalue(String),
+    /// A value of the wrong LLVM type, e.g. a float where an integer is needed
+    TypeMismatch { expected: String, found: String },
+    /// The LLVM IR builder rejected an instruction
+    BuilderError(String),
+    /// A builtin needs a capability that was not granted
+    CapabilityDenied {
+        capability: String,
+        operation: String,
+    },
+    /// A statement, builtin or value this backend cannot compile yet
+    Unsupported(String),
+    /// The finished module failed LLVM's verifier
+    VerificationFailed(String),
+    /// A function without a body whose return type is not `Unit`
+    MissingBody { function: String, return_ty: String },
+    /// A local that may be read before it is assigned
+    UninitializedLocal { function: String, local: String },
+    /// Two functions share a name
+    DuplicateDefinition { name: String },
+    /// Two functions would be emitted under the same native symbol
+    DuplicateSymbol {
+        symbol: String,
+        first: String,
+        second: String,
+    },
+    /// The native target could not be initialized or optimized for
+    Target(String),
+    /// Writing an output file or running the archiver failed
+    Io(String),
+    /// MIR that breaks an invariant codegen relies on, e.g. a branch to a
+    /// block that does not exist
+    Internal(String),
+}
+
+impl CodegenError {
+    /// Error for a builder call that failed, e.g. `builder("store", e)`.
+    fn builder(operation: &str, err: impl std::fmt::Debug) -> Self {
+        CodegenError::BuilderError(format!("{} failed: {:?}", operation, err))
+    }
 }
 
 impl std::fmt::Display for CodegenError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "codegen error: {}", self.message)
+        write!(f, "codegen error: ")?;
+        match self {
+            CodegenError::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+            CodegenError::UnsupportedRvalue(what) | CodegenError::Unsupported(what) => {
+                write!(f, "{} is not supported in LLVM codegen", what)
+            }
+            CodegenError::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            CodegenError::VerificationFailed(message) => {
+                write!(f, "module verification failed: {}", message)
+            }
+            CodegenError::MissingBody {
+                function,
+                return_ty,
+            } => write!(
+                f,
+                "function `{}` has no body but returns `{}`",
+                function, return_ty
+            ),
+            CodegenError::UninitializedLocal { function, local } => write!(
+                f,
+                "use of possibly-uninitialized variable `{}` in function `{}`",
+                local, function
+            ),
+            CodegenError::DuplicateDefinition { name } => {
+                write!(f, "duplicate definition of function `{}`", name)
+            }
+            CodegenError::DuplicateSymbol {
+                symbol,
+                first,
+                second,
+            } => write!(
+                f,
+                "duplicate definition of symbol `{}` by functions `{}` and `{}`",
+                symbol, first, second
+            ),
+            CodegenError::BuilderError(message)
+            | CodegenError::Target(message)
+            | CodegenError::Io(message)
+            | CodegenError::Internal(message) => write!(f, "{}", message),
+            CodegenError::CapabilityDenied {
+                capability,
+                operation,
+            } => write!(
+                f,
+                "capability '{}' required for operation '{}' (pass --allow-{})",
+                capability, operation, capability
+            ),
+        }
     }
 }
 
 impl std::error::Error for CodegenError {}
 
+/// DWARF debug info state, present when debug info is enabled.
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    compile_unit: DICompileUnit<'ctx>,
+    /// Subprogram scope of the function currently being compiled
+    scope: Option<DIScope<'ctx>>,
+}
+
 /// LLVM code generator for FORMA programs.
 pub struct LLVMCodegen<'ctx> {
     context: &'ctx Context,
@@ -66,10 +180,75 @@ pub struct LLVMCodegen<'ctx> {
     locals: HashMap<usize, PointerValue<'ctx>>,
     /// Map from local variable indices to their LLVM types
     local_types: HashMap<usize, BasicTypeEnum<'ctx>>,
+    /// Interned string literals: identical text shares one private global
+    string_constants: HashMap<String, PointerValue<'ctx>>,
+    /// Interned `b"..."` literals, laid out as `{ i64 len, [N x i8] }`
+    byte_constants: HashMap<Vec<u8>, PointerValue<'ctx>>,
+    /// Locals of MIR type `Char`, compared as unsigned Unicode scalars
+    char_locals: HashSet<usize>,
+    /// Locals whose MIR type is a user enum, laid out by `enum_type`
+    enum_locals: HashSet<usize>,
+    /// Locals of unsigned integer MIR type, zero-extended by widening casts
+    unsigned_locals: HashSet<usize>,
+    /// Pointee types of reference and pointer locals, used when dereferencing
+    pointee_types: HashMap<usize, BasicTypeEnum<'ctx>>,
+    /// Signatures of function-typed locals, used for indirect calls, with the
+    /// aggregate they return through an `sret` pointer, if any
+    fn_ptr_types: HashMap<
+        usize,
+        (
+            Vec<BasicTypeEnum<'ctx>>,
+            FunctionType<'ctx>,
+            Option<StructType<'ctx>>,
+        ),
+    >,
+    /// Functions returning a large aggregate through an `sret` pointer
+    sret_returns: HashMap<String, StructType<'ctx>>,
+    /// The `sret` pointer of the function being compiled and the aggregate
+    /// stored through it
+    sret_slot: Option<(PointerValue<'ctx>, StructType<'ctx>)>,
+    /// Whether the block being compiled ends in a self-call whose result is returned
+    tail_call_block: bool,
+    /// Span of the statement or terminator being compiled, reported by traps
+    trap_span: Option<Span>,
+    /// User enum variant tags: (enum_name, variant_name) -> tag
+    enum_variants: HashMap<(String, String), usize>,
+    /// User enum payload sizes in i64 words: enum_name -> largest variant field count
+    enum_payload_sizes: HashMap<String, usize>,
     /// Current function being compiled
     current_function: Option<FunctionValue<'ctx>>,
     /// Optimization level
     opt_level: OptimizationLevel,
+    /// Target triple to compile for; the host when unset
+    target_triple: Option<String>,
+    /// Relocation model for emitted objects
+    reloc_mode: RelocMode,
+    /// Code model: how far apart code and data may be placed
+    code_model: CodeModel,
+    /// Guard integer `/` and `%` against zero divisors and `MIN / -1`
+    div_checks: bool,
+    /// Check array and vector indices against their length, from `--bounds-checks`
+    bounds_checks: bool,
+    /// Debug info builder, if `-g` was requested
+    debug_info: Option<DebugInfo<'ctx>>,
+    /// Whether to count basic block hits, from `--coverage`
+    coverage: bool,
+    /// Block counters, created by `compile` when coverage is enabled
+    coverage_counters: Option<CoverageCounters<'ctx>>,
+    /// Capabilities granted with `--allow-*`; gated builtins are rejected without them
+    capabilities: HashSet<String>,
+}
+
+/// Globals backing `--coverage` instrumentation.
+struct CoverageCounters<'ctx> {
+    /// `[N x i64]` hit counts, one per basic block of every function
+    counters: GlobalValue<'ctx>,
+    /// `[N x ptr]` of `"<function> bb_<i>"` labels, parallel to `counters`
+    names: GlobalValue<'ctx>,
+    /// Number of counters
+    len: usize,
+    /// Index of each function's `bb_0` counter, keyed by MIR name
+    offsets: HashMap<String, usize>,
 }
 
 impl<'ctx> LLVMCodegen<'ctx> {
@@ -85,8 +264,30 @@ impl<'ctx> LLVMCodegen<'ctx> {
             functions: HashMap::new(),
             locals: HashMap::new(),
             local_types: HashMap::new(),
+            string_constants: HashMap::new(),
+            byte_constants: HashMap::new(),
+            char_locals: HashSet::new(),
+            enum_locals: HashSet::new(),
+            unsigned_locals: HashSet::new(),
+            pointee_types: HashMap::new(),
+            fn_ptr_types: HashMap::new(),
+            sret_returns: HashMap::new(),
+            sret_slot: None,
+            tail_call_block: false,
+            trap_span: None,
+            enum_variants: HashMap::new(),
+            enum_payload_sizes: HashMap::new(),
             current_function: None,
             opt_level: OptimizationLevel::Default,
+            target_triple: None,
+            reloc_mode: RelocMode::Default,
+            code_model: CodeModel::Default,
+            div_checks: true,
+            bounds_checks: false,
+            debug_info: None,
+            coverage: false,
+            coverage_counters: None,
+            capabilities: HashSet::new(),
         }
     }
 
@@ -100,12 +301,353 @@ impl<'ctx> LLVMCodegen<'ctx> {
         };
     }
 
+    /// Compile for `triple` instead of the host.
+    ///
+    /// Stamps the module with the target's triple and data layout so pointer
+    /// sizes and struct layouts match the destination. Call before `compile`.
+    pub fn set_target(&mut self, triple: &str) -> Result<(), CodegenError> {
+        self.target_triple = Some(triple.to_string());
+        let machine = self.target_machine()?;
+        self.module.set_triple(&machine.get_triple());
+        self.module
+            .set_data_layout(&machine.get_target_data().get_data_layout());
+        Ok(())
+    }
+
+    /// Set the relocation model used for object files, e.g. `RelocMode::PIC`
+    /// for shared libraries and PIE executables or `RelocMode::Static` for
+    /// targets without a dynamic loader.
+    pub fn set_reloc_mode(&mut self, mode: RelocMode) {
+        self.reloc_mode = mode;
+    }
+
+    /// Set the code model, e.g. `CodeModel::Large` for programs whose code
+    /// and data do not fit in 2 GiB or `CodeModel::Kernel` for kernel code.
+    pub fn set_code_model(&mut self, model: CodeModel) {
+        self.code_model = model;
+    }
+
+    /// Enable or disable runtime checks on integer division (on by default).
+    ///
+    /// With checks disabled, dividing by zero or `MIN / -1` is undefined
+    /// behaviour and typically dies with SIGFPE.
+    pub fn set_div_checks(&mut self, enabled: bool) {
+        self.div_checks = enabled;
+    }
+
+    /// Enable or disable bounds checks on array and vector indexing (off by
+    /// default).
+    ///
+    /// When enabled, an index outside `0..len` panics through
+    /// `forma_panic_index` with the index and the length. String and `Bytes`
+    /// indexing is always checked; raw pointer indexing never is.
+    pub fn set_bounds_checks(&mut self, enabled: bool) {
+        self.bounds_checks = enabled;
+    }
+
+    /// Count how many times each basic block runs.
+    ///
+    /// The counts are written by `forma_coverage_dump` when `main` returns.
+    /// Must be called before `compile`.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = true;
+    }
+
+    /// Grant a capability (e.g. `"env"`, or `"all"`) to the compiled program.
+    ///
+    /// Builtins gated behind a capability only get their runtime symbol
+    /// declared when it is granted; otherwise `compile` fails. Must be called
+    /// before `compile`.
+    pub fn grant_capability(&mut self, capability: &str) {
+        self.capabilities.insert(capability.to_string());
+    }
+
+    /// Fail unless `capability` (or `"all"`) has been granted.
+    fn require_capability(&self, capability: &str, operation: &str) -> Result<(), CodegenError> {
+        if self.capabilities.contains(capability) || self.capabilities.contains("all") {
+            Ok(())
+        } else {
+            Err(CodegenError::CapabilityDenied {
+                capability: capability.to_string(),
+                operation: operation.to_string(),
+            })
+        }
+    }
+
+    /// Emit DWARF debug info for the given source file.
+    ///
+    /// Must be called before `compile`.
+    pub fn enable_debug_info(&mut self, source_path: &Path) {
+        let filename = source_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let directory = source_path
+            .parent()
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (builder, compile_unit) = self.module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &filename,
+            &directory,
+            "forma",
+            self.opt_level != OptimizationLevel::None,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+
+        let version = self.context.i32_type().const_int(3, false);
+        self.module
+            .add_basic_value_flag("Debug Info Version", FlagBehavior::Warning, version);
+
+        self.debug_info = Some(DebugInfo {
+            builder,
+            compile_unit,
+            scope: None,
+        });
+    }
+
+    /// Create the DWARF subprogram for a function and attach it.
+    fn begin_debug_function(&mut self, func: &Function, fn_value: FunctionValue<'ctx>) {
+        let is_optimized = self.opt_level != OptimizationLevel::None;
+        let Some(di) = self.debug_info.as_mut() else {
+            return;
+        };
+        let file = di.compile_unit.get_file();
+        let line = func.span.map(|s| s.line as u32).unwrap_or(0);
+        let subroutine_type = di
+            .builder
+            .create_subroutine_type(file, None, &[], DIFlags::PUBLIC);
+        let linkage_name = fn_value.get_name().to_string_lossy();
+        let subprogram = di.builder.create_function(
+            di.compile_unit.as_debug_info_scope(),
+            &func.name,
+            Some(&linkage_name),
+            file,
+            line,
+            subroutine_type,
+            false,
+            true,
+            line,
+            DIFlags::PUBLIC,
+            is_optimized,
+        );
+        fn_value.set_subprogram(subprogram);
+        di.scope = Some(subprogram.as_debug_info_scope());
+        self.set_debug_location(Some(func.span.unwrap_or_default()));
+    }
+
+    /// Point subsequent instructions at a source location.
+    ///
+    /// A missing span keeps the previous location so every instruction in
+    /// a function with debug info carries one.
+    fn set_debug_location(&self, span: Option<Span>) {
+        let (Some(di), Some(span)) = (self.debug_info.as_ref(), span) else {
+            return;
+        };
+        let Some(scope) = di.scope else {
+            return;
+        };
+        let location = di.builder.create_debug_location(
+            self.context,
+            span.line as u32,
+            span.column as u32,
+            scope,
+            None,
+        );
+        self.builder.set_current_debug_location(location);
+    }
+
+    /// Call `forma_panic` with `msg` and the current source location, then
+    /// mark the block unreachable. Every trap in compiled code ends here.
+    fn build_panic(&self, msg: PointerValue<'ctx>) -> Result<(), CodegenError> {
+        let (file, line) = self.trap_location();
+        let panic_fn = self.get_or_declare_runtime_function("forma_panic")?;
+        self.builder
+            .build_call(panic_fn, &[msg.into(), file.into(), line.into()], "")
+            .map_err(|e| CodegenError::builder("call", e))?;
+        self.builder
+            .build_unreachable()
+            .map_err(|e| CodegenError::builder("unreachable", e))?;
+        Ok(())
+    }
+
+    /// Source file name and line reported by traps at the current statement.
+    fn trap_location(&self) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let file = match self.module.get_global("__forma_source_file") {
+            Some(global) => global.as_pointer_value(),
+            None => {
+                let name = self.module.get_name().to_string_lossy().into_owned();
+                let text = self.context.const_string(name.as_bytes(), true);
+                let global = self
+                    .module
+                    .add_global(text.get_type(), None, "__forma_source_file");
+                global.set_linkage(inkwell::module::Linkage::Private);
+                global.set_constant(true);
+                global.set_initializer(&text);
+                global.as_pointer_value()
+            }
+        };
+        let line = self
+            .context
+            .i64_type()
+            .const_int(self.trap_span.map_or(0, |s| s.line) as u64, false);
+        (file, line)
+    }
+
+    /// With bounds checks enabled, panic unless `0 <= index < len`. A
+    /// negative index compares as a huge unsigned one.
+    fn build_index_check(
+        &self,
+        index: IntValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) -> Result<(), CodegenError> {
+        if !self.bounds_checks {
+            return Ok(());
+        }
+        let current_fn = self.current_function.ok_or_else(|| {
+            CodegenError::Internal("No current function for bounds check".to_string())
+        })?;
+        let i64_type = self.context.i64_type();
+        let index = self
+            .builder
+            .build_int_s_extend_or_bit_cast(index, i64_type, "index_i64")
+            .map_err(|e| CodegenError::builder("index extend", e))?;
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, index, len, "in_bounds")
+            .map_err(|e| CodegenError::builder("bounds compare", e))?;
+        let oob_block = self.context.append_basic_block(current_fn, "index_oob");
+        let ok_block = self.context.append_basic_block(current_fn, "index_ok");
+        self.builder
+            .build_conditional_branch(in_bounds, ok_block, oob_block)
+            .map_err(|e| CodegenError::builder("cond branch", e))?;
+
+        self.builder.position_at_end(oob_block);
+        let (file, line) = self.trap_location();
+        let panic_fn = self.get_or_declare_runtime_function("forma_panic_index")?;
+        self.builder
+            .build_call(
+                panic_fn,
+                &[index.into(), len.into(), file.into(), line.into()],
+                "",
+            )
+            .map_err(|e| CodegenError::builder("call", e))?;
+        self.builder
+            .build_unreachable()
+            .map_err(|e| CodegenError::builder("unreachable", e))?;
+
+        self.builder.position_at_end(ok_block);
+        Ok(())
+    }
+
+    /// Build a `forma_panic` call with a constant message.
+    fn build_panic_str(&mut self, msg: &str) -> Result<(), CodegenError> {
+        let msg_ptr = self.string_constant(msg);
+        self.build_panic(msg_ptr)
+    }
+
+    /// Pointer to a NUL-terminated global holding `text`. Each distinct
+    /// string is emitted once per module and shared by every use.
+    fn string_constant(&mut self, text: &str) -> PointerValue<'ctx> {
+        if let Some(ptr) = self.string_constants.get(text) {
+            return *ptr;
+        }
+        let value = self.context.const_string(text.as_bytes(), true);
+        let global = self.module.add_global(value.get_type(), None, "str");
+        global.set_linkage(inkwell::module::Linkage::Private);
+        global.set_unnamed_addr(true);
+        global.set_constant(true);
+        global.set_initializer(&value);
+        let ptr = global.as_pointer_value();
+        self.string_constants.insert(text.to_string(), ptr);
+        ptr
+    }
+
+    /// Pointer to a constant length-prefixed buffer holding `bytes`, the
+    /// layout of a `Bytes` value. Like strings, equal literals share a global.
+    fn bytes_constant(&mut self, bytes: &[u8]) -> PointerValue<'ctx> {
+        if let Some(ptr) = self.byte_constants.get(bytes) {
+            return *ptr;
+        }
+        let len = self.context.i64_type().const_int(bytes.len() as u64, false);
+        let data = self.context.const_string(bytes, false);
+        let value = self.context.const_struct(&[len.into(), data.into()], false);
+        let global = self.module.add_global(value.get_type(), None, "bytes");
+        global.set_linkage(inkwell::module::Linkage::Private);
+        global.set_unnamed_addr(true);
+        global.set_constant(true);
+        global.set_initializer(&value);
+        let ptr = global.as_pointer_value();
+        self.byte_constants.insert(bytes.to_vec(), ptr);
+        ptr
+    }
+
+    /// Load the length header of a `Bytes` buffer.
+    fn build_bytes_len(&self, bytes: PointerValue<'ctx>) -> Result<IntValue<'ctx>, CodegenError> {
+        self.builder
+            .build_load(self.context.i64_type(), bytes, "bytes_len")
+            .map(|v| v.into_int_value())
+            .map_err(|e| CodegenError::builder("load", e))
+    }
+
+    /// Load byte `index` of a `Bytes` buffer, panicking when it is out of
+    /// bounds. A negative index compares as a huge unsigned one.
+    fn build_bytes_get(
+        &mut self,
+        bytes: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> Result<IntValue<'ctx>, CodegenError> {
+        let current_fn = self.current_function.ok_or_else(|| {
+            CodegenError::Internal("No current function for byte index".to_string())
+        })?;
+        let len = self.build_bytes_len(bytes)?;
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, index, len, "bytes_in_bounds")
+            .map_err(|e| CodegenError::builder("bounds compare", e))?;
+        let oob_block = self.context.append_basic_block(current_fn, "bytes_oob");
+        let ok_block = self.context.append_basic_block(current_fn, "bytes_ok");
+        self.builder
+            .build_conditional_branch(in_bounds, ok_block, oob_block)
+            .map_err(|e| CodegenError::builder("cond branch", e))?;
+        self.builder.position_at_end(oob_block);
+        self.build_panic_str("byte index out of bounds")?;
+
+        self.builder.position_at_end(ok_block);
+        let i8_type = self.context.i8_type();
+        // The data starts right after the 8-byte length header
+        let offset = self
+            .builder
+            .build_int_add(index, index.get_type().const_int(8, false), "bytes_offset")
+            .map_err(|e| CodegenError::builder("add", e))?;
+        let byte_ptr = unsafe {
+            self.builder
+                .build_gep(i8_type, bytes, &[offset], "byte_ptr")
+                .map_err(|e| CodegenError::builder("gep", e))?
+        };
+        self.builder
+            .build_load(i8_type, byte_ptr, "byte")
+            .map(|v| v.into_int_value())
+            .map_err(|e| CodegenError::builder("load", e))
+    }
+
     /// Safely convert a BasicValueEnum to IntValue.
     fn as_int_value(&self, val: BasicValueEnum<'ctx>) -> Result<IntValue<'ctx>, CodegenError> {
         match val {
             BasicValueEnum::IntValue(i) => Ok(i),
-            _ => Err(CodegenError {
-                message: format!("Expected integer, got {:?}", val.get_type()),
+            _ => Err(CodegenError::TypeMismatch {
+                expected: "integer".to_string(),
+                found: format!("{:?}", val.get_type()),
             }),
         }
     }
@@ -114,8 +656,9 @@ impl<'ctx> LLVMCodegen<'ctx> {
     fn as_float_value(&self, val: BasicValueEnum<'ctx>) -> Result<FloatValue<'ctx>, CodegenError> {
         match val {
             BasicValueEnum::FloatValue(f) => Ok(f),
-            _ => Err(CodegenError {
-                message: format!("Expected float, got {:?}", val.get_type()),
+            _ => Err(CodegenError::TypeMismatch {
+                expected: "float".to_string(),
+                found: format!("{:?}", val.get_type()),
             }),
         }
     }
@@ -127,8 +670,9 @@ impl<'ctx> LLVMCodegen<'ctx> {
     ) -> Result<StructValue<'ctx>, CodegenError> {
         match val {
             BasicValueEnum::StructValue(s) => Ok(s),
-            _ => Err(CodegenError {
-                message: format!("Expected struct, got {:?}", val.get_type()),
+            _ => Err(CodegenError::TypeMismatch {
+                expected: "struct".to_string(),
+                found: format!("{:?}", val.get_type()),
             }),
         }
     }
@@ -140,29 +684,185 @@ impl<'ctx> LLVMCodegen<'ctx> {
     ) -> Result<PointerValue<'ctx>, CodegenError> {
         match val {
             BasicValueEnum::PointerValue(p) => Ok(p),
-            _ => Err(CodegenError {
-                message: format!("Expected pointer, got {:?}", val.get_type()),
+            _ => Err(CodegenError::TypeMismatch {
+                expected: "pointer".to_string(),
+                found: format!("{:?}", val.get_type()),
             }),
         }
     }
 
     /// Compile a MIR program to LLVM IR.
     pub fn compile(&mut self, program: &Program) -> Result<(), CodegenError> {
+        check_duplicate_functions(program)?;
+        self.enum_variants = program.enum_variants.clone();
+        self.enum_payload_sizes = program.enum_payload_sizes.clone();
+
+        // Emit everything in name order rather than hash order, so the same
+        // program always produces the same module
+        let mut externs: Vec<&ExternFunction> = program.externs.values().collect();
+        externs.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut globals: Vec<&Global> = program.globals.values().collect();
+        globals.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut functions: Vec<(&String, &Function)> = program.functions.iter().collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+
+        // Foreign functions are declared under their C names, without bodies
+        for ext in externs {
+            self.declare_extern(ext)?;
+        }
+
+        // Top-level constants become read-only globals under their mangled names
+        for global in globals {
+            let value = self.compile_constant(&global.value);
+            let symbol = mangle_symbol(&global.name);
+            let global_value = self.module.add_global(value.get_type(), None, &symbol);
+            global_value.set_constant(true);
+            global_value.set_initializer(&value);
+        }
+
+        if self.coverage {
+            self.declare_coverage_counters(program);
+        }
+
         // First pass: declare all functions
-        for func in program.functions.values() {
-            self.declare_function(func)?;
+        for &(name, func) in &functions {
+            self.declare_function(name, func)?;
         }
 
         // Second pass: compile function bodies
-        for func in program.functions.values() {
-            self.compile_function(func)?;
+        for (name, func) in functions {
+            self.compile_function(name, func)?;
+        }
+
+        if let Some(di) = &self.debug_info {
+            di.builder.finalize();
+        }
+
+        self.verify_module()
+    }
+
+    /// Run the LLVM verifier over the module, so malformed IR is reported
+    /// here instead of crashing a later pass.
+    pub fn verify_module(&self) -> Result<(), CodegenError> {
+        self.module
+            .verify()
+            .map_err(|e| CodegenError::VerificationFailed(e.to_string().trim_end().to_string()))
+    }
+
+    /// Create the counter and label globals for every block in `program`.
+    ///
+    /// Functions are laid out in name order so the dumped report matches the
+    /// one `forma run --coverage` writes.
+    fn declare_coverage_counters(&mut self, program: &Program) {
+        let mut names: Vec<&String> = program.functions.keys().collect();
+        names.sort();
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let mut offsets = HashMap::new();
+        let mut labels = Vec::new();
+        for name in names {
+            offsets.insert(name.clone(), labels.len());
+            for i in 0..program.functions[name].blocks.len() {
+                let text = format!("{} bb_{}", name, i);
+                let label = self.context.const_string(text.as_bytes(), true);
+                let global = self.module.add_global(label.get_type(), None, "cov_label");
+                global.set_constant(true);
+                global.set_initializer(&label);
+                labels.push(global.as_pointer_value());
+            }
         }
 
+        let len = labels.len();
+        let counter_type = self.context.i64_type().array_type(len as u32);
+        let counters = self
+            .module
+            .add_global(counter_type, None, "__forma_coverage_counters");
+        counters.set_initializer(&counter_type.const_zero());
+
+        let name_array = ptr_type.const_array(&labels);
+        let names = self
+            .module
+            .add_global(name_array.get_type(), None, "__forma_coverage_names");
+        names.set_constant(true);
+        names.set_initializer(&name_array);
+
+        self.coverage_counters = Some(CoverageCounters {
+            counters,
+            names,
+            len,
+            offsets,
+        });
+    }
+
+    /// Increment the hit counter of block `block` in function `name`.
+    fn count_block_hit(&self, name: &str, block: usize) -> Result<(), CodegenError> {
+        let Some(coverage) = &self.coverage_counters else {
+            return Ok(());
+        };
+        let Some(&offset) = coverage.offsets.get(name) else {
+            return Ok(());
+        };
+        let i64_type = self.context.i64_type();
+        let counter_type = i64_type.array_type(coverage.len as u32);
+        let idx = i64_type.const_int((offset + block) as u64, false);
+        let ptr = unsafe {
+            self.builder
+                .build_gep(
+                    counter_type,
+                    coverage.counters.as_pointer_value(),
+                    &[i64_type.const_zero(), idx],
+                    "cov_ptr",
+                )
+                .map_err(|e| CodegenError::builder("gep", e))?
+        };
+        let hits = self
+            .builder
+            .build_load(i64_type, ptr, "cov_hits")
+            .map_err(|e| CodegenError::builder("load", e))?
+            .into_int_value();
+        let hits = self
+            .builder
+            .build_int_add(hits, i64_type.const_int(1, false), "cov_inc")
+            .map_err(|e| CodegenError::builder("add", e))?;
+        self.builder
+            .build_store(ptr, hits)
+            .map_err(|e| CodegenError::builder("store", e))?;
+        Ok(())
+    }
+
+    /// Write the coverage counters out before `main` returns.
+    fn emit_coverage_dump(&self) -> Result<(), CodegenError> {
+        let Some(coverage) = &self.coverage_counters else {
+            return Ok(());
+        };
+        let is_main = self
+            .current_function
+            .is_some_and(|f| f.get_name().to_bytes() == b"main");
+        if !is_main {
+            return Ok(());
+        }
+        let counters = coverage.counters.as_pointer_value();
+        let names = coverage.names.as_pointer_value();
+        let len = self
+            .context
+            .i64_type()
+            .const_int(coverage.len as u64, false);
+        let dump_fn = self.get_or_declare_runtime_function("forma_coverage_dump")?;
+        self.builder
+            .build_call(dump_fn, &[counters.into(), names.into(), len.into()], "")
+            .map_err(|e| CodegenError::builder("call", e))?;
         Ok(())
     }
 
     /// Declare a function (create signature without body).
-    fn declare_function(&mut self, func: &Function) -> Result<(), CodegenError> {
+    ///
+    /// `name` is the function's key in the MIR program, which qualifies impl
+    /// methods as `Type::method`. The LLVM symbol is its mangled form, except
+    /// for the `main` entry point and `@no_mangle` functions.
+    ///
+    /// Only `main` and exported functions keep default visibility (and are
+    /// `dllexport` on Windows), so a library exposes just its public surface.
+    fn declare_function(&mut self, name: &str, func: &Function) -> Result<(), CodegenError> {
         let return_type = self.lower_type(&func.return_ty)?;
         let param_types: Vec<BasicMetadataTypeEnum> = func
             .params
@@ -170,39 +870,100 @@ impl<'ctx> LLVMCodegen<'ctx> {
             .map(|(_, ty)| self.lower_type(ty).map(|t| t.into()))
             .collect::<Result<Vec<_>, _>>()?;
 
-        let fn_type = match return_type {
-            BasicTypeEnum::IntType(t) => t.fn_type(&param_types, false),
-            BasicTypeEnum::FloatType(t) => t.fn_type(&param_types, false),
-            BasicTypeEnum::PointerType(t) => t.fn_type(&param_types, false),
-            _ => {
-                // Default to i64 for unknown types
-                self.context.i64_type().fn_type(&param_types, false)
-            }
+        let return_type = match return_type {
+            BasicTypeEnum::IntType(_)
+            | BasicTypeEnum::FloatType(_)
+            | BasicTypeEnum::PointerType(_)
+            | BasicTypeEnum::StructType(_) => return_type,
+            // Default to i64 for unknown types
+            _ => self.context.i64_type().into(),
         };
+        let (fn_type, sret) = self.function_type(return_type, &param_types);
 
-        let fn_value = self.module.add_function(&func.name, fn_type, None);
-        self.functions.insert(func.name.clone(), fn_value);
+        let symbol = func.symbol(name);
+        let fn_value = self.module.add_function(&symbol, fn_type, None);
+        if let Some(ty) = sret {
+            fn_value.add_attribute(AttributeLoc::Param(0), self.sret_attribute(ty));
+            self.sret_returns.insert(name.to_string(), ty);
+        }
+        if let Some(section) = &func.section {
+            fn_value.set_section(Some(section));
+        }
+        fn_value.set_call_conventions(llvm_call_conv(func.call_conv));
+        let global = fn_value.as_global_value();
+        if func.exported || name == "main" {
+            global.set_visibility(GlobalVisibility::Default);
+            if self.targets_windows() {
+                global.set_dll_storage_class(DLLStorageClass::Export);
+            }
+        } else {
+            global.set_visibility(GlobalVisibility::Hidden);
+        }
+        self.functions.insert(name.to_string(), fn_value);
 
         Ok(())
     }
 
+    /// Declare an `extern "C"` function as an external LLVM symbol.
+    fn declare_extern(&mut self, ext: &ExternFunction) -> Result<(), CodegenError> {
+        let param_types: Vec<BasicMetadataTypeEnum> = ext
+            .params
+            .iter()
+            .map(|ty| self.lower_type(ty).map(|t| t.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fn_type = match ext.return_ty {
+            Ty::Unit => self.context.void_type().fn_type(&param_types, false),
+            ref ty => self.lower_type(ty)?.fn_type(&param_types, false),
+        };
+        let fn_value =
+            self.module
+                .add_function(&ext.name, fn_type, Some(inkwell::module::Linkage::External));
+        fn_value.set_call_conventions(llvm_call_conv(ext.call_conv));
+        self.functions.insert(ext.name.clone(), fn_value);
+        Ok(())
+    }
+
     /// Compile a function body.
-    fn compile_function(&mut self, func: &Function) -> Result<(), CodegenError> {
+    fn compile_function(&mut self, name: &str, func: &Function) -> Result<(), CodegenError> {
         let fn_value = self
             .functions
-            .get(&func.name)
+            .get(name)
             .copied()
-            .ok_or_else(|| CodegenError {
-                message: format!("Function {} not declared", func.name),
-            })?;
+            .ok_or_else(|| CodegenError::UnknownFunction(name.to_string()))?;
+
+        // Without blocks there is no value to return
+        if func.blocks.is_empty() && func.return_ty != Ty::Unit {
+            return Err(CodegenError::MissingBody {
+                function: func.name.clone(),
+                return_ty: func.return_ty.to_string(),
+            });
+        }
+
+        // Allocas are not initialized, so reject reads that may precede a write
+        if let Some(uninit) = find_uninitialized_uses(func).first() {
+            let name = func.locals[uninit.local.0 as usize]
+                .name
+                .clone()
+                .unwrap_or_else(|| uninit.local.to_string());
+            return Err(CodegenError::UninitializedLocal {
+                function: func.name.clone(),
+                local: name,
+            });
+        }
 
         self.current_function = Some(fn_value);
         self.locals.clear();
         self.local_types.clear();
+        self.char_locals.clear();
+        self.enum_locals.clear();
+        self.unsigned_locals.clear();
+        self.pointee_types.clear();
+        self.fn_ptr_types.clear();
 
         // Create entry block
         let entry = self.context.append_basic_block(fn_value, "entry");
         self.builder.position_at_end(entry);
+        self.begin_debug_function(func, fn_value);
 
         // Allocate locals
         for (i, local) in func.locals.iter().enumerate() {
@@ -210,21 +971,51 @@ impl<'ctx> LLVMCodegen<'ctx> {
             let alloca = self
                 .builder
                 .build_alloca(ty, &format!("local_{}", i))
-                .map_err(|e| CodegenError {
-                    message: format!("alloca failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("alloca", e))?;
             self.locals.insert(i, alloca);
             self.local_types.insert(i, ty);
+            if local.ty == Ty::Char {
+                self.char_locals.insert(i);
+            }
+            if let Ty::Named(id, _) = &local.ty
+                && self.enum_payload_sizes.contains_key(&id.name)
+            {
+                self.enum_locals.insert(i);
+            }
+            if local.ty.is_unsigned_integer() {
+                self.unsigned_locals.insert(i);
+            }
+            if let Ty::Ref(inner, _) | Ty::Ptr(inner, _) | Ty::RawPtr(inner) = &local.ty {
+                let pointee = self.lower_type(inner)?;
+                self.pointee_types.insert(i, pointee);
+            }
+            if let Ty::Fn(params, ret) = &local.ty {
+                let param_types: Vec<BasicTypeEnum> = params
+                    .iter()
+                    .map(|ty| self.lower_type(ty))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let meta_types: Vec<BasicMetadataTypeEnum> =
+                    param_types.iter().map(|t| (*t).into()).collect();
+                let (fn_type, sret) = self.function_type(self.lower_type(ret)?, &meta_types);
+                self.fn_ptr_types.insert(i, (param_types, fn_type, sret));
+            }
         }
 
-        // Store function parameters into their locals
-        for (i, param) in fn_value.get_param_iter().enumerate() {
+        // Store function parameters into their locals, after the hidden
+        // `sret` pointer if there is one
+        let sret = self.sret_returns.get(name).copied();
+        self.sret_slot = sret
+            .zip(fn_value.get_first_param())
+            .map(|(ty, slot)| (slot.into_pointer_value(), ty));
+        for (i, param) in fn_value
+            .get_param_iter()
+            .skip(usize::from(sret.is_some()))
+            .enumerate()
+        {
             if let Some(alloca) = self.locals.get(&i) {
                 self.builder
                     .build_store(*alloca, param)
-                    .map_err(|e| CodegenError {
-                        message: format!("store failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("store", e))?;
             }
         }
 
@@ -237,22 +1028,35 @@ impl<'ctx> LLVMCodegen<'ctx> {
             blocks.insert(i, bb);
         }
 
-        // Jump from entry to first block
+        // Jump from entry to first block; a unit function without blocks
+        // returns straight away
         if let Some(&first_block) = blocks.get(&0) {
             self.builder
                 .build_unconditional_branch(first_block)
-                .map_err(|e| CodegenError {
-                    message: format!("branch failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("branch", e))?;
+        } else {
+            let result = match fn_value.get_type().get_return_type() {
+                Some(ty) => self.builder.build_return(Some(&ty.const_zero())),
+                None => self.builder.build_return(None),
+            };
+            result.map_err(|e| CodegenError::builder("return", e))?;
         }
 
         // Compile each block
         for (i, block) in func.blocks.iter().enumerate() {
             if let Some(&bb) = blocks.get(&i) {
                 self.builder.position_at_end(bb);
+                self.count_block_hit(name, i)?;
+                self.tail_call_block = is_self_tail_call(name, func, block);
                 self.compile_block(block, &blocks)?;
             }
         }
+        self.tail_call_block = false;
+
+        if let Some(di) = self.debug_info.as_mut() {
+            di.scope = None;
+            self.builder.unset_current_debug_location();
+        }
 
         self.current_function = None;
         Ok(())
@@ -266,11 +1070,15 @@ impl<'ctx> LLVMCodegen<'ctx> {
     ) -> Result<(), CodegenError> {
         // Compile statements
         for stmt in &block.stmts {
+            self.set_debug_location(stmt.span);
+            self.trap_span = stmt.span;
             self.compile_statement(stmt)?;
         }
 
         // Compile terminator
         if let Some(ref term) = block.terminator {
+            self.set_debug_location(block.terminator_span);
+            self.trap_span = block.terminator_span;
             self.compile_terminator(term, blocks)?;
         }
 
@@ -281,11 +1089,18 @@ impl<'ctx> LLVMCodegen<'ctx> {
     fn compile_statement(&mut self, stmt: &Statement) -> Result<(), CodegenError> {
         match &stmt.kind {
             StatementKind::Assign(local, rvalue) => {
-                let value = self.compile_rvalue(rvalue)?;
                 let idx = local.0 as usize;
                 // Get alloca and type before mutable borrow
                 let alloca = self.locals.get(&idx).copied();
                 let target_ty = self.local_types.get(&idx).copied();
+                let value = match rvalue {
+                    Rvalue::Use(op) => self.compile_operand_as(op, target_ty)?,
+                    Rvalue::EnumField(..) => {
+                        let word = self.compile_rvalue(rvalue)?;
+                        self.unpack_payload_word(word, target_ty)?
+                    }
+                    _ => self.compile_rvalue(rvalue)?,
+                };
 
                 if let Some(alloca) = alloca {
                     // Check if we need to re-type the local (e.g., MIR said Unit/Int but
@@ -307,16 +1122,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
                         let coerced_value = self.coerce_value(value, target_ty)?;
                         self.builder
                             .build_store(alloca, coerced_value)
-                            .map_err(|e| CodegenError {
-                                message: format!("store failed: {:?}", e),
-                            })?;
+                            .map_err(|e| CodegenError::builder("store", e))?;
                     }
                 }
             }
             StatementKind::IndexAssign(_local, _index, _value) => {
-                return Err(CodegenError {
-                    message: "IndexAssign is not yet supported in LLVM codegen".to_string(),
-                });
+                return Err(CodegenError::Unsupported("IndexAssign".to_string()));
             }
             StatementKind::Nop => {}
         }
@@ -328,9 +1139,25 @@ impl<'ctx> LLVMCodegen<'ctx> {
         match rvalue {
             Rvalue::Use(operand) => self.compile_operand(operand),
             Rvalue::BinaryOp(op, left, right) => {
-                let lhs = self.compile_operand(left)?;
-                let rhs = self.compile_operand(right)?;
-                self.compile_binop(*op, lhs, rhs)
+                let mut lhs = self.compile_operand(left)?;
+                let mut rhs = self.compile_operand(right)?;
+                // An integer constant takes the width of the other operand
+                if let (Operand::Constant(Constant::Int(..)), BasicValueEnum::IntValue(other)) =
+                    (left, rhs)
+                {
+                    lhs = self.compile_operand_as(left, Some(other.get_type().into()))?;
+                } else if let (
+                    BasicValueEnum::IntValue(other),
+                    Operand::Constant(Constant::Int(..)),
+                ) = (lhs, right)
+                {
+                    rhs = self.compile_operand_as(right, Some(other.get_type().into()))?;
+                }
+                if self.is_char_operand(left) && self.is_char_operand(right) {
+                    self.compile_char_compare(*op, lhs, rhs)
+                } else {
+                    self.compile_binop(*op, lhs, rhs)
+                }
             }
             Rvalue::UnaryOp(op, operand) => {
                 let val = self.compile_operand(operand)?;
@@ -338,12 +1165,28 @@ impl<'ctx> LLVMCodegen<'ctx> {
             }
             Rvalue::Cast(operand, target_ty) => {
                 let val = self.compile_operand(operand)?;
-                self.compile_cast(val, target_ty)
+                let src_unsigned = self.is_unsigned_operand(operand);
+                self.compile_cast(val, src_unsigned, target_ty)
             }
             Rvalue::Closure {
                 func_name,
                 captures,
             } => self.compile_closure(func_name, captures),
+            // Function value: the address of the named function
+            Rvalue::FnRef(name) => {
+                let fn_value = self
+                    .functions
+                    .get(name)
+                    .ok_or_else(|| CodegenError::UnknownFunction(name.to_string()))?;
+                // Calls through function values use the C convention
+                if fn_value.get_call_conventions() != llvm_call_conv(CallConv::C) {
+                    return Err(CodegenError::Unsupported(format!(
+                        "using function `{}`, which does not use the C calling convention, as a value",
+                        name
+                    )));
+                }
+                Ok(fn_value.as_global_value().as_pointer_value().into())
+            }
             // Tuple construction
             Rvalue::Tuple(elements) => {
                 let mut values = Vec::new();
@@ -358,9 +1201,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     tuple = self
                         .builder
                         .build_insert_value(tuple, val, i as u32, "tuple_insert")
-                        .map_err(|e| CodegenError {
-                            message: format!("Failed to insert tuple value: {:?}", e),
-                        })?
+                        .map_err(|e| CodegenError::builder("insert tuple value", e))?
                         .into_struct_value();
                 }
                 Ok(tuple.into())
@@ -368,9 +1209,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
             // Array construction
             Rvalue::Array(elements) => {
                 if elements.is_empty() {
-                    return Err(CodegenError {
-                        message: "Empty array not supported in LLVM codegen".to_string(),
-                    });
+                    return Err(CodegenError::UnsupportedRvalue("empty array".to_string()));
                 }
                 let first = self.compile_operand(&elements[0])?;
                 let elem_type = first.get_type();
@@ -378,9 +1217,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let array_alloca = self
                     .builder
                     .build_alloca(array_type, "array")
-                    .map_err(|e| CodegenError {
-                        message: format!("array alloca failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("array alloca", e))?;
 
                 for (i, elem) in elements.iter().enumerate() {
                     let val = self.compile_operand(elem)?;
@@ -393,33 +1230,25 @@ impl<'ctx> LLVMCodegen<'ctx> {
                                 &[self.context.i32_type().const_zero(), idx],
                                 "elem_ptr",
                             )
-                            .map_err(|e| CodegenError {
-                                message: format!("array gep failed: {:?}", e),
-                            })?
+                            .map_err(|e| CodegenError::builder("array gep", e))?
                     };
                     self.builder
                         .build_store(ptr, val)
-                        .map_err(|e| CodegenError {
-                            message: format!("array store failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("array store", e))?;
                 }
                 Ok(self
                     .builder
                     .build_load(array_type, array_alloca, "array_val")
-                    .map_err(|e| CodegenError {
-                        message: format!("array load failed: {:?}", e),
-                    })?)
+                    .map_err(|e| CodegenError::builder("array load", e))?)
             }
             // Field access by name - requires struct type info to map name to index
             Rvalue::Field(_base, field_name) => {
                 // TODO: Need struct type info to map field name to index
                 // For now, return error since we can't resolve field names without type info
-                Err(CodegenError {
-                    message: format!(
-                        "Field access by name '{}' not yet supported in LLVM codegen - need type info",
-                        field_name
-                    ),
-                })
+                Err(CodegenError::UnsupportedRvalue(format!(
+                    "field access by name '{}'",
+                    field_name
+                )))
             }
             // Tuple field access
             Rvalue::TupleField(base, idx) => {
@@ -427,9 +1256,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let struct_val = self.as_struct_value(base_val)?;
                 self.builder
                     .build_extract_value(struct_val, *idx as u32, "tuple_field")
-                    .map_err(|e| CodegenError {
-                        message: format!("Failed to extract tuple field: {:?}", e),
-                    })
+                    .map_err(|e| CodegenError::builder("extract tuple field", e))
             }
             // Struct construction
             Rvalue::Struct(_name, fields) => {
@@ -444,9 +1271,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     struct_val = self
                         .builder
                         .build_insert_value(struct_val, val, i as u32, "struct_insert")
-                        .map_err(|e| CodegenError {
-                            message: format!("Failed to insert struct value: {:?}", e),
-                        })?
+                        .map_err(|e| CodegenError::builder("insert struct value", e))?
                         .into_struct_value();
                 }
                 Ok(struct_val.into())
@@ -458,33 +1283,41 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     // Return the pointer to the local
                     Ok((*alloca).into())
                 } else {
-                    Err(CodegenError {
-                        message: format!("Cannot create reference to unknown local: {}", local.0),
-                    })
+                    Err(CodegenError::Internal(format!(
+                        "Cannot create reference to unknown local: {}",
+                        local.0
+                    )))
                 }
             }
             // Dereference: *operand
             Rvalue::Deref(operand) => {
                 let ptr_val = self.compile_operand(operand)?;
                 let ptr = self.as_pointer_value(ptr_val)?;
-                // Load the value through the pointer
-                // Default to i64 type if we don't know the pointee type
-                let pointee_ty = self.context.i64_type();
+                // Load the value through the pointer, defaulting to i64 when
+                // the operand's pointee type is unknown
+                let pointee_ty = match operand {
+                    Operand::Local(local) => self.pointee_types.get(&(local.0 as usize)).copied(),
+                    _ => None,
+                }
+                .unwrap_or_else(|| self.context.i64_type().into());
                 self.builder
                     .build_load(pointee_ty, ptr, "deref")
-                    .map_err(|e| CodegenError {
-                        message: format!("deref load failed: {:?}", e),
-                    })
+                    .map_err(|e| CodegenError::builder("deref load", e))
             }
             // Enum construction: Some(42), None, Ok(x), Err(e), etc.
+            Rvalue::Enum {
+                type_name,
+                variant,
+                fields,
+            } if self.enum_payload_sizes.contains_key(type_name) => {
+                self.compile_user_enum(type_name, variant, fields)
+            }
             Rvalue::Enum {
                 type_name: _,
-                variant: _,
+                variant,
                 fields,
             } => {
-                // Enum layout: { i32 discriminant, field0, field1, ... }
-                // For simplicity, compute discriminant from variant name hash
-                // In practice, we'd use a proper enum registry
+                // Built-in Option/Result layout: { i32 discriminant, field0, field1, ... }
                 let i32_type = self.context.i32_type();
                 let i64_type = self.context.i64_type();
 
@@ -507,14 +1340,13 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let enum_type = self.context.struct_type(&field_types, false);
                 let mut enum_val = enum_type.get_undef();
 
-                // Insert discriminant (use 0 for now - proper mapping would use enum registry)
-                let disc_val = i32_type.const_int(0, false);
+                // Discriminants match the interpreter: None/Ok are 0, Some/Err are 1
+                let tag = matches!(variant.as_str(), "Some" | "Err") as u64;
+                let disc_val = i32_type.const_int(tag, false);
                 enum_val = self
                     .builder
                     .build_insert_value(enum_val, disc_val, 0, "enum_disc")
-                    .map_err(|e| CodegenError {
-                        message: format!("insert discriminant failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("insert discriminant", e))?
                     .into_struct_value();
 
                 // Insert fields
@@ -522,9 +1354,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     enum_val = self
                         .builder
                         .build_insert_value(enum_val, fv, (i + 1) as u32, "enum_field")
-                        .map_err(|e| CodegenError {
-                            message: format!("insert enum field failed: {:?}", e),
-                        })?
+                        .map_err(|e| CodegenError::builder("insert enum field", e))?
                         .into_struct_value();
                 }
 
@@ -543,26 +1373,20 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     let enum_val = self
                         .builder
                         .build_load(local_ty, *alloca, "enum_val")
-                        .map_err(|e| CodegenError {
-                            message: format!("load enum failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("load enum", e))?;
 
                     // Try to extract discriminant (first field, i32)
                     if let BasicValueEnum::StructValue(sv) = enum_val {
-                        let disc =
-                            self.builder
-                                .build_extract_value(sv, 0, "disc")
-                                .map_err(|e| CodegenError {
-                                    message: format!("extract discriminant failed: {:?}", e),
-                                })?;
+                        let disc = self
+                            .builder
+                            .build_extract_value(sv, 0, "disc")
+                            .map_err(|e| CodegenError::builder("extract discriminant", e))?;
                         // Extend i32 to i64 for consistency
                         if let BasicValueEnum::IntValue(iv) = disc {
                             let extended = self
                                 .builder
                                 .build_int_z_extend(iv, self.context.i64_type(), "disc_ext")
-                                .map_err(|e| CodegenError {
-                                    message: format!("extend discriminant failed: {:?}", e),
-                                })?;
+                                .map_err(|e| CodegenError::builder("extend discriminant", e))?;
                             Ok(extended.into())
                         } else {
                             Ok(disc)
@@ -572,9 +1396,10 @@ impl<'ctx> LLVMCodegen<'ctx> {
                         Ok(self.context.i64_type().const_zero().into())
                     }
                 } else {
-                    Err(CodegenError {
-                        message: format!("Cannot get discriminant of unknown local: {}", local.0),
-                    })
+                    Err(CodegenError::Internal(format!(
+                        "Cannot get discriminant of unknown local: {}",
+                        local.0
+                    )))
                 }
             }
             // Extract field from enum variant
@@ -589,28 +1414,39 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     let enum_val = self
                         .builder
                         .build_load(local_ty, *alloca, "enum_val")
-                        .map_err(|e| CodegenError {
-                            message: format!("load enum failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("load enum", e))?;
 
-                    if let BasicValueEnum::StructValue(sv) = enum_val {
+                    if let BasicValueEnum::StructValue(sv) = enum_val
+                        && self.enum_locals.contains(&idx)
+                    {
+                        // User enum: field 1 is the payload array of i64 words
+                        let payload = self
+                            .builder
+                            .build_extract_value(sv, 1, "enum_payload")
+                            .map_err(|e| CodegenError::builder("extract enum payload", e))?
+                            .into_array_value();
+                        let field = self
+                            .builder
+                            .build_extract_value(payload, *field_idx as u32, "enum_field")
+                            .map_err(|e| CodegenError::builder("extract enum field", e))?;
+                        Ok(field)
+                    } else if let BasicValueEnum::StructValue(sv) = enum_val {
                         // Field 0 is discriminant, so add 1 to field_idx
                         let field = self
                             .builder
                             .build_extract_value(sv, (*field_idx + 1) as u32, "enum_field")
-                            .map_err(|e| CodegenError {
-                                message: format!("extract enum field failed: {:?}", e),
-                            })?;
+                            .map_err(|e| CodegenError::builder("extract enum field", e))?;
                         Ok(field)
                     } else {
-                        Err(CodegenError {
-                            message: "EnumField on non-struct value".to_string(),
-                        })
+                        Err(CodegenError::Internal(
+                            "EnumField on non-struct value".to_string(),
+                        ))
                     }
                 } else {
-                    Err(CodegenError {
-                        message: format!("Cannot extract field from unknown local: {}", local.0),
-                    })
+                    Err(CodegenError::Internal(format!(
+                        "Cannot extract field from unknown local: {}",
+                        local.0
+                    )))
                 }
             }
             // Index access: array[index]
@@ -624,50 +1460,44 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     // For array values, we need to alloca, store, then GEP
                     let arr_ty = arr.get_type();
                     let elem_ty = arr_ty.get_element_type();
-                    let alloca =
-                        self.builder
-                            .build_alloca(arr_ty, "arr_tmp")
-                            .map_err(|e| CodegenError {
-                                message: format!("alloca failed: {:?}", e),
-                            })?;
+                    let len = self
+                        .context
+                        .i64_type()
+                        .const_int(arr_ty.len() as u64, false);
+                    self.build_index_check(idx_int, len)?;
+                    let alloca = self
+                        .builder
+                        .build_alloca(arr_ty, "arr_tmp")
+                        .map_err(|e| CodegenError::builder("alloca", e))?;
                     self.builder
                         .build_store(alloca, arr)
-                        .map_err(|e| CodegenError {
-                            message: format!("store failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("store", e))?;
 
                     let zero = self.context.i64_type().const_zero();
                     let gep = unsafe {
                         self.builder
                             .build_gep(arr_ty, alloca, &[zero, idx_int], "elem_ptr")
-                            .map_err(|e| CodegenError {
-                                message: format!("gep failed: {:?}", e),
-                            })?
+                            .map_err(|e| CodegenError::builder("gep", e))?
                     };
                     self.builder
                         .build_load(elem_ty, gep, "elem")
-                        .map_err(|e| CodegenError {
-                            message: format!("load failed: {:?}", e),
-                        })
+                        .map_err(|e| CodegenError::builder("load", e))
                 } else if let BasicValueEnum::PointerValue(ptr) = base_val {
                     // Pointer indexing - assume i64 elements
                     let elem_ty = self.context.i64_type();
                     let gep = unsafe {
                         self.builder
                             .build_gep(elem_ty, ptr, &[idx_int], "elem_ptr")
-                            .map_err(|e| CodegenError {
-                                message: format!("gep failed: {:?}", e),
-                            })?
+                            .map_err(|e| CodegenError::builder("gep", e))?
                     };
                     self.builder
                         .build_load(elem_ty, gep, "elem")
-                        .map_err(|e| CodegenError {
-                            message: format!("load failed: {:?}", e),
-                        })
+                        .map_err(|e| CodegenError::builder("load", e))
                 } else {
-                    Err(CodegenError {
-                        message: format!("Index on unsupported type: {:?}", base_val.get_type()),
-                    })
+                    Err(CodegenError::UnsupportedRvalue(format!(
+                        "indexing {:?}",
+                        base_val.get_type()
+                    )))
                 }
             }
         }
@@ -686,145 +1516,220 @@ impl<'ctx> LLVMCodegen<'ctx> {
                         .unwrap_or_else(|| self.context.i64_type().into());
                     self.builder
                         .build_load(ty, *alloca, "load")
-                        .map_err(|e| CodegenError {
-                            message: format!("load failed: {:?}", e),
-                        })
+                        .map_err(|e| CodegenError::builder("load", e))
                 } else {
-                    Err(CodegenError {
-                        message: format!("Unknown local: {}", local.0),
-                    })
+                    Err(CodegenError::Internal(format!(
+                        "Unknown local: {}",
+                        local.0
+                    )))
                 }
             }
-            Operand::Constant(constant) => {
-                match constant {
-                    Constant::Int(n) => {
-                        let val = self.context.i64_type().const_int(*n as u64, true);
-                        Ok(val.into())
-                    }
-                    Constant::Bool(b) => {
-                        let val = self
-                            .context
-                            .bool_type()
-                            .const_int(if *b { 1 } else { 0 }, false);
-                        Ok(val.into())
-                    }
-                    Constant::Float(f) => {
-                        let val = self.context.f64_type().const_float(*f);
-                        Ok(val.into())
-                    }
-                    Constant::Char(c) => {
-                        let val = self.context.i32_type().const_int(*c as u64, false);
-                        Ok(val.into())
-                    }
-                    Constant::Str(s) => {
-                        // Create a global string constant
-                        let str_val = self.context.const_string(s.as_bytes(), true);
-                        let global = self.module.add_global(str_val.get_type(), None, "str");
-                        global.set_constant(true);
-                        global.set_initializer(&str_val);
-                        Ok(global.as_pointer_value().into())
-                    }
-                    Constant::Unit => {
-                        // Unit type as i8 zero
-                        Ok(self.context.i8_type().const_zero().into())
-                    }
-                }
+            Operand::Constant(constant) => Ok(self.compile_constant(constant)),
+        }
+    }
+
+    /// Build the LLVM constant for a MIR constant.
+    fn compile_constant(&mut self, constant: &Constant) -> BasicValueEnum<'ctx> {
+        match constant {
+            Constant::Int(n, ty) => self
+                .context
+                .custom_width_int_type(ty.numeric_bits().unwrap_or(64))
+                .const_int(*n as u64, !ty.is_unsigned_integer())
+                .into(),
+            Constant::Bool(b) => self
+                .context
+                .bool_type()
+                .const_int(if *b { 1 } else { 0 }, false)
+                .into(),
+            Constant::Float(f) => self.context.f64_type().const_float(*f).into(),
+            Constant::Char(c) => self.context.i32_type().const_int(*c as u64, false).into(),
+            Constant::Str(s) => self.string_constant(s).into(),
+            Constant::Bytes(bytes) => self.bytes_constant(bytes).into(),
+            // Unit type as i8 zero
+            Constant::Unit => self.context.i8_type().const_zero().into(),
+        }
+    }
+
+    /// Whether an operand is a `Char`, which compares as unsigned.
+    fn is_char_operand(&self, operand: &Operand) -> bool {
+        match operand {
+            Operand::Constant(Constant::Char(_)) => true,
+            Operand::Local(l) | Operand::Copy(l) | Operand::Move(l) => {
+                self.char_locals.contains(&(l.0 as usize))
             }
+            Operand::Constant(_) => false,
         }
     }
 
-    /// Compile a binary operation.
-    fn compile_binop(
+    /// Whether an integer operand should be zero-extended: unsigned integers,
+    /// chars and bools.
+    fn is_unsigned_operand(&self, operand: &Operand) -> bool {
+        match operand {
+            Operand::Constant(Constant::Char(_) | Constant::Bool(_)) => true,
+            Operand::Local(l) | Operand::Copy(l) | Operand::Move(l) => {
+                let idx = l.0 as usize;
+                self.unsigned_locals.contains(&idx) || self.char_locals.contains(&idx)
+            }
+            Operand::Constant(_) => false,
+        }
+    }
+
+    /// Compare two chars. Unicode scalars are unsigned, so ordering uses the
+    /// unsigned predicates; anything other than a comparison falls back to
+    /// integer arithmetic.
+    fn compile_char_compare(
         &mut self,
         op: BinOp,
         lhs: BasicValueEnum<'ctx>,
         rhs: BasicValueEnum<'ctx>,
     ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
-        // Check if operands are floats
-        if lhs.is_float_value() && rhs.is_float_value() {
-            let lhs_float = self.as_float_value(lhs)?;
-            let rhs_float = self.as_float_value(rhs)?;
-            return match op {
-                BinOp::Add => Ok(self
-                    .builder
-                    .build_float_add(lhs_float, rhs_float, "fadd")
-                    .map_err(|e| CodegenError {
-                        message: format!("fadd failed: {:?}", e),
-                    })?
-                    .into()),
-                BinOp::Sub => Ok(self
-                    .builder
-                    .build_float_sub(lhs_float, rhs_float, "fsub")
-                    .map_err(|e| CodegenError {
-                        message: format!("fsub failed: {:?}", e),
-                    })?
-                    .into()),
-                BinOp::Mul => Ok(self
-                    .builder
-                    .build_float_mul(lhs_float, rhs_float, "fmul")
-                    .map_err(|e| CodegenError {
-                        message: format!("fmul failed: {:?}", e),
-                    })?
-                    .into()),
-                BinOp::Div => Ok(self
-                    .builder
-                    .build_float_div(lhs_float, rhs_float, "fdiv")
-                    .map_err(|e| CodegenError {
-                        message: format!("fdiv failed: {:?}", e),
-                    })?
+        let predicate = match op {
+            BinOp::Eq => IntPredicate::EQ,
+            BinOp::Ne => IntPredicate::NE,
+            BinOp::Lt => IntPredicate::ULT,
+            BinOp::Le => IntPredicate::ULE,
+            BinOp::Gt => IntPredicate::UGT,
+            BinOp::Ge => IntPredicate::UGE,
+            _ => return self.compile_binop(op, lhs, rhs),
+        };
+        let lhs_int = self.as_int_value(lhs)?;
+        let rhs_int = self.as_int_value(rhs)?;
+        Ok(self
+            .builder
+            .build_int_compare(predicate, lhs_int, rhs_int, "charcmp")
+            .map_err(|e| CodegenError::builder("char compare", e))?
+            .into())
+    }
+
+    /// Branch to a panic when `lhs op rhs` would divide by zero or overflow.
+    ///
+    /// Leaves the builder positioned in the block where the division is safe.
+    fn emit_div_guard(
+        &mut self,
+        op: BinOp,
+        lhs: IntValue<'ctx>,
+        rhs: IntValue<'ctx>,
+    ) -> Result<(), CodegenError> {
+        let current_fn = self.current_function.ok_or_else(|| {
+            CodegenError::Internal("No current function for division check".to_string())
+        })?;
+        let (zero_msg, overflow_msg) = match op {
+            BinOp::Rem => ("remainder by zero", "integer overflow in remainder"),
+            _ => ("division by zero", "integer overflow in division"),
+        };
+        let int_type = rhs.get_type();
+        let min = int_type.const_int(1u64 << (int_type.get_bit_width() - 1), false);
+
+        let is_zero = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, rhs, int_type.const_zero(), "div_zero")
+            .map_err(|e| CodegenError::builder("div check compare", e))?;
+        let is_min = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, lhs, min, "div_min")
+            .map_err(|e| CodegenError::builder("div check compare", e))?;
+        let is_neg_one = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, rhs, int_type.const_all_ones(), "div_neg1")
+            .map_err(|e| CodegenError::builder("div check compare", e))?;
+        let overflows = self
+            .builder
+            .build_and(is_min, is_neg_one, "div_overflow")
+            .map_err(|e| CodegenError::builder("div check and", e))?;
+
+        let zero_block = self.context.append_basic_block(current_fn, "div_by_zero");
+        let nonzero_block = self.context.append_basic_block(current_fn, "div_nonzero");
+        let overflow_block = self.context.append_basic_block(current_fn, "div_overflow");
+        let ok_block = self.context.append_basic_block(current_fn, "div_ok");
+
+        self.builder
+            .build_conditional_branch(is_zero, zero_block, nonzero_block)
+            .map_err(|e| CodegenError::builder("cond branch", e))?;
+        self.builder.position_at_end(nonzero_block);
+        self.builder
+            .build_conditional_branch(overflows, overflow_block, ok_block)
+            .map_err(|e| CodegenError::builder("cond branch", e))?;
+
+        for (block, msg) in [(zero_block, zero_msg), (overflow_block, overflow_msg)] {
+            self.builder.position_at_end(block);
+            self.build_panic_str(msg)?;
+        }
+
+        self.builder.position_at_end(ok_block);
+        Ok(())
+    }
+
+    /// Compile a binary operation.
+    fn compile_binop(
+        &mut self,
+        op: BinOp,
+        lhs: BasicValueEnum<'ctx>,
+        rhs: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        // Check if operands are floats
+        if lhs.is_float_value() && rhs.is_float_value() {
+            let lhs_float = self.as_float_value(lhs)?;
+            let rhs_float = self.as_float_value(rhs)?;
+            return match op {
+                BinOp::Add => Ok(self
+                    .builder
+                    .build_float_add(lhs_float, rhs_float, "fadd")
+                    .map_err(|e| CodegenError::builder("fadd", e))?
+                    .into()),
+                BinOp::Sub => Ok(self
+                    .builder
+                    .build_float_sub(lhs_float, rhs_float, "fsub")
+                    .map_err(|e| CodegenError::builder("fsub", e))?
+                    .into()),
+                BinOp::Mul => Ok(self
+                    .builder
+                    .build_float_mul(lhs_float, rhs_float, "fmul")
+                    .map_err(|e| CodegenError::builder("fmul", e))?
+                    .into()),
+                BinOp::Div => Ok(self
+                    .builder
+                    .build_float_div(lhs_float, rhs_float, "fdiv")
+                    .map_err(|e| CodegenError::builder("fdiv", e))?
                     .into()),
                 BinOp::Rem => Ok(self
                     .builder
                     .build_float_rem(lhs_float, rhs_float, "frem")
-                    .map_err(|e| CodegenError {
-                        message: format!("frem failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("frem", e))?
                     .into()),
                 BinOp::Eq => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::OEQ, lhs_float, rhs_float, "feq")
-                    .map_err(|e| CodegenError {
-                        message: format!("feq failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("feq", e))?
                     .into()),
                 BinOp::Ne => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::ONE, lhs_float, rhs_float, "fne")
-                    .map_err(|e| CodegenError {
-                        message: format!("fne failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("fne", e))?
                     .into()),
                 BinOp::Lt => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::OLT, lhs_float, rhs_float, "flt")
-                    .map_err(|e| CodegenError {
-                        message: format!("flt failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("flt", e))?
                     .into()),
                 BinOp::Le => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::OLE, lhs_float, rhs_float, "fle")
-                    .map_err(|e| CodegenError {
-                        message: format!("fle failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("fle", e))?
                     .into()),
                 BinOp::Gt => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::OGT, lhs_float, rhs_float, "fgt")
-                    .map_err(|e| CodegenError {
-                        message: format!("fgt failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("fgt", e))?
                     .into()),
                 BinOp::Ge => Ok(self
                     .builder
                     .build_float_compare(FloatPredicate::OGE, lhs_float, rhs_float, "fge")
-                    .map_err(|e| CodegenError {
-                        message: format!("fge failed: {:?}", e),
-                    })?
+                    .map_err(|e| CodegenError::builder("fge", e))?
                     .into()),
-                _ => Err(CodegenError {
-                    message: format!("Float operation not supported: {:?}", op),
-                }),
+                _ => Err(CodegenError::UnsupportedRvalue(format!(
+                    "float operation {:?}",
+                    op
+                ))),
             };
         }
 
@@ -832,117 +1737,84 @@ impl<'ctx> LLVMCodegen<'ctx> {
         let lhs_int = self.as_int_value(lhs)?;
         let rhs_int = self.as_int_value(rhs)?;
 
+        if self.div_checks && matches!(op, BinOp::Div | BinOp::Rem) {
+            self.emit_div_guard(op, lhs_int, rhs_int)?;
+        }
+
         let result: IntValue = match op {
             BinOp::Add => self
                 .builder
                 .build_int_add(lhs_int, rhs_int, "add")
-                .map_err(|e| CodegenError {
-                    message: format!("add failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("add", e))?,
             BinOp::Sub => self
                 .builder
                 .build_int_sub(lhs_int, rhs_int, "sub")
-                .map_err(|e| CodegenError {
-                    message: format!("sub failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("sub", e))?,
             BinOp::Mul => self
                 .builder
                 .build_int_mul(lhs_int, rhs_int, "mul")
-                .map_err(|e| CodegenError {
-                    message: format!("mul failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("mul", e))?,
             BinOp::Div => self
                 .builder
                 .build_int_signed_div(lhs_int, rhs_int, "div")
-                .map_err(|e| CodegenError {
-                    message: format!("div failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("div", e))?,
             BinOp::Rem => self
                 .builder
                 .build_int_signed_rem(lhs_int, rhs_int, "rem")
-                .map_err(|e| CodegenError {
-                    message: format!("rem failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("rem", e))?,
             BinOp::Eq => self
                 .builder
                 .build_int_compare(IntPredicate::EQ, lhs_int, rhs_int, "eq")
-                .map_err(|e| CodegenError {
-                    message: format!("eq failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("eq", e))?,
             BinOp::Ne => self
                 .builder
                 .build_int_compare(IntPredicate::NE, lhs_int, rhs_int, "ne")
-                .map_err(|e| CodegenError {
-                    message: format!("ne failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("ne", e))?,
             BinOp::Lt => self
                 .builder
                 .build_int_compare(IntPredicate::SLT, lhs_int, rhs_int, "lt")
-                .map_err(|e| CodegenError {
-                    message: format!("lt failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("lt", e))?,
             BinOp::Le => self
                 .builder
                 .build_int_compare(IntPredicate::SLE, lhs_int, rhs_int, "le")
-                .map_err(|e| CodegenError {
-                    message: format!("le failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("le", e))?,
             BinOp::Gt => self
                 .builder
                 .build_int_compare(IntPredicate::SGT, lhs_int, rhs_int, "gt")
-                .map_err(|e| CodegenError {
-                    message: format!("gt failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("gt", e))?,
             BinOp::Ge => self
                 .builder
                 .build_int_compare(IntPredicate::SGE, lhs_int, rhs_int, "ge")
-                .map_err(|e| CodegenError {
-                    message: format!("ge failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("ge", e))?,
             BinOp::And => self
                 .builder
                 .build_and(lhs_int, rhs_int, "and")
-                .map_err(|e| CodegenError {
-                    message: format!("and failed: {:?}", e),
-                })?,
-            BinOp::Or => {
-                self.builder
-                    .build_or(lhs_int, rhs_int, "or")
-                    .map_err(|e| CodegenError {
-                        message: format!("or failed: {:?}", e),
-                    })?
-            }
+                .map_err(|e| CodegenError::builder("and", e))?,
+            BinOp::Or => self
+                .builder
+                .build_or(lhs_int, rhs_int, "or")
+                .map_err(|e| CodegenError::builder("or", e))?,
             // Bitwise operations
             BinOp::BitAnd => self
                 .builder
                 .build_and(lhs_int, rhs_int, "bitand")
-                .map_err(|e| CodegenError {
-                    message: format!("bitand failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("bitand", e))?,
             BinOp::BitOr => self
                 .builder
                 .build_or(lhs_int, rhs_int, "bitor")
-                .map_err(|e| CodegenError {
-                    message: format!("bitor failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("bitor", e))?,
             BinOp::BitXor => self
                 .builder
                 .build_xor(lhs_int, rhs_int, "bitxor")
-                .map_err(|e| CodegenError {
-                    message: format!("bitxor failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("bitxor", e))?,
             BinOp::Shl => self
                 .builder
                 .build_left_shift(lhs_int, rhs_int, "shl")
-                .map_err(|e| CodegenError {
-                    message: format!("shl failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("shl", e))?,
             BinOp::Shr => self
                 .builder
                 .build_right_shift(lhs_int, rhs_int, false, "shr")
-                .map_err(|e| CodegenError {
-                    message: format!("shr failed: {:?}", e),
-                })?,
+                .map_err(|e| CodegenError::builder("shr", e))?,
         };
 
         Ok(result.into())
@@ -959,16 +1831,11 @@ impl<'ctx> LLVMCodegen<'ctx> {
             UnOp::Neg => self
                 .builder
                 .build_int_neg(int_val, "neg")
-                .map_err(|e| CodegenError {
-                    message: format!("neg failed: {:?}", e),
-                })?,
-            UnOp::Not | UnOp::BitNot => {
-                self.builder
-                    .build_not(int_val, "not")
-                    .map_err(|e| CodegenError {
-                        message: format!("not failed: {:?}", e),
-                    })?
-            }
+                .map_err(|e| CodegenError::builder("neg", e))?,
+            UnOp::Not | UnOp::BitNot => self
+                .builder
+                .build_not(int_val, "not")
+                .map_err(|e| CodegenError::builder("not", e))?,
         };
         Ok(result.into())
     }
@@ -1002,59 +1869,38 @@ impl<'ctx> LLVMCodegen<'ctx> {
         });
 
         // Calculate size and allocate
-        let env_size = env_struct_type.size_of().ok_or_else(|| CodegenError {
-            message: "Cannot get size of unsized closure environment".to_string(),
+        let env_size = env_struct_type.size_of().ok_or_else(|| {
+            CodegenError::Internal("Cannot get size of unsized closure environment".to_string())
         })?;
         let malloc_result = self
             .builder
             .build_call(malloc_fn, &[env_size.into()], "env_alloc")
-            .map_err(|e| CodegenError {
-                message: format!("malloc call failed: {:?}", e),
-            })?
+            .map_err(|e| CodegenError::builder("malloc call", e))?
             .try_as_basic_value()
             .left()
-            .ok_or_else(|| CodegenError {
-                message: "malloc returned void".into(),
-            })?;
+            .ok_or_else(|| CodegenError::Internal("malloc returned void".into()))?;
         let env_ptr_raw = self.as_pointer_value(malloc_result)?;
 
         // Check if malloc returned null
         let is_null = self
             .builder
             .build_is_null(env_ptr_raw, "is_null")
-            .map_err(|e| CodegenError {
-                message: format!("is_null check failed: {:?}", e),
-            })?;
-
-        // Get or declare abort for allocation failure
-        let abort_fn = self.module.get_function("abort").unwrap_or_else(|| {
-            let abort_type = self.context.void_type().fn_type(&[], false);
-            self.module.add_function("abort", abort_type, None)
-        });
+            .map_err(|e| CodegenError::builder("is_null check", e))?;
 
         // Create basic blocks for null check
-        let current_fn = self.current_function.ok_or_else(|| CodegenError {
-            message: "No current function for malloc null check".to_string(),
+        let current_fn = self.current_function.ok_or_else(|| {
+            CodegenError::Internal("No current function for malloc null check".to_string())
         })?;
         let alloc_ok = self.context.append_basic_block(current_fn, "alloc_ok");
         let alloc_fail = self.context.append_basic_block(current_fn, "alloc_fail");
 
         self.builder
             .build_conditional_branch(is_null, alloc_fail, alloc_ok)
-            .map_err(|e| CodegenError {
-                message: format!("cond branch failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("cond branch", e))?;
 
-        // In alloc_fail: call abort and unreachable
+        // In alloc_fail: panic with an out-of-memory message
         self.builder.position_at_end(alloc_fail);
-        self.builder
-            .build_call(abort_fn, &[], "")
-            .map_err(|e| CodegenError {
-                message: format!("abort call failed: {:?}", e),
-            })?;
-        self.builder.build_unreachable().map_err(|e| CodegenError {
-            message: format!("unreachable failed: {:?}", e),
-        })?;
+        self.build_panic_str("out of memory allocating closure environment")?;
 
         // Continue in alloc_ok
         self.builder.position_at_end(alloc_ok);
@@ -1063,9 +1909,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
         let env_struct_ptr = self
             .builder
             .build_pointer_cast(env_ptr_raw, ptr_type, "env_struct_ptr")
-            .map_err(|e| CodegenError {
-                message: format!("pointer cast failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("pointer cast", e))?;
 
         // 5. Store captured values into environment struct
         for (i, val) in env_values.iter().enumerate() {
@@ -1077,14 +1921,10 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     i as u32,
                     &format!("env_field_{}", i),
                 )
-                .map_err(|e| CodegenError {
-                    message: format!("struct gep failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("struct gep", e))?;
             self.builder
                 .build_store(field_ptr, *val)
-                .map_err(|e| CodegenError {
-                    message: format!("store failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("store", e))?;
         }
 
         // 6. Get the lifted function pointer
@@ -1092,9 +1932,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
             .functions
             .get(func_name)
             .copied()
-            .ok_or_else(|| CodegenError {
-                message: format!("Lifted closure function not found: {}", func_name),
-            })?;
+            .ok_or_else(|| CodegenError::UnknownFunction(func_name.to_string()))?;
 
         // 7. Cast function pointer to opaque ptr
         let fn_ptr_cast = self
@@ -1104,17 +1942,13 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 ptr_type,
                 "fn_ptr_cast",
             )
-            .map_err(|e| CodegenError {
-                message: format!("fn pointer cast failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("fn pointer cast", e))?;
 
         // 8. Cast env pointer to opaque ptr
         let env_ptr_cast = self
             .builder
             .build_pointer_cast(env_struct_ptr, ptr_type, "env_ptr_cast")
-            .map_err(|e| CodegenError {
-                message: format!("env pointer cast failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("env pointer cast", e))?;
 
         // 9. Create closure fat pointer struct: { ptr, ptr }
         let closure_struct_type = self
@@ -1126,23 +1960,170 @@ impl<'ctx> LLVMCodegen<'ctx> {
         let closure_with_fn = self
             .builder
             .build_insert_value(closure_undef, fn_ptr_cast, 0, "closure_fn")
-            .map_err(|e| CodegenError {
-                message: format!("insert fn failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("insert fn", e))?;
         let closure_complete = self
             .builder
             .build_insert_value(closure_with_fn, env_ptr_cast, 1, "closure_env")
-            .map_err(|e| CodegenError {
-                message: format!("insert env failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("insert env", e))?;
 
         // closure_complete is an AggregateValueEnum, convert safely
         match closure_complete {
             inkwell::values::AggregateValueEnum::StructValue(sv) => Ok(sv.into()),
-            _ => Err(CodegenError {
-                message: "Expected struct value for closure".to_string(),
-            }),
+            _ => Err(CodegenError::Internal(
+                "Expected struct value for closure".to_string(),
+            )),
+        }
+    }
+
+    /// Layout of a user enum: `{ i32 tag, [payload x i64] }`, where `payload` is
+    /// the field count of the enum's largest variant.
+    fn enum_type(&self, payload: usize) -> StructType<'ctx> {
+        let words = self.context.i64_type().array_type(payload as u32);
+        self.context
+            .struct_type(&[self.context.i32_type().into(), words.into()], false)
+    }
+
+    /// Build a user enum value: the variant's tag plus its fields packed into
+    /// the payload words.
+    fn compile_user_enum(
+        &mut self,
+        type_name: &str,
+        variant: &str,
+        fields: &[Operand],
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let tag = self
+            .enum_variants
+            .get(&(type_name.to_string(), variant.to_string()))
+            .copied()
+            .ok_or_else(|| {
+                CodegenError::Internal(format!("Unknown variant {}::{}", type_name, variant))
+            })?;
+        let enum_type = self.enum_type(self.enum_payload_sizes[type_name]);
+
+        let mut payload = enum_type
+            .get_field_type_at_index(1)
+            .map(|t| t.into_array_type().get_undef())
+            .ok_or_else(|| CodegenError::Internal(format!("Enum {} has no payload", type_name)))?;
+        for (i, field) in fields.iter().enumerate() {
+            let value = self.compile_operand(field)?;
+            let word = self.pack_payload_word(value)?;
+            payload = self
+                .builder
+                .build_insert_value(payload, word, i as u32, "enum_word")
+                .map_err(|e| CodegenError::builder("insert enum field", e))?
+                .into_array_value();
+        }
+
+        let mut enum_val = enum_type.get_undef();
+        let tag_val = self.context.i32_type().const_int(tag as u64, false);
+        for (idx, value) in [(0, tag_val.into()), (1, BasicValueEnum::from(payload))] {
+            enum_val = self
+                .builder
+                .build_insert_value(enum_val, value, idx, "enum_val")
+                .map_err(|e| CodegenError::builder("insert enum value", e))?
+                .into_struct_value();
+        }
+        Ok(enum_val.into())
+    }
+
+    /// Pack a scalar into an i64 payload word.
+    fn pack_payload_word(
+        &mut self,
+        value: BasicValueEnum<'ctx>,
+    ) -> Result<IntValue<'ctx>, CodegenError> {
+        let i64_type = self.context.i64_type();
+        match value {
+            BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 1 => self
+                .builder
+                .build_int_z_extend(iv, i64_type, "word")
+                .map_err(|e| CodegenError::builder("zext", e)),
+            BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() < 64 => self
+                .builder
+                .build_int_s_extend(iv, i64_type, "word")
+                .map_err(|e| CodegenError::builder("sext", e)),
+            BasicValueEnum::IntValue(iv) => self
+                .builder
+                .build_int_truncate_or_bit_cast(iv, i64_type, "word")
+                .map_err(|e| CodegenError::builder("trunc", e)),
+            BasicValueEnum::FloatValue(fv) => {
+                let fv = self
+                    .builder
+                    .build_float_cast(fv, self.context.f64_type(), "fext")
+                    .map_err(|e| CodegenError::builder("fpext", e))?;
+                Ok(self
+                    .builder
+                    .build_bit_cast(fv, i64_type, "word")
+                    .map_err(|e| CodegenError::builder("bitcast", e))?
+                    .into_int_value())
+            }
+            BasicValueEnum::PointerValue(pv) => self
+                .builder
+                .build_ptr_to_int(pv, i64_type, "word")
+                .map_err(|e| CodegenError::builder("ptrtoint", e)),
+            _ => Err(CodegenError::Unsupported(format!(
+                "enum payload of type {:?}",
+                value.get_type()
+            ))),
+        }
+    }
+
+    /// Unpack an enum payload word into the type of the local receiving it.
+    fn unpack_payload_word(
+        &mut self,
+        value: BasicValueEnum<'ctx>,
+        target_ty: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let BasicValueEnum::IntValue(word) = value else {
+            return Ok(value);
+        };
+        match target_ty {
+            Some(BasicTypeEnum::FloatType(ft)) => {
+                let f = self
+                    .builder
+                    .build_bit_cast(word, self.context.f64_type(), "unword")
+                    .map_err(|e| CodegenError::builder("bitcast", e))?
+                    .into_float_value();
+                Ok(self
+                    .builder
+                    .build_float_cast(f, ft, "unword")
+                    .map_err(|e| CodegenError::builder("fptrunc", e))?
+                    .into())
+            }
+            Some(BasicTypeEnum::PointerType(pt)) => Ok(self
+                .builder
+                .build_int_to_ptr(word, pt, "unword")
+                .map_err(|e| CodegenError::builder("inttoptr", e))?
+                .into()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Compile an operand for a slot of type `target_ty`.
+    ///
+    /// An integer constant is built at the width of its own MIR type and then
+    /// cast to the slot, sign-extending unless that type is unsigned, so e.g.
+    /// `-1` for an `i8` is the 8-bit constant `0xFF`. Other operands are
+    /// compiled normally and coerced.
+    fn compile_operand_as(
+        &mut self,
+        operand: &Operand,
+        target_ty: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        if let (
+            Operand::Constant(constant @ Constant::Int(..)),
+            Some(BasicTypeEnum::IntType(int_ty)),
+        ) = (operand, target_ty)
+        {
+            let value = self.compile_constant(constant).into_int_value();
+            let signed = !constant.ty().is_unsigned_integer();
+            return Ok(self
+                .builder
+                .build_int_cast_sign_flag(value, int_ty, signed, "const_cast")
+                .map_err(|e| CodegenError::builder("int cast", e))?
+                .into());
         }
+        let value = self.compile_operand(operand)?;
+        self.coerce_value(value, target_ty)
     }
 
     /// Coerce a value to match a target type (e.g., i1 to i64 for comparisons stored in Int).
@@ -1166,18 +2147,14 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let extended = self
                     .builder
                     .build_int_z_extend(iv, target_int, "zext")
-                    .map_err(|e| CodegenError {
-                        message: format!("zext failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("zext", e))?;
                 return Ok(extended.into());
             } else if src_width > dst_width {
                 // Truncate larger integers
                 let truncated = self
                     .builder
                     .build_int_truncate(iv, target_int, "trunc")
-                    .map_err(|e| CodegenError {
-                        message: format!("trunc failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("trunc", e))?;
                 return Ok(truncated.into());
             }
         }
@@ -1185,10 +2162,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
         Ok(value)
     }
 
-    /// Compile a cast operation.
+    /// Compile a cast operation. `src_unsigned` selects zero-extension and
+    /// unsigned int-to-float conversion for the source value.
     fn compile_cast(
         &mut self,
         value: BasicValueEnum<'ctx>,
+        src_unsigned: bool,
         target_ty: &Ty,
     ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
         let target_llvm_ty = self.lower_type(target_ty)?;
@@ -1201,45 +2180,55 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
                 if src_width == dst_width {
                     Ok(value)
-                } else if src_width < dst_width {
-                    // Widening: use sign-extend for signed types, zero-extend for unsigned
-                    // For simplicity, use sign-extend (signed semantics)
-                    let extended = self
+                } else if dst_width == 1 {
+                    // Int to Bool tests against zero rather than keeping the low bit
+                    let zero = iv.get_type().const_zero();
+                    let nonzero = self
                         .builder
-                        .build_int_s_extend(iv, target_int, "sext")
-                        .map_err(|e| CodegenError {
-                            message: format!("sext failed: {:?}", e),
-                        })?;
+                        .build_int_compare(IntPredicate::NE, iv, zero, "tobool")
+                        .map_err(|e| CodegenError::builder("cmp", e))?;
+                    Ok(nonzero.into())
+                } else if src_width < dst_width {
+                    // Widening: zero-extend unsigned sources (and i1 bools),
+                    // sign-extend everything else
+                    let extended = if src_unsigned || src_width == 1 {
+                        self.builder.build_int_z_extend(iv, target_int, "zext")
+                    } else {
+                        self.builder.build_int_s_extend(iv, target_int, "sext")
+                    }
+                    .map_err(|e| CodegenError::builder("extend", e))?;
                     Ok(extended.into())
                 } else {
                     // Narrowing: truncate
                     let truncated = self
                         .builder
                         .build_int_truncate(iv, target_int, "trunc")
-                        .map_err(|e| CodegenError {
-                            message: format!("trunc failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("trunc", e))?;
                     Ok(truncated.into())
                 }
             }
             // Int to Float cast
             (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(target_float)) => {
-                let result = self
-                    .builder
-                    .build_signed_int_to_float(iv, target_float, "sitofp")
-                    .map_err(|e| CodegenError {
-                        message: format!("sitofp failed: {:?}", e),
-                    })?;
+                let result = if src_unsigned {
+                    self.builder
+                        .build_unsigned_int_to_float(iv, target_float, "uitofp")
+                } else {
+                    self.builder
+                        .build_signed_int_to_float(iv, target_float, "sitofp")
+                }
+                .map_err(|e| CodegenError::builder("int to float", e))?;
                 Ok(result.into())
             }
             // Float to Int cast
             (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(target_int)) => {
-                let result = self
-                    .builder
-                    .build_float_to_signed_int(fv, target_int, "fptosi")
-                    .map_err(|e| CodegenError {
-                        message: format!("fptosi failed: {:?}", e),
-                    })?;
+                let result = if target_ty.is_unsigned_integer() {
+                    self.builder
+                        .build_float_to_unsigned_int(fv, target_int, "fptoui")
+                } else {
+                    self.builder
+                        .build_float_to_signed_int(fv, target_int, "fptosi")
+                }
+                .map_err(|e| CodegenError::builder("float to int", e))?;
                 Ok(result.into())
             }
             // Float to Float cast
@@ -1253,18 +2242,14 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     let extended = self
                         .builder
                         .build_float_ext(fv, target_float, "fpext")
-                        .map_err(|e| CodegenError {
-                            message: format!("fpext failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("fpext", e))?;
                     Ok(extended.into())
                 } else {
                     // Truncate f64 to f32
                     let truncated = self
                         .builder
                         .build_float_trunc(fv, target_float, "fptrunc")
-                        .map_err(|e| CodegenError {
-                            message: format!("fptrunc failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("fptrunc", e))?;
                     Ok(truncated.into())
                 }
             }
@@ -1278,9 +2263,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
         matches!(
             name,
             "print"
+                | "__print_fmt"
                 | "eprintln"
                 | "str"
                 | "str_len"
+                | "bytes_len"
+                | "bytes_get"
                 | "str_concat"
                 | "str_contains"
                 | "str_starts_with"
@@ -1290,12 +2278,14 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 | "str_to_int"
                 | "str_replace_all"
                 | "str_char_at"
+                | "str_index"
                 | "str_slice"
                 | "int_to_str"
                 | "char_to_str"
                 | "type_of"
                 | "panic"
                 | "assert"
+                | "select"
                 | "exit"
                 | "unwrap"
                 | "expect"
@@ -1384,15 +2374,25 @@ impl<'ctx> LLVMCodegen<'ctx> {
             "forma_print_bool" | "forma_println_bool" => {
                 void_type.fn_type(&[bool_type.into()], false)
             }
+            "forma_print_fmt" => {
+                void_type.fn_type(&[ptr_type.into(), ptr_type.into(), i64_type.into()], false)
+            }
             "forma_read_line" => ptr_type.fn_type(&[], false),
+            "forma_coverage_dump" => {
+                void_type.fn_type(&[ptr_type.into(), ptr_type.into(), i64_type.into()], false)
+            }
 
             // String operations
             "forma_str_len" => i64_type.fn_type(&[ptr_type.into()], false),
+            "forma_str_index" => self
+                .context
+                .i32_type()
+                .fn_type(&[ptr_type.into(), i64_type.into()], false),
             "forma_str_concat" => ptr_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
             "forma_str_eq" => bool_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
             "forma_str_contains" => bool_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
             "forma_str_find" => i64_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
-            "forma_str_substr" => {
+            "forma_str_substr" | "forma_str_substring" => {
                 ptr_type.fn_type(&[ptr_type.into(), i64_type.into(), i64_type.into()], false)
             }
             "forma_str_dup" => ptr_type.fn_type(&[ptr_type.into()], false),
@@ -1443,6 +2443,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
             "forma_vec_set" => {
                 void_type.fn_type(&[ptr_type.into(), i64_type.into(), ptr_type.into()], false)
             }
+            "forma_vec_pop" => bool_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
             "forma_vec_free" => void_type.fn_type(&[ptr_type.into()], false),
 
             // Map operations
@@ -1467,16 +2468,25 @@ impl<'ctx> LLVMCodegen<'ctx> {
             "forma_env_set" => void_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
 
             // Panic / error handling
-            "forma_panic" => void_type.fn_type(&[ptr_type.into()], false),
+            "forma_panic" => {
+                void_type.fn_type(&[ptr_type.into(), ptr_type.into(), i64_type.into()], false)
+            }
             "forma_assert" => void_type.fn_type(&[bool_type.into(), ptr_type.into()], false),
             "forma_unreachable" => void_type.fn_type(&[], false),
             "forma_bounds_check" => void_type.fn_type(&[i64_type.into(), i64_type.into()], false),
+            "forma_panic_index" => void_type.fn_type(
+                &[
+                    i64_type.into(),
+                    i64_type.into(),
+                    ptr_type.into(),
+                    i64_type.into(),
+                ],
+                false,
+            ),
             "forma_div_check" => void_type.fn_type(&[i64_type.into()], false),
 
             _ => {
-                return Err(CodegenError {
-                    message: format!("Unknown runtime function: {}", name),
-                });
+                return Err(CodegenError::UnknownFunction(name.to_string()));
             }
         };
 
@@ -1485,6 +2495,43 @@ impl<'ctx> LLVMCodegen<'ctx> {
             .add_function(name, fn_type, Some(inkwell::module::Linkage::External)))
     }
 
+    /// Allocate a stack slot at the start of the entry block, so it is
+    /// allocated once however often the current block runs.
+    fn build_entry_alloca(
+        &self,
+        ty: BasicTypeEnum<'ctx>,
+        name: &str,
+    ) -> Result<PointerValue<'ctx>, CodegenError> {
+        let current_fn = self
+            .current_function
+            .ok_or_else(|| CodegenError::Internal("No current function".to_string()))?;
+        let entry = current_fn
+            .get_first_basic_block()
+            .ok_or_else(|| CodegenError::Internal("No entry block".to_string()))?;
+
+        // Save current position
+        let current_block = self
+            .builder
+            .get_insert_block()
+            .ok_or_else(|| CodegenError::Internal("No current block".to_string()))?;
+
+        // Insert alloca at the start of the entry block
+        if let Some(first_inst) = entry.get_first_instruction() {
+            self.builder.position_before(&first_inst);
+        } else {
+            self.builder.position_at_end(entry);
+        }
+
+        let alloca = self
+            .builder
+            .build_alloca(ty, name)
+            .map_err(|e| CodegenError::builder("alloca", e));
+
+        // Restore position
+        self.builder.position_at_end(current_block);
+        alloca
+    }
+
     /// Store the result of a builtin call into a destination local.
     /// If the result type doesn't match the local's alloca type, re-allocates
     /// the local with the correct type so subsequent loads get the right type.
@@ -1502,57 +2549,21 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
         if needs_realloc {
             // Re-create the alloca with the actual result type
-            // We need to position at the entry block temporarily
-            let current_fn = self.current_function.ok_or_else(|| CodegenError {
-                message: "No current function".to_string(),
-            })?;
-            let entry = current_fn
-                .get_first_basic_block()
-                .ok_or_else(|| CodegenError {
-                    message: "No entry block".to_string(),
-                })?;
-
-            // Save current position
-            let current_block = self
-                .builder
-                .get_insert_block()
-                .ok_or_else(|| CodegenError {
-                    message: "No current block".to_string(),
-                })?;
-
-            // Insert alloca at the start of the entry block
-            if let Some(first_inst) = entry.get_first_instruction() {
-                self.builder.position_before(&first_inst);
-            } else {
-                self.builder.position_at_end(entry);
-            }
-
-            let new_alloca = self
-                .builder
-                .build_alloca(result.get_type(), &format!("local_{}_retyped", idx))
-                .map_err(|e| CodegenError {
-                    message: format!("alloca failed: {:?}", e),
-                })?;
+            let new_alloca =
+                self.build_entry_alloca(result.get_type(), &format!("local_{}_retyped", idx))?;
 
             // Update the local
             self.locals.insert(idx, new_alloca);
             self.local_types.insert(idx, result.get_type());
 
-            // Restore position
-            self.builder.position_at_end(current_block);
-
             // Store the result
             self.builder
                 .build_store(new_alloca, result)
-                .map_err(|e| CodegenError {
-                    message: format!("store failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("store", e))?;
         } else if let Some(alloca) = self.locals.get(&idx).copied() {
             self.builder
                 .build_store(alloca, result)
-                .map_err(|e| CodegenError {
-                    message: format!("store failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("store", e))?;
         }
 
         Ok(())
@@ -1572,15 +2583,65 @@ impl<'ctx> LLVMCodegen<'ctx> {
         let call = self
             .builder
             .build_call(f, &args_meta, label)
-            .map_err(|e| CodegenError {
-                message: format!("call failed: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::builder("call", e))?;
         if let Some(result) = call.try_as_basic_value().left() {
             self.store_builtin_result(result, dest)?;
         }
         Ok(())
     }
 
+    /// Emit a formatted print: the arguments after the format string are
+    /// packed as (tag, payload) pairs matching the runtime's `FormaFmtArg`.
+    /// Strings are stored as pointers so they keep their provenance.
+    fn emit_print_fmt(&mut self, args: &[Operand]) -> Result<(), CodegenError> {
+        let i64_type = self.context.i64_type();
+        let fmt = self.compile_operand(&args[0])?;
+        let count = args.len() - 1;
+        let array_type = i64_type.array_type((count * 2).max(1) as u32);
+        let array_alloca = self
+            .builder
+            .build_alloca(array_type, "fmt_args")
+            .map_err(|e| CodegenError::builder("alloca", e))?;
+
+        for (i, arg) in args[1..].iter().enumerate() {
+            let val = self.compile_operand(arg)?;
+            let tag = match val {
+                BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 1 => 2,
+                BasicValueEnum::FloatValue(_) => 1,
+                BasicValueEnum::PointerValue(_) => 3,
+                _ => 0,
+            };
+            let payload = match val {
+                BasicValueEnum::PointerValue(_) => val,
+                _ => self.pack_payload_word(val)?.into(),
+            };
+            let tag = i64_type.const_int(tag, false).into();
+            for (offset, field) in [(0, tag), (1, payload)] {
+                let idx = i64_type.const_int((i * 2 + offset) as u64, false);
+                let ptr = unsafe {
+                    self.builder
+                        .build_gep(
+                            array_type,
+                            array_alloca,
+                            &[i64_type.const_zero(), idx],
+                            "fmt_arg_ptr",
+                        )
+                        .map_err(|e| CodegenError::builder("gep", e))?
+                };
+                self.builder
+                    .build_store(ptr, field)
+                    .map_err(|e| CodegenError::builder("store", e))?;
+            }
+        }
+
+        let f = self.get_or_declare_runtime_function("forma_print_fmt")?;
+        let count = i64_type.const_int(count as u64, false);
+        self.builder
+            .build_call(f, &[fmt.into(), array_alloca.into(), count.into()], "")
+            .map_err(|e| CodegenError::builder("call", e))?;
+        Ok(())
+    }
+
     /// Dispatch a print call for a single value based on its LLVM type.
     fn emit_print_value(&mut self, val: BasicValueEnum<'ctx>) -> Result<(), CodegenError> {
         match val {
@@ -1588,43 +2649,33 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_println")?;
                 self.builder
                     .build_call(f, &[val.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             BasicValueEnum::IntValue(iv) => {
                 if iv.get_type().get_bit_width() == 1 {
                     let f = self.get_or_declare_runtime_function("forma_println_bool")?;
                     self.builder
                         .build_call(f, &[val.into()], "")
-                        .map_err(|e| CodegenError {
-                            message: format!("call failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("call", e))?;
                 } else {
                     let f = self.get_or_declare_runtime_function("forma_println_int")?;
                     self.builder
                         .build_call(f, &[val.into()], "")
-                        .map_err(|e| CodegenError {
-                            message: format!("call failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("call", e))?;
                 }
             }
             BasicValueEnum::FloatValue(_) => {
                 let f = self.get_or_declare_runtime_function("forma_println_float")?;
                 self.builder
                     .build_call(f, &[val.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             _ => {
                 let f = self.get_or_declare_runtime_function("forma_println_int")?;
                 let zero = self.context.i64_type().const_zero();
                 self.builder
                     .build_call(f, &[zero.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
         }
         Ok(())
@@ -1646,10 +2697,18 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     self.emit_print_value(val)?;
                 }
             }
+            "__print_fmt" => {
+                self.emit_print_fmt(args)?;
+            }
             "str_len" => {
                 let val = self.compile_operand(&args[0])?;
                 self.call_runtime_and_store("forma_str_len", &[val], "str_len", dest)?;
             }
+            "str_index" => {
+                let s = self.compile_operand(&args[0])?;
+                let index = self.compile_operand(&args[1])?;
+                self.call_runtime_and_store("forma_str_index", &[s, index], "str_index", dest)?;
+            }
             "str_concat" => {
                 let a = self.compile_operand(&args[0])?;
                 let b = self.compile_operand(&args[1])?;
@@ -1660,6 +2719,38 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let b = self.compile_operand(&args[1])?;
                 self.call_runtime_and_store("forma_str_contains", &[a, b], "str_contains", dest)?;
             }
+            "str_slice" => {
+                // str_slice(s, start, end) -> substring of end - start chars
+                let s = self.compile_operand(&args[0])?;
+                let start = self.compile_operand(&args[1])?;
+                let end = self.compile_operand(&args[2])?;
+                let start_int = self.as_int_value(start)?;
+                let end_int = self.as_int_value(end)?;
+                let len = self
+                    .builder
+                    .build_int_sub(end_int, start_int, "slice_len")
+                    .map_err(|e| CodegenError::builder("sub", e))?;
+                self.call_runtime_and_store(
+                    "forma_str_substring",
+                    &[s, start, len.into()],
+                    "str_slice",
+                    dest,
+                )?;
+            }
+            "bytes_len" => {
+                let bytes = self.compile_operand(&args[0])?;
+                let bytes = self.as_pointer_value(bytes)?;
+                let len = self.build_bytes_len(bytes)?;
+                self.store_builtin_result(len.into(), dest)?;
+            }
+            "bytes_get" => {
+                let bytes = self.compile_operand(&args[0])?;
+                let index = self.compile_operand(&args[1])?;
+                let bytes = self.as_pointer_value(bytes)?;
+                let index = self.as_int_value(index)?;
+                let byte = self.build_bytes_get(bytes, index)?;
+                self.store_builtin_result(byte.into(), dest)?;
+            }
             "int_to_str" => {
                 let val = self.compile_operand(&args[0])?;
                 self.call_runtime_and_store("forma_int_to_str", &[val], "int_to_str", dest)?;
@@ -1684,15 +2775,8 @@ impl<'ctx> LLVMCodegen<'ctx> {
             }
             "panic" => {
                 let val = self.compile_operand(&args[0])?;
-                let f = self.get_or_declare_runtime_function("forma_panic")?;
-                self.builder
-                    .build_call(f, &[val.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
-                self.builder.build_unreachable().map_err(|e| CodegenError {
-                    message: format!("unreachable failed: {:?}", e),
-                })?;
+                let msg = self.as_pointer_value(val)?;
+                self.build_panic(msg)?;
                 return Ok(()); // Don't branch after noreturn
             }
             "assert" => {
@@ -1700,18 +2784,34 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let msg_ptr = if args.len() > 1 {
                     self.compile_operand(&args[1])?
                 } else {
-                    let msg = self.context.const_string(b"assertion failed", true);
-                    let global = self.module.add_global(msg.get_type(), None, "assert_msg");
-                    global.set_constant(true);
-                    global.set_initializer(&msg);
-                    global.as_pointer_value().into()
+                    self.string_constant("assertion failed").into()
                 };
-                let f = self.get_or_declare_runtime_function("forma_assert")?;
+                let cond = self.as_int_value(cond)?;
+                let msg_ptr = self.as_pointer_value(msg_ptr)?;
+                let current_fn = self.current_function.ok_or_else(|| {
+                    CodegenError::Internal("No current function for assert".to_string())
+                })?;
+                let fail_block = self.context.append_basic_block(current_fn, "assert_fail");
+                let ok_block = self.context.append_basic_block(current_fn, "assert_ok");
                 self.builder
-                    .build_call(f, &[cond.into(), msg_ptr.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .build_conditional_branch(cond, ok_block, fail_block)
+                    .map_err(|e| CodegenError::builder("cond branch", e))?;
+                self.builder.position_at_end(fail_block);
+                self.build_panic(msg_ptr)?;
+                self.builder.position_at_end(ok_block);
+            }
+            "select" => {
+                // Both operands are already evaluated; pick one with an LLVM
+                // `select` so there is no branch on the condition.
+                let cond = self.compile_operand(&args[0])?;
+                let then_val = self.compile_operand(&args[1])?;
+                let else_val = self.compile_operand(&args[2])?;
+                let cond = self.as_int_value(cond)?;
+                let chosen = self
+                    .builder
+                    .build_select(cond, then_val, else_val, "select")
+                    .map_err(|e| CodegenError::builder("select", e))?;
+                self.store_builtin_result(chosen, dest)?;
             }
             "exit" => {
                 let code = self.compile_operand(&args[0])?;
@@ -1730,9 +2830,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     if iv.get_type().get_bit_width() != 32 {
                         self.builder
                             .build_int_truncate(iv, self.context.i32_type(), "trunc")
-                            .map_err(|e| CodegenError {
-                                message: format!("trunc failed: {:?}", e),
-                            })?
+                            .map_err(|e| CodegenError::builder("trunc", e))?
                             .into()
                     } else {
                         code
@@ -1742,12 +2840,10 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 };
                 self.builder
                     .build_call(exit_fn, &[code_i32.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
-                self.builder.build_unreachable().map_err(|e| CodegenError {
-                    message: format!("unreachable failed: {:?}", e),
-                })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
+                self.builder
+                    .build_unreachable()
+                    .map_err(|e| CodegenError::builder("unreachable", e))?;
                 return Ok(());
             }
             // Math f64->f64
@@ -1773,9 +2869,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_dealloc")?;
                 self.builder
                     .build_call(f, &[ptr.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             // Vector operations
             "vec_new" => {
@@ -1792,13 +2886,25 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_vec_push")?;
                 self.builder
                     .build_call(f, &[v.into(), elem.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             "vec_get" => {
                 let v = self.compile_operand(&args[0])?;
                 let idx = self.compile_operand(&args[1])?;
+                if self.bounds_checks {
+                    let len_fn = self.get_or_declare_runtime_function("forma_vec_len")?;
+                    let len = self
+                        .builder
+                        .build_call(len_fn, &[v.into()], "vec_len")
+                        .map_err(|e| CodegenError::builder("call", e))?
+                        .try_as_basic_value()
+                        .left()
+                        .ok_or_else(|| {
+                            CodegenError::Internal("forma_vec_len returned void".to_string())
+                        })?
+                        .into_int_value();
+                    self.build_index_check(self.as_int_value(idx)?, len)?;
+                }
                 self.call_runtime_and_store("forma_vec_get", &[v, idx], "vec_get", dest)?;
             }
             "vec_set" => {
@@ -1808,18 +2914,14 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_vec_set")?;
                 self.builder
                     .build_call(f, &[v.into(), idx.into(), elem.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             "vec_free" => {
                 let v = self.compile_operand(&args[0])?;
                 let f = self.get_or_declare_runtime_function("forma_vec_free")?;
                 self.builder
                     .build_call(f, &[v.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             // Map operations
             "map_new" => {
@@ -1841,9 +2943,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_map_set")?;
                 self.builder
                     .build_call(f, &[m.into(), key.into(), val.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             "map_contains" => {
                 let m = self.compile_operand(&args[0])?;
@@ -1860,9 +2960,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_map_free")?;
                 self.builder
                     .build_call(f, &[m.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             // Time
             "time_now_ms" | "time_now" => {
@@ -1873,9 +2971,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let f = self.get_or_declare_runtime_function("forma_sleep_ms")?;
                 self.builder
                     .build_call(f, &[ms.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             // Environment / args
             "args_count" => {
@@ -1886,26 +2982,24 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 self.call_runtime_and_store("forma_args_get", &[idx], "args_get", dest)?;
             }
             "env_get" => {
+                self.require_capability("env", "env_get")?;
                 let name = self.compile_operand(&args[0])?;
                 self.call_runtime_and_store("forma_env_get", &[name], "env_get", dest)?;
             }
             "env_set" => {
+                self.require_capability("env", "env_set")?;
                 let name = self.compile_operand(&args[0])?;
                 let val = self.compile_operand(&args[1])?;
                 let f = self.get_or_declare_runtime_function("forma_env_set")?;
                 self.builder
                     .build_call(f, &[name.into(), val.into()], "")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
             }
             _ => {
-                return Err(CodegenError {
-                    message: format!(
-                        "Builtin '{}' not yet implemented in LLVM codegen",
-                        func_name
-                    ),
-                });
+                return Err(CodegenError::Unsupported(format!(
+                    "builtin '{}'",
+                    func_name
+                )));
             }
         }
 
@@ -1913,11 +3007,85 @@ impl<'ctx> LLVMCodegen<'ctx> {
         if let Some(&bb) = blocks.get(&(next.0 as usize)) {
             self.builder
                 .build_unconditional_branch(bb)
-                .map_err(|e| CodegenError {
-                    message: format!("branch failed: {:?}", e),
-                })?;
+                .map_err(|e| CodegenError::builder("branch", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Call through a plain function pointer, such as a named function
+    /// passed as a value.
+    fn compile_fn_ptr_call(
+        &mut self,
+        callee: &Operand,
+        fn_ptr: PointerValue<'ctx>,
+        args: &[Operand],
+        dest: &Option<crate::mir::Local>,
+        blocks: &HashMap<usize, inkwell::basic_block::BasicBlock>,
+        next: &crate::mir::BlockId,
+    ) -> Result<(), CodegenError> {
+        let declared = match callee {
+            Operand::Local(l) | Operand::Copy(l) | Operand::Move(l) => {
+                self.fn_ptr_types.get(&(l.0 as usize)).cloned()
+            }
+            Operand::Constant(_) => None,
+        };
+
+        let mut compiled_args: Vec<BasicMetadataValueEnum> = Vec::new();
+        let param_types: Vec<BasicTypeEnum> = declared
+            .as_ref()
+            .map(|(params, ..)| params.clone())
+            .unwrap_or_default();
+        let sret_slot = match declared.as_ref().and_then(|(.., sret)| *sret) {
+            Some(ty) => {
+                let slot = self.build_entry_alloca(ty.into(), "sret")?;
+                compiled_args.push(slot.into());
+                Some((slot, ty))
+            }
+            None => None,
+        };
+        for (i, arg) in args.iter().enumerate() {
+            let val = self.compile_operand_as(arg, param_types.get(i).copied())?;
+            compiled_args.push(val.into());
+        }
+        // Without a declared signature, assume the arguments' own types and
+        // an i64 result, matching how Unit-returning functions are declared
+        let fn_type = match declared {
+            Some((_, t, _)) => t,
+            None => {
+                let params: Vec<BasicMetadataTypeEnum> = compiled_args
+                    .iter()
+                    .map(|a| match a {
+                        BasicMetadataValueEnum::PointerValue(p) => p.get_type().into(),
+                        BasicMetadataValueEnum::FloatValue(f) => f.get_type().into(),
+                        BasicMetadataValueEnum::StructValue(s) => s.get_type().into(),
+                        _ => self.context.i64_type().into(),
+                    })
+                    .collect();
+                self.context.i64_type().fn_type(&params, false)
+            }
+        };
+
+        let call = self
+            .builder
+            .build_indirect_call(fn_type, fn_ptr, &compiled_args, "fn_ptr_call")
+            .map_err(|e| CodegenError::builder("indirect call", e))?;
+        if let Some((slot, ty)) = sret_slot {
+            call.add_attribute(AttributeLoc::Param(0), self.sret_attribute(ty));
+            let result = self
+                .builder
+                .build_load(ty, slot, "sret_result")
+                .map_err(|e| CodegenError::builder("load", e))?;
+            self.store_builtin_result(result, dest)?;
+        } else if let Some(result) = call.try_as_basic_value().left() {
+            self.store_builtin_result(result, dest)?;
         }
 
+        if let Some(&bb) = blocks.get(&(next.0 as usize)) {
+            self.builder
+                .build_unconditional_branch(bb)
+                .map_err(|e| CodegenError::builder("branch", e))?;
+        }
         Ok(())
     }
 
@@ -1929,30 +3097,39 @@ impl<'ctx> LLVMCodegen<'ctx> {
     ) -> Result<(), CodegenError> {
         match terminator {
             Terminator::Return(operand) => {
-                if let Some(op) = operand {
-                    let val = self.compile_operand(op)?;
+                self.emit_coverage_dump()?;
+                if let (Some(op), Some((slot, ty))) = (operand, self.sret_slot) {
+                    // Large aggregates go back through the caller's slot
+                    let val = self.compile_operand_as(op, Some(ty.into()))?;
+                    self.builder
+                        .build_store(slot, val)
+                        .map_err(|e| CodegenError::builder("store", e))?;
+                    self.builder
+                        .build_return(None)
+                        .map_err(|e| CodegenError::builder("return", e))?;
+                } else if let Some(op) = operand {
+                    // Temporaries may have been widened (e.g. Unit -> i64), so match
+                    // the declared return type before returning.
+                    let return_ty = self
+                        .current_function
+                        .and_then(|f| f.get_type().get_return_type());
+                    let val = self.compile_operand_as(op, return_ty)?;
                     self.builder
                         .build_return(Some(&val))
-                        .map_err(|e| CodegenError {
-                            message: format!("return failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("return", e))?;
                 } else {
-                    self.builder.build_return(None).map_err(|e| CodegenError {
-                        message: format!("return failed: {:?}", e),
-                    })?;
+                    self.builder
+                        .build_return(None)
+                        .map_err(|e| CodegenError::builder("return", e))?;
                 }
             }
             Terminator::Goto(target) => {
-                let bb = blocks
-                    .get(&(target.0 as usize))
-                    .ok_or_else(|| CodegenError {
-                        message: format!("Goto: target block {} not found", target.0),
-                    })?;
+                let bb = blocks.get(&(target.0 as usize)).ok_or_else(|| {
+                    CodegenError::Internal(format!("Goto: target block {} not found", target.0))
+                })?;
                 self.builder
                     .build_unconditional_branch(*bb)
-                    .map_err(|e| CodegenError {
-                        message: format!("branch failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("branch", e))?;
             }
             Terminator::If {
                 cond,
@@ -1964,33 +3141,24 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let then_bb = blocks
                     .get(&(then_block.0 as usize))
                     .copied()
-                    .ok_or_else(|| CodegenError {
-                        message: "Missing then block".into(),
-                    })?;
+                    .ok_or_else(|| CodegenError::Internal("Missing then block".into()))?;
                 let else_bb = blocks
                     .get(&(else_block.0 as usize))
                     .copied()
-                    .ok_or_else(|| CodegenError {
-                        message: "Missing else block".into(),
-                    })?;
-
-                // Convert to bool if needed (compare != 0 for non-bool types)
-                let cond_bool = if cond_val.get_type().get_bit_width() == 1 {
-                    cond_val
-                } else {
-                    let zero = cond_val.get_type().const_zero();
-                    self.builder
-                        .build_int_compare(IntPredicate::NE, cond_val, zero, "tobool")
-                        .map_err(|e| CodegenError {
-                            message: format!("cmp failed: {:?}", e),
-                        })?
-                };
+                    .ok_or_else(|| CodegenError::Internal("Missing else block".into()))?;
+
+                // Conditions are `Bool` (i1); integers are never tested
+                // against zero implicitly
+                if cond_val.get_type().get_bit_width() != 1 {
+                    return Err(CodegenError::TypeMismatch {
+                        expected: "Bool condition".to_string(),
+                        found: format!("i{}", cond_val.get_type().get_bit_width()),
+                    });
+                }
 
                 self.builder
-                    .build_conditional_branch(cond_bool, then_bb, else_bb)
-                    .map_err(|e| CodegenError {
-                        message: format!("branch failed: {:?}", e),
-                    })?;
+                    .build_conditional_branch(cond_val, then_bb, else_bb)
+                    .map_err(|e| CodegenError::builder("branch", e))?;
             }
             Terminator::Switch {
                 operand,
@@ -1999,20 +3167,18 @@ impl<'ctx> LLVMCodegen<'ctx> {
             } => {
                 let switch_operand = self.compile_operand(operand)?;
                 let val = self.as_int_value(switch_operand)?;
-                let default_bb =
-                    blocks
-                        .get(&(default.0 as usize))
-                        .copied()
-                        .ok_or_else(|| CodegenError {
-                            message: "Missing default block".into(),
-                        })?;
+                let default_bb = blocks
+                    .get(&(default.0 as usize))
+                    .copied()
+                    .ok_or_else(|| CodegenError::Internal("Missing default block".into()))?;
 
                 // Build switch cases
                 let cases: Vec<(IntValue, inkwell::basic_block::BasicBlock)> = targets
                     .iter()
                     .filter_map(|(value, target)| {
                         blocks.get(&(target.0 as usize)).map(|&bb| {
-                            let const_val = self.context.i64_type().const_int(*value as u64, false);
+                            // Cases must match the operand's width (i1 for Bool)
+                            let const_val = val.get_type().const_int(*value as u64, false);
                             (const_val, bb)
                         })
                     })
@@ -2020,9 +3186,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
                 self.builder
                     .build_switch(val, default_bb, &cases)
-                    .map_err(|e| CodegenError {
-                        message: format!("switch failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("switch", e))?;
             }
             Terminator::Call {
                 func,
@@ -2040,24 +3204,52 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     .functions
                     .get(func)
                     .copied()
-                    .ok_or_else(|| CodegenError {
-                        message: format!("Unknown function: {}", func),
-                    })?;
-
-                let compiled_args: Vec<BasicMetadataValueEnum> = args
-                    .iter()
-                    .map(|a| self.compile_operand(a).map(|v| v.into()))
-                    .collect::<Result<Vec<_>, _>>()?;
+                    .ok_or_else(|| CodegenError::UnknownFunction(func.to_string()))?;
+
+                // Coerce each argument to the callee's declared parameter type. The
+                // callee may not have been compiled yet (forward or mutually
+                // recursive calls), so use its declared signature.
+                let sret = self.sret_returns.get(func).copied();
+                let param_types: Vec<BasicTypeEnum> = fn_value
+                    .get_param_iter()
+                    .skip(usize::from(sret.is_some()))
+                    .map(|p| p.get_type())
+                    .collect();
+                let mut compiled_args: Vec<BasicMetadataValueEnum> = Vec::new();
+                let sret_slot = match sret {
+                    Some(ty) => {
+                        let slot = self.build_entry_alloca(ty.into(), "sret")?;
+                        compiled_args.push(slot.into());
+                        Some((slot, ty))
+                    }
+                    None => None,
+                };
+                for (i, arg) in args.iter().enumerate() {
+                    let val = self.compile_operand_as(arg, param_types.get(i).copied())?;
+                    compiled_args.push(val.into());
+                }
 
                 let call = self
                     .builder
                     .build_call(fn_value, &compiled_args, "call")
-                    .map_err(|e| CodegenError {
-                        message: format!("call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("call", e))?;
+                call.set_call_convention(fn_value.get_call_conventions());
+                // Let LLVM turn self-recursion in tail position into a loop.
+                // A `tail` call may not touch the caller's stack, which an
+                // `sret` slot is.
+                if self.tail_call_block && sret_slot.is_none() {
+                    call.set_tail_call(true);
+                }
 
                 // Store result if there's a destination
-                if let Some(result) = call.try_as_basic_value().left() {
+                if let Some((slot, ty)) = sret_slot {
+                    call.add_attribute(AttributeLoc::Param(0), self.sret_attribute(ty));
+                    let result = self
+                        .builder
+                        .build_load(ty, slot, "sret_result")
+                        .map_err(|e| CodegenError::builder("load", e))?;
+                    self.store_builtin_result(result, dest)?;
+                } else if let Some(result) = call.try_as_basic_value().left() {
                     self.store_builtin_result(result, dest)?;
                 }
 
@@ -2065,9 +3257,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 if let Some(&bb) = blocks.get(&(next.0 as usize)) {
                     self.builder
                         .build_unconditional_branch(bb)
-                        .map_err(|e| CodegenError {
-                            message: format!("branch failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("branch", e))?;
                 }
             }
             Terminator::CallIndirect {
@@ -2082,22 +3272,21 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
                 // 1. Compile the closure operand (should be a fat pointer struct)
                 let closure_val = self.compile_operand(callee)?;
+                if let BasicValueEnum::PointerValue(fn_ptr) = closure_val {
+                    return self.compile_fn_ptr_call(callee, fn_ptr, args, dest, blocks, next);
+                }
                 let closure_struct = self.as_struct_value(closure_val)?;
 
                 // 2. Extract function pointer and environment pointer
                 let fn_ptr_raw = self
                     .builder
                     .build_extract_value(closure_struct, 0, "fn_ptr_raw")
-                    .map_err(|e| CodegenError {
-                        message: format!("extract fn_ptr failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("extract fn_ptr", e))?;
                 let fn_ptr_i8 = self.as_pointer_value(fn_ptr_raw)?;
                 let env_ptr_raw = self
                     .builder
                     .build_extract_value(closure_struct, 1, "env_ptr")
-                    .map_err(|e| CodegenError {
-                        message: format!("extract env_ptr failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("extract env_ptr", e))?;
                 let env_ptr = self.as_pointer_value(env_ptr_raw)?;
 
                 // 3. Compile arguments (environment pointer is implicit first arg)
@@ -2119,17 +3308,13 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 let fn_ptr_typed = self
                     .builder
                     .build_pointer_cast(fn_ptr_i8, ptr_type, "fn_ptr_typed")
-                    .map_err(|e| CodegenError {
-                        message: format!("fn type cast failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("fn type cast", e))?;
 
                 // 6. Build indirect call
                 let call = self
                     .builder
                     .build_indirect_call(fn_type, fn_ptr_typed, &compiled_args, "indirect_call")
-                    .map_err(|e| CodegenError {
-                        message: format!("indirect call failed: {:?}", e),
-                    })?;
+                    .map_err(|e| CodegenError::builder("indirect call", e))?;
 
                 // 7. Store result if there's a destination
                 if let Some(local) = dest
@@ -2138,18 +3323,14 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 {
                     self.builder
                         .build_store(*alloca, result)
-                        .map_err(|e| CodegenError {
-                            message: format!("store failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("store", e))?;
                 }
 
                 // 8. Jump to next block
                 if let Some(&bb) = blocks.get(&(next.0 as usize)) {
                     self.builder
                         .build_unconditional_branch(bb)
-                        .map_err(|e| CodegenError {
-                            message: format!("branch failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("branch", e))?;
                 }
             }
             // Spawn an async task - for LLVM, execute synchronously
@@ -2164,22 +3345,19 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 {
                     self.builder
                         .build_store(*alloca, val)
-                        .map_err(|e| CodegenError {
-                            message: format!("store failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("store", e))?;
                 }
 
                 // Jump to next block
                 if let Some(&bb) = blocks.get(&(next.0 as usize)) {
                     self.builder
                         .build_unconditional_branch(bb)
-                        .map_err(|e| CodegenError {
-                            message: format!("branch failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("branch", e))?;
                 } else {
-                    return Err(CodegenError {
-                        message: format!("Spawn: missing next block {}", next.0),
-                    });
+                    return Err(CodegenError::Internal(format!(
+                        "Spawn: missing next block {}",
+                        next.0
+                    )));
                 }
             }
             // Await a task/future - for LLVM, the value is already resolved
@@ -2193,28 +3371,23 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 {
                     self.builder
                         .build_store(*alloca, val)
-                        .map_err(|e| CodegenError {
-                            message: format!("store failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("store", e))?;
                 }
 
                 // Jump to next block
                 if let Some(&bb) = blocks.get(&(next.0 as usize)) {
                     self.builder
                         .build_unconditional_branch(bb)
-                        .map_err(|e| CodegenError {
-                            message: format!("branch failed: {:?}", e),
-                        })?;
+                        .map_err(|e| CodegenError::builder("branch", e))?;
                 } else {
-                    return Err(CodegenError {
-                        message: format!("Await: missing next block {}", next.0),
-                    });
+                    return Err(CodegenError::Internal(format!(
+                        "Await: missing next block {}",
+                        next.0
+                    )));
                 }
             }
             Terminator::Unreachable => {
-                self.builder.build_unreachable().map_err(|e| CodegenError {
-                    message: format!("unreachable failed: {:?}", e),
-                })?;
+                self.build_panic_str("entered unreachable code")?;
             }
         }
         Ok(())
@@ -2244,7 +3417,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
             // Unit is used as a placeholder type for temporaries in MIR;
             // use i64 so it can hold any integer/pointer-sized value without truncation
             Ty::Unit => Ok(self.context.i64_type().into()),
-            Ty::Str => Ok(self.context.ptr_type(AddressSpace::default()).into()),
+            Ty::Str | Ty::Bytes | Ty::Ref(..) | Ty::Ptr(..) | Ty::RawPtr(_) | Ty::Fn(..) => {
+                Ok(self.context.ptr_type(AddressSpace::default()).into())
+            }
+            Ty::Named(id, _) if self.enum_payload_sizes.contains_key(&id.name) => {
+                Ok(self.enum_type(self.enum_payload_sizes[&id.name]).into())
+            }
             _ => {
                 // Default to i64 for complex types
                 Ok(self.context.i64_type().into())
@@ -2252,44 +3430,171 @@ impl<'ctx> LLVMCodegen<'ctx> {
         }
     }
 
-    /// Write the module to an object file.
-    pub fn write_object_file(&self, path: &Path) -> Result<(), CodegenError> {
-        Target::initialize_native(&InitializationConfig::default()).map_err(|e| CodegenError {
-            message: format!("Failed to initialize LLVM: {}", e),
-        })?;
+    /// The aggregate a function returning `return_type` passes back through
+    /// an `sret` pointer, when it is too large to return by value.
+    fn sret_type(&self, return_type: BasicTypeEnum<'ctx>) -> Option<StructType<'ctx>> {
+        let BasicTypeEnum::StructType(ty) = return_type else {
+            return None;
+        };
+        let layout = self.module.get_data_layout();
+        let target_data = TargetData::create(layout.as_str().to_str().unwrap_or_default());
+        (target_data.get_abi_size(&ty) > SRET_THRESHOLD_BYTES).then_some(ty)
+    }
 
-        let triple = TargetMachine::get_default_triple();
-        let target = Target::from_triple(&triple).map_err(|e| CodegenError {
-            message: format!("Failed to get target: {:?}", e),
-        })?;
+    /// LLVM type of a function returning `return_type`. A large aggregate is
+    /// returned through a pointer prepended to `params` instead, and is
+    /// returned alongside the type.
+    fn function_type(
+        &self,
+        return_type: BasicTypeEnum<'ctx>,
+        params: &[BasicMetadataTypeEnum<'ctx>],
+    ) -> (FunctionType<'ctx>, Option<StructType<'ctx>>) {
+        match self.sret_type(return_type) {
+            Some(ty) => {
+                let mut with_slot = vec![self.context.ptr_type(AddressSpace::default()).into()];
+                with_slot.extend_from_slice(params);
+                (
+                    self.context.void_type().fn_type(&with_slot, false),
+                    Some(ty),
+                )
+            }
+            None => (return_type.fn_type(params, false), None),
+        }
+    }
+
+    /// The `sret(<ty>)` attribute marking a hidden return-value pointer.
+    fn sret_attribute(&self, ty: StructType<'ctx>) -> Attribute {
+        self.context
+            .create_type_attribute(Attribute::get_named_enum_kind_id("sret"), ty.into())
+    }
+
+    /// Whether the chosen target (the host by default) is Windows.
+    fn targets_windows(&self) -> bool {
+        match &self.target_triple {
+            Some(triple) => triple.contains("windows"),
+            None => cfg!(windows),
+        }
+    }
 
-        let machine = target
+    /// Create a target machine for the chosen target (the host by default)
+    /// at the configured optimization level, relocation model and code model.
+    fn target_machine(&self) -> Result<TargetMachine, CodegenError> {
+        let triple = match &self.target_triple {
+            Some(triple) => {
+                Target::initialize_all(&InitializationConfig::default());
+                TargetTriple::create(triple)
+            }
+            None => {
+                Target::initialize_native(&InitializationConfig::default()).map_err(|e| {
+                    CodegenError::Target(format!("Failed to initialize LLVM: {}", e))
+                })?;
+                TargetMachine::get_default_triple()
+            }
+        };
+        let target = Target::from_triple(&triple)
+            .map_err(|e| CodegenError::Target(format!("Failed to get target: {:?}", e)))?;
+
+        target
             .create_target_machine(
                 &triple,
                 "generic",
                 "",
                 self.opt_level,
-                RelocMode::Default,
-                CodeModel::Default,
+                self.reloc_mode,
+                self.code_model,
             )
-            .ok_or_else(|| CodegenError {
-                message: "Failed to create target machine".into(),
-            })?;
+            .ok_or_else(|| CodegenError::Target("Failed to create target machine".into()))
+    }
+
+    /// Run LLVM's standard optimization pipeline for the configured level.
+    ///
+    /// Among other things this turns self-recursive `tail` calls into loops.
+    /// Does nothing at opt-level 0.
+    pub fn run_optimization_passes(&self) -> Result<(), CodegenError> {
+        let level = match self.opt_level {
+            OptimizationLevel::None => return Ok(()),
+            OptimizationLevel::Less => 1,
+            OptimizationLevel::Default => 2,
+            OptimizationLevel::Aggressive => 3,
+        };
+        let machine = self.target_machine()?;
+        self.module
+            .run_passes(
+                &format!("default<O{}>", level),
+                &machine,
+                PassBuilderOptions::create(),
+            )
+            .map_err(|e| CodegenError::Target(format!("Optimization passes failed: {}", e)))
+    }
+
+    /// Write the module to an object file, optimizing it first.
+    pub fn write_object_file(&self, path: &Path) -> Result<(), CodegenError> {
+        self.run_optimization_passes()?;
+        let machine = self.target_machine()?;
 
         machine
             .write_to_file(&self.module, FileType::Object, path)
-            .map_err(|e| CodegenError {
-                message: format!("Failed to write object file: {:?}", e),
-            })?;
+            .map_err(|e| CodegenError::Io(format!("Failed to write object file: {:?}", e)))?;
 
         Ok(())
     }
 
+    /// Write the module as a static library at `path`.
+    ///
+    /// The object is written next to the archive under the same stem and
+    /// added with `$AR`, `ar` or `llvm-ar`, whichever is found first.
+    pub fn write_static_library(&self, path: &Path) -> Result<(), CodegenError> {
+        let obj_path = path.with_extension("o");
+        self.write_object_file(&obj_path)?;
+        // `ar r` keeps members it doesn't replace, so start from scratch
+        let _ = std::fs::remove_file(path);
+
+        let archivers: Vec<String> = std::env::var("AR")
+            .ok()
+            .into_iter()
+            .chain(["ar".to_string(), "llvm-ar".to_string()])
+            .collect();
+        let mut result = None;
+        for archiver in &archivers {
+            match std::process::Command::new(archiver)
+                .arg("rcs")
+                .arg(path)
+                .arg(&obj_path)
+                .status()
+            {
+                Ok(status) => {
+                    result = Some((archiver, status));
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    let _ = std::fs::remove_file(&obj_path);
+                    return Err(CodegenError::Io(format!(
+                        "Failed to run {}: {}",
+                        archiver, e
+                    )));
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&obj_path);
+
+        match result {
+            Some((_, status)) if status.success() => Ok(()),
+            Some((archiver, status)) => Err(CodegenError::Io(format!(
+                "{} failed with {}",
+                archiver, status
+            ))),
+            None => Err(CodegenError::Io(
+                "No archiver found: install `ar` (binutils) or `llvm-ar`, or set $AR".into(),
+            )),
+        }
+    }
+
     /// Write the module to LLVM IR text file.
     pub fn write_llvm_ir(&self, path: &Path) -> Result<(), CodegenError> {
-        self.module.print_to_file(path).map_err(|e| CodegenError {
-            message: format!("Failed to write IR: {:?}", e),
-        })?;
+        self.module
+            .print_to_file(path)
+            .map_err(|e| CodegenError::Io(format!("Failed to write IR: {:?}", e)))?;
         Ok(())
     }
 
@@ -2299,10 +3604,148 @@ impl<'ctx> LLVMCodegen<'ctx> {
     }
 }
 
+/// Whether `block` ends in a call to `name` (the function being compiled)
+/// whose result is returned unchanged, possibly after being copied through
+/// temporaries and unconditional jumps. Calls taking references are excluded,
+/// since a `tail` call must not access the caller's stack.
+fn is_self_tail_call(name: &str, func: &Function, block: &BasicBlock) -> bool {
+    let Some(Terminator::Call {
+        func: callee,
+        args,
+        arg_pass_modes,
+        dest: Some(dest),
+        next,
+    }) = &block.terminator
+    else {
+        return false;
+    };
+    if callee != name
+        || arg_pass_modes
+            .iter()
+            .any(|m| *m != crate::mir::mir::PassMode::Owned)
+    {
+        return false;
+    }
+    let borrows = args.iter().any(|arg| match arg {
+        Operand::Local(l) | Operand::Copy(l) | Operand::Move(l) => matches!(
+            func.locals.get(l.0 as usize).map(|d| &d.ty),
+            Some(Ty::Ref(..) | Ty::Ptr(..) | Ty::RawPtr(_))
+        ),
+        Operand::Constant(_) => false,
+    });
+    if borrows {
+        return false;
+    }
+
+    let mut holders = HashSet::from([*dest]);
+    let mut visited = HashSet::new();
+    let mut current = *next;
+    while visited.insert(current) {
+        let Some(block) = func.blocks.get(current.0 as usize) else {
+            return false;
+        };
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StatementKind::Assign(
+                    to,
+                    Rvalue::Use(Operand::Local(from) | Operand::Copy(from) | Operand::Move(from)),
+                ) if holders.contains(from) => {
+                    holders.insert(*to);
+                }
+                StatementKind::Nop => {}
+                _ => return false,
+            }
+        }
+        match &block.terminator {
+            Some(Terminator::Goto(target)) => current = *target,
+            Some(Terminator::Return(Some(
+                Operand::Local(l) | Operand::Copy(l) | Operand::Move(l),
+            ))) => return holders.contains(l),
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// LLVM's numeric id for a calling convention.
+fn llvm_call_conv(conv: CallConv) -> u32 {
+    match conv {
+        CallConv::C => 0,
+        CallConv::Fast => 8,
+    }
+}
+
+/// Reject programs in which two functions share a name or a native symbol.
+///
+/// Functions are declared by name and symbol, so a duplicate would silently
+/// replace one definition or have LLVM rename the second symbol.
+fn check_duplicate_functions(program: &Program) -> Result<(), CodegenError> {
+    let mut names: Vec<(&str, String)> = program
+        .externs
+        .keys()
+        .map(|name| (name.as_str(), name.clone()))
+        .chain(
+            program
+                .functions
+                .iter()
+                .map(|(name, func)| (name.as_str(), func.symbol(name))),
+        )
+        .collect();
+    names.sort();
+
+    let mut seen = HashSet::new();
+    let mut symbols: HashMap<String, &str> = HashMap::new();
+    for (name, symbol) in names {
+        if !seen.insert(name) {
+            return Err(CodegenError::DuplicateDefinition {
+                name: name.to_string(),
+            });
+        }
+        if let Some(other) = symbols.insert(symbol.clone(), name) {
+            return Err(CodegenError::DuplicateSymbol {
+                symbol,
+                first: other.to_string(),
+                second: name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A function with no parameters, entering at its first block.
+    ///
+    /// Build test functions through this, so a new `Function` field only
+    /// needs a default here.
+    fn make_function(
+        name: &str,
+        return_ty: Ty,
+        locals: Vec<crate::mir::LocalDecl>,
+        blocks: Vec<BasicBlock>,
+    ) -> Function {
+        Function {
+            name: name.to_string(),
+            params: vec![],
+            param_names: vec![],
+            param_pass_modes: vec![],
+            return_ty,
+            locals,
+            blocks,
+            entry_block: crate::mir::BlockId(0),
+            preconditions: vec![],
+            postconditions: vec![],
+            span: None,
+            no_mangle: false,
+            section: None,
+            is_const: false,
+            exported: false,
+            call_conv: CallConv::C,
+        }
+    }
+
     fn make_empty_main() -> Program {
         let mut functions = HashMap::new();
         let entry_block = BasicBlock {
@@ -2310,79 +3753,1454 @@ mod tests {
             stmts: vec![],
             terminator: Some(Terminator::Return(Some(Operand::Constant(Constant::Int(
                 0,
+                Ty::Int,
             ))))),
+            terminator_span: None,
         };
-        let func = Function {
-            name: "main".to_string(),
-            params: vec![],
-            param_names: vec![],
-            param_pass_modes: vec![],
-            return_ty: Ty::Int,
-            locals: vec![],
-            blocks: vec![entry_block],
-            entry_block: crate::mir::BlockId(0),
-            preconditions: vec![],
-            postconditions: vec![],
-        };
+        let func = make_function("main", Ty::Int, vec![], vec![entry_block]);
         functions.insert("main".to_string(), func);
         Program {
             functions,
             entry: Some("main".to_string()),
-            enum_variants: HashMap::new(),
+            ..Program::default()
         }
     }
 
+    /// Run source through the front end and lower it to MIR.
+    fn lower_source(source: &str) -> Program {
+        let (tokens, _) = crate::Scanner::new(source).scan_all();
+        let ast = crate::Parser::new(&tokens)
+            .parse()
+            .expect("parse should succeed");
+        crate::mir::Lowerer::new()
+            .lower(&ast)
+            .expect("lowering should succeed")
+    }
+
+    /// Run source through the front end and lower it to MIR, then compile to LLVM IR.
+    fn compile_source<'ctx>(ctx: &'ctx Context, source: &str) -> LLVMCodegen<'ctx> {
+        let program = lower_source(source);
+        let mut codegen = LLVMCodegen::new(ctx, "test");
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen
+    }
+
+    fn jit<'ctx>(codegen: &LLVMCodegen<'ctx>) -> inkwell::execution_engine::ExecutionEngine<'ctx> {
+        Target::initialize_native(&InitializationConfig::default())
+            .expect("native target should initialize");
+        codegen
+            .module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .expect("JIT engine should be created")
+    }
+
+    type IntFn = unsafe extern "C" fn(i64) -> i64;
+
     #[test]
-    fn test_compile_empty_main() {
-        let program = make_empty_main();
+    fn test_self_recursion() {
         let ctx = Context::create();
-        let mut codegen = LLVMCodegen::new(&ctx, "test");
-        assert!(codegen.compile(&program).is_ok());
+        let codegen = compile_source(
+            &ctx,
+            "f fib(n: Int) -> Int\n    if n < 2 then n else fib(n - 1) + fib(n - 2)\n",
+        );
+        let engine = jit(&codegen);
+        let fib = unsafe { engine.get_function::<IntFn>(&mangle_symbol("fib")) }
+            .expect("fib should be compiled");
+        assert_eq!(unsafe { fib.call(10) }, 55);
+        assert_eq!(unsafe { fib.call(1) }, 1);
+    }
+
+    #[test]
+    fn test_self_tail_call_runs_in_constant_stack() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f sum_to(n: Int, acc: Int) -> Int
+    if n == 0 then acc else sum_to(n - 1, acc + n)
+
+f sum(n: Int) -> Int = sum_to(n, 0)
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
         let ir = codegen.get_llvm_ir();
         assert!(
-            ir.contains("define"),
-            "IR should contain function definitions"
+            ir.contains("tail call"),
+            "self tail call should be marked:\n{}",
+            ir
         );
+        // `sum` calls a different function, so that call stays a plain call
+        assert_eq!(ir.matches("tail call").count(), 1, "{}", ir);
+
+        codegen
+            .run_optimization_passes()
+            .expect("optimization should succeed");
+        let engine = jit(&codegen);
+        let sum = unsafe { engine.get_function::<IntFn>(&mangle_symbol("sum")) }
+            .expect("sum should be compiled");
+        assert_eq!(unsafe { sum.call(1_000_000) }, 500_000_500_000);
     }
 
     #[test]
-    fn test_compile_integer_arithmetic() {
-        let mut functions = HashMap::new();
-        let result_local = crate::mir::Local(0);
-        let entry_block = BasicBlock {
-            id: crate::mir::BlockId(0),
-            stmts: vec![Statement {
-                kind: StatementKind::Assign(
-                    result_local,
-                    Rvalue::BinaryOp(
-                        BinOp::Add,
-                        Operand::Constant(Constant::Int(2)),
-                        Operand::Constant(Constant::Int(3)),
-                    ),
-                ),
-            }],
-            terminator: Some(Terminator::Return(Some(Operand::Local(result_local)))),
+    fn test_mutual_recursion() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f is_even(n: Int) -> Bool
+    if n == 0 then true else is_odd(n - 1)
+
+f is_odd(n: Int) -> Bool
+    if n == 0 then false else is_even(n - 1)
+
+f even_flag(n: Int) -> Int
+    if is_even(n) then 1 else 0
+"#,
+        );
+        let engine = jit(&codegen);
+        let even_flag = unsafe { engine.get_function::<IntFn>(&mangle_symbol("even_flag")) }
+            .expect("even_flag should be compiled");
+        assert_eq!(unsafe { even_flag.call(10) }, 1);
+        assert_eq!(unsafe { even_flag.call(7) }, 0);
+    }
+
+    #[test]
+    fn test_char_return_and_compare() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f last_letter() -> Char
+    'Z'
+
+f ordered() -> Bool
+    'a' < 'b'
+
+f high_scalar() -> Bool
+    '\u{1F600}' > 'a'
+"#,
+        );
+        let engine = jit(&codegen);
+        let last_letter = unsafe {
+            engine.get_function::<unsafe extern "C" fn() -> u32>(&mangle_symbol("last_letter"))
+        }
+        .expect("last_letter should be compiled");
+        assert_eq!(unsafe { last_letter.call() }, 'Z' as u32);
+        for name in ["ordered", "high_scalar"] {
+            let func = unsafe {
+                engine.get_function::<unsafe extern "C" fn() -> bool>(&mangle_symbol(name))
+            }
+            .expect("function should be compiled");
+            assert!(unsafe { func.call() }, "{name} should return true");
+        }
+    }
+
+    #[test]
+    fn test_short_circuit_skips_rhs() {
+        // With n == 0 the division would trap if the right operand ran.
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f guarded(n: Int) -> Int
+    if n != 0 && 100 / n > 1 then 1 else 0
+
+f either(n: Int) -> Int
+    if n == 0 || 100 / n > 1 then 1 else 0
+"#,
+        );
+        let engine = jit(&codegen);
+        let guarded = unsafe { engine.get_function::<IntFn>(&mangle_symbol("guarded")) }
+            .expect("guarded should be compiled");
+        assert_eq!(unsafe { guarded.call(0) }, 0);
+        assert_eq!(unsafe { guarded.call(5) }, 1);
+        let either = unsafe { engine.get_function::<IntFn>(&mangle_symbol("either")) }
+            .expect("either should be compiled");
+        assert_eq!(unsafe { either.call(0) }, 1);
+    }
+
+    #[test]
+    fn test_if_expression_value() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f max_with_ten(a: Int) -> Int
+    b := 10
+    m := if a > b then a else b
+    m
+"#,
+        );
+        let engine = jit(&codegen);
+        let max = unsafe { engine.get_function::<IntFn>(&mangle_symbol("max_with_ten")) }
+            .expect("max_with_ten should be compiled");
+        assert_eq!(unsafe { max.call(3) }, 10);
+        assert_eq!(unsafe { max.call(42) }, 42);
+    }
+
+    #[test]
+    fn test_for_range_sum() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f sum_to(n: Int) -> Int
+    total := 0
+    for i in 1..=n
+        total = total + i
+    total
+"#,
+        );
+        let engine = jit(&codegen);
+        let sum_to = unsafe { engine.get_function::<IntFn>(&mangle_symbol("sum_to")) }
+            .expect("sum_to should be compiled");
+        assert_eq!(unsafe { sum_to.call(5) }, 15);
+        assert_eq!(unsafe { sum_to.call(0) }, 0);
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f first_at_least(n: Int) -> Int
+    i := 0
+    wh true
+        if i >= n then br
+        i = i + 1
+    i
+
+f sum_odd(n: Int) -> Int
+    i := 0
+    total := 0
+    wh i < n
+        i = i + 1
+        if i % 2 == 0 then ct
+        total = total + i
+    total
+"#,
+        );
+        let engine = jit(&codegen);
+        let first = unsafe { engine.get_function::<IntFn>(&mangle_symbol("first_at_least")) }
+            .expect("first_at_least should be compiled");
+        assert_eq!(unsafe { first.call(3) }, 3);
+        let sum_odd = unsafe { engine.get_function::<IntFn>(&mangle_symbol("sum_odd")) }
+            .expect("sum_odd should be compiled");
+        assert_eq!(unsafe { sum_odd.call(9) }, 25);
+    }
+
+    #[test]
+    fn test_const_int_widths() {
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        let func = codegen
+            .module
+            .add_function("widths", ctx.void_type().fn_type(&[], false), None);
+        codegen
+            .builder
+            .position_at_end(ctx.append_basic_block(func, "entry"));
+        let mut lower = |n: i64, bits: u32| {
+            let int_ty = ctx.custom_width_int_type(bits);
+            codegen
+                .compile_operand_as(
+                    &Operand::Constant(Constant::Int(n, Ty::Int)),
+                    Some(int_ty.into()),
+                )
+                .expect("constant should compile")
+                .into_int_value()
         };
-        let func = Function {
-            name: "main".to_string(),
-            params: vec![],
-            param_names: vec![],
-            param_pass_modes: vec![],
-            return_ty: Ty::Int,
-            locals: vec![crate::mir::LocalDecl {
-                ty: Ty::Int,
-                name: Some("result".to_string()),
-            }],
-            blocks: vec![entry_block],
-            entry_block: crate::mir::BlockId(0),
-            preconditions: vec![],
-            postconditions: vec![],
+
+        let min_i8 = lower(-128, 8);
+        assert_eq!(min_i8.get_type().get_bit_width(), 8);
+        assert_eq!(min_i8.get_sign_extended_constant(), Some(-128));
+        assert_eq!(min_i8.get_zero_extended_constant(), Some(0x80));
+
+        let neg_one = lower(-1, 8);
+        assert_eq!(neg_one.get_zero_extended_constant(), Some(0xFF));
+
+        let max_i32 = lower(i32::MAX as i64, 32);
+        assert_eq!(max_i32.get_sign_extended_constant(), Some(2147483647));
+        assert_eq!(max_i32.get_type().get_bit_width(), 32);
+
+        let min_i64 = lower(i64::MIN, 64);
+        assert_eq!(min_i64.get_sign_extended_constant(), Some(i64::MIN));
+
+        let wide = lower(-2, 128);
+        assert_eq!(wide.get_type().get_bit_width(), 128);
+        assert_eq!(wide.get_sign_extended_constant(), Some(-2));
+
+        // Unsigned constants zero-extend
+        let byte = codegen
+            .compile_operand_as(
+                &Operand::Constant(Constant::Int(200, Ty::U8)),
+                Some(ctx.i32_type().into()),
+            )
+            .expect("constant should compile")
+            .into_int_value();
+        assert_eq!(byte.get_zero_extended_constant(), Some(200));
+    }
+
+    #[test]
+    fn test_return_constants_at_declared_width() {
+        let const_fn = |name: &str, ty: Ty, n: i64| {
+            let block = BasicBlock {
+                id: crate::mir::BlockId(0),
+                stmts: vec![],
+                terminator: Some(Terminator::Return(Some(Operand::Constant(Constant::Int(
+                    n,
+                    Ty::Int,
+                ))))),
+                terminator_span: None,
+            };
+            make_function(name, ty, vec![], vec![block])
         };
-        functions.insert("main".to_string(), func);
-        let program = Program {
-            functions,
-            entry: Some("main".to_string()),
-            enum_variants: HashMap::new(),
+        let mut program = make_empty_main();
+        for func in [
+            const_fn("min_i8", Ty::I8, -128),
+            const_fn("max_i32", Ty::I32, i32::MAX as i64),
+            const_fn("min_i64", Ty::I64, i64::MIN),
+        ] {
+            program.functions.insert(func.name.clone(), func);
+        }
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.compile(&program).expect("codegen should succeed");
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("ret i8 -128"), "{ir}");
+        assert!(ir.contains("ret i32 2147483647"), "{ir}");
+        assert!(ir.contains("ret i64 -9223372036854775808"), "{ir}");
+    }
+
+    #[test]
+    fn test_enum_tagged_union() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"e Shape
+    Circle(Int)
+    Rect(Int, Int)
+
+f radius(s: Shape) -> Int
+    m s
+        Circle(r) -> r
+        Rect(w, h) -> 0 - w * h
+
+f circle_radius(n: Int) -> Int
+    radius(Circle(n))
+
+f rect_radius(n: Int) -> Int
+    radius(Rect(n, 2))
+"#,
+        );
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("{ i32, [2 x i64] }"), "{ir}");
+
+        let engine = jit(&codegen);
+        let circle = unsafe { engine.get_function::<IntFn>(&mangle_symbol("circle_radius")) }
+            .expect("circle_radius should be compiled");
+        assert_eq!(unsafe { circle.call(5) }, 5);
+        let rect = unsafe { engine.get_function::<IntFn>(&mangle_symbol("rect_radius")) }
+            .expect("rect_radius should be compiled");
+        assert_eq!(unsafe { rect.call(3) }, -6);
+    }
+
+    #[test]
+    fn test_match_int_literals() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f name(n: Int) -> Int
+    m n
+        1 -> 10
+        2 -> 20
+        _ -> 0
+"#,
+        );
+        let engine = jit(&codegen);
+        let name = unsafe { engine.get_function::<IntFn>(&mangle_symbol("name")) }
+            .expect("name should be compiled");
+        assert_eq!(unsafe { name.call(1) }, 10);
+        assert_eq!(unsafe { name.call(2) }, 20);
+        assert_eq!(unsafe { name.call(7) }, 0);
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let ctx = Context::create();
+        let (tokens, _) = crate::Scanner::new(
+            "f add(a: Int, b: Int) -> Int\n    c := a + b\n    c\n\nf main() -> Int\n    add(1, 2)\n",
+        )
+        .scan_all();
+        let ast = crate::Parser::new(&tokens)
+            .parse()
+            .expect("parse should succeed");
+        let program = crate::mir::Lowerer::new()
+            .lower(&ast)
+            .expect("lowering should succeed");
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.enable_debug_info(Path::new("src/test.forma"));
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen.module.verify().expect("module should verify");
+
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("!llvm.dbg.cu"), "missing compile unit:\n{}", ir);
+        assert!(
+            ir.contains("!DISubprogram(name: \"add\""),
+            "missing subprogram:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("!DILocation(line: 2"),
+            "missing statement location:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("filename: \"test.forma\""),
+            "missing file:\n{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_div_checks() {
+        let source =
+            "f divide(a: Int, b: Int) -> Int = a / b\n\nf rem(a: Int, b: Int) -> Int = a % b\n";
+        let ctx = Context::create();
+        let codegen = compile_source(&ctx, source);
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("division by zero"),
+            "missing zero check:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("remainder by zero"),
+            "missing zero check:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("integer overflow in division"),
+            "missing MIN / -1 check:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("call void @forma_panic(ptr @str"),
+            "missing panic call:\n{}",
+            ir
+        );
+
+        let (tokens, _) = crate::Scanner::new(source).scan_all();
+        let ast = crate::Parser::new(&tokens)
+            .parse()
+            .expect("parse should succeed");
+        let program = crate::mir::Lowerer::new()
+            .lower(&ast)
+            .expect("lowering should succeed");
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.set_div_checks(false);
+        codegen.compile(&program).expect("codegen should succeed");
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            !ir.contains("division by zero"),
+            "unexpected check:\n{}",
+            ir
+        );
+        assert!(ir.contains("sdiv"), "missing division:\n{}", ir);
+    }
+
+    #[test]
+    fn test_symbol_mangling() {
+        assert_eq!(mangle_symbol("helper"), "_F6helper");
+        assert_eq!(mangle_symbol("geometry::helper"), "_F8geometry6helper");
+
+        // Same-named functions from two modules get distinct symbols
+        let mut program = make_empty_main();
+        let helper = program.functions["main"].clone();
+        for module in ["geometry", "physics"] {
+            let mut func = helper.clone();
+            func.name = "helper".to_string();
+            program
+                .functions
+                .insert(format!("{}::helper", module), func);
+        }
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen.module.verify().expect("module should verify");
+        assert!(codegen.module.get_function("_F8geometry6helper").is_some());
+        assert!(codegen.module.get_function("_F7physics6helper").is_some());
+        assert!(codegen.module.get_function("main").is_some());
+        assert!(codegen.module.get_function("_F4main").is_none());
+
+        // @no_mangle keeps the source name for FFI entry points
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "@no_mangle\nf exported(x: Int) -> Int = x + 1\n\nf internal(x: Int) -> Int = exported(x)\n",
+        );
+        assert!(codegen.module.get_function("exported").is_some());
+        assert!(codegen.module.get_function("_F8internal").is_some());
+        assert!(codegen.module.get_function("internal").is_none());
+    }
+
+    #[test]
+    fn test_unknown_function_call() {
+        let mut program = make_empty_main();
+        let main = program.functions.get_mut("main").unwrap();
+        main.blocks[0].terminator = Some(Terminator::Call {
+            func: "missing".to_string(),
+            args: vec![],
+            arg_pass_modes: vec![],
+            dest: None,
+            next: crate::mir::BlockId(1),
+        });
+        main.blocks.push(BasicBlock {
+            id: crate::mir::BlockId(1),
+            stmts: vec![],
+            terminator: Some(Terminator::Return(Some(Operand::Constant(Constant::Int(
+                0,
+                Ty::Int,
+            ))))),
+            terminator_span: None,
+        });
+
+        let ctx = Context::create();
+        let err = LLVMCodegen::new(&ctx, "test")
+            .compile(&program)
+            .expect_err("call to an undeclared function should be rejected");
+        assert!(
+            matches!(&err, CodegenError::UnknownFunction(name) if name == "missing"),
+            "{}",
+            err
+        );
+        assert_eq!(err.to_string(), "codegen error: unknown function `missing`");
+    }
+
+    #[test]
+    fn test_duplicate_function_names() {
+        // A mangled `helper` and a `@no_mangle` `_F6helper` share a symbol
+        let mut program = make_empty_main();
+        let mut helper = program.functions["main"].clone();
+        helper.name = "helper".to_string();
+        program
+            .functions
+            .insert("helper".to_string(), helper.clone());
+        helper.name = "_F6helper".to_string();
+        helper.no_mangle = true;
+        program.functions.insert("_F6helper".to_string(), helper);
+
+        let ctx = Context::create();
+        let err = LLVMCodegen::new(&ctx, "test")
+            .compile(&program)
+            .expect_err("duplicate symbol should be rejected");
+        assert!(
+            matches!(
+                &err,
+                CodegenError::DuplicateSymbol { symbol, first, second }
+                    if symbol == "_F6helper" && first == "_F6helper" && second == "helper"
+            ),
+            "{}",
+            err
+        );
+
+        // An extern and a function with the same name
+        let mut program = make_empty_main();
+        program.externs.insert(
+            "main".to_string(),
+            ExternFunction {
+                name: "main".to_string(),
+                params: vec![],
+                return_ty: Ty::Int,
+                call_conv: CallConv::C,
+            },
+        );
+        let ctx = Context::create();
+        let err = LLVMCodegen::new(&ctx, "test")
+            .compile(&program)
+            .expect_err("duplicate name should be rejected");
+        assert!(
+            matches!(&err, CodegenError::DuplicateDefinition { name } if name == "main"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "codegen error: duplicate definition of function `main`"
+        );
+    }
+
+    #[test]
+    fn test_function_without_blocks() {
+        // A unit function with no blocks returns immediately
+        let mut program = make_empty_main();
+        let mut noop = program.functions["main"].clone();
+        noop.name = "noop".to_string();
+        noop.return_ty = Ty::Unit;
+        noop.blocks.clear();
+        program.functions.insert("noop".to_string(), noop.clone());
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen.module.verify().expect("module should verify");
+
+        // Any other return type needs a body to produce the value
+        noop.name = "answer".to_string();
+        noop.return_ty = Ty::Int;
+        program.functions.insert("answer".to_string(), noop);
+        let ctx = Context::create();
+        let err = LLVMCodegen::new(&ctx, "test")
+            .compile(&program)
+            .expect_err("bodyless Int function should be rejected");
+        assert!(
+            matches!(&err, CodegenError::MissingBody { function, .. } if function == "answer"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "codegen error: function `answer` has no body but returns `Int`"
+        );
+    }
+
+    #[test]
+    fn test_verifier_rejects_bad_mir() {
+        // A block without a terminator leaves its LLVM block unterminated
+        let mut program = make_empty_main();
+        let main = program.functions.get_mut("main").unwrap();
+        main.blocks[0].terminator = None;
+
+        let ctx = Context::create();
+        let err = LLVMCodegen::new(&ctx, "test")
+            .compile(&program)
+            .expect_err("unterminated block should fail verification");
+        let CodegenError::VerificationFailed(message) = &err else {
+            panic!("expected a verification error, got {}", err);
+        };
+        assert!(!message.is_empty(), "verifier message should not be empty");
+        assert!(
+            err.to_string()
+                .starts_with("codegen error: module verification failed: "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_large_aggregate_return_uses_sret() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "e Shape\n    Box(Int, Int, Int)\n    Dot\n\ne Flag\n    On(Int)\n    Off\n\nf make(n: Int) -> Shape = Box(n, n + 1, n + 2)\n\nf flag(n: Int) -> Flag = On(n)\n\nf main() -> Int\n    m make(1)\n        Box(a, b, c) -> a + b + c\n        Dot -> 0\n",
+        );
+        let ir = codegen.get_llvm_ir();
+
+        // Shape is a tag plus three payload words, too large for registers
+        let make = codegen.module.get_function("_F4make").unwrap();
+        assert!(make.get_type().get_return_type().is_none(), "{}", ir);
+        assert_eq!(make.count_params(), 2);
+        assert!(
+            make.get_enum_attribute(
+                AttributeLoc::Param(0),
+                Attribute::get_named_enum_kind_id("sret")
+            )
+            .is_some(),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("sret({ i32, [3 x i64] })"), "{}", ir);
+
+        // Flag fits in two registers and is still returned by value
+        let flag = codegen.module.get_function("_F4flag").unwrap();
+        assert!(flag.get_type().get_return_type().is_some(), "{}", ir);
+        assert_eq!(flag.count_params(), 1);
+
+        let engine = jit(&codegen);
+        let main = unsafe { engine.get_function::<unsafe extern "C" fn() -> i64>("main") }
+            .expect("main should be compiled");
+        assert_eq!(unsafe { main.call() }, 6);
+    }
+
+    #[test]
+    fn test_call_conventions() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "@callconv(\"fast\")\nf step(x: Int) -> Int = x + 1\n\n@callconv(\"C\")\nf plain(x: Int) -> Int = step(x)\n\nf main() -> Int = plain(1)\n",
+        );
+        let step = codegen.module.get_function("_F4step").unwrap();
+        assert_eq!(step.get_call_conventions(), 8);
+        let plain = codegen.module.get_function("_F5plain").unwrap();
+        assert_eq!(plain.get_call_conventions(), 0);
+
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("define hidden fastcc i64 @_F4step"), "{}", ir);
+        assert!(ir.contains("call fastcc i64 @_F4step"), "{}", ir);
+        assert!(!ir.contains("call fastcc i64 @_F5plain"), "{}", ir);
+
+        let engine = jit(&codegen);
+        let main = unsafe { engine.get_function::<unsafe extern "C" fn() -> i64>("main") }
+            .expect("main should be compiled");
+        assert_eq!(unsafe { main.call() }, 2);
+    }
+
+    #[test]
+    fn test_symbol_visibility() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "@no_mangle\npub f exported(x: Int) -> Int = x + 1\n\nf internal(x: Int) -> Int = exported(x)\n\n@visibility(\"default\")\nf kept() -> Int = 0\n\n@no_mangle\n@visibility(\"hidden\")\npub f private_entry() -> Int = 0\n\nf main() -> Int = internal(1)\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let visibility = |symbol: &str| {
+            codegen
+                .module
+                .get_function(symbol)
+                .unwrap_or_else(|| panic!("missing {}", symbol))
+                .as_global_value()
+                .get_visibility()
+        };
+        assert_eq!(visibility("exported"), GlobalVisibility::Default);
+        assert_eq!(visibility("main"), GlobalVisibility::Default);
+        assert_eq!(visibility("_F4kept"), GlobalVisibility::Default);
+        assert_eq!(visibility("_F8internal"), GlobalVisibility::Hidden);
+        assert_eq!(visibility("private_entry"), GlobalVisibility::Hidden);
+
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("define hidden i64 @_F8internal"), "{}", ir);
+        assert!(ir.contains("define i64 @exported"), "{}", ir);
+    }
+
+    #[test]
+    fn test_target_data_layout() {
+        let (tokens, _) = crate::Scanner::new("f main() -> Int = 0\n").scan_all();
+        let ast = crate::Parser::new(&tokens).parse().unwrap();
+        let program = crate::mir::Lowerer::new().lower(&ast).unwrap();
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen
+            .set_target("i686-unknown-linux-gnu")
+            .expect("32-bit x86 target should be available");
+        codegen.compile(&program).expect("codegen should succeed");
+
+        assert_eq!(
+            codegen.module.get_triple().as_str().to_str().unwrap(),
+            "i686-unknown-linux-gnu"
+        );
+        let layout = codegen.module.get_data_layout();
+        let layout = layout.as_str().to_str().unwrap();
+        assert!(
+            layout.contains("p:32:32"),
+            "expected 32-bit pointers: {}",
+            layout
+        );
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("target triple = \"i686-unknown-linux-gnu\""),
+            "{}",
+            ir
+        );
+
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        assert!(codegen.set_target("not-a-real-target").is_err());
+    }
+
+    #[test]
+    fn test_constant_global_initializer() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "SECONDS :: 60 * 60\n\nf hours(n: Int) -> Int = n * SECONDS\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let global = codegen
+            .module
+            .get_global(&mangle_symbol("SECONDS"))
+            .expect("SECONDS should be emitted as a global");
+        assert!(global.is_constant());
+        let init = global
+            .get_initializer()
+            .expect("global should have an initializer");
+        assert_eq!(
+            init.into_int_value().get_sign_extended_constant(),
+            Some(3600)
+        );
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("constant i64 3600"), "{}", ir);
+
+        let engine = jit(&codegen);
+        let hours = unsafe { engine.get_function::<IntFn>(&mangle_symbol("hours")) }
+            .expect("hours should be compiled");
+        assert_eq!(unsafe { hours.call(2) }, 7200);
+    }
+
+    #[test]
+    fn test_section_attribute() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "@section(\".text.boot\")\nf boot() -> Int = 1\n\nf other() -> Int = boot()\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let boot = codegen
+            .module
+            .get_function(&mangle_symbol("boot"))
+            .expect("boot should be declared");
+        assert_eq!(
+            boot.get_section().map(|s| s.to_str().unwrap().to_string()),
+            Some(".text.boot".to_string())
+        );
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("section \".text.boot\""),
+            "missing section:\n{}",
+            ir
+        );
+        let other = codegen
+            .module
+            .get_function(&mangle_symbol("other"))
+            .expect("other should be declared");
+        assert!(other.get_section().is_none());
+    }
+
+    #[test]
+    fn test_trap_reports_location() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "f check(n: Int) -> Int\n    assert(n > 0)\n    100 / n\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("@__forma_source_file = private constant [5 x i8] c\"test\\00\""),
+            "missing source file:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("@__forma_source_file, i64 2)"),
+            "missing assert location:\n{}",
+            ir
+        );
+        assert!(
+            ir.contains("@__forma_source_file, i64 3)"),
+            "missing division location:\n{}",
+            ir
+        );
+        assert!(
+            !ir.contains("@forma_assert"),
+            "assert should branch:\n{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_int_casts() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f widen(x: Int) -> i64 = (x as i32) as i64
+
+f widen_unsigned(x: Int) -> u64 = (x as u32) as u64
+
+f narrow(x: i64) -> i8 = x as i8
+
+f to_float(x: i64) -> f64 = x as f64
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("sext i32"), "missing sext:\n{}", ir);
+        assert!(ir.contains("zext i32"), "missing zext:\n{}", ir);
+        assert!(ir.contains("trunc i64"), "missing trunc:\n{}", ir);
+        assert!(ir.contains("sitofp i64"), "missing sitofp:\n{}", ir);
+
+        let engine = jit(&codegen);
+        let widen = unsafe { engine.get_function::<IntFn>(&mangle_symbol("widen")) }
+            .expect("widen should be compiled");
+        assert_eq!(unsafe { widen.call(-5) }, -5);
+        assert_eq!(unsafe { widen.call(1 << 32) }, 0);
+        let widen_unsigned =
+            unsafe { engine.get_function::<IntFn>(&mangle_symbol("widen_unsigned")) }
+                .expect("widen_unsigned should be compiled");
+        assert_eq!(unsafe { widen_unsigned.call(-1) }, u32::MAX as i64);
+        type NarrowFn = unsafe extern "C" fn(i64) -> i8;
+        let narrow = unsafe { engine.get_function::<NarrowFn>(&mangle_symbol("narrow")) }
+            .expect("narrow should be compiled");
+        assert_eq!(unsafe { narrow.call(300) }, 44);
+        assert_eq!(unsafe { narrow.call(200) }, -56);
+        type ToFloatFn = unsafe extern "C" fn(i64) -> f64;
+        let to_float = unsafe { engine.get_function::<ToFloatFn>(&mangle_symbol("to_float")) }
+            .expect("to_float should be compiled");
+        assert_eq!(unsafe { to_float.call(-7) }, -7.0);
+    }
+
+    #[test]
+    fn test_bool_to_int_cast() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "f flag(b: Bool) -> i64 = b as i64\n\nf truthy(n: Int) -> Bool = n as Bool\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("zext i1"), "bool should zero-extend:\n{}", ir);
+
+        let engine = jit(&codegen);
+        type FlagFn = unsafe extern "C" fn(bool) -> i64;
+        let flag = unsafe { engine.get_function::<FlagFn>(&mangle_symbol("flag")) }
+            .expect("flag should be compiled");
+        assert_eq!(unsafe { flag.call(true) }, 1);
+        assert_eq!(unsafe { flag.call(false) }, 0);
+        type TruthyFn = unsafe extern "C" fn(i64) -> bool;
+        let truthy = unsafe { engine.get_function::<TruthyFn>(&mangle_symbol("truthy")) }
+            .expect("truthy should be compiled");
+        assert!(unsafe { truthy.call(2) });
+        assert!(!unsafe { truthy.call(0) });
+    }
+
+    #[test]
+    fn test_int_condition_is_rejected() {
+        // The type checker rejects this; codegen must not fall back to `!= 0`
+        let source = "f bad(x: Int) -> Int = if x then 1 else 0\n";
+        let (tokens, _) = crate::Scanner::new(source).scan_all();
+        let ast = crate::Parser::new(&tokens).parse().unwrap();
+        let program = crate::mir::Lowerer::new().lower(&ast).unwrap();
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        let err = codegen.compile(&program).unwrap_err();
+        assert!(
+            err.to_string().contains("if condition must be Bool"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_string_length_folds_to_constant_global() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "L :: str_len(\"hello\")\nSECOND :: \"héllo\"[1]\n\nf size(n: Int) -> Int = n + L + \"abc\".len()\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let global = codegen
+            .module
+            .get_global(&mangle_symbol("L"))
+            .expect("L should be emitted as a global");
+        let init = global
+            .get_initializer()
+            .expect("global should have an initializer");
+        assert_eq!(init.into_int_value().get_sign_extended_constant(), Some(5));
+        let second = codegen
+            .module
+            .get_global(&mangle_symbol("SECOND"))
+            .and_then(|g| g.get_initializer())
+            .expect("SECOND should be emitted as a global");
+        assert_eq!(
+            second.into_int_value().get_zero_extended_constant(),
+            Some('é' as u64)
+        );
+        // Literal lengths need no runtime call
+        let ir = codegen.get_llvm_ir();
+        assert!(!ir.contains("forma_str_len"), "{}", ir);
+
+        let engine = jit(&codegen);
+        let size = unsafe { engine.get_function::<IntFn>(&mangle_symbol("size")) }
+            .expect("size should be compiled");
+        assert_eq!(unsafe { size.call(1) }, 9);
+    }
+
+    #[test]
+    fn test_string_constants_are_shared() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f first() -> Str = "error"
+
+f second() -> Str = "error"
+
+f other() -> Str = "warning"
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        let globals: Vec<&str> = ir
+            .lines()
+            .filter(|line| line.contains("c\"error\\00\""))
+            .collect();
+        assert_eq!(globals.len(), 1, "expected one shared global:\n{}", ir);
+        assert!(globals[0].contains("private"), "{}", globals[0]);
+        assert_eq!(ir.matches("c\"warning\\00\"").count(), 1, "{}", ir);
+    }
+
+    #[test]
+    fn test_byte_string_literal() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f byte_at(i: Int) -> Int
+    data := b"\x00\x01\xFF"
+    data[i] as Int
+
+f byte_count(n: Int) -> Int = bytes_len(b"\x00\x01\xFF") + n
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        // Both literals share one length-prefixed global
+        assert_eq!(
+            ir.matches(r#"{ i64 3, [3 x i8] c"\00\01\FF" }"#).count(),
+            1,
+            "{}",
+            ir
+        );
+
+        let engine = jit(&codegen);
+        let byte_at = unsafe { engine.get_function::<IntFn>(&mangle_symbol("byte_at")) }
+            .expect("byte_at should be compiled");
+        assert_eq!(unsafe { byte_at.call(0) }, 0x00);
+        assert_eq!(unsafe { byte_at.call(1) }, 0x01);
+        assert_eq!(unsafe { byte_at.call(2) }, 0xFF);
+        let byte_count = unsafe { engine.get_function::<IntFn>(&mangle_symbol("byte_count")) }
+            .expect("byte_count should be compiled");
+        assert_eq!(unsafe { byte_count.call(0) }, 3);
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_relocation_model() {
+        let asm = |mode: RelocMode| {
+            let ctx = Context::create();
+            let mut codegen = compile_source(&ctx, "f greeting() -> Str = \"hello\"\n");
+            codegen.set_reloc_mode(mode);
+            let buffer = codegen
+                .target_machine()
+                .expect("target machine should be created")
+                .write_to_memory_buffer(&codegen.module, FileType::Assembly)
+                .expect("assembly should be emitted");
+            String::from_utf8_lossy(buffer.as_slice()).into_owned()
+        };
+
+        // Position-independent code addresses the literal relative to %rip
+        let pic = asm(RelocMode::PIC);
+        assert!(pic.contains(".Lstr(%rip)"), "{}", pic);
+        // Static code may use its absolute address
+        let fixed = asm(RelocMode::Static);
+        assert!(fixed.contains("$.Lstr"), "{}", fixed);
+        assert!(!fixed.contains(".Lstr(%rip)"), "{}", fixed);
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_code_model() {
+        let asm = |model: CodeModel| {
+            let ctx = Context::create();
+            let mut codegen = compile_source(&ctx, "f greeting() -> Str = \"hello\"\n");
+            codegen.set_reloc_mode(RelocMode::Static);
+            codegen.set_code_model(model);
+            let buffer = codegen
+                .target_machine()
+                .expect("target machine should be created")
+                .write_to_memory_buffer(&codegen.module, FileType::Assembly)
+                .expect("assembly should be emitted");
+            String::from_utf8_lossy(buffer.as_slice()).into_owned()
+        };
+
+        for model in [CodeModel::Small, CodeModel::Kernel, CodeModel::Medium] {
+            assert!(asm(model).contains("greeting"), "{:?}", model);
+        }
+        // Only the large model needs a full 64-bit immediate for the address
+        assert!(!asm(CodeModel::Small).contains("movabsq"));
+        let large = asm(CodeModel::Large);
+        assert!(large.contains("movabsq\t$.Lstr"), "{}", large);
+    }
+
+    #[test]
+    fn test_write_static_library() {
+        let ctx = Context::create();
+        let codegen = compile_source(&ctx, "f area(w: Int, h: Int) -> Int = w * h\n");
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("libshapes.a");
+        codegen
+            .write_static_library(&archive)
+            .expect("archive should be written");
+
+        let listing = std::process::Command::new("ar")
+            .arg("t")
+            .arg(&archive)
+            .output()
+            .expect("ar should run");
+        assert!(listing.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&listing.stdout).trim(),
+            "libshapes.o"
+        );
+        // The intermediate object is removed
+        assert!(!dir.path().join("libshapes.o").exists());
+    }
+
+    #[test]
+    fn test_select_is_branchless() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "f pick(c: Bool, x: Int, y: Int) -> Int\n    select(c, x, y)\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        let body = ir
+            .split("define ")
+            .find(|f| f.contains("pick"))
+            .expect("pick should be defined");
+        assert!(body.contains("select i1"), "missing select:\n{}", body);
+        assert!(
+            !body.contains("br i1"),
+            "select should not branch:\n{}",
+            body
+        );
+    }
+
+    #[test]
+    fn test_coverage_counters() {
+        let source = "f sign(x: Int) -> Int\n    if x < 0 then -1 else 1\n\nf main() -> Int\n    sign(5)\n    0\n";
+        let (tokens, _) = crate::Scanner::new(source).scan_all();
+        let ast = crate::Parser::new(&tokens).parse().unwrap();
+        let program = crate::mir::Lowerer::new().lower(&ast).unwrap();
+        let blocks: usize = program.functions.values().map(|f| f.blocks.len()).sum();
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.enable_coverage();
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen.module.verify().expect("module should verify");
+
+        let counters = codegen
+            .module
+            .get_global("__forma_coverage_counters")
+            .expect("counter array should be emitted");
+        assert_eq!(
+            counters.get_value_type().into_array_type().len() as usize,
+            blocks
+        );
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("c\"sign bb_0\\00\""), "missing label:\n{}", ir);
+        assert!(
+            ir.contains("call void @forma_coverage_dump"),
+            "missing dump call:\n{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_extern_declaration() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            "extern \"C\" f labs(x: Int) -> Int\n\nf absolute(x: Int) -> Int = labs(x)\n",
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("declare i64 @labs(i64)"),
+            "missing declare:\n{}",
+            ir
+        );
+        assert!(ir.contains("call i64 @labs("), "missing call:\n{}", ir);
+
+        // The declaration resolves against libc's labs at link time
+        let engine = jit(&codegen);
+        let absolute = unsafe { engine.get_function::<IntFn>(&mangle_symbol("absolute")) }
+            .expect("absolute should be compiled");
+        assert_eq!(unsafe { absolute.call(-7) }, 7);
+    }
+
+    #[test]
+    fn test_reference_to_local() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f read_back(n: Int) -> Int
+    x := n
+    r := &x
+    *r
+
+f get(p: &Int) -> Int = *p
+
+f via_param(n: Int) -> Int
+    x := n + 1
+    get(&x)
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("define i64 @_F3get(ptr"),
+            "reference parameter should be a pointer:\n{}",
+            ir
+        );
+        let engine = jit(&codegen);
+        let read_back = unsafe { engine.get_function::<IntFn>(&mangle_symbol("read_back")) }
+            .expect("read_back should be compiled");
+        assert_eq!(unsafe { read_back.call(42) }, 42);
+        let via_param = unsafe { engine.get_function::<IntFn>(&mangle_symbol("via_param")) }
+            .expect("via_param should be compiled");
+        assert_eq!(unsafe { via_param.call(9) }, 10);
+    }
+
+    #[test]
+    fn test_function_pointer_call() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f double(x: Int) -> Int = x * 2
+
+f apply(g: (Int) -> Int, v: Int) -> Int = g(v)
+
+f run(n: Int) -> Int
+    g := double
+    g(n) + apply(double, n)
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        let engine = jit(&codegen);
+        let run = unsafe { engine.get_function::<IntFn>(&mangle_symbol("run")) }
+            .expect("run should be compiled");
+        assert_eq!(unsafe { run.call(5) }, 20);
+    }
+
+    #[test]
+    fn test_uninitialized_local_is_rejected() {
+        let mut program = make_empty_main();
+        let main = program.functions.get_mut("main").unwrap();
+        main.locals.push(crate::mir::LocalDecl {
+            ty: Ty::Int,
+            name: Some("x".to_string()),
+        });
+        main.blocks[0].terminator = Some(Terminator::Return(Some(Operand::Copy(
+            crate::mir::Local(0),
+        ))));
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        let err = codegen.compile(&program).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                CodegenError::UninitializedLocal { function, local }
+                    if function == "main" && local == "x"
+            ),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "codegen error: use of possibly-uninitialized variable `x` in function `main`"
+        );
+    }
+
+    #[test]
+    fn test_bounds_checks() {
+        let program = lower_source("f pick(i: Int) -> Int\n    xs := [10, 20, 30]\n    xs[i]\n");
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.compile(&program).expect("codegen should succeed");
+        assert!(!codegen.get_llvm_ir().contains("forma_panic_index"));
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.set_bounds_checks(true);
+        codegen.compile(&program).expect("codegen should succeed");
+        codegen.module.verify().expect("module should verify");
+        let ir = codegen.get_llvm_ir();
+        assert!(ir.contains("icmp ult i64"), "{}", ir);
+        assert!(
+            ir.contains("call void @forma_panic_index(i64 %"),
+            "index and length should be passed to the panic:\n{}",
+            ir
+        );
+        assert!(ir.contains("i64 3, ptr"), "{}", ir);
+    }
+
+    #[test]
+    fn test_env_builtins_require_capability() {
+        let program = lower_source("f main() -> Int\n    _ := env_get(\"HOME\")\n    0\n");
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        let err = codegen.compile(&program).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                CodegenError::CapabilityDenied { capability, operation }
+                    if capability == "env" && operation == "env_get"
+            ),
+            "{}",
+            err
+        );
+
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        codegen.grant_capability("env");
+        codegen.compile(&program).expect("codegen should succeed");
+        assert!(codegen.get_llvm_ir().contains("declare ptr @forma_env_get"));
+    }
+
+    #[test]
+    fn test_constant_operand_types() {
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        let mut lower = |c: Constant| {
+            codegen
+                .compile_operand(&Operand::Constant(c))
+                .expect("constant should compile")
+        };
+
+        let int = lower(Constant::Int(-7, Ty::Int));
+        assert_eq!(int.into_int_value().get_type().get_bit_width(), 64);
+        assert_eq!(int.into_int_value().get_sign_extended_constant(), Some(-7));
+
+        let small = lower(Constant::Int(-1, Ty::I8));
+        assert_eq!(small.into_int_value().get_type().get_bit_width(), 8);
+        assert_eq!(
+            small.into_int_value().get_zero_extended_constant(),
+            Some(0xFF)
+        );
+
+        let wide = lower(Constant::Int(5, Ty::U128));
+        assert_eq!(wide.into_int_value().get_type().get_bit_width(), 128);
+
+        let b = lower(Constant::Bool(true));
+        assert_eq!(b.into_int_value().get_type().get_bit_width(), 1);
+        assert_eq!(b.into_int_value().get_zero_extended_constant(), Some(1));
+
+        let f = lower(Constant::Float(1.5));
+        assert!(f.is_float_value());
+        assert_eq!(f.into_float_value().get_constant(), Some((1.5, false)));
+
+        let c = lower(Constant::Char('é'));
+        assert_eq!(c.into_int_value().get_type().get_bit_width(), 32);
+        assert_eq!(
+            c.into_int_value().get_zero_extended_constant(),
+            Some('é' as u64)
+        );
+
+        assert!(lower(Constant::Str("hi".to_string())).is_pointer_value());
+
+        let unit = lower(Constant::Unit);
+        assert_eq!(unit.into_int_value().get_type().get_bit_width(), 8);
+    }
+
+    #[test]
+    fn test_formatted_print_declares_runtime_call() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f main()
+    print("{} is {}", 42, "the answer")
+"#,
+        );
+        codegen.module.verify().expect("module should verify");
+        assert!(codegen.module.get_function("forma_print_fmt").is_some());
+    }
+
+    #[test]
+    fn test_inferred_let_types() {
+        let ctx = Context::create();
+        let codegen = compile_source(
+            &ctx,
+            r#"f clamp(n: Int) -> Int
+    limit := 10
+    over := n > limit
+    if over then limit else n
+"#,
+        );
+        let engine = jit(&codegen);
+        let clamp = unsafe { engine.get_function::<IntFn>(&mangle_symbol("clamp")) }
+            .expect("clamp should be compiled");
+        assert_eq!(unsafe { clamp.call(25) }, 10);
+        assert_eq!(unsafe { clamp.call(4) }, 4);
+    }
+
+    #[test]
+    fn test_deterministic_ir() {
+        // Lowering twice gives maps with different hash orders, so emission
+        // order must not depend on them
+        let source = r#"extern "C" f labs(x: Int) -> Int
+extern "C" f abs(x: Int) -> Int
+
+A :: 1
+B :: 2
+C :: 3
+D :: 4
+
+f one() -> Int = A
+f two() -> Int = B
+f three() -> Int = C
+f four() -> Int = D
+f five() -> Int = labs(0 - 5)
+f six() -> Int = abs(0 - 6)
+f seven() -> Int = one() + six()
+f eight() -> Int = two() * four()
+
+f main() -> Int = seven() + eight()
+"#;
+        let ir = || {
+            let ctx = Context::create();
+            compile_source(&ctx, source).get_llvm_ir()
+        };
+        let first = ir();
+        for _ in 0..4 {
+            assert_eq!(ir(), first);
+        }
+    }
+
+    #[test]
+    fn test_compile_empty_main() {
+        let program = make_empty_main();
+        let ctx = Context::create();
+        let mut codegen = LLVMCodegen::new(&ctx, "test");
+        assert!(codegen.compile(&program).is_ok());
+        let ir = codegen.get_llvm_ir();
+        assert!(
+            ir.contains("define"),
+            "IR should contain function definitions"
+        );
+    }
+
+    #[test]
+    fn test_compile_integer_arithmetic() {
+        let mut functions = HashMap::new();
+        let result_local = crate::mir::Local(0);
+        let entry_block = BasicBlock {
+            id: crate::mir::BlockId(0),
+            stmts: vec![Statement {
+                kind: StatementKind::Assign(
+                    result_local,
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        Operand::Constant(Constant::Int(2, Ty::Int)),
+                        Operand::Constant(Constant::Int(3, Ty::Int)),
+                    ),
+                ),
+                span: None,
+            }],
+            terminator: Some(Terminator::Return(Some(Operand::Local(result_local)))),
+            terminator_span: None,
+        };
+        let func = make_function(
+            "main",
+            Ty::Int,
+            vec![crate::mir::LocalDecl {
+                ty: Ty::Int,
+                name: Some("result".to_string()),
+            }],
+            vec![entry_block],
+        );
+        functions.insert("main".to_string(), func);
+        let program = Program {
+            functions,
+            entry: Some("main".to_string()),
+            ..Program::default()
         };
 
         let ctx = Context::create();
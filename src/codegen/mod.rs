@@ -3,9 +3,15 @@
 //! This module provides backends for compiling FORMA programs to native code.
 //! Currently supports:
 //! - LLVM IR generation (with the `llvm` feature)
+//!
+//! The object cache used by incremental builds lives here too.
+
+pub mod cache;
 
 #[cfg(feature = "llvm")]
 pub mod llvm;
 
 #[cfg(feature = "llvm")]
-pub use llvm::LLVMCodegen;
+pub use llvm::{LLVMCodegen, mangle_symbol};
+
+pub use cache::ObjectCache;
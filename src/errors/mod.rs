@@ -10,4 +10,6 @@ pub use diagnostic::{
     CompileError, Diagnostic, Label, LabelStyle, LexError, MultiResult, ParseError, Result,
     Severity, TypeError,
 };
-pub use report::{ErrorContext, report_error, report_errors, report_warning};
+pub use report::{
+    DEFAULT_TAB_WIDTH, ErrorContext, report_error, report_errors, report_warning, span_end,
+};
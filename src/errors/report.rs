@@ -3,15 +3,56 @@
 //! This module provides nice error output with source context,
 //! colored output, and helpful messages.
 
-use ariadne::{Color, Label, Report, ReportKind, Source};
+use ariadne::{Color, Config, IndexType, Label, Report, ReportKind, Source};
 
 use crate::lexer::Span;
 
+/// Default number of columns a tab advances when aligning carets.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Renderer configuration. Spans hold byte offsets, while ariadne counts
+/// chars by default, which misplaces labels after multibyte characters.
+fn config(tab_width: usize) -> Config {
+    Config::default()
+        .with_index_type(IndexType::Byte)
+        .with_tab_width(tab_width)
+}
+
+/// Line and column (both 1-indexed) just past the end of `span`.
+///
+/// Columns count Unicode scalar values from the start of the line, matching
+/// the scanner. Falls back to byte arithmetic if the span does not lie
+/// within `source`.
+pub fn span_end(source: &str, span: Span) -> (usize, usize) {
+    let Some(text) = source.get(span.start..span.end) else {
+        return (span.line, span.column + span.end.saturating_sub(span.start));
+    };
+    match text.rfind('\n') {
+        Some(last) => (
+            span.line + text.matches('\n').count(),
+            text[last + 1..].chars().count() + 1,
+        ),
+        None => (span.line, span.column + text.chars().count()),
+    }
+}
+
 /// Report a single error with source context.
 pub fn report_error(filename: &str, source: &str, span: Span, message: &str, help: Option<&str>) {
+    report_error_with_tab_width(filename, source, span, message, help, DEFAULT_TAB_WIDTH);
+}
+
+fn report_error_with_tab_width(
+    filename: &str,
+    source: &str,
+    span: Span,
+    message: &str,
+    help: Option<&str>,
+    tab_width: usize,
+) {
     let offset = span.start;
 
     let mut report = Report::build(ReportKind::Error, filename, offset)
+        .with_config(config(tab_width))
         .with_message(message)
         .with_label(
             Label::new((filename, offset..span.end))
@@ -28,9 +69,20 @@ pub fn report_error(filename: &str, source: &str, span: Span, message: &str, hel
 
 /// Report a warning with source context.
 pub fn report_warning(filename: &str, source: &str, span: Span, message: &str) {
+    report_warning_with_tab_width(filename, source, span, message, DEFAULT_TAB_WIDTH);
+}
+
+fn report_warning_with_tab_width(
+    filename: &str,
+    source: &str,
+    span: Span,
+    message: &str,
+    tab_width: usize,
+) {
     let offset = span.start;
 
     let _ = Report::build(ReportKind::Warning, filename, offset)
+        .with_config(config(tab_width))
         .with_message(message)
         .with_label(
             Label::new((filename, offset..span.end))
@@ -52,22 +104,47 @@ pub fn report_errors(filename: &str, source: &str, errors: &[(Span, String, Opti
 pub struct ErrorContext<'a> {
     filename: &'a str,
     source: &'a str,
+    tab_width: usize,
 }
 
 impl<'a> ErrorContext<'a> {
     pub fn new(filename: &'a str, source: &'a str) -> Self {
-        Self { filename, source }
+        Self {
+            filename,
+            source,
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    /// Set how many columns a tab expands to when aligning carets.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
     }
 
     pub fn error(&self, span: Span, message: &str) {
-        report_error(self.filename, self.source, span, message, None);
+        report_error_with_tab_width(
+            self.filename,
+            self.source,
+            span,
+            message,
+            None,
+            self.tab_width,
+        );
     }
 
     pub fn error_with_help(&self, span: Span, message: &str, help: &str) {
-        report_error(self.filename, self.source, span, message, Some(help));
+        report_error_with_tab_width(
+            self.filename,
+            self.source,
+            span,
+            message,
+            Some(help),
+            self.tab_width,
+        );
     }
 
     pub fn warning(&self, span: Span, message: &str) {
-        report_warning(self.filename, self.source, span, message);
+        report_warning_with_tab_width(self.filename, self.source, span, message, self.tab_width);
     }
 }
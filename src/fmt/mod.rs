@@ -3,13 +3,52 @@
 //! This module provides a pretty-printer that formats FORMA source code
 //! with consistent indentation and spacing.
 
+use crate::lexer::{Comment, Span};
 use crate::parser::*;
 
+/// Line ending written by the formatter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+impl LineEnding {
+    /// The dominant line ending of `source`: CRLF when more lines end in
+    /// `\r\n` than in a bare `\n`, otherwise LF.
+    pub fn detect(source: &str) -> Self {
+        let lines = source.matches('\n').count();
+        let crlf = source.matches("\r\n").count();
+        if crlf > lines - crlf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
 /// A code formatter for FORMA source files.
 pub struct Formatter {
     output: String,
     indent: usize,
     indent_size: usize,
+    line_ending: LineEnding,
+    /// Source comments, in source order
+    comments: Vec<Comment>,
+    /// Index of the next comment to emit
+    next_comment: usize,
+    /// Source line of the item, statement or arm being written
+    src_line: usize,
 }
 
 impl Formatter {
@@ -18,13 +57,35 @@ impl Formatter {
             output: String::new(),
             indent: 0,
             indent_size: 4,
+            line_ending: LineEnding::Lf,
+            comments: Vec::new(),
+            next_comment: 0,
+            src_line: 0,
         }
     }
 
-    /// Format a source file and return the formatted output.
+    /// Re-emit these comments (from `Scanner::scan_all_with_comments`) in
+    /// the formatted output. Own-line comments are placed above the next
+    /// item, statement, field or arm; trailing comments stay at the end of
+    /// their line.
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// End every line of the output with `line_ending`.
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Format a source file and return the formatted output, which ends in
+    /// exactly one line ending unless it is empty.
     pub fn format(&mut self, source: &SourceFile) -> String {
         self.output.clear();
         self.indent = 0;
+        self.next_comment = 0;
+        self.src_line = 0;
 
         for (i, item) in source.items.iter().enumerate() {
             if i > 0 {
@@ -33,7 +94,19 @@ impl Formatter {
             self.format_item(item);
         }
 
-        self.output.clone()
+        // Comments after the last item
+        if self.next_comment < self.comments.len() && !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.leading_comments(usize::MAX);
+
+        let body = self.output.trim_end_matches('\n');
+        if body.is_empty() {
+            return String::new();
+        }
+        let mut formatted = body.replace('\n', self.line_ending.as_str());
+        formatted.push_str(self.line_ending.as_str());
+        formatted
     }
 
     fn write(&mut self, s: &str) {
@@ -41,9 +114,44 @@ impl Formatter {
     }
 
     fn newline(&mut self) {
+        if let Some(comment) = self.comments.get(self.next_comment)
+            && comment.trailing
+            && comment.span.line <= self.src_line
+        {
+            let text = format!("  #{}", comment.text.trim_end());
+            self.output.push_str(&text);
+            self.next_comment += 1;
+        }
         self.output.push('\n');
     }
 
+    /// Note that the construct at `span` is about to be written, emitting
+    /// any comments that come before it on their own lines.
+    fn mark(&mut self, span: Span) {
+        self.leading_comments(span.line);
+        self.src_line = span.line;
+    }
+
+    /// Emit pending comments from lines before `line`, each on its own line.
+    fn leading_comments(&mut self, line: usize) {
+        let mut previous_line = None;
+        while let Some(comment) = self.comments.get(self.next_comment)
+            && comment.span.line < line
+        {
+            // Keep blank lines that separate groups of comments
+            let comment_line = comment.span.line;
+            if previous_line.is_some_and(|prev| comment_line > prev + 1) {
+                self.output.push('\n');
+            }
+            previous_line = Some(comment_line);
+            let text = format!("#{}", comment.text.trim_end());
+            self.next_comment += 1;
+            self.write_indent();
+            self.output.push_str(&text);
+            self.output.push('\n');
+        }
+    }
+
     fn write_indent(&mut self) {
         for _ in 0..(self.indent * self.indent_size) {
             self.output.push(' ');
@@ -51,6 +159,7 @@ impl Formatter {
     }
 
     fn format_item(&mut self, item: &Item) {
+        self.mark(item.span);
         match &item.kind {
             ItemKind::Function(f) => self.format_function(f),
             ItemKind::Struct(s) => self.format_struct(s),
@@ -119,16 +228,25 @@ impl Formatter {
     }
 
     fn format_function(&mut self, f: &Function) {
+        self.mark(f.span);
         self.write_indent();
 
         if f.visibility == Visibility::Public {
             self.write("pub ");
         }
 
+        if f.is_const {
+            self.write("const ");
+        }
+
         if f.is_async {
             self.write("as ");
         }
 
+        if let Some(ref abi) = f.abi {
+            self.write(&format!("extern \"{}\" ", abi));
+        }
+
         self.write("f ");
         self.write(&f.name.name);
         self.write("(");
@@ -204,6 +322,7 @@ impl Formatter {
                 self.newline();
                 self.indent += 1;
                 for field in fields {
+                    self.mark(field.span);
                     self.write_indent();
                     self.write(&field.name.name);
                     self.write(": ");
@@ -240,6 +359,7 @@ impl Formatter {
         self.indent += 1;
 
         for variant in &e.variants {
+            self.mark(variant.span);
             self.write_indent();
             self.write(&variant.name.name);
             self.newline();
@@ -331,6 +451,10 @@ impl Formatter {
     }
 
     fn format_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::Empty = stmt.kind {
+            return;
+        }
+        self.mark(stmt.span);
         self.write_indent();
         match &stmt.kind {
             StmtKind::Let(let_stmt) => {
@@ -350,6 +474,20 @@ impl Formatter {
         self.newline();
     }
 
+    /// Write `@invariant(...)` lines ahead of a loop header.
+    fn format_invariants(&mut self, invariants: &[Contract]) {
+        for invariant in invariants {
+            self.write("@invariant(");
+            self.format_expr(&invariant.condition);
+            if let Some(message) = &invariant.message {
+                self.write(&format!(", \"{}\"", message));
+            }
+            self.write(")");
+            self.newline();
+            self.write_indent();
+        }
+    }
+
     fn format_expr(&mut self, expr: &Expr) {
         match &expr.kind {
             ExprKind::Literal(lit) => self.format_literal(lit),
@@ -513,6 +651,7 @@ impl Formatter {
                 self.newline();
                 self.indent += 1;
                 for arm in arms {
+                    self.mark(arm.span);
                     self.write_indent();
                     self.format_pattern(&arm.pattern);
                     self.write(" -> ");
@@ -521,7 +660,8 @@ impl Formatter {
                 }
                 self.indent -= 1;
             }
-            ExprKind::For(_label, pattern, iterable, body) => {
+            ExprKind::For(_label, pattern, iterable, body, invariants) => {
+                self.format_invariants(invariants);
                 self.write("fo ");
                 self.format_pattern(pattern);
                 self.write(" in ");
@@ -533,7 +673,8 @@ impl Formatter {
                 }
                 self.indent -= 1;
             }
-            ExprKind::While(_label, cond, body) => {
+            ExprKind::While(_label, cond, body, invariants) => {
+                self.format_invariants(invariants);
                 self.write("wh ");
                 self.format_expr(cond);
                 self.newline();
@@ -707,6 +848,7 @@ impl Formatter {
                 self.write(s);
                 self.write("\"");
             }
+            LiteralKind::Bytes(bytes) => self.write(&byte_string_source(bytes)),
             LiteralKind::Char(c) => {
                 self.write("'");
                 self.output.push(*c);
@@ -743,6 +885,7 @@ impl Formatter {
 
     fn format_unaryop(&mut self, op: &UnaryOp) {
         let s = match op {
+            UnaryOp::Plus => "+",
             UnaryOp::Neg => "-",
             UnaryOp::Not => "!",
             UnaryOp::Ref => "&",
@@ -6,5 +6,5 @@
 pub mod scanner;
 pub mod token;
 
-pub use scanner::Scanner;
-pub use token::{FStringPart, Span, Token, TokenKind};
+pub use scanner::{IndentPolicy, Scanner};
+pub use token::{Comment, FStringPart, Span, Token, TokenKind};
@@ -4,7 +4,20 @@
 //! handling indentation-significant syntax.
 
 use crate::errors::LexError;
-use crate::lexer::token::{FStringPart, Span, Token, TokenKind};
+use crate::lexer::token::{Comment, FStringPart, Span, Token, TokenKind};
+
+/// Which whitespace characters may be used for indentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndentPolicy {
+    /// Only spaces may indent lines.
+    SpacesOnly,
+    /// Only tabs may indent lines.
+    TabsOnly,
+    /// Either may be used, but every line in a file must use the same one
+    /// as the first indented line.
+    #[default]
+    Consistent,
+}
 
 /// The lexer that tokenizes FORMA source code.
 pub struct Scanner<'a> {
@@ -26,12 +39,20 @@ pub struct Scanner<'a> {
     // Bracket nesting depth - suppress INDENT/DEDENT inside brackets (like Python)
     bracket_depth: usize,
 
+    // Indentation character policy, and the character and line that first
+    // indented this file
+    indent_policy: IndentPolicy,
+    indent_char: Option<(char, usize)>,
+
     // String interpolation support
     interpolation_depth: usize,
     brace_depth_stack: Vec<usize>,
 
     // Errors collected during scanning
     errors: Vec<LexError>,
+
+    // Comments collected during scanning
+    comments: Vec<Comment>,
 }
 
 impl<'a> Scanner<'a> {
@@ -50,12 +71,32 @@ impl<'a> Scanner<'a> {
             interpolation_depth: 0,
             brace_depth_stack: Vec::new(),
             bracket_depth: 0,
+            indent_policy: IndentPolicy::default(),
+            indent_char: None,
             errors: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
+    /// Set which characters may be used for indentation.
+    pub fn with_indent_policy(mut self, policy: IndentPolicy) -> Self {
+        self.indent_policy = policy;
+        self
+    }
+
     /// Tokenize the entire source and return all tokens.
     pub fn scan_all(mut self) -> (Vec<Token>, Vec<LexError>) {
+        let tokens = self.scan_tokens();
+        (tokens, self.errors)
+    }
+
+    /// Tokenize the entire source, also returning the comments in source order.
+    pub fn scan_all_with_comments(mut self) -> (Vec<Token>, Vec<LexError>, Vec<Comment>) {
+        let tokens = self.scan_tokens();
+        (tokens, self.errors, self.comments)
+    }
+
+    fn scan_tokens(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
 
         loop {
@@ -67,7 +108,7 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        (tokens, self.errors)
+        tokens
     }
 
     /// Get the next token from the source.
@@ -295,8 +336,16 @@ impl<'a> Scanner<'a> {
             }
 
             // String literals
-            '"' => self.scan_string(),
-            '`' => self.scan_raw_string(),
+            '"' => {
+                if self.peek() == Some('"') && self.peek_next() == Some('"') {
+                    self.advance();
+                    self.advance();
+                    self.scan_multiline_string()
+                } else {
+                    self.scan_string()
+                }
+            }
+            '`' => self.scan_raw_string('`'),
             '\'' => self.scan_char(),
 
             // Numbers
@@ -312,14 +361,18 @@ impl<'a> Scanner<'a> {
     fn handle_indentation(&mut self) -> Option<Token> {
         // Count leading whitespace
         let mut indent = 0;
+        let mut line_start = self.current;
+        let (mut spaces, mut tabs) = (false, false);
         while let Some(&(_, c)) = self.chars.peek() {
             match c {
                 ' ' => {
                     indent += 1;
+                    spaces = true;
                     self.advance();
                 }
                 '\t' => {
                     indent += 4; // Treat tabs as 4 spaces
+                    tabs = true;
                     self.advance();
                 }
                 '\n' => {
@@ -328,12 +381,15 @@ impl<'a> Scanner<'a> {
                     self.line += 1;
                     self.column = 1;
                     indent = 0;
+                    line_start = self.current;
+                    (spaces, tabs) = (false, false);
                     continue;
                 }
                 '#' => {
                     // Comment line, skip to end
-                    self.skip_line_comment();
+                    self.skip_line_comment(false);
                     indent = 0;
+                    (spaces, tabs) = (false, false);
                     continue;
                 }
                 _ => break,
@@ -350,6 +406,16 @@ impl<'a> Scanner<'a> {
             return None;
         }
 
+        if let Some(message) = self.check_indent_chars(spaces, tabs) {
+            let span = Span::new(line_start, self.current, self.line, 1);
+            self.errors.push(LexError::new(message.clone(), span));
+            return Some(Token::new(
+                TokenKind::Error(message),
+                span,
+                &self.source[line_start..self.current],
+            ));
+        }
+
         let current_indent = self.indent_stack.last().copied().unwrap_or(0);
 
         if indent > current_indent {
@@ -384,6 +450,44 @@ impl<'a> Scanner<'a> {
         None
     }
 
+    /// Check the characters used to indent the current line against the
+    /// indentation policy, returning an error message on a violation.
+    fn check_indent_chars(&mut self, spaces: bool, tabs: bool) -> Option<String> {
+        if spaces && tabs {
+            return Some("inconsistent use of tabs and spaces in indentation".to_string());
+        }
+        let c = match (spaces, tabs) {
+            (true, _) => ' ',
+            (_, true) => '\t',
+            _ => return None,
+        };
+        let name = |c: char| if c == ' ' { "spaces" } else { "tabs" };
+        match self.indent_policy {
+            IndentPolicy::SpacesOnly if c == '\t' => {
+                Some("indentation must use spaces, found tabs".to_string())
+            }
+            IndentPolicy::TabsOnly if c == ' ' => {
+                Some("indentation must use tabs, found spaces".to_string())
+            }
+            IndentPolicy::Consistent => match self.indent_char {
+                None => {
+                    self.indent_char = Some((c, self.line));
+                    None
+                }
+                Some((first, line)) if first != c => Some(format!(
+                    "inconsistent use of tabs and spaces in indentation: \
+                     line {} is indented with {}, but line {} uses {}",
+                    self.line,
+                    name(c),
+                    line,
+                    name(first)
+                )),
+                Some(_) => None,
+            },
+            _ => None,
+        }
+    }
+
     fn skip_whitespace_and_comments(&mut self) {
         loop {
             match self.peek() {
@@ -391,20 +495,27 @@ impl<'a> Scanner<'a> {
                     self.advance();
                 }
                 Some('#') => {
-                    self.skip_line_comment();
+                    self.skip_line_comment(true);
                 }
                 _ => break,
             }
         }
     }
 
-    fn skip_line_comment(&mut self) {
+    fn skip_line_comment(&mut self, trailing: bool) {
+        let start = self.current;
+        let column = self.column;
         while let Some(c) = self.peek() {
             if c == '\n' {
                 break;
             }
             self.advance();
         }
+        self.comments.push(Comment {
+            text: self.source[start + 1..self.current].to_string(),
+            span: Span::new(start, self.current, self.line, column),
+            trailing,
+        });
     }
 
     fn scan_string(&mut self) -> Token {
@@ -421,44 +532,135 @@ impl<'a> Scanner<'a> {
                 }
                 Some('\\') => {
                     self.advance();
-                    match self.advance() {
-                        Some('n') => value.push('\n'),
-                        Some('r') => value.push('\r'),
-                        Some('t') => value.push('\t'),
-                        Some('\\') => value.push('\\'),
-                        Some('\'') => value.push('\''),
-                        Some('"') => value.push('"'),
-                        Some('0') => value.push('\0'),
+                    match self.scan_escape() {
+                        Ok(ch) => value.push(ch),
+                        Err(message) => return self.error_token(message),
+                    }
+                }
+                Some('{') => {
+                    // String interpolation - for now, just include the brace
+                    // Full interpolation support will be handled in a future phase
+                    self.advance();
+                    value.push('{');
+                }
+                Some(c) => {
+                    self.advance();
+                    value.push(c);
+                }
+            }
+        }
+
+        self.make_token(TokenKind::String(value))
+    }
+
+    /// Scan a byte string body after `b"`. Only ASCII characters are allowed;
+    /// `\xNN` escapes cover the full byte range.
+    fn scan_byte_string(&mut self) -> Token {
+        let mut value = Vec::new();
+
+        loop {
+            match self.peek() {
+                None | Some('\n') => {
+                    return self.error_token("unterminated byte string");
+                }
+                Some('"') => {
+                    self.advance();
+                    break;
+                }
+                Some('\\') => {
+                    self.advance();
+                    match self.peek() {
                         Some('x') => {
-                            if let Some(ch) = self.scan_hex_escape(2) {
-                                value.push(ch);
-                            } else {
-                                return self.error_token("invalid hex escape");
+                            self.advance();
+                            match self.scan_hex_byte() {
+                                Some(b) => value.push(b),
+                                None => return self.error_token("invalid hex escape"),
                             }
                         }
                         Some('u') => {
-                            if !self.match_char('{') {
-                                return self.error_token("expected '{' in unicode escape");
-                            }
-                            if let Some(ch) = self.scan_unicode_escape() {
-                                value.push(ch);
-                            } else {
-                                return self.error_token("invalid unicode escape");
-                            }
-                        }
-                        Some(c) => {
-                            return self.error_token(format!("invalid escape sequence: \\{}", c));
-                        }
-                        None => {
-                            return self.error_token("unterminated string");
+                            return self
+                                .error_token("unicode escapes are not allowed in byte strings");
                         }
+                        _ => match self.scan_escape() {
+                            Ok(ch) => value.push(ch as u8),
+                            Err(message) => return self.error_token(message),
+                        },
                     }
                 }
-                Some('{') => {
-                    // String interpolation - for now, just include the brace
-                    // Full interpolation support will be handled in a future phase
+                Some(c) if c.is_ascii() => {
                     self.advance();
-                    value.push('{');
+                    value.push(c as u8);
+                }
+                Some(c) => {
+                    return self.error_token(format!(
+                        "non-ASCII character '{}' in byte string (use \\x escapes)",
+                        c
+                    ));
+                }
+            }
+        }
+
+        self.make_token(TokenKind::ByteString(value))
+    }
+
+    /// Scan the character after a backslash in a string literal.
+    fn scan_escape(&mut self) -> Result<char, String> {
+        match self.advance() {
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('\\') => Ok('\\'),
+            Some('\'') => Ok('\''),
+            Some('"') => Ok('"'),
+            Some('0') => Ok('\0'),
+            Some('x') => self
+                .scan_hex_escape(2)
+                .ok_or_else(|| "invalid hex escape".to_string()),
+            Some('u') => {
+                if !self.match_char('{') {
+                    return Err("expected '{' in unicode escape".to_string());
+                }
+                self.scan_unicode_escape()
+                    .ok_or_else(|| "invalid unicode escape".to_string())
+            }
+            Some(c) => Err(format!("invalid escape sequence: \\{}", c)),
+            None => Err("unterminated string".to_string()),
+        }
+    }
+
+    /// Scan a triple-quoted string: """...""". Newlines are kept verbatim and
+    /// escapes are processed as in ordinary strings.
+    fn scan_multiline_string(&mut self) -> Token {
+        let start_line = self.line;
+        let mut value = String::new();
+
+        loop {
+            match self.peek() {
+                None => {
+                    return self.unterminated_error(
+                        "unterminated multi-line string",
+                        start_line,
+                        3,
+                    );
+                }
+                Some('"') if self.source[self.current..].starts_with("\"\"\"") => {
+                    self.advance();
+                    self.advance();
+                    self.advance();
+                    break;
+                }
+                Some('\n') => {
+                    self.advance();
+                    self.line += 1;
+                    self.column = 1;
+                    value.push('\n');
+                }
+                Some('\\') => {
+                    self.advance();
+                    match self.scan_escape() {
+                        Ok(ch) => value.push(ch),
+                        Err(message) => return self.error_token(message),
+                    }
                 }
                 Some(c) => {
                     self.advance();
@@ -470,15 +672,23 @@ impl<'a> Scanner<'a> {
         self.make_token(TokenKind::String(value))
     }
 
-    fn scan_raw_string(&mut self) -> Token {
+    /// Scan an undelimited raw string: `...`, r`...` or r"...".
+    fn scan_raw_string(&mut self, quote: char) -> Token {
+        let start_line = self.line;
+        // The opening delimiter is the quote, plus the `r` prefix if present
+        let opening_len = self.current - self.start;
         let mut value = String::new();
 
         loop {
             match self.peek() {
                 None => {
-                    return self.error_token("unterminated raw string");
+                    return self.unterminated_error(
+                        "unterminated raw string",
+                        start_line,
+                        opening_len,
+                    );
                 }
-                Some('`') => {
+                Some(c) if c == quote => {
                     self.advance();
                     break;
                 }
@@ -498,8 +708,10 @@ impl<'a> Scanner<'a> {
         self.make_token(TokenKind::String(value))
     }
 
-    /// Scan a delimited raw string: r#`...`# or r##`...`## etc.
+    /// Scan a delimited raw string: r#`...`#, r#"..."# or r##`...`## etc.
     fn scan_delimited_raw_string(&mut self) -> Token {
+        let start_line = self.line;
+
         // Count opening # characters
         let mut hash_count = 0;
         while self.peek() == Some('#') {
@@ -507,20 +719,26 @@ impl<'a> Scanner<'a> {
             hash_count += 1;
         }
 
-        // Expect opening backtick
-        if self.peek() != Some('`') {
-            return self.error_token("expected '`' after r# in raw string");
-        }
-        self.advance(); // consume opening backtick
+        // Expect an opening backtick or double quote
+        let quote = match self.peek() {
+            Some(c @ ('`' | '"')) => c,
+            _ => return self.error_token("expected '`' or '\"' after r# in raw string"),
+        };
+        self.advance(); // consume opening quote
+        let opening_len = self.current - self.start;
 
         let mut value = String::new();
 
         loop {
             match self.peek() {
                 None => {
-                    return self.error_token("unterminated delimited raw string");
+                    return self.unterminated_error(
+                        "unterminated delimited raw string",
+                        start_line,
+                        opening_len,
+                    );
                 }
-                Some('`') => {
+                Some(c) if c == quote => {
                     self.advance();
                     // Check if followed by the right number of # characters
                     let mut found_hashes = 0;
@@ -532,8 +750,8 @@ impl<'a> Scanner<'a> {
                         // End of delimited raw string
                         break;
                     }
-                    // Not enough hashes — include the backtick and hashes as content
-                    value.push('`');
+                    // Not enough hashes — include the quote and hashes as content
+                    value.push(quote);
                     for _ in 0..found_hashes {
                         value.push('#');
                     }
@@ -595,6 +813,22 @@ impl<'a> Scanner<'a> {
         };
 
         if !self.match_char('\'') {
+            // `'ab'`: consume through the closing quote so scanning resumes
+            // after the literal rather than inside it. Only letters, digits
+            // and non-ASCII scalars can extend the literal's body, so a later
+            // quote on the line, as in `'a + f('b')`, is not taken as its end.
+            let rest = &self.source[self.current..];
+            let body = rest
+                .chars()
+                .take_while(|&ch| ch.is_alphanumeric() || !(ch.is_ascii() || ch.is_whitespace()))
+                .count();
+            if rest.chars().nth(body) == Some('\'') {
+                for _ in 0..=body {
+                    self.advance();
+                }
+                return self
+                    .error_token("character literal must contain exactly one Unicode scalar");
+            }
             return self.error_token("unterminated character literal");
         }
 
@@ -611,6 +845,16 @@ impl<'a> Scanner<'a> {
         char::from_u32(value)
     }
 
+    fn scan_hex_byte(&mut self) -> Option<u8> {
+        let mut value = 0u8;
+        for _ in 0..2 {
+            let digit = self.peek()?.to_digit(16)?;
+            self.advance();
+            value = value * 16 + digit as u8;
+        }
+        Some(value)
+    }
+
     fn scan_unicode_escape(&mut self) -> Option<char> {
         let mut value = 0u32;
         let mut digits = 0;
@@ -855,11 +1099,17 @@ impl<'a> Scanner<'a> {
             return self.scan_fstring();
         }
 
-        // Check for raw string with prefix: r`...` or r#`...`#
+        // Check for byte string: b"..."
+        if lexeme == "b" && self.peek() == Some('"') {
+            self.advance(); // consume the opening quote
+            return self.scan_byte_string();
+        }
+
+        // Check for raw string with prefix: r`...`, r"..." or r#`...`#
         if lexeme == "r" {
-            if self.peek() == Some('`') {
-                self.advance(); // consume the opening backtick
-                return self.scan_raw_string();
+            if let Some(quote @ ('`' | '"')) = self.peek() {
+                self.advance(); // consume the opening quote
+                return self.scan_raw_string(quote);
             }
             if self.peek() == Some('#') {
                 return self.scan_delimited_raw_string();
@@ -1159,6 +1409,23 @@ impl<'a> Scanner<'a> {
         self.errors.push(LexError::new(msg.clone(), span));
         Token::new(TokenKind::Error(msg), span, self.current_lexeme())
     }
+
+    /// Error for a literal that runs to end of input. The error points at the
+    /// opening delimiter, which may be many lines above where scanning stopped.
+    fn unterminated_error(&mut self, message: &str, line: usize, opening_len: usize) -> Token {
+        let span = Span::new(
+            self.start,
+            self.start + opening_len,
+            line,
+            self.start_column,
+        );
+        self.errors.push(LexError::new(message, span));
+        Token::new(
+            TokenKind::Error(message.to_string()),
+            span,
+            self.current_lexeme(),
+        )
+    }
 }
 
 fn is_ident_start(c: char) -> bool {
@@ -1257,6 +1524,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_byte_strings() {
+        assert_eq!(
+            scan("b\"\\x00\\x01\\xFF\""),
+            vec![TokenKind::ByteString(vec![0, 1, 255]), TokenKind::Eof]
+        );
+        assert_eq!(
+            scan("b\"hi\\n\""),
+            vec![TokenKind::ByteString(b"hi\n".to_vec()), TokenKind::Eof]
+        );
+        assert!(matches!(scan("b\"\u{e9}\"")[0], TokenKind::Error(_)));
+    }
+
     #[test]
     fn test_chars() {
         assert_eq!(scan("'a'"), vec![TokenKind::Char('a'), TokenKind::Eof]);
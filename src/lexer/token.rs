@@ -3,6 +3,7 @@
 //! This module defines all tokens that can appear in FORMA source code,
 //! including keywords, operators, literals, and delimiters.
 
+use serde::Serialize;
 use std::fmt;
 
 /// Part of an f-string: either literal text or an expression to interpolate.
@@ -14,6 +15,17 @@ pub enum FStringPart {
     Expr(String),
 }
 
+/// A `#` comment. Comments are not tokens; the scanner collects them
+/// separately so that the formatter can re-emit them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// Comment text after the `#`
+    pub text: String,
+    pub span: Span,
+    /// Whether code precedes the comment on its line
+    pub trailing: bool,
+}
+
 /// A token with its location in the source file.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
@@ -34,10 +46,33 @@ impl Token {
     pub fn is(&self, kind: TokenKind) -> bool {
         self.kind == kind
     }
+
+    /// Byte offset of the token's first byte.
+    pub fn byte_start(&self) -> usize {
+        self.span.start
+    }
+
+    /// Byte offset just past the token's last byte.
+    pub fn byte_end(&self) -> usize {
+        self.span.end
+    }
+
+    /// 1-based line the token starts on.
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+
+    /// 1-based column the token starts at, in Unicode scalar values.
+    pub fn column(&self) -> usize {
+        self.span.column
+    }
 }
 
 /// Source location information.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+///
+/// `start` and `end` are byte offsets into the source; `line` and `column`
+/// are 1-based, with columns counted in Unicode scalar values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -186,6 +221,8 @@ pub enum TokenKind {
     Int(i128),
     Float(f64),
     String(String),
+    /// Byte string `b"..."`
+    ByteString(Vec<u8>),
     Char(char),
     /// Interpolated string (f-string) with parts: either literal text or expressions to interpolate
     FString(Vec<FStringPart>),
@@ -312,6 +349,7 @@ impl TokenKind {
             TokenKind::Int(_)
                 | TokenKind::Float(_)
                 | TokenKind::String(_)
+                | TokenKind::ByteString(_)
                 | TokenKind::Char(_)
                 | TokenKind::FString(_)
                 | TokenKind::True
@@ -407,6 +445,13 @@ impl fmt::Display for TokenKind {
             TokenKind::Int(n) => write!(f, "{}", n),
             TokenKind::Float(n) => write!(f, "{}", n),
             TokenKind::String(s) => write!(f, "\"{}\"", s),
+            TokenKind::ByteString(bytes) => {
+                write!(f, "b\"")?;
+                for &b in bytes {
+                    write!(f, "{}", std::ascii::escape_default(b))?;
+                }
+                write!(f, "\"")
+            }
             TokenKind::Char(c) => write!(f, "'{}'", c),
             TokenKind::FString(parts) => {
                 write!(f, "f\"")?;
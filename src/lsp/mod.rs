@@ -10,7 +10,7 @@ use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
 use crate::borrow::BorrowChecker;
-use crate::fmt::Formatter;
+use crate::fmt::{Formatter, LineEnding};
 use crate::lexer::{Scanner, Span};
 use crate::parser::{ItemKind, Parser};
 use crate::types::TypeChecker;
@@ -71,9 +71,12 @@ impl FormaLanguageServer {
                     crate::lexer::TokenKind::Ident(name) => {
                         if let Some(info) = get_builtin_info(name) {
                             info
+                        } else if let Some(info) = get_symbol_type_info(content, name) {
+                            info
+                        } else if let Some(ty) = crate::types::type_at(content, token.span.start) {
+                            format!("{}: {}", name, ty)
                         } else {
-                            get_symbol_type_info(content, name)
-                                .unwrap_or_else(|| format!("identifier: {}", name))
+                            format!("identifier: {}", name)
                         }
                     }
                     crate::lexer::TokenKind::Int(n) => format!("Int literal: {}", n),
@@ -271,19 +274,29 @@ impl LanguageServer for FormaLanguageServer {
             let col = position.character as usize + 1;
 
             // Find identifier at cursor
-            let identifier_name = tokens
-                .iter()
-                .find(|token| {
-                    let token_end = token.span.column + (token.span.end - token.span.start);
-                    token.span.line == line && token.span.column <= col && col <= token_end
-                })
-                .and_then(|token| {
-                    if let crate::lexer::TokenKind::Ident(name) = &token.kind {
-                        Some(name.clone())
-                    } else {
-                        None
-                    }
-                });
+            let cursor_token = tokens.iter().find(|token| {
+                let token_end = token.span.column + (token.span.end - token.span.start);
+                token.span.line == line && token.span.column <= col && col <= token_end
+            });
+
+            // Prefer the scope-aware lookup, which also resolves locals
+            if let Some(def_span) = cursor_token
+                .and_then(|token| crate::types::definition_of(&content, token.span.start))
+            {
+                let location = Location {
+                    uri: uri.clone(),
+                    range: span_to_range(def_span),
+                };
+                return Ok(Some(GotoDefinitionResponse::Scalar(location)));
+            }
+
+            let identifier_name = cursor_token.and_then(|token| {
+                if let crate::lexer::TokenKind::Ident(name) = &token.kind {
+                    Some(name.clone())
+                } else {
+                    None
+                }
+            });
 
             if let Some(name) = identifier_name {
                 let parser = crate::parser::Parser::new(&tokens);
@@ -315,7 +328,7 @@ impl LanguageServer for FormaLanguageServer {
 
         if let Some(content) = content {
             let scanner = Scanner::new(&content);
-            let (tokens, lex_errors) = scanner.scan_all();
+            let (tokens, lex_errors, comments) = scanner.scan_all_with_comments();
 
             if !lex_errors.is_empty() {
                 return Ok(None);
@@ -324,7 +337,9 @@ impl LanguageServer for FormaLanguageServer {
             let parser = Parser::new(&tokens);
             match parser.parse() {
                 Ok(ast) => {
-                    let mut formatter = Formatter::new();
+                    let mut formatter = Formatter::new()
+                        .with_comments(comments)
+                        .with_line_ending(LineEnding::detect(&content));
                     let formatted = formatter.format(&ast);
 
                     let line_count = content.lines().count() as u32;
@@ -483,6 +498,9 @@ fn get_builtin_info(name: &str) -> Option<String> {
         "vec_len" => Some("vec_len(vec: [T]) -> Int\nGet the length of a vector".to_string()),
         "vec_get" => Some("vec_get(vec: [T], index: Int) -> T?\nGet an item by index".to_string()),
         "str_len" => Some("str_len(s: Str) -> Int\nGet the length of a string".to_string()),
+        "str_index" => Some("str_index(s: Str, index: Int) -> Char\nGet the character at an index".to_string()),
+        "bytes_len" => Some("bytes_len(b: Bytes) -> Int\nGet the number of bytes".to_string()),
+        "bytes_get" => Some("bytes_get(b: Bytes, index: Int) -> u8\nGet the byte at an index".to_string()),
         "str_split" => Some("str_split(s: Str, sep: Str) -> [Str]\nSplit a string by separator".to_string()),
         "str_contains" => Some("str_contains(s: Str, sub: Str) -> Bool\nCheck if string contains substring".to_string()),
         "map_new" => Some("map_new() -> Map\nCreate a new empty map".to_string()),
@@ -848,10 +866,8 @@ fn get_signature_help(content: &str, position: Position) -> Option<SignatureHelp
                     fn_name = None;
                 }
             }
-            crate::lexer::TokenKind::Comma => {
-                if fn_name.is_some() && paren_depth > 0 {
-                    active_param += 1;
-                }
+            crate::lexer::TokenKind::Comma if fn_name.is_some() && paren_depth > 0 => {
+                active_param += 1;
             }
             _ => {}
         }
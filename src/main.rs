@@ -3,19 +3,21 @@
 //! Command-line interface for the FORMA compiler.
 
 use clap::{Parser, Subcommand, ValueEnum};
-use forma::errors::ErrorContext;
+use forma::errors::{Diagnostic, ErrorContext, Severity, span_end};
 use forma::lexer::Span;
 use forma::mir::{Interpreter, Lowerer, Value};
 use forma::module::ModuleLoader;
+use forma::types::{Lint, LintLevel, LintLevels};
 use forma::{BorrowChecker, Parser as FormaParser, Scanner, TypeChecker};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::time::{Duration, Instant};
 
 /// Error format for output
 #[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
@@ -27,6 +29,65 @@ enum ErrorFormat {
     Json,
 }
 
+/// Extra build outputs selected with `--emit`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum EmitKind {
+    /// JSON map from functions and blocks to source lines (<output>.sourcemap.json)
+    Sourcemap,
+}
+
+/// Relocation model for `build --relocation-model`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum RelocationModel {
+    /// The target's default
+    #[default]
+    Default,
+    /// Position-independent code, for PIE executables and shared libraries
+    Pic,
+    /// Absolute addresses, for targets without a dynamic loader
+    Static,
+    /// Absolute addresses for code, PIC-style access to external data
+    DynamicNoPic,
+}
+
+/// What `build --crate-type` produces
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum CrateType {
+    /// An executable linked with the FORMA runtime (default)
+    #[default]
+    Bin,
+    /// A static library archive (lib<name>.a) for linking into other programs
+    Staticlib,
+}
+
+/// Code model for `build --code-model`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum CodeModelArg {
+    /// The target's default
+    #[default]
+    Default,
+    /// Code and data within the low 2 GiB
+    Small,
+    /// Code and data within the top 2 GiB, for kernels
+    Kernel,
+    /// Code within 2 GiB, data anywhere
+    Medium,
+    /// No assumptions about addresses
+    Large,
+}
+
+/// Line ending for `fmt --line-ending`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum LineEndingArg {
+    /// Whichever ending most lines of the input already use
+    #[default]
+    Auto,
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
 /// Grammar output format
 #[derive(Clone, Copy, Debug, Default, ValueEnum)]
 enum GrammarFormat {
@@ -37,6 +98,36 @@ enum GrammarFormat {
     Json,
 }
 
+/// Lex command output format
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum LexFormat {
+    /// One token per line (default)
+    #[default]
+    Text,
+    /// JSON array of tokens for tooling
+    Json,
+}
+
+/// Parse command output format
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum ParseFormat {
+    /// Item summary (default)
+    #[default]
+    Text,
+    /// Full AST as JSON for tooling
+    Json,
+}
+
+/// Capabilities command output format
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum CapabilitiesFormat {
+    /// One capability per line, followed by its uses (default)
+    #[default]
+    Text,
+    /// JSON report for tooling
+    Json,
+}
+
 /// Explain command output format
 #[derive(Clone, Copy, Debug, Default, ValueEnum)]
 enum ExplainFormat {
@@ -59,6 +150,23 @@ enum VerifyFormat {
     Json,
 }
 
+/// One use of a capability-gated builtin, for `capabilities --format json`
+#[derive(Serialize)]
+struct JsonCapabilityUse {
+    builtin: String,
+    function: String,
+    line: usize,
+    column: usize,
+}
+
+/// A capability the program needs, for `capabilities --format json`
+#[derive(Serialize)]
+struct JsonCapability {
+    capability: String,
+    flag: String,
+    uses: Vec<JsonCapabilityUse>,
+}
+
 /// A structured error for JSON output
 #[derive(Serialize)]
 struct JsonError {
@@ -67,13 +175,30 @@ struct JsonError {
     column: u32,
     end_line: u32,
     end_column: u32,
-    severity: String,
+    level: String,
     code: String,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     help: Option<String>,
 }
 
+/// A single token for JSON lex output
+#[derive(Serialize)]
+struct JsonToken {
+    kind: String,
+    text: String,
+    byte_start: usize,
+    byte_end: usize,
+    line: usize,
+    /// Counted in Unicode scalar values
+    column: usize,
+    // `col`, `start` and `end` predate the names above and are kept for
+    // existing consumers
+    col: usize,
+    start: usize,
+    end: usize,
+}
+
 /// Collection of errors for JSON output
 #[derive(Serialize)]
 struct JsonOutput {
@@ -111,9 +236,68 @@ enum Commands {
         #[arg(short = 'O', long, default_value = "0")]
         opt_level: u8,
 
+        /// Disable @pre/@post contract checking
+        #[arg(long)]
+        no_check_contracts: bool,
+
+        /// Elide `assert` statements (contracts are controlled separately)
+        #[arg(long)]
+        no_assertions: bool,
+
         /// Disable MIR optimization pass
         #[arg(long)]
         no_optimize: bool,
+
+        /// Emit DWARF debug info for source-level debugging
+        #[arg(short = 'g', long)]
+        debug: bool,
+
+        /// Abort on integer division by zero or overflow instead of trapping
+        #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+        div_checks: bool,
+
+        /// Panic with the index and length on out-of-bounds array and vector
+        /// indexing (strings and bytes are always checked)
+        #[arg(long)]
+        bounds_checks: bool,
+
+        /// Print time spent in each compiler phase (or write JSON to FILE)
+        #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+        timings: Option<Option<PathBuf>>,
+
+        /// Extra outputs to write next to the executable
+        #[arg(long, value_enum, value_delimiter = ',')]
+        emit: Vec<EmitKind>,
+
+        /// Count basic block hits and write them to $FORMA_COVERAGE_FILE
+        /// (default: forma.coverage) when main returns
+        #[arg(long)]
+        coverage: bool,
+
+        /// Relocation model for the object file
+        #[arg(long, value_enum, default_value_t)]
+        relocation_model: RelocationModel,
+
+        /// Code model for the object file
+        #[arg(long, value_enum, default_value_t)]
+        code_model: CodeModelArg,
+
+        /// Kind of output to produce
+        #[arg(long, value_enum, default_value_t)]
+        crate_type: CrateType,
+
+        /// Cache compiled objects in DIR and reuse them while the source,
+        /// its imports and the build flags are unchanged
+        #[arg(long, value_name = "DIR")]
+        incremental: Option<PathBuf>,
+
+        /// Allow environment variable access
+        #[arg(long)]
+        allow_env: bool,
+
+        /// Allow all capabilities
+        #[arg(long)]
+        allow_all: bool,
     },
 
     /// Run a FORMA program
@@ -121,7 +305,7 @@ enum Commands {
         /// Input file
         file: PathBuf,
 
-        /// Arguments to pass to the program
+        /// Arguments to pass to the program, after `--`
         args: Vec<String>,
 
         /// Dump MIR before running (for debugging)
@@ -132,59 +316,87 @@ enum Commands {
         #[arg(long)]
         no_check_contracts: bool,
 
-        /// Disable MIR optimization pass
+        /// Elide `assert` statements (contracts are controlled separately)
         #[arg(long)]
-        no_optimize: bool,
+        no_assertions: bool,
 
-        /// Allow file read access
+        /// Disable MIR optimization pass
         #[arg(long)]
-        allow_read: bool,
+        no_optimize: bool,
 
-        /// Allow file write access
-        #[arg(long)]
-        allow_write: bool,
+        /// Print time spent in each compiler phase (or write JSON to FILE)
+        #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+        timings: Option<Option<PathBuf>>,
 
-        /// Allow network access
-        #[arg(long)]
-        allow_network: bool,
+        #[command(flatten)]
+        caps: CapabilityConfig,
 
-        /// Allow process execution
+        /// Re-run whenever the input file changes
         #[arg(long)]
-        allow_exec: bool,
+        watch: bool,
 
-        /// Allow environment variable access
+        /// Count basic block hits and write them to $FORMA_COVERAGE_FILE
+        /// (default: forma.coverage)
         #[arg(long)]
-        allow_env: bool,
-
-        /// Allow unsafe/FFI operations (pointers, memory allocation)
-        #[arg(long)]
-        allow_unsafe: bool,
+        coverage: bool,
 
-        /// Allow all capabilities
-        #[arg(long)]
-        allow_all: bool,
+        /// Stack size in bytes for the thread running the program
+        #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_RUN_STACK_SIZE, value_parser = parse_stack_size)]
+        stack_size: usize,
     },
 
     /// Lex a file and print tokens (for debugging)
     Lex {
         /// Input file
         file: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(long, value_enum, default_value = "text")]
+        format: LexFormat,
     },
 
     /// Parse a file and print AST (for debugging)
     Parse {
         /// Input file
         file: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(long, value_enum, default_value = "text")]
+        format: ParseFormat,
     },
 
     /// Check a file for errors without compiling
     Check {
-        /// Input file
-        file: PathBuf,
+        /// Input files, checked in parallel when more than one is given
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
 
         /// Enable partial checking (validates incomplete code)
         #[arg(long)]
         partial: bool,
+
+        /// Re-check whenever the input file changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Fail when any warning is reported
+        #[arg(long)]
+        deny_warnings: bool,
+
+        /// Silence a lint (unused_variable, dead_code, narrowing_cast)
+        #[arg(short = 'A', long, value_name = "LINT")]
+        allow: Vec<String>,
+
+        /// Report a lint as a warning
+        #[arg(short = 'W', long, value_name = "LINT")]
+        warn: Vec<String>,
+
+        /// Report a lint as an error
+        #[arg(short = 'D', long, value_name = "LINT")]
+        deny: Vec<String>,
+
+        #[command(flatten)]
+        caps: CapabilityConfig,
     },
 
     /// Get completion suggestions at a position
@@ -220,9 +432,68 @@ enum Commands {
         #[arg(short = 'O', long, default_value = "0")]
         opt_level: u8,
 
+        /// Disable @pre/@post contract checking
+        #[arg(long)]
+        no_check_contracts: bool,
+
+        /// Elide `assert` statements (contracts are controlled separately)
+        #[arg(long)]
+        no_assertions: bool,
+
         /// Disable MIR optimization pass
         #[arg(long)]
         no_optimize: bool,
+
+        /// Emit DWARF debug info for source-level debugging
+        #[arg(short = 'g', long)]
+        debug: bool,
+
+        /// Abort on integer division by zero or overflow instead of trapping
+        #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+        div_checks: bool,
+
+        /// Panic with the index and length on out-of-bounds array and vector
+        /// indexing (strings and bytes are always checked)
+        #[arg(long)]
+        bounds_checks: bool,
+
+        /// Print time spent in each compiler phase (or write JSON to FILE)
+        #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+        timings: Option<Option<PathBuf>>,
+
+        /// Extra outputs to write next to the executable
+        #[arg(long, value_enum, value_delimiter = ',')]
+        emit: Vec<EmitKind>,
+
+        /// Count basic block hits and write them to $FORMA_COVERAGE_FILE
+        /// (default: forma.coverage) when main returns
+        #[arg(long)]
+        coverage: bool,
+
+        /// Relocation model for the object file
+        #[arg(long, value_enum, default_value_t)]
+        relocation_model: RelocationModel,
+
+        /// Code model for the object file
+        #[arg(long, value_enum, default_value_t)]
+        code_model: CodeModelArg,
+
+        /// Kind of output to produce
+        #[arg(long, value_enum, default_value_t)]
+        crate_type: CrateType,
+
+        /// Cache compiled objects in DIR and reuse them while the source,
+        /// its imports and the build flags are unchanged
+        #[arg(long, value_name = "DIR")]
+        incremental: Option<PathBuf>,
+
+        /// Allow environment variable access
+        #[arg(long)]
+        allow_env: bool,
+
+        /// Allow all capabilities
+        #[arg(long)]
+        allow_all: bool,
     },
 
     /// Export the FORMA grammar
@@ -256,6 +527,10 @@ enum Commands {
         /// Check if file is formatted (exit with error if not)
         #[arg(short, long)]
         check: bool,
+
+        /// Line ending to write
+        #[arg(long, value_enum, default_value_t)]
+        line_ending: LineEndingArg,
     },
 
     /// Start the LSP server for IDE support
@@ -320,9 +595,65 @@ enum Commands {
         #[arg(long)]
         allow_side_effects: bool,
     },
+
+    /// List the capabilities a program needs, and where it uses them
+    Capabilities {
+        /// Input file
+        file: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(long, value_enum, default_value = "text")]
+        format: CapabilitiesFormat,
+    },
+}
+
+/// Stack size for the compiler thread. The recursive-descent parser and the
+/// later passes recurse once per level of expression nesting, so the default
+/// main-thread stack is not enough to reach the parser's nesting limit in
+/// unoptimized builds.
+const COMPILER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Default stack size for the thread `forma run` executes the program on.
+/// The interpreter recurses on the native stack for every FORMA call.
+const DEFAULT_RUN_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Smallest `--stack-size` accepted; below this not even `main` fits.
+const MIN_RUN_STACK_SIZE: usize = 64 * 1024;
+
+/// Parse one `--allow-net` address, `host:port`.
+fn parse_net_address(s: &str) -> Result<String, String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(s.to_string()),
+        _ => Err(format!("invalid address '{}': expected HOST:PORT", s)),
+    }
+}
+
+/// Parse a `--stack-size` value in bytes.
+fn parse_stack_size(s: &str) -> Result<usize, String> {
+    let bytes: usize = s
+        .parse()
+        .map_err(|_| format!("invalid stack size '{}': expected a number of bytes", s))?;
+    if bytes < MIN_RUN_STACK_SIZE {
+        return Err(format!(
+            "stack size must be at least {} bytes",
+            MIN_RUN_STACK_SIZE
+        ));
+    }
+    Ok(bytes)
 }
 
 fn main() {
+    let handle = std::thread::Builder::new()
+        .name("forma".to_string())
+        .stack_size(COMPILER_STACK_SIZE)
+        .spawn(run_cli)
+        .expect("failed to spawn compiler thread");
+    if handle.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run_cli() {
     let cli = Cli::parse();
     let error_format = cli.error_format;
 
@@ -331,12 +662,42 @@ fn main() {
             file,
             output,
             opt_level,
+            no_check_contracts,
+            no_assertions,
             no_optimize,
+            debug,
+            div_checks,
+            bounds_checks,
+            timings,
+            emit,
+            coverage,
+            relocation_model,
+            code_model,
+            crate_type,
+            incremental,
+            allow_env,
+            allow_all,
         } => build(
             &file,
             output.as_ref(),
-            opt_level,
-            !no_optimize,
+            &BuildConfig {
+                opt_level,
+                check_contracts: !no_check_contracts,
+                assertions: !no_assertions,
+                optimize: !no_optimize,
+                debug,
+                div_checks,
+                bounds_checks,
+                timings,
+                emit,
+                coverage,
+                relocation_model,
+                code_model,
+                crate_type,
+                incremental,
+                allow_env,
+                allow_all,
+            },
             error_format,
         ),
         Commands::Run {
@@ -344,56 +705,115 @@ fn main() {
             args,
             dump_mir,
             no_check_contracts,
+            no_assertions,
             no_optimize,
-            allow_read,
-            allow_write,
-            allow_network,
-            allow_exec,
-            allow_env,
-            allow_unsafe,
-            allow_all,
+            timings,
+            caps,
+            watch,
+            coverage,
+            stack_size,
         } => {
-            let caps = CapabilityConfig {
-                allow_read,
-                allow_write,
-                allow_network,
-                allow_exec,
-                allow_env,
-                allow_unsafe,
-                allow_all,
-            };
-            run(
-                &file,
-                &args,
+            let config = RunConfig {
                 dump_mir,
-                !no_check_contracts,
-                !no_optimize,
-                &caps,
-                error_format,
-            )
-        }
-        Commands::Lex { file } => lex(&file, error_format),
-        Commands::Parse { file } => parse(&file, error_format),
-        Commands::Check { file, partial } => check(&file, partial, error_format),
+                check_contracts: !no_check_contracts,
+                assertions: !no_assertions,
+                optimize: !no_optimize,
+                timings,
+                coverage,
+                stack_size,
+            };
+            let run_once = || run(&file, &args, &config, &caps, error_format);
+            if watch {
+                watch_file(&file, error_format, || match run_once()? {
+                    0 => Ok(()),
+                    code => Err(format!("program exited with code {}", code)),
+                })
+            } else {
+                run_once().map(|exit_code| {
+                    if exit_code != 0 {
+                        process::exit(exit_code);
+                    }
+                })
+            }
+        }
+        Commands::Lex { file, format } => lex(&file, format, error_format),
+        Commands::Parse { file, format } => parse(&file, format, error_format),
+        Commands::Check {
+            files,
+            partial,
+            watch,
+            deny_warnings,
+            allow,
+            warn,
+            deny,
+            caps,
+        } => {
+            let lints = lint_levels(&allow, &warn, &deny, deny_warnings);
+            let granted = caps.granted();
+            match files.as_slice() {
+                [file] if watch => watch_file(file, error_format, || {
+                    check(file, partial, &lints, &granted, error_format)
+                }),
+                [file] => check(file, partial, &lints, &granted, error_format),
+                _ if watch => Err("--watch takes a single file".to_string()),
+                _ if partial => Err("--partial takes a single file".to_string()),
+                _ => check_files(&files, &lints, &granted, error_format),
+            }
+        }
         Commands::Complete { file, position } => complete(&file, &position, error_format),
         Commands::Typeof { file, position } => typeof_at(&file, &position, error_format),
         Commands::Build {
             file,
             output,
             opt_level,
+            no_check_contracts,
+            no_assertions,
             no_optimize,
+            debug,
+            div_checks,
+            bounds_checks,
+            timings,
+            emit,
+            coverage,
+            relocation_model,
+            code_model,
+            crate_type,
+            incremental,
+            allow_env,
+            allow_all,
         } => build(
             &file,
             output.as_ref(),
-            opt_level,
-            !no_optimize,
+            &BuildConfig {
+                opt_level,
+                check_contracts: !no_check_contracts,
+                assertions: !no_assertions,
+                optimize: !no_optimize,
+                debug,
+                div_checks,
+                bounds_checks,
+                timings,
+                emit,
+                coverage,
+                relocation_model,
+                code_model,
+                crate_type,
+                incremental,
+                allow_env,
+                allow_all,
+            },
             error_format,
         ),
         Commands::Grammar { format } => grammar(format),
         Commands::New { name } => new_project(&name),
         Commands::Init => init_project(),
         Commands::Repl => repl(),
-        Commands::Fmt { file, write, check } => fmt(&file, write, check, error_format),
+        Commands::Fmt {
+            file,
+            write,
+            check,
+            line_ending,
+        } => fmt(&file, write, check, line_ending, error_format),
         Commands::Lsp => lsp(),
         Commands::Explain {
             file,
@@ -433,6 +853,7 @@ fn main() {
             },
             error_format,
         ),
+        Commands::Capabilities { file, format } => capabilities(&file, format, error_format),
     };
 
     if let Err(e) = result {
@@ -446,6 +867,67 @@ fn main() {
     }
 }
 
+/// How often `--watch` polls the input file for modification
+const WATCH_POLL_MS: u64 = 250;
+
+/// Quiet period after a change before re-running, so editors that write a
+/// file several times in quick succession trigger a single re-run
+const WATCH_DEBOUNCE_MS: u64 = 100;
+
+fn modified_time(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Run `action`, then re-run it every time `file` is modified. Runs until the
+/// process is interrupted (Ctrl-C).
+fn watch_file(
+    file: &Path,
+    error_format: ErrorFormat,
+    mut action: impl FnMut() -> Result<(), String>,
+) -> Result<(), String> {
+    use std::io::{IsTerminal, Write};
+
+    let human = error_format == ErrorFormat::Human;
+    let mut last_modified = modified_time(file);
+    loop {
+        if human && std::io::stdout().is_terminal() {
+            // Clear the screen so each run starts from a clean slate
+            print!("\x1b[2J\x1b[H");
+            let _ = std::io::stdout().flush();
+        }
+        if let Err(e) = action()
+            && human
+        {
+            eprintln!("error: {}", e);
+        }
+        if human {
+            eprintln!(
+                "[watching {} for changes, press Ctrl-C to exit]",
+                file.display()
+            );
+        }
+
+        loop {
+            std::thread::sleep(Duration::from_millis(WATCH_POLL_MS));
+            let mut current = modified_time(file);
+            if current == last_modified {
+                continue;
+            }
+            // Debounce: wait until the modification time settles
+            loop {
+                std::thread::sleep(Duration::from_millis(WATCH_DEBOUNCE_MS));
+                let settled = modified_time(file);
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+            last_modified = current;
+            break;
+        }
+    }
+}
+
 /// Helper to print JSON without panicking on serialization errors
 fn print_json<T: Serialize>(value: &T) {
     match serde_json::to_string_pretty(value) {
@@ -455,40 +937,89 @@ fn print_json<T: Serialize>(value: &T) {
 }
 
 /// Configuration for runtime capabilities.
+#[derive(clap::Args)]
 struct CapabilityConfig {
+    /// Allow file read access
+    #[arg(long)]
     allow_read: bool,
+
+    /// Allow file write access
+    #[arg(long)]
     allow_write: bool,
+
+    /// Allow network access
+    #[arg(long)]
     allow_network: bool,
+
+    /// Allow TCP connections to any address, or with `=HOST:PORT,...` only
+    /// to the listed ones
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ',',
+        value_parser = parse_net_address
+    )]
+    allow_net: Option<Vec<String>>,
+
+    /// Allow process execution
+    #[arg(long)]
     allow_exec: bool,
+
+    /// Allow environment variable access
+    #[arg(long)]
     allow_env: bool,
+
+    /// Allow unsafe/FFI operations (pointers, memory allocation)
+    #[arg(long)]
     allow_unsafe: bool,
+
+    /// Allow spawning OS threads
+    #[arg(long)]
+    allow_threads: bool,
+
+    /// Allow all capabilities
+    #[arg(long)]
     allow_all: bool,
 }
 
 impl CapabilityConfig {
-    /// Apply capability grants to an interpreter.
-    fn apply(&self, interp: &mut Interpreter) {
+    /// The capabilities granted by the flags, `"all"` standing for every one.
+    fn granted(&self) -> HashSet<String> {
         if self.allow_all {
-            interp.grant_capability("all");
-        } else {
-            if self.allow_read {
-                interp.grant_capability("read");
+            return HashSet::from(["all".to_string()]);
+        }
+        let flags = [
+            (self.allow_read, "read"),
+            (self.allow_write, "write"),
+            (self.allow_network, "network"),
+            (self.allow_exec, "exec"),
+            (self.allow_env, "env"),
+            (self.allow_unsafe, "unsafe"),
+            (self.allow_threads, "threads"),
+        ];
+        let mut granted: HashSet<String> = flags
+            .into_iter()
+            .filter(|(allowed, _)| *allowed)
+            .map(|(_, capability)| capability.to_string())
+            .collect();
+        match self.allow_net.as_deref() {
+            Some([]) => {
+                granted.insert("network".to_string());
             }
-            if self.allow_write {
-                interp.grant_capability("write");
-            }
-            if self.allow_network {
-                interp.grant_capability("network");
-            }
-            if self.allow_exec {
-                interp.grant_capability("exec");
-            }
-            if self.allow_env {
-                interp.grant_capability("env");
-            }
-            if self.allow_unsafe {
-                interp.grant_capability("unsafe");
+            Some(addrs) => {
+                granted.extend(addrs.iter().map(|addr| format!("network:{}", addr)));
             }
+            None => {}
+        }
+        granted
+    }
+
+    /// Apply capability grants to an interpreter.
+    fn apply(&self, interp: &mut Interpreter) {
+        for capability in self.granted() {
+            interp.grant_capability(&capability);
         }
     }
 }
@@ -496,18 +1027,20 @@ impl CapabilityConfig {
 /// Helper to create a JsonError from a span and message
 fn span_to_json_error(
     file: &str,
+    source: &str,
     span: Span,
     code: &str,
     message: &str,
     help: Option<&str>,
 ) -> JsonError {
+    let (end_line, end_column) = span_end(source, span);
     JsonError {
         file: file.to_string(),
         line: span.line as u32,
         column: span.column as u32,
-        end_line: span.line as u32,
-        end_column: (span.column + (span.end - span.start)) as u32,
-        severity: "error".to_string(),
+        end_line: end_line as u32,
+        end_column: end_column as u32,
+        level: "error".to_string(),
         code: code.to_string(),
         message: message.to_string(),
         help: help.map(|s| s.to_string()),
@@ -517,7 +1050,7 @@ fn span_to_json_error(
 /// Output errors in JSON format
 fn output_json_errors(errors: Vec<JsonError>, items_count: Option<usize>) {
     let output = JsonOutput {
-        success: errors.is_empty(),
+        success: errors.iter().all(|e| e.level != "error"),
         errors,
         items_count,
     };
@@ -627,6 +1160,142 @@ struct VerifyConfig {
     allow_side_effects: bool,
 }
 
+#[derive(Clone, Debug)]
+struct RunConfig {
+    dump_mir: bool,
+    check_contracts: bool,
+    assertions: bool,
+    optimize: bool,
+    timings: Option<Option<PathBuf>>,
+    coverage: bool,
+    stack_size: usize,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "llvm"), allow(dead_code))] // codegen options are read only by LLVM
+struct BuildConfig {
+    opt_level: u8,
+    check_contracts: bool,
+    assertions: bool,
+    optimize: bool,
+    debug: bool,
+    div_checks: bool,
+    bounds_checks: bool,
+    timings: Option<Option<PathBuf>>,
+    emit: Vec<EmitKind>,
+    coverage: bool,
+    relocation_model: RelocationModel,
+    code_model: CodeModelArg,
+    crate_type: CrateType,
+    incremental: Option<PathBuf>,
+    allow_env: bool,
+    allow_all: bool,
+}
+
+impl BuildConfig {
+    /// The flags that change the object file, as part of the incremental
+    /// cache key.
+    #[cfg(feature = "llvm")]
+    fn object_fingerprint(&self) -> String {
+        format!(
+            "{} O{} contracts={} assertions={} optimize={} debug={} div_checks={} bounds_checks={} coverage={} allow_env={} allow_all={} {:?} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.opt_level,
+            self.check_contracts,
+            self.assertions,
+            self.optimize,
+            self.debug,
+            self.div_checks,
+            self.bounds_checks,
+            self.coverage,
+            self.allow_env,
+            self.allow_all,
+            self.relocation_model,
+            self.code_model,
+        )
+    }
+}
+
+/// Wall-clock time spent in each compiler phase, as requested by `--timings`.
+///
+/// The report is emitted on drop so that a build stopping at, say, a type
+/// error still shows the phases it got through. `None` disables reporting,
+/// `Some(None)` prints to stderr and `Some(Some(path))` writes JSON to `path`.
+struct PhaseTimer {
+    file: String,
+    output: Option<Option<PathBuf>>,
+    phases: Vec<(&'static str, Duration)>,
+    last: Instant,
+}
+
+impl PhaseTimer {
+    fn new(file: &str, output: Option<Option<PathBuf>>) -> Self {
+        Self {
+            file: file.to_string(),
+            output,
+            phases: Vec::new(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Record the time since the previous phase ended under `name`.
+    fn phase(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.phases.push((name, now - self.last));
+        self.last = now;
+    }
+
+    fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    fn to_human(&self) -> String {
+        let mut out = format!("Timings for {}:\n", self.file);
+        for (name, duration) in &self.phases {
+            out.push_str(&format!("  {:<10} {:>10.3} ms\n", name, millis(*duration)));
+        }
+        out.push_str(&format!(
+            "  {:<10} {:>10.3} ms\n",
+            "total",
+            millis(self.total())
+        ));
+        out
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let phases: Vec<_> = self
+            .phases
+            .iter()
+            .map(|(name, duration)| serde_json::json!({ "phase": name, "ms": millis(*duration) }))
+            .collect();
+        serde_json::json!({
+            "file": self.file,
+            "phases": phases,
+            "total_ms": millis(self.total()),
+        })
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        match &self.output {
+            None => {}
+            Some(None) => eprint!("{}", self.to_human()),
+            Some(Some(path)) => {
+                let json = serde_json::to_string_pretty(&self.to_json())
+                    .expect("timings are always serializable");
+                if let Err(e) = fs::write(path, json + "\n") {
+                    eprintln!("error: cannot write timings to {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
 fn compile_program_for_analysis(
     file: &PathBuf,
     error_format: ErrorFormat,
@@ -647,6 +1316,7 @@ fn compile_program_for_analysis(
                     ErrorFormat::Human => ctx.error(error.span, &error.message),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error.span,
                         "LEX",
                         &error.message,
@@ -677,6 +1347,7 @@ fn compile_program_for_analysis(
                         ErrorFormat::Json => {
                             json_errors.push(span_to_json_error(
                                 &filename,
+                                &source,
                                 error.span(),
                                 "PARSE",
                                 &format!("{}", error),
@@ -717,6 +1388,7 @@ fn compile_program_for_analysis(
                     ErrorFormat::Json => {
                         json_errors.push(span_to_json_error(
                             &filename,
+                            &source,
                             error_span,
                             "MODULE",
                             &format!("{}", e),
@@ -739,6 +1411,7 @@ fn compile_program_for_analysis(
                     ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error.span,
                         "TYPE",
                         &format!("{}", error),
@@ -762,6 +1435,7 @@ fn compile_program_for_analysis(
                     ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error.span,
                         "BORROW",
                         &format!("{}", error),
@@ -784,7 +1458,7 @@ fn compile_program_for_analysis(
                     match error_format {
                         ErrorFormat::Human => ctx.error(e.span, &e.message),
                         ErrorFormat::Json => json_errors.push(span_to_json_error(
-                            &filename, e.span, "LOWER", &e.message, None,
+                            &filename, &source, e.span, "LOWER", &e.message, None,
                         )),
                     }
                 }
@@ -1858,23 +2532,25 @@ fn verify(path: &Path, config: VerifyConfig, error_format: ErrorFormat) -> Resul
     Ok(())
 }
 
+/// Run a program in the interpreter, returning the exit code produced by `main`.
 fn run(
     file: &PathBuf,
     program_args: &[String],
-    dump_mir: bool,
-    check_contracts: bool,
-    do_optimize: bool,
+    config: &RunConfig,
     caps: &CapabilityConfig,
     error_format: ErrorFormat,
-) -> Result<(), String> {
+) -> Result<i32, String> {
+    let check_contracts = config.check_contracts;
     let source = read_file(file)?;
     let filename = file.to_string_lossy().to_string();
     let ctx = ErrorContext::new(&filename, &source);
     let mut json_errors: Vec<JsonError> = vec![];
+    let mut timer = PhaseTimer::new(&filename, config.timings.clone());
 
     // Lex
     let scanner = Scanner::new(&source);
     let (tokens, lex_errors) = scanner.scan_all();
+    timer.phase("lex");
 
     if !lex_errors.is_empty() {
         for error in &lex_errors {
@@ -1882,6 +2558,7 @@ fn run(
                 ErrorFormat::Human => ctx.error(error.span, &error.message),
                 ErrorFormat::Json => json_errors.push(span_to_json_error(
                     &filename,
+                    &source,
                     error.span,
                     "LEX",
                     &error.message,
@@ -1897,7 +2574,9 @@ fn run(
 
     // Parse
     let parser = FormaParser::new(&tokens);
-    let parsed_ast = match parser.parse() {
+    let parse_result = parser.parse();
+    timer.phase("parse");
+    let parsed_ast = match parse_result {
         Ok(ast) => ast,
         Err(errors) => {
             for error in &errors {
@@ -1910,6 +2589,7 @@ fn run(
                     ErrorFormat::Json => {
                         json_errors.push(span_to_json_error(
                             &filename,
+                            &source,
                             error.span(),
                             "PARSE",
                             &format!("{}", error),
@@ -1927,7 +2607,9 @@ fn run(
 
     // Load imports (module system)
     let mut module_loader = ModuleLoader::from_source_file(file);
-    let ast = match module_loader.load_imports(&parsed_ast) {
+    let imports = module_loader.load_imports(&parsed_ast);
+    timer.phase("imports");
+    let ast = match imports {
         Ok(imported_items) => {
             // Combine imports with main file items
             let mut combined_items = imported_items;
@@ -1951,6 +2633,7 @@ fn run(
                 ErrorFormat::Json => {
                     json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error_span,
                         "MODULE",
                         &format!("{}", e),
@@ -1965,12 +2648,15 @@ fn run(
 
     // Type check
     let mut type_checker = TypeChecker::new();
-    if let Err(errors) = type_checker.check(&ast) {
+    let type_result = type_checker.check(&ast);
+    timer.phase("typecheck");
+    if let Err(errors) = type_result {
         for error in &errors {
             match error_format {
                 ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
                 ErrorFormat::Json => json_errors.push(span_to_json_error(
                     &filename,
+                    &source,
                     error.span,
                     "TYPE",
                     &format!("{}", error),
@@ -1986,12 +2672,15 @@ fn run(
 
     // Borrow check
     let mut borrow_checker = BorrowChecker::new();
-    if let Err(errors) = borrow_checker.check(&ast) {
+    let borrow_result = borrow_checker.check(&ast);
+    timer.phase("borrowck");
+    if let Err(errors) = borrow_result {
         for error in &errors {
             match error_format {
                 ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
                 ErrorFormat::Json => json_errors.push(span_to_json_error(
                     &filename,
+                    &source,
                     error.span,
                     "BORROW",
                     &format!("{}", error),
@@ -2006,14 +2695,19 @@ fn run(
     }
 
     // Lower to MIR
-    let mut program = match Lowerer::new().lower(&ast) {
+    let lowered = Lowerer::new()
+        .with_invariant_checks(check_contracts)
+        .with_assertions(config.assertions)
+        .lower(&ast);
+    timer.phase("lower");
+    let mut program = match lowered {
         Ok(prog) => prog,
         Err(errors) => {
             for e in &errors {
                 match error_format {
                     ErrorFormat::Human => ctx.error(e.span, &e.message),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
-                        &filename, e.span, "LOWER", &e.message, None,
+                        &filename, &source, e.span, "LOWER", &e.message, None,
                     )),
                 }
             }
@@ -2025,12 +2719,13 @@ fn run(
     };
 
     // Optimize MIR
-    if do_optimize {
+    if config.optimize {
         forma::mir::optimize::optimize(&mut program);
     }
+    timer.phase("optimize");
 
     // Dump MIR if requested
-    if dump_mir {
+    if config.dump_mir {
         eprintln!("=== MIR ===");
         eprintln!("{}", program);
         eprintln!("=== END MIR ===\n");
@@ -2047,7 +2742,7 @@ fn run(
                     column: 1,
                     end_line: 1,
                     end_column: 1,
-                    severity: "error".to_string(),
+                    level: "error".to_string(),
                     code: "MAIN".to_string(),
                     message: "no 'main' function found".to_string(),
                     help: Some("add a main function: f main()".to_string()),
@@ -2067,6 +2762,16 @@ fn run(
 
     // Apply contract checking setting
     interp.set_check_contracts(check_contracts);
+    if config.coverage {
+        interp.enable_coverage();
+    }
+
+    // argv for args()/args_get(), with the program path first as in a native build
+    interp.set_args(
+        std::iter::once(filename.clone())
+            .chain(program_args.iter().cloned())
+            .collect(),
+    );
 
     // Pass program arguments as ARGV/ARGC environment variables
     interp.set_env("ARGC", &program_args.len().to_string());
@@ -2076,16 +2781,39 @@ fn run(
         interp.set_env(&format!("ARGV_{}", i), arg);
     }
 
-    match interp.run("main", &[]) {
+    // Deep FORMA recursion is deep native recursion in the interpreter, so
+    // run on a thread whose stack the user can size with --stack-size
+    let run_result = std::thread::scope(|scope| {
+        let handle = std::thread::Builder::new()
+            .name("forma-main".to_string())
+            .stack_size(config.stack_size)
+            .spawn_scoped(scope, || interp.run("main", &[]))
+            .map_err(|e| {
+                format!(
+                    "failed to start program thread with a {}-byte stack: {}",
+                    config.stack_size, e
+                )
+            })?;
+        handle
+            .join()
+            .map_err(|_| "internal error: interpreter panicked".to_string())
+    })?;
+    timer.phase("interpret");
+    if let Some(report) = interp.coverage_report() {
+        write_coverage(&report)?;
+    }
+    match run_result {
         Ok(result) => {
+            // An Int from main is the exit status, clamped to what a process
+            // can report; negative values fail with 255, like `exit(-1)`, so
+            // no nonzero result reads as success. Any other result (usually
+            // unit) means success
             let exit_code = match &result {
-                Value::Int(n) => *n as i32,
+                Value::Int(n) if *n < 0 => 255,
+                Value::Int(n) => (*n).min(255) as i32,
                 _ => 0,
             };
-            if exit_code != 0 {
-                process::exit(exit_code);
-            }
-            Ok(())
+            Ok(exit_code)
         }
         Err(e) => {
             match error_format {
@@ -2097,7 +2825,7 @@ fn run(
                         column: 1,
                         end_line: 1,
                         end_column: 1,
-                        severity: "error".to_string(),
+                        level: "error".to_string(),
                         code: "RUNTIME".to_string(),
                         message: e.to_string(),
                         help: None,
@@ -2105,17 +2833,130 @@ fn run(
                     output_json_errors(json_errors, None);
                 }
             }
-            Err(format!("error[RUNTIME]: {}", e))
+            Err(format!("error[RUNTIME]: {}", e))
+        }
+    }
+}
+
+/// Write a block coverage report to `$FORMA_COVERAGE_FILE`, or
+/// `forma.coverage` when it is unset. Natively built programs read the same
+/// variable in `forma_coverage_dump`.
+fn write_coverage(report: &str) -> Result<(), String> {
+    let path = std::env::var("FORMA_COVERAGE_FILE").unwrap_or_else(|_| "forma.coverage".into());
+    fs::write(&path, report).map_err(|e| format!("failed to write coverage to '{}': {}", path, e))
+}
+
+/// Token kind name without its payload (e.g. `Ident` for `Ident("x")`)
+/// Report the capabilities `file` needs, each with the calls that need it.
+fn capabilities(
+    file: &PathBuf,
+    format: CapabilitiesFormat,
+    error_format: ErrorFormat,
+) -> Result<(), String> {
+    use forma::mir::capability::{CAPABILITIES, capability_flag, capability_uses};
+
+    let program = compile_program_for_analysis(file, error_format, true)?;
+    let uses = capability_uses(&program);
+    let required: Vec<JsonCapability> = CAPABILITIES
+        .iter()
+        .map(|&capability| JsonCapability {
+            capability: capability.to_string(),
+            flag: capability_flag(capability),
+            uses: uses
+                .iter()
+                .filter(|u| u.capability == capability)
+                .map(|u| {
+                    let span = u.span.unwrap_or_default();
+                    JsonCapabilityUse {
+                        builtin: u.builtin.clone(),
+                        function: u.function.clone(),
+                        line: span.line,
+                        column: span.column,
+                    }
+                })
+                .collect(),
+        })
+        .filter(|c| !c.uses.is_empty())
+        .collect();
+
+    match format {
+        CapabilitiesFormat::Json => print_json(&serde_json::json!({
+            "file": file.to_string_lossy(),
+            "capabilities": required,
+        })),
+        CapabilitiesFormat::Text => {
+            if required.is_empty() {
+                println!("No capabilities required");
+            }
+            for capability in &required {
+                println!("{} ({})", capability.capability, capability.flag);
+                for u in &capability.uses {
+                    println!(
+                        "  {}:{}:{}: {} in {}",
+                        file.display(),
+                        u.line,
+                        u.column,
+                        u.builtin,
+                        u.function
+                    );
+                }
+            }
         }
     }
+    Ok(())
+}
+
+fn token_kind_name(kind: &forma::TokenKind) -> String {
+    format!("{:?}", kind)
+        .split('(')
+        .next()
+        .unwrap_or("")
+        .to_string()
 }
 
-fn lex(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
+fn lex(file: &PathBuf, format: LexFormat, error_format: ErrorFormat) -> Result<(), String> {
     let source = read_file(file)?;
     let filename = file.to_string_lossy().to_string();
     let scanner = Scanner::new(&source);
     let (tokens, errors) = scanner.scan_all();
 
+    if format == LexFormat::Json {
+        let json_tokens: Vec<JsonToken> = tokens
+            .iter()
+            .map(|token| JsonToken {
+                kind: token_kind_name(&token.kind),
+                text: token.lexeme.clone(),
+                byte_start: token.byte_start(),
+                byte_end: token.byte_end(),
+                line: token.line(),
+                column: token.column(),
+                col: token.column(),
+                start: token.byte_start(),
+                end: token.byte_end(),
+            })
+            .collect();
+        if !errors.is_empty() && error_format == ErrorFormat::Json {
+            // One JSON document on stdout: the diagnostics replace the tokens
+            let json_errors: Vec<JsonError> = errors
+                .iter()
+                .map(|e| span_to_json_error(&filename, &source, e.span, "LEX", &e.message, None))
+                .collect();
+            output_json_errors(json_errors, Some(tokens.len()));
+            return Err("lexer errors occurred".to_string());
+        }
+        print_json(&json_tokens);
+        if !errors.is_empty() {
+            for error in &errors {
+                eprintln!(
+                    "error[LEX]: {}:{}: {}",
+                    error.span.line, error.span.column, error.message
+                );
+            }
+            return Err("lexer errors occurred".to_string());
+        }
+        return Ok(());
+    }
+
     match error_format {
         ErrorFormat::Human => {
             for token in &tokens {
@@ -2123,7 +2964,7 @@ fn lex(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
                     "{:4}:{:<3} {:15} {:?}",
                     token.span.line,
                     token.span.column,
-                    format!("{:?}", token.kind).split('(').next().unwrap_or(""),
+                    token_kind_name(&token.kind),
                     token.lexeme
                 );
             }
@@ -2144,7 +2985,7 @@ fn lex(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
         ErrorFormat::Json => {
             let json_errors: Vec<JsonError> = errors
                 .iter()
-                .map(|e| span_to_json_error(&filename, e.span, "LEX", &e.message, None))
+                .map(|e| span_to_json_error(&filename, &source, e.span, "LEX", &e.message, None))
                 .collect();
             output_json_errors(json_errors, Some(tokens.len()));
             if !errors.is_empty() {
@@ -2156,7 +2997,7 @@ fn lex(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
     Ok(())
 }
 
-fn parse(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
+fn parse(file: &PathBuf, format: ParseFormat, error_format: ErrorFormat) -> Result<(), String> {
     let source = read_file(file)?;
     let filename = file.to_string_lossy().to_string();
     let scanner = Scanner::new(&source);
@@ -2175,7 +3016,9 @@ fn parse(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
             ErrorFormat::Json => {
                 let json_errors: Vec<JsonError> = lex_errors
                     .iter()
-                    .map(|e| span_to_json_error(&filename, e.span, "LEX", &e.message, None))
+                    .map(|e| {
+                        span_to_json_error(&filename, &source, e.span, "LEX", &e.message, None)
+                    })
                     .collect();
                 output_json_errors(json_errors, None);
             }
@@ -2185,6 +3028,10 @@ fn parse(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
 
     let parser = FormaParser::new(&tokens);
     match parser.parse() {
+        Ok(ast) if format == ParseFormat::Json => {
+            print_json(&ast);
+            Ok(())
+        }
         Ok(ast) => {
             match error_format {
                 ErrorFormat::Human => {
@@ -2210,6 +3057,7 @@ fn parse(file: &PathBuf, error_format: ErrorFormat) -> Result<(), String> {
                     ErrorFormat::Json => {
                         json_errors.push(span_to_json_error(
                             &filename,
+                            &source,
                             span,
                             "PARSE",
                             &format!("{}", error),
@@ -2278,11 +3126,155 @@ fn print_item(item: &forma::parser::Item, indent: usize) {
     }
 }
 
-fn check(file: &PathBuf, partial: bool, error_format: ErrorFormat) -> Result<(), String> {
+/// A diagnostic produced while checking one file.
+struct CheckDiagnostic {
+    severity: Severity,
+    span: Span,
+    code: &'static str,
+    message: String,
+    help: Option<String>,
+}
+
+/// Diagnostics collected for one file by `forma check`.
+struct CheckReport {
+    filename: String,
+    source: String,
+    diagnostics: Vec<CheckDiagnostic>,
+    /// Number of top-level items, once the file has parsed
+    items: Option<usize>,
+    /// Summary error returned to the caller when checking failed
+    failure: Option<String>,
+}
+
+impl CheckReport {
+    fn new(file: &Path, source: String) -> Self {
+        Self {
+            filename: file.to_string_lossy().to_string(),
+            source,
+            diagnostics: Vec::new(),
+            items: None,
+            failure: None,
+        }
+    }
+
+    fn push(&mut self, span: Span, code: &'static str, message: String, help: Option<&str>) {
+        self.diagnostics.push(CheckDiagnostic {
+            severity: Severity::Error,
+            span,
+            code,
+            message,
+            help: help.map(|h| h.to_string()),
+        });
+    }
+
+    /// Record a lint diagnostic at the level configured for it.
+    fn push_lint(&mut self, warning: &Diagnostic, levels: &LintLevels) {
+        let Some(lint) = warning.code.as_deref().and_then(Lint::from_code) else {
+            return;
+        };
+        let severity = match levels.level(lint) {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        self.diagnostics.push(CheckDiagnostic {
+            severity,
+            span: warning.span,
+            code: lint.code(),
+            message: warning.message.clone(),
+            help: warning
+                .notes
+                .iter()
+                .find_map(|n| n.strip_prefix("help: "))
+                .map(|h| h.to_string()),
+        });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+
+    /// Print diagnostics in human-readable form.
+    fn print_human(&self) {
+        let ctx = ErrorContext::new(&self.filename, &self.source);
+        for diag in &self.diagnostics {
+            if diag.severity == Severity::Warning {
+                ctx.warning(diag.span, &diag.message);
+                continue;
+            }
+            match diag.code {
+                "PARSE" => ctx.error_with_help(
+                    diag.span,
+                    &diag.message,
+                    diag.help.as_deref().unwrap_or("check syntax"),
+                ),
+                "MODULE" => ctx.error(diag.span, &format!("module error: {}", diag.message)),
+                _ => ctx.error(diag.span, &diag.message),
+            }
+        }
+    }
+
+    fn json_errors(&self) -> Vec<JsonError> {
+        self.diagnostics
+            .iter()
+            .map(|d| {
+                let mut error = span_to_json_error(
+                    &self.filename,
+                    &self.source,
+                    d.span,
+                    d.code,
+                    &d.message,
+                    d.help.as_deref(),
+                );
+                if d.severity == Severity::Warning {
+                    error.level = "warning".to_string();
+                }
+                error
+            })
+            .collect()
+    }
+}
+
+/// Build lint levels from `check`'s flags. When a lint is named by several
+/// flags the strictest one wins; unknown lint names are reported and ignored.
+fn lint_levels(
+    allow: &[String],
+    warn: &[String],
+    deny: &[String],
+    deny_warnings: bool,
+) -> LintLevels {
+    let mut levels = LintLevels::default();
+    let flags = [
+        (allow, LintLevel::Allow),
+        (warn, LintLevel::Warn),
+        (deny, LintLevel::Deny),
+    ];
+    for (names, level) in flags {
+        for name in names {
+            if !levels.set(name, level) {
+                eprintln!("warning: unknown lint '{}'", name);
+            }
+        }
+    }
+    if deny_warnings {
+        levels.deny_warnings();
+    }
+    levels
+}
+
+/// Run the front end over one file and collect its diagnostics.
+///
+/// Lints only fail the check when `lints` denies them.
+fn check_file(
+    file: &PathBuf,
+    lints: &LintLevels,
+    capabilities: &HashSet<String>,
+) -> Result<CheckReport, String> {
     let source = read_file(file)?;
-    let filename = file.to_string_lossy().to_string();
-    let ctx = ErrorContext::new(&filename, &source);
-    let mut json_errors: Vec<JsonError> = vec![];
+    let mut report = CheckReport::new(file, source.clone());
 
     // Lex
     let scanner = Scanner::new(&source);
@@ -2290,21 +3282,10 @@ fn check(file: &PathBuf, partial: bool, error_format: ErrorFormat) -> Result<(),
 
     if !lex_errors.is_empty() {
         for error in &lex_errors {
-            match error_format {
-                ErrorFormat::Human => ctx.error(error.span, &error.message),
-                ErrorFormat::Json => json_errors.push(span_to_json_error(
-                    &filename,
-                    error.span,
-                    "LEX",
-                    &error.message,
-                    None,
-                )),
-            }
-        }
-        if matches!(error_format, ErrorFormat::Json) {
-            output_json_errors(json_errors, None);
+            report.push(error.span, "LEX", error.message.clone(), None);
         }
-        return Err(format!("{} lexer error(s)", lex_errors.len()));
+        report.failure = Some(format!("{} lexer error(s)", lex_errors.len()));
+        return Ok(report);
     }
 
     // Parse
@@ -2313,30 +3294,16 @@ fn check(file: &PathBuf, partial: bool, error_format: ErrorFormat) -> Result<(),
         Ok(ast) => ast,
         Err(errors) => {
             for error in &errors {
-                match error_format {
-                    ErrorFormat::Human => ctx.error_with_help(
-                        error.span(),
-                        &format!("{}", error),
-                        error.help().unwrap_or("check syntax"),
-                    ),
-                    ErrorFormat::Json => {
-                        json_errors.push(span_to_json_error(
-                            &filename,
-                            error.span(),
-                            "PARSE",
-                            &format!("{}", error),
-                            error.help(),
-                        ));
-                    }
-                }
-            }
-            if error_format == ErrorFormat::Json {
-                output_json_errors(json_errors, None);
+                report.push(error.span(), "PARSE", format!("{}", error), error.help());
             }
-            return Err(format!("{} parse error(s)", errors.len()));
+            report.failure = Some(format!("{} parse error(s)", errors.len()));
+            return Ok(report);
         }
     };
 
+    // Lint this file only, not the items it imports
+    let warnings = forma::types::lint(&ast);
+
     // Load imports (module system)
     let mut module_loader = ModuleLoader::from_source_file(file);
     let ast = match module_loader.load_imports(&ast) {
@@ -2355,96 +3322,194 @@ fn check(file: &PathBuf, partial: bool, error_format: ErrorFormat) -> Result<(),
                 line: 1,
                 column: 1,
             });
-            match error_format {
-                ErrorFormat::Human => {
-                    ctx.error(error_span, &format!("module error: {}", e));
-                }
-                ErrorFormat::Json => {
-                    json_errors.push(span_to_json_error(
-                        &filename,
-                        error_span,
-                        "MODULE",
-                        &format!("{}", e),
-                        None,
-                    ));
-                    output_json_errors(json_errors, None);
-                }
-            }
-            return Err(format!("module error: {}", e));
+            report.push(error_span, "MODULE", format!("{}", e), None);
+            report.failure = Some(format!("module error: {}", e));
+            return Ok(report);
         }
     };
-
-    let mut error_count = 0;
+    report.items = Some(ast.items.len());
 
     // Type check
     let mut type_checker = TypeChecker::new();
     if let Err(errors) = type_checker.check(&ast) {
         for error in &errors {
-            match error_format {
-                ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
-                ErrorFormat::Json => json_errors.push(span_to_json_error(
-                    &filename,
-                    error.span,
-                    "TYPE",
-                    &format!("{}", error),
-                    None,
-                )),
-            }
+            report.push(error.span, "TYPE", format!("{}", error), None);
         }
-        error_count += errors.len();
     }
 
     // Borrow check
     let mut borrow_checker = BorrowChecker::new();
     if let Err(errors) = borrow_checker.check(&ast) {
         for error in &errors {
-            match error_format {
-                ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
-                ErrorFormat::Json => json_errors.push(span_to_json_error(
-                    &filename,
-                    error.span,
-                    "BORROW",
-                    &format!("{}", error),
-                    None,
-                )),
-            }
+            report.push(error.span, "BORROW", format!("{}", error), None);
         }
-        error_count += errors.len();
     }
 
-    if error_count > 0 {
-        if matches!(error_format, ErrorFormat::Json) {
-            if partial {
-                // Partial check returns structured result even with errors
-                let result = serde_json::json!({
-                    "valid": false,
-                    "errors": json_errors,
-                    "holes": [],  // TODO: identify incomplete expressions
-                    "items": ast.items.len()
-                });
-                print_json(&result);
-            } else {
-                output_json_errors(json_errors, Some(ast.items.len()));
+    // Lower a well-typed program, rejecting capability-gated builtins whose
+    // capability was not granted
+    if report.count(Severity::Error) == 0
+        && let Err(errors) = Lowerer::new()
+            .with_capabilities(capabilities.clone())
+            .lower(&ast)
+    {
+        for error in &errors {
+            report.push(error.span, "LOWER", error.message.clone(), None);
+        }
+    }
+
+    for warning in warnings.iter().chain(type_checker.warnings()) {
+        report.push_lint(warning, lints);
+    }
+
+    let errors = report.count(Severity::Error);
+    if errors > 0 {
+        report.failure = Some(format!("{} error(s) found", errors));
+    }
+    Ok(report)
+}
+
+fn check(
+    file: &PathBuf,
+    partial: bool,
+    lints: &LintLevels,
+    capabilities: &HashSet<String>,
+    error_format: ErrorFormat,
+) -> Result<(), String> {
+    let report = check_file(file, lints, capabilities)?;
+
+    match error_format {
+        ErrorFormat::Human => {
+            report.print_human();
+            if report.failure.is_none() {
+                println!("No errors found ({} items)", report.items.unwrap_or(0));
             }
         }
-        Err(format!("{} error(s) found", error_count))
-    } else {
-        match error_format {
-            ErrorFormat::Human => println!("No errors found ({} items)", ast.items.len()),
-            ErrorFormat::Json => {
-                if partial {
+        ErrorFormat::Json => {
+            let errors = report.json_errors();
+            match report.items {
+                // Partial check returns a structured result even with errors
+                Some(items) if partial => {
                     let result = serde_json::json!({
-                        "valid": true,
-                        "errors": [],
-                        "holes": [],
-                        "items": ast.items.len()
+                        "valid": errors.iter().all(|e| e.level != "error"),
+                        "errors": errors,
+                        "holes": [],  // TODO: identify incomplete expressions
+                        "items": items
                     });
                     print_json(&result);
+                }
+                // Otherwise a bare array of diagnostics, `[]` for a clean file
+                _ => print_json(&errors),
+            }
+        }
+    }
+
+    match report.failure {
+        Some(failure) => Err(failure),
+        None => Ok(()),
+    }
+}
+
+/// Check several files in parallel, reporting diagnostics sorted by file
+/// name and then by position so the output does not depend on scheduling.
+/// Check one file, turning a read failure or a checker panic into a failed
+/// report so one bad file does not stop the rest.
+fn check_file_report(
+    file: &PathBuf,
+    lints: &LintLevels,
+    capabilities: &HashSet<String>,
+) -> CheckReport {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let result = catch_unwind(AssertUnwindSafe(|| check_file(file, lints, capabilities)))
+        .unwrap_or_else(|_| {
+            let mut report = CheckReport::new(file, String::new());
+            report.push(
+                Span::default(),
+                "INTERNAL",
+                "internal compiler error: checker panicked".to_string(),
+                None,
+            );
+            report.failure = Some("checker panicked".to_string());
+            Ok(report)
+        });
+    result.unwrap_or_else(|e| {
+        let mut report = CheckReport::new(file, String::new());
+        report.push(Span::default(), "IO", e.clone(), None);
+        report.failure = Some(e);
+        report
+    })
+}
+
+fn check_files(
+    files: &[PathBuf],
+    lints: &LintLevels,
+    capabilities: &HashSet<String>,
+    error_format: ErrorFormat,
+) -> Result<(), String> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A fixed pool of workers takes files from a shared counter, since every
+    // compiler thread reserves COMPILER_STACK_SIZE of address space
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len())
+        .max(1);
+    let next = AtomicUsize::new(0);
+    let mut reports: Vec<CheckReport> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                std::thread::Builder::new()
+                    .stack_size(COMPILER_STACK_SIZE)
+                    .spawn_scoped(scope, || {
+                        let mut reports = Vec::new();
+                        while let Some(file) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            reports.push(check_file_report(file, lints, capabilities));
+                        }
+                        reports
+                    })
+                    .expect("failed to spawn checker thread")
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("checker thread panicked"))
+            .collect()
+    });
+
+    reports.sort_by(|a, b| a.filename.cmp(&b.filename));
+    for report in &mut reports {
+        report
+            .diagnostics
+            .sort_by_key(|d| (d.span.line, d.span.column, d.span.start));
+    }
+
+    let failed = reports.iter().filter(|r| r.failure.is_some()).count();
+    match error_format {
+        ErrorFormat::Human => {
+            for report in &reports {
+                if report.source.is_empty() {
+                    for diag in &report.diagnostics {
+                        eprintln!("error: {}: {}", report.filename, diag.message);
+                    }
                 } else {
-                    output_json_errors(vec![], Some(ast.items.len()));
+                    report.print_human();
                 }
             }
+            println!("Checked {} file(s): {} with errors", reports.len(), failed);
         }
+        ErrorFormat::Json => {
+            let errors: Vec<JsonError> = reports.iter().flat_map(|r| r.json_errors()).collect();
+            print_json(&errors);
+        }
+    }
+
+    if failed > 0 {
+        Err(format!(
+            "{} of {} file(s) failed to check",
+            failed,
+            reports.len()
+        ))
+    } else {
         Ok(())
     }
 }
@@ -2653,8 +3718,9 @@ fn typeof_at(file: &PathBuf, position: &str, error_format: ErrorFormat) -> Resul
             // Found token at position - infer its type from context
             match &token.kind {
                 forma::lexer::TokenKind::Ident(_) => {
-                    // Look up identifier in type environment
-                    result_type = Some("(identifier - run type checker for actual type)".into());
+                    result_type = type_checker
+                        .type_at(token.span.start)
+                        .map(|ty| ty.to_string());
                     context = "identifier";
                 }
                 forma::lexer::TokenKind::Int(_) => {
@@ -2760,18 +3826,19 @@ fn find_runtime_lib() -> Option<PathBuf> {
 fn build(
     file: &PathBuf,
     output: Option<&PathBuf>,
-    opt_level: u8,
-    do_optimize: bool,
+    config: &BuildConfig,
     error_format: ErrorFormat,
 ) -> Result<(), String> {
     let source = read_file(file)?;
     let filename = file.to_string_lossy().to_string();
     let ctx = ErrorContext::new(&filename, &source);
     let mut json_errors: Vec<JsonError> = vec![];
+    let mut timer = PhaseTimer::new(&filename, config.timings.clone());
 
     // Lex
     let scanner = Scanner::new(&source);
     let (tokens, lex_errors) = scanner.scan_all();
+    timer.phase("lex");
 
     if !lex_errors.is_empty() {
         for error in &lex_errors {
@@ -2779,6 +3846,7 @@ fn build(
                 ErrorFormat::Human => ctx.error(error.span, &error.message),
                 ErrorFormat::Json => json_errors.push(span_to_json_error(
                     &filename,
+                    &source,
                     error.span,
                     "LEX",
                     &error.message,
@@ -2794,7 +3862,9 @@ fn build(
 
     // Parse
     let parser = FormaParser::new(&tokens);
-    let parsed_ast = match parser.parse() {
+    let parse_result = parser.parse();
+    timer.phase("parse");
+    let parsed_ast = match parse_result {
         Ok(ast) => ast,
         Err(errors) => {
             for error in &errors {
@@ -2802,6 +3872,7 @@ fn build(
                     ErrorFormat::Human => ctx.error(error.span(), &format!("{}", error)),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error.span(),
                         "PARSE",
                         &format!("{}", error),
@@ -2816,9 +3887,18 @@ fn build(
         }
     };
 
+    // The source map only covers this file's own functions
+    #[cfg(feature = "llvm")]
+    let sourcemap_ast = config
+        .emit
+        .contains(&EmitKind::Sourcemap)
+        .then(|| parsed_ast.clone());
+
     // Load imports
     let mut module_loader = ModuleLoader::from_source_file(file);
-    let ast = match module_loader.load_imports(&parsed_ast) {
+    let imports = module_loader.load_imports(&parsed_ast);
+    timer.phase("imports");
+    let ast = match imports {
         Ok(imported_items) => {
             let mut combined_items = imported_items;
             combined_items.extend(parsed_ast.items);
@@ -2841,6 +3921,7 @@ fn build(
                 ErrorFormat::Json => {
                     json_errors.push(span_to_json_error(
                         &filename,
+                        &source,
                         error_span,
                         "MODULE",
                         &format!("{}", e),
@@ -2853,14 +3934,37 @@ fn build(
         }
     };
 
+    // Incremental builds key the object on the sources it was compiled from
+    #[cfg(feature = "llvm")]
+    let object_cache = match &config.incremental {
+        Some(dir) if config.crate_type == CrateType::Bin => {
+            let cache = forma::codegen::ObjectCache::open(dir, file)
+                .map_err(|e| format!("Failed to open cache {}: {}", dir.display(), e))?;
+            let mut parts = vec![
+                config.object_fingerprint().into_bytes(),
+                source.clone().into_bytes(),
+            ];
+            for path in module_loader.loaded_paths() {
+                parts.push(path.to_string_lossy().into_owned().into_bytes());
+                parts.push(fs::read(path).unwrap_or_default());
+            }
+            let key = forma::codegen::cache::content_hash(&parts);
+            Some((cache, key))
+        }
+        _ => None,
+    };
+
     // Type check
     let mut type_checker = TypeChecker::new();
-    if let Err(errors) = type_checker.check(&ast) {
+    let type_result = type_checker.check(&ast);
+    timer.phase("typecheck");
+    if let Err(errors) = type_result {
         for error in &errors {
             match error_format {
                 ErrorFormat::Human => ctx.error(error.span, &format!("{}", error)),
                 ErrorFormat::Json => json_errors.push(span_to_json_error(
                     &filename,
+                    &source,
                     error.span,
                     "TYPE",
                     &format!("{}", error),
@@ -2875,17 +3979,29 @@ fn build(
     }
 
     // Determine output path
-    let output_path = output.cloned().unwrap_or_else(|| file.with_extension(""));
+    let output_path = output.cloned().unwrap_or_else(|| match config.crate_type {
+        CrateType::Bin => file.with_extension(""),
+        CrateType::Staticlib => {
+            let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+            file.with_file_name(format!("lib{}.a", stem))
+        }
+    });
 
     // Lower to MIR
-    let mut program = match Lowerer::new().lower(&ast) {
+    let lowered = Lowerer::new()
+        .with_contract_checks(config.check_contracts)
+        .with_invariant_checks(config.check_contracts)
+        .with_assertions(config.assertions)
+        .lower(&ast);
+    timer.phase("lower");
+    let mut program = match lowered {
         Ok(prog) => prog,
         Err(errors) => {
             for e in &errors {
                 match error_format {
                     ErrorFormat::Human => ctx.error(e.span, &e.message),
                     ErrorFormat::Json => json_errors.push(span_to_json_error(
-                        &filename, e.span, "LOWER", &e.message, None,
+                        &filename, &source, e.span, "LOWER", &e.message, None,
                     )),
                 }
             }
@@ -2897,9 +4013,10 @@ fn build(
     };
 
     // Optimize MIR
-    if do_optimize {
+    if config.optimize {
         forma::mir::optimize::optimize(&mut program);
     }
+    timer.phase("optimize");
 
     // LLVM codegen
     #[cfg(feature = "llvm")]
@@ -2909,7 +4026,33 @@ fn build(
 
         let context = Context::create();
         let mut codegen = LLVMCodegen::new(&context, &filename);
-        codegen.set_opt_level(opt_level);
+        codegen.set_opt_level(config.opt_level);
+        codegen.set_div_checks(config.div_checks);
+        codegen.set_bounds_checks(config.bounds_checks);
+        codegen.set_reloc_mode(match config.relocation_model {
+            RelocationModel::Default => inkwell::targets::RelocMode::Default,
+            RelocationModel::Pic => inkwell::targets::RelocMode::PIC,
+            RelocationModel::Static => inkwell::targets::RelocMode::Static,
+            RelocationModel::DynamicNoPic => inkwell::targets::RelocMode::DynamicNoPic,
+        });
+        codegen.set_code_model(match config.code_model {
+            CodeModelArg::Default => inkwell::targets::CodeModel::Default,
+            CodeModelArg::Small => inkwell::targets::CodeModel::Small,
+            CodeModelArg::Kernel => inkwell::targets::CodeModel::Kernel,
+            CodeModelArg::Medium => inkwell::targets::CodeModel::Medium,
+            CodeModelArg::Large => inkwell::targets::CodeModel::Large,
+        });
+        if config.debug {
+            codegen.enable_debug_info(file);
+        }
+        if config.coverage {
+            codegen.enable_coverage();
+        }
+        if config.allow_all {
+            codegen.grant_capability("all");
+        } else if config.allow_env {
+            codegen.grant_capability("env");
+        }
 
         // Dump MIR for debugging (if FORMA_DEBUG is set)
         if std::env::var("FORMA_DEBUG").is_ok() {
@@ -2930,7 +4073,15 @@ fn build(
             }
         }
 
-        if let Err(e) = codegen.compile(&program) {
+        let cached_object = object_cache
+            .as_ref()
+            .and_then(|(cache, key)| cache.lookup(key));
+        let compiled = match cached_object {
+            Some(_) => Ok(()),
+            None => codegen.compile(&program),
+        };
+        timer.phase("codegen");
+        if let Err(e) = compiled {
             match error_format {
                 ErrorFormat::Human => {
                     eprintln!("error[CODEGEN]: {}", e);
@@ -2942,7 +4093,7 @@ fn build(
                         column: 1,
                         end_line: 1,
                         end_column: 1,
-                        severity: "error".to_string(),
+                        level: "error".to_string(),
                         code: "CODEGEN".to_string(),
                         message: e.to_string(),
                         help: None,
@@ -2953,45 +4104,111 @@ fn build(
             return Err(format!("Codegen error: {}", e));
         }
 
-        // Write object file
-        let obj_path = output_path.with_extension("o");
-        if let Err(e) = codegen.write_object_file(&obj_path) {
-            return Err(format!("Failed to write object file: {}", e));
-        }
+        if config.crate_type == CrateType::Staticlib {
+            let written = codegen.write_static_library(&output_path);
+            timer.phase("archive");
+            if let Err(e) = written {
+                return Err(format!("Failed to write static library: {}", e));
+            }
+        } else {
+            // Write object file, unless an identical build is cached
+            let obj_path = output_path.with_extension("o");
+            let link_object = match &cached_object {
+                Some(cached) => cached.clone(),
+                None => {
+                    let written = codegen.write_object_file(&obj_path);
+                    timer.phase("object");
+                    if let Err(e) = written {
+                        return Err(format!("Failed to write object file: {}", e));
+                    }
+                    if let Some((cache, key)) = &object_cache
+                        && let Err(e) = cache.store(key, &obj_path)
+                    {
+                        eprintln!("warning: could not cache object: {}", e);
+                    }
+                    obj_path.clone()
+                }
+            };
 
-        // Find the runtime library
-        let runtime_lib_path = find_runtime_lib()
-            .ok_or_else(|| "Cannot find libforma_runtime.a - build the runtime first: cd runtime && cargo build --release".to_string())?;
+            // Find the runtime library
+            let runtime_lib_path = find_runtime_lib()
+                .ok_or_else(|| "Cannot find libforma_runtime.a - build the runtime first: cd runtime && cargo build --release".to_string())?;
+
+            // Link to executable with the FORMA runtime
+            let mut link = std::process::Command::new("cc");
+            link.arg(&link_object)
+                .arg("-L")
+                .arg(&runtime_lib_path)
+                .arg("-lforma_runtime")
+                .arg("-o")
+                .arg(&output_path);
+            // Objects with absolute relocations cannot go into a PIE, which is
+            // the default output of most Linux toolchains
+            if cfg!(target_os = "linux")
+                && matches!(
+                    config.relocation_model,
+                    RelocationModel::Static | RelocationModel::DynamicNoPic
+                )
+            {
+                link.arg("-no-pie");
+            }
+            let status = link
+                .status()
+                .map_err(|e| format!("Failed to run linker: {}", e))?;
+            timer.phase("link");
 
-        // Link to executable with the FORMA runtime
-        let status = std::process::Command::new("cc")
-            .arg(&obj_path)
-            .arg("-L")
-            .arg(&runtime_lib_path)
-            .arg("-lforma_runtime")
-            .arg("-o")
-            .arg(&output_path)
-            .status()
-            .map_err(|e| format!("Failed to run linker: {}", e))?;
+            if !status.success() {
+                return Err("Linking failed".into());
+            }
 
-        if !status.success() {
-            return Err("Linking failed".into());
+            // Clean up object file (a cached copy stays in the cache)
+            let _ = std::fs::remove_file(&obj_path);
         }
 
-        // Clean up object file
-        let _ = std::fs::remove_file(&obj_path);
+        let sourcemap_path = match &sourcemap_ast {
+            Some(parsed) => {
+                let map =
+                    forma::mir::sourcemap::SourceMap::new(&filename, &source, parsed, &program);
+                let path = PathBuf::from(format!("{}.sourcemap.json", output_path.display()));
+                let json = serde_json::to_string_pretty(&map)
+                    .map_err(|e| format!("Failed to serialize source map: {}", e))?;
+                fs::write(&path, json)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                Some(path)
+            }
+            None => None,
+        };
 
         match error_format {
             ErrorFormat::Human => {
-                println!("Compiled {} -> {}", file.display(), output_path.display());
+                let reused = if cached_object.is_some() {
+                    " (cached object)"
+                } else {
+                    ""
+                };
+                println!(
+                    "Compiled {} -> {}{}",
+                    file.display(),
+                    output_path.display(),
+                    reused
+                );
+                if let Some(path) = &sourcemap_path {
+                    println!("Wrote source map {}", path.display());
+                }
             }
             ErrorFormat::Json => {
-                let result = serde_json::json!({
+                let mut result = serde_json::json!({
                     "status": "success",
                     "input": file.to_string_lossy(),
                     "output": output_path.to_string_lossy(),
-                    "opt_level": opt_level
+                    "opt_level": config.opt_level
                 });
+                if object_cache.is_some() {
+                    result["cached"] = cached_object.is_some().into();
+                }
+                if let Some(path) = &sourcemap_path {
+                    result["sourcemap"] = path.to_string_lossy().into();
+                }
                 print_json(&result);
             }
         }
@@ -3238,6 +4455,7 @@ AttrArgList = Expression [ "," String ] ;
 (* @pre(condition) or @pre(condition, "message") *)
 (* @post(condition) or @post(condition, "message") *)
 (* @post(old(x) + delta == result) — old() captures entry state *)
+(* @invariant(condition) on the lines before "wh" or "for" — checked each iteration *)
 (* Named patterns: @sorted(arr) @nonempty(x) @permutation(a,b) etc. *)
 
 (* ============================================ *)
@@ -3317,7 +4535,7 @@ AddExpr = MulExpr {{ ( "+" | "-" ) MulExpr }} ;
 
 MulExpr = UnaryExpr {{ ( "*" | "/" | "%" ) UnaryExpr }} ;
 
-UnaryExpr = ( "-" | "!" | "&" [ "mut" ] | "*" ) UnaryExpr
+UnaryExpr = ( "+" | "-" | "!" | "&" [ "mut" ] | "*" ) UnaryExpr
           | PostfixExpr
           ;
 
@@ -3534,6 +4752,7 @@ DEDENT = ? decrease in indentation level ? ;
 (* Pointer types: *T (raw pointer), *Void (void pointer) *)
 
 (* JSON type: Json *)
+(* Byte string type: Bytes, written b"..." with \xNN escapes *)
 
 (* Shorthand Keywords *)
 (* These are aliases for common keywords to reduce token count *)
@@ -3570,7 +4789,7 @@ indentation = INDENT statement* DEDENT ;
 (* Operator Precedence (highest to lowest) *)
 (* 1. Primary: literals, identifiers, parenthesized expressions *)
 (* 2. Postfix: function calls, method calls, field access, indexing *)
-(* 3. Unary: -, !, & *)
+(* 3. Unary: +, -, !, &, * (so -2 * 3 is (-2) * 3 and !a == b is (!a) == b) *)
 (* 4. Multiplicative: *, /, % *)
 (* 5. Additive: +, - *)
 (* 6. Shift: <<, >> *)
@@ -4165,13 +5384,19 @@ fn repl_type_of(expr: &str, session_code: &str) {
 }
 
 /// Format a FORMA source file
-fn fmt(file: &PathBuf, write: bool, check: bool, error_format: ErrorFormat) -> Result<(), String> {
+fn fmt(
+    file: &PathBuf,
+    write: bool,
+    check: bool,
+    line_ending: LineEndingArg,
+    error_format: ErrorFormat,
+) -> Result<(), String> {
     let source = read_file(file)?;
     let filename = file.to_string_lossy().to_string();
 
     // Lex
     let scanner = Scanner::new(&source);
-    let (tokens, lex_errors) = scanner.scan_all();
+    let (tokens, lex_errors, comments) = scanner.scan_all_with_comments();
 
     if !lex_errors.is_empty() {
         match error_format {
@@ -4186,7 +5411,9 @@ fn fmt(file: &PathBuf, write: bool, check: bool, error_format: ErrorFormat) -> R
             ErrorFormat::Json => {
                 let json_errors: Vec<JsonError> = lex_errors
                     .iter()
-                    .map(|e| span_to_json_error(&filename, e.span, "LEX", &e.message, None))
+                    .map(|e| {
+                        span_to_json_error(&filename, &source, e.span, "LEX", &e.message, None)
+                    })
                     .collect();
                 output_json_errors(json_errors, None);
             }
@@ -4211,6 +5438,7 @@ fn fmt(file: &PathBuf, write: bool, check: bool, error_format: ErrorFormat) -> R
                         .map(|e| {
                             span_to_json_error(
                                 &filename,
+                                &source,
                                 e.span(),
                                 "PARSE",
                                 &format!("{}", e),
@@ -4226,7 +5454,14 @@ fn fmt(file: &PathBuf, write: bool, check: bool, error_format: ErrorFormat) -> R
     };
 
     // Format
-    let mut formatter = forma::Formatter::new();
+    let line_ending = match line_ending {
+        LineEndingArg::Auto => forma::LineEnding::detect(&source),
+        LineEndingArg::Lf => forma::LineEnding::Lf,
+        LineEndingArg::Crlf => forma::LineEnding::Crlf,
+    };
+    let mut formatter = forma::Formatter::new()
+        .with_comments(comments)
+        .with_line_ending(line_ending);
     let formatted = formatter.format(&ast);
 
     if check {
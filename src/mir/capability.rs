@@ -0,0 +1,84 @@
+//! Capabilities required by builtins.
+//!
+//! Builtins that touch the outside world are gated behind a capability that
+//! the user grants with an `--allow-*` flag. The interpreter checks the grant
+//! when the builtin runs; the lowerer can check it up front, so a program that
+//! would be denied is rejected before it runs.
+
+use crate::lexer::Span;
+
+use super::mir::{Program, Terminator};
+
+/// Every capability, in the order reports list them.
+pub const CAPABILITIES: &[&str] = &[
+    "read", "write", "network", "exec", "env", "unsafe", "threads",
+];
+
+/// The capability a call to builtin `name` requires, if any.
+///
+/// Keep in sync with the `require_capability` calls in the interpreter.
+pub fn builtin_capability(name: &str) -> Option<&'static str> {
+    let capability = match name {
+        "file_read" | "file_read_bytes" | "file_exists" | "dir_list" => "read",
+        "file_write" | "file_write_bytes" | "file_append" | "file_remove" | "file_move"
+        | "file_copy" | "dir_create" | "dir_create_all" | "dir_remove" | "dir_remove_all"
+        | "chdir" | "db_open" => "write",
+        "http_get" | "http_post" | "http_post_json" | "http_put" | "http_delete" | "http_serve"
+        | "tcp_connect" | "tcp_listen" | "udp_bind" | "tls_connect" => "network",
+        "exec" => "exec",
+        "env_get" | "env_set" | "env_remove" | "env_vars" => "env",
+        "ptr_null" | "ptr_is_null" | "ptr_offset" | "ptr_addr" | "ptr_from_addr"
+        | "str_to_cstr" | "cstr_to_str" | "cstr_to_str_len" | "cstr_free" | "alloc"
+        | "alloc_zeroed" | "dealloc" | "mem_copy" | "mem_set" => "unsafe",
+        _ => return None,
+    };
+    Some(capability)
+}
+
+/// The command-line flag that grants `capability`.
+pub fn capability_flag(capability: &str) -> String {
+    format!("--allow-{}", capability)
+}
+
+/// A call to a capability-gated builtin.
+#[derive(Debug, Clone)]
+pub struct CapabilityUse {
+    pub capability: &'static str,
+    /// The builtin called
+    pub builtin: String,
+    /// The function containing the call
+    pub function: String,
+    /// Location of the call, when known
+    pub span: Option<Span>,
+}
+
+/// Find every call to a capability-gated builtin in `program`.
+///
+/// Functions and externs the program defines itself shadow builtins of the
+/// same name and are not reported. Uses are sorted by source position.
+pub fn capability_uses(program: &Program) -> Vec<CapabilityUse> {
+    let mut uses = Vec::new();
+    for (name, func) in &program.functions {
+        for block in &func.blocks {
+            let Some(Terminator::Call { func: callee, .. }) = &block.terminator else {
+                continue;
+            };
+            if program.functions.contains_key(callee) || program.externs.contains_key(callee) {
+                continue;
+            }
+            if let Some(capability) = builtin_capability(callee) {
+                uses.push(CapabilityUse {
+                    capability,
+                    builtin: callee.clone(),
+                    function: name.clone(),
+                    span: block.terminator_span.or(func.span),
+                });
+            }
+        }
+    }
+    uses.sort_by_key(|u| {
+        let span = u.span.unwrap_or_default();
+        (span.line, span.column, u.function.clone())
+    });
+    uses
+}
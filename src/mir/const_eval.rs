@@ -0,0 +1,386 @@
+//! Compile-time evaluation of const functions.
+//!
+//! A small interpreter over MIR that runs `const f` functions for constant
+//! initializers and array sizes. Values are [`Constant`]s, in practice
+//! integers, floats, booleans and chars. Anything a const function cannot do
+//! at compile time, such as calling a function that is not `const`, is a
+//! [`ConstEvalError`], and a step limit stops loops that never finish.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::mir::{
+    BinOp, Constant, Function, Local, Operand, Program, Rvalue, StatementKind, Terminator, UnOp,
+};
+use super::optimize::{fold_binop, fold_unop};
+use crate::types::Ty;
+
+/// Default number of statements and terminators an evaluation may execute.
+pub const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+/// Deepest chain of const function calls, so runaway recursion is an error
+/// rather than a stack overflow in the compiler.
+const MAX_CALL_DEPTH: usize = 256;
+
+/// Error during compile-time evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstEvalError {
+    pub message: String,
+}
+
+impl ConstEvalError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConstEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConstEvalError {}
+
+/// Compile-time interpreter for the const functions of a program.
+pub struct ConstEvaluator<'a> {
+    program: &'a Program,
+    max_steps: usize,
+    steps: usize,
+    depth: usize,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            max_steps: DEFAULT_MAX_STEPS,
+            steps: 0,
+            depth: 0,
+        }
+    }
+
+    /// Set the step limit shared by every call of an evaluation.
+    pub fn with_max_steps(mut self, max: usize) -> Self {
+        self.max_steps = max;
+        self
+    }
+
+    /// Call const function `name` with `args` and return its result.
+    pub fn call(&mut self, name: &str, args: Vec<Constant>) -> Result<Constant, ConstEvalError> {
+        let func = self
+            .program
+            .functions
+            .get(name)
+            .filter(|f| f.is_const)
+            .ok_or_else(|| {
+                ConstEvalError::new(format!("cannot call non-const `{}` at compile time", name))
+            })?;
+        if args.len() != func.params.len() {
+            return Err(ConstEvalError::new(format!(
+                "`{}` takes {} argument(s), found {}",
+                name,
+                func.params.len(),
+                args.len()
+            )));
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(ConstEvalError::new(format!(
+                "const evaluation exceeded the call depth limit of {}",
+                MAX_CALL_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let mut locals: HashMap<Local, Constant> = func
+            .params
+            .iter()
+            .map(|(local, _)| *local)
+            .zip(args)
+            .collect();
+        let result = self.run(func, &mut locals);
+        self.depth -= 1;
+        result
+    }
+
+    fn run(
+        &mut self,
+        func: &Function,
+        locals: &mut HashMap<Local, Constant>,
+    ) -> Result<Constant, ConstEvalError> {
+        let mut block_id = func.entry_block;
+        loop {
+            let block = func.blocks.get(block_id.0 as usize).ok_or_else(|| {
+                ConstEvalError::new(format!("`{}` has no block {}", func.name, block_id.0))
+            })?;
+            for stmt in &block.stmts {
+                self.step()?;
+                match &stmt.kind {
+                    StatementKind::Assign(local, rvalue) => {
+                        let value = self.eval_rvalue(rvalue, locals)?;
+                        locals.insert(*local, value);
+                    }
+                    StatementKind::IndexAssign(..) => {
+                        return Err(ConstEvalError::new(
+                            "cannot assign through an index at compile time",
+                        ));
+                    }
+                    StatementKind::Nop => {}
+                }
+            }
+            self.step()?;
+            block_id = match &block.terminator {
+                Some(Terminator::Return(value)) => {
+                    return match value {
+                        Some(op) => self.eval_operand(op, locals),
+                        None => Ok(Constant::Unit),
+                    };
+                }
+                Some(Terminator::Goto(target)) => *target,
+                Some(Terminator::If {
+                    cond,
+                    then_block,
+                    else_block,
+                }) => match self.eval_operand(cond, locals)? {
+                    Constant::Bool(true) => *then_block,
+                    Constant::Bool(false) => *else_block,
+                    other => {
+                        return Err(ConstEvalError::new(format!(
+                            "condition is not a Bool: {:?}",
+                            other
+                        )));
+                    }
+                },
+                Some(Terminator::Switch {
+                    operand,
+                    targets,
+                    default,
+                }) => {
+                    let Constant::Int(n, _) = self.eval_operand(operand, locals)? else {
+                        return Err(ConstEvalError::new("switch on a non-integer value"));
+                    };
+                    targets
+                        .iter()
+                        .find(|(value, _)| *value == n)
+                        .map_or(*default, |(_, target)| *target)
+                }
+                Some(Terminator::Call {
+                    func: callee,
+                    args,
+                    dest,
+                    next,
+                    ..
+                }) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.eval_operand(arg, locals))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let value = self.call(callee, args)?;
+                    if let Some(dest) = dest {
+                        locals.insert(*dest, value);
+                    }
+                    *next
+                }
+                Some(Terminator::CallIndirect { .. }) => {
+                    return Err(ConstEvalError::new(
+                        "cannot call a function value at compile time",
+                    ));
+                }
+                Some(Terminator::Spawn { .. } | Terminator::Await { .. }) => {
+                    return Err(ConstEvalError::new(
+                        "cannot spawn or await tasks at compile time",
+                    ));
+                }
+                Some(Terminator::Unreachable) | None => {
+                    return Err(ConstEvalError::new(format!(
+                        "reached unreachable code in `{}`",
+                        func.name
+                    )));
+                }
+            };
+        }
+    }
+
+    fn step(&mut self) -> Result<(), ConstEvalError> {
+        self.steps += 1;
+        if self.steps > self.max_steps {
+            return Err(ConstEvalError::new(format!(
+                "const evaluation exceeded the step limit of {}",
+                self.max_steps
+            )));
+        }
+        Ok(())
+    }
+
+    fn eval_operand(
+        &self,
+        op: &Operand,
+        locals: &HashMap<Local, Constant>,
+    ) -> Result<Constant, ConstEvalError> {
+        match op {
+            Operand::Constant(c) => Ok(c.clone()),
+            Operand::Local(local) | Operand::Copy(local) | Operand::Move(local) => {
+                locals.get(local).cloned().ok_or_else(|| {
+                    ConstEvalError::new(format!("use of uninitialized local {}", local.0))
+                })
+            }
+        }
+    }
+
+    fn eval_rvalue(
+        &self,
+        rvalue: &Rvalue,
+        locals: &HashMap<Local, Constant>,
+    ) -> Result<Constant, ConstEvalError> {
+        match rvalue {
+            Rvalue::Use(op) => self.eval_operand(op, locals),
+            Rvalue::BinaryOp(op, left, right) => {
+                let left = self.eval_operand(left, locals)?;
+                let right = self.eval_operand(right, locals)?;
+                eval_binop(*op, &left, &right)
+            }
+            Rvalue::UnaryOp(op, operand) => {
+                let value = self.eval_operand(operand, locals)?;
+                fold_unop(*op, &value).ok_or_else(|| match (op, &value) {
+                    (UnOp::Neg, Constant::Int(..)) => ConstEvalError::new("integer overflow"),
+                    _ => ConstEvalError::new(format!("cannot apply {:?} to {:?}", op, value)),
+                })
+            }
+            Rvalue::Cast(op, ty) => {
+                let value = self.eval_operand(op, locals)?;
+                cast(value, ty)
+            }
+            _ => Err(ConstEvalError::new(
+                "only scalar operations can be evaluated at compile time",
+            )),
+        }
+    }
+}
+
+fn eval_binop(op: BinOp, left: &Constant, right: &Constant) -> Result<Constant, ConstEvalError> {
+    if let Some(value) = fold_binop(op, left, right) {
+        return Ok(value);
+    }
+    let zero =
+        matches!(right, Constant::Int(0, _)) || matches!(right, Constant::Float(f) if *f == 0.0);
+    Err(match (op, left, right) {
+        (BinOp::Div | BinOp::Rem, _, _) if zero => ConstEvalError::new("division by zero"),
+        (
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem,
+            Constant::Int(..),
+            Constant::Int(..),
+        ) => ConstEvalError::new("integer overflow"),
+        _ => ConstEvalError::new(format!(
+            "cannot apply {:?} to {:?} and {:?}",
+            op, left, right
+        )),
+    })
+}
+
+/// Numeric casts, with the same truncation as the interpreter.
+fn cast(value: Constant, target: &Ty) -> Result<Constant, ConstEvalError> {
+    let truncate = |n: i64| match target {
+        Ty::I8 => n as i8 as i64,
+        Ty::I16 => n as i16 as i64,
+        Ty::I32 => n as i32 as i64,
+        Ty::U8 => n as u8 as i64,
+        Ty::U16 => n as u16 as i64,
+        Ty::U32 => n as u32 as i64,
+        _ => n,
+    };
+    match (value, target) {
+        (Constant::Int(n, _), ty) if ty.is_integer() => Ok(Constant::Int(truncate(n), ty.clone())),
+        (Constant::Int(n, _), Ty::F32) => Ok(Constant::Float(n as f32 as f64)),
+        (Constant::Int(n, _), ty) if ty.is_float() => Ok(Constant::Float(n as f64)),
+        (Constant::Float(f), ty) if ty.is_integer() => {
+            Ok(Constant::Int(truncate(f as i64), ty.clone()))
+        }
+        (Constant::Float(f), Ty::F32) => Ok(Constant::Float(f as f32 as f64)),
+        (Constant::Float(f), ty) if ty.is_float() => Ok(Constant::Float(f)),
+        (Constant::Bool(b), ty) if ty.is_integer() => Ok(Constant::Int(b as i64, ty.clone())),
+        (Constant::Char(c), ty) if ty.is_integer() => Ok(Constant::Int(c as i64, ty.clone())),
+        (Constant::Int(n, _), Ty::Bool) => Ok(Constant::Bool(n != 0)),
+        (value, ty) if value.ty() == *ty => Ok(value),
+        (value, ty) => Err(ConstEvalError::new(format!(
+            "cannot cast {:?} to {} at compile time",
+            value, ty
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+    use crate::mir::Lowerer;
+    use crate::parser::Parser;
+
+    fn lower_source(source: &str) -> Program {
+        let scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_all();
+        let ast = Parser::new(&tokens).parse().expect("parse should succeed");
+        Lowerer::new().lower(&ast).expect("lowering should succeed")
+    }
+
+    #[test]
+    fn test_iterative_factorial() {
+        let program = lower_source(
+            "const f factorial(n: Int) -> Int\n    result := 1\n    i := 2\n    wh i <= n\n        result := result * i\n        i := i + 1\n    result\n",
+        );
+        let value = ConstEvaluator::new(&program)
+            .call("factorial", vec![Constant::Int(10, Ty::Int)])
+            .unwrap();
+        assert!(matches!(value, Constant::Int(3_628_800, Ty::Int)));
+
+        let error = ConstEvaluator::new(&program)
+            .call("factorial", vec![Constant::Int(21, Ty::Int)])
+            .unwrap_err();
+        assert_eq!(error.message, "integer overflow");
+    }
+
+    #[test]
+    fn test_step_limit_stops_infinite_loop() {
+        let program = lower_source(
+            "const f spin() -> Int\n    i := 0\n    wh true\n        i := i + 1\n    i\n",
+        );
+        let error = ConstEvaluator::new(&program)
+            .with_max_steps(1000)
+            .call("spin", vec![])
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "const evaluation exceeded the step limit of 1000"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let program = lower_source(
+            "const f ratio(a: Int, b: Int) -> Int = a / b\n\nf plain() -> Int = 1\n\nconst f forever(n: Int) -> Int = forever(n + 1)\n",
+        );
+        let error = ConstEvaluator::new(&program)
+            .call(
+                "ratio",
+                vec![Constant::Int(1, Ty::Int), Constant::Int(0, Ty::Int)],
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "division by zero");
+
+        let error = ConstEvaluator::new(&program)
+            .call("plain", vec![])
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "cannot call non-const `plain` at compile time"
+        );
+
+        let error = ConstEvaluator::new(&program)
+            .call("forever", vec![Constant::Int(0, Ty::Int)])
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "const evaluation exceeded the call depth limit of 256"
+        );
+    }
+}
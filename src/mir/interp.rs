@@ -27,6 +27,7 @@ use super::mir::{
     BinOp, BlockId, Constant, Function, Local, Operand, Program, Rvalue, StatementKind, Terminator,
     UnOp,
 };
+use crate::parser::split_format_string;
 use crate::types::Ty;
 
 /// Maximum buffer size for network read operations (64 MB).
@@ -79,6 +80,8 @@ pub enum Value {
     Float(f64),
     Char(char),
     Str(String),
+    /// Byte buffer from a `b"..."` literal
+    Bytes(Vec<u8>),
     Tuple(Vec<Value>),
     Array(Vec<Value>),
     Struct(String, HashMap<String, Value>),
@@ -269,6 +272,7 @@ impl std::fmt::Display for Value {
                 write!(f, "]>")
             }
             Value::Json(json) => write!(f, "{}", json),
+            Value::Bytes(bytes) => write!(f, "{}", crate::parser::ast::byte_string_source(bytes)),
             Value::Task(inner) => write!(f, "Task({})", inner),
             Value::Future(inner) => write!(f, "Future({})", inner),
             Value::Channel(id) => write!(f, "Channel({})", id),
@@ -491,6 +495,10 @@ pub struct Interpreter {
     capabilities: HashSet<String>,
     /// Whether to check @pre/@post contracts at runtime (default: true)
     check_contracts: bool,
+    /// Per-function basic block hit counts, when coverage is enabled
+    coverage: Option<HashMap<String, Vec<u64>>>,
+    /// Program argv: the program path followed by the arguments after `--`
+    program_args: Arc<Vec<String>>,
 }
 
 impl Interpreter {
@@ -526,6 +534,8 @@ impl Interpreter {
             env_vars: Arc::new(RwLock::new(HashMap::new())),
             capabilities: HashSet::new(),
             check_contracts: true,
+            coverage: None,
+            program_args: Arc::new(Vec::new()),
         })
     }
 
@@ -552,6 +562,12 @@ impl Interpreter {
         }
     }
 
+    /// Set the arguments returned by `args`, `args_count` and `args_get`.
+    /// By convention the first is the program path.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.program_args = Arc::new(args);
+    }
+
     /// Grant a capability to this interpreter.
     pub fn grant_capability(&mut self, capability: &str) {
         self.capabilities.insert(capability.to_string());
@@ -567,9 +583,33 @@ impl Interpreter {
         self.check_contracts = check;
     }
 
+    /// Count how many times each basic block is entered.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(HashMap::new);
+    }
+
+    /// Block hit counts as `<function> bb_<N> <count>` lines, sorted by
+    /// function name, with every block of every function listed. Returns
+    /// `None` unless coverage was enabled.
+    pub fn coverage_report(&self) -> Option<String> {
+        let counts = self.coverage.as_ref()?;
+        let mut names: Vec<&String> = self.program.functions.keys().collect();
+        names.sort();
+        let mut out = String::new();
+        for name in names {
+            let blocks = self.program.functions[name].blocks.len();
+            for i in 0..blocks {
+                let hits = counts.get(name).and_then(|c| c.get(i)).copied();
+                out.push_str(&format!("{} bb_{} {}\n", name, i, hits.unwrap_or(0)));
+            }
+        }
+        Some(out)
+    }
+
     /// Check if a capability is granted, returning an error if not.
     ///
-    /// Capability mapping (keep in sync when adding builtins):
+    /// Capability mapping (keep in sync when adding builtins, along with
+    /// `capability::builtin_capability`):
     ///   "read"    — file_read, file_exists, dir_list
     ///   "write"   — file_write, file_append, file_remove, file_move, file_copy,
     ///               dir_create, dir_create_all, dir_remove, dir_remove_all,
@@ -594,6 +634,22 @@ impl Interpreter {
         self.capabilities.contains(capability) || self.capabilities.contains("all")
     }
 
+    /// Check that `tcp_connect` may reach `addr`: either the whole "network"
+    /// capability is granted or just this `host:port`, as `"network:host:port"`
+    /// (from `--allow-net=host:port`).
+    fn require_network_address(&self, addr: &str) -> Result<(), InterpError> {
+        if self.has_capability("network")
+            || self.capabilities.contains(&format!("network:{}", addr))
+        {
+            Ok(())
+        } else {
+            Err(InterpError::capability_denied(
+                "network",
+                &format!("tcp_connect to {}", addr),
+            ))
+        }
+    }
+
     /// Create a minimal interpreter for running spawned tasks.
     /// This shares the program and global runtime via Arc but has its own call stack and state.
     pub fn new_for_task(program: Arc<Program>) -> Result<Self, InterpError> {
@@ -628,6 +684,8 @@ impl Interpreter {
             env_vars: Arc::new(RwLock::new(HashMap::new())),
             capabilities: HashSet::new(),
             check_contracts: true,
+            coverage: None,
+            program_args: Arc::new(Vec::new()),
         })
     }
 
@@ -755,7 +813,7 @@ impl Interpreter {
             let mut old_exprs: Vec<&crate::parser::Expr> = Vec::new();
             for contract in &func.postconditions {
                 if let Some(ref condition) = contract.condition {
-                    Self::collect_old_expr_args(condition, &mut old_exprs);
+                    condition.collect_old_args(&mut old_exprs);
                 }
             }
 
@@ -839,6 +897,7 @@ impl Interpreter {
                 LiteralKind::Int(n) => Ok(Value::Int(*n as i64)),
                 LiteralKind::Float(f) => Ok(Value::Float(*f)),
                 LiteralKind::String(s) => Ok(Value::Str(s.clone())),
+                LiteralKind::Bytes(bytes) => Ok(Value::Bytes(bytes.clone())),
                 LiteralKind::Char(c) => Ok(Value::Char(*c)),
                 LiteralKind::Bool(b) => Ok(Value::Bool(*b)),
                 LiteralKind::None => Ok(Value::Enum {
@@ -1010,6 +1069,12 @@ impl Interpreter {
                             message: format!("cannot apply ! to {:?}", val),
                         }),
                     },
+                    AstUnaryOp::Plus => match val {
+                        Value::Int(_) | Value::Float(_) => Ok(val),
+                        _ => Err(InterpError {
+                            message: format!("cannot apply + to {:?}", val),
+                        }),
+                    },
                     AstUnaryOp::Neg => match val {
                         Value::Int(n) => Ok(Value::Int(-n)),
                         Value::Float(f) => Ok(Value::Float(-f)),
@@ -1477,6 +1542,14 @@ impl Interpreter {
                         .ok_or_else(|| InterpError {
                             message: format!("string index {} out of bounds", i),
                         }),
+                    Value::Bytes(bytes) => {
+                        bytes
+                            .get(i)
+                            .map(|&b| Value::Int(b as i64))
+                            .ok_or_else(|| InterpError {
+                                message: format!("index {} out of bounds", i),
+                            })
+                    }
                     other => Err(InterpError {
                         message: format!("cannot index into {:?}", other),
                     }),
@@ -1521,81 +1594,6 @@ impl Interpreter {
         (expr.span.start, expr.span.end)
     }
 
-    fn collect_old_expr_args<'a>(
-        expr: &'a crate::parser::Expr,
-        out: &mut Vec<&'a crate::parser::Expr>,
-    ) {
-        use crate::parser::ast::ExprKind;
-
-        match &expr.kind {
-            ExprKind::Call(callee, args) => {
-                if let ExprKind::Ident(name) = &callee.kind
-                    && name.name == "old"
-                    && args.len() == 1
-                {
-                    out.push(&args[0].value);
-                }
-                Self::collect_old_expr_args(callee, out);
-                for arg in args {
-                    Self::collect_old_expr_args(&arg.value, out);
-                }
-            }
-            ExprKind::Binary(left, _, right)
-            | ExprKind::Coalesce(left, right)
-            | ExprKind::Pipeline(left, right)
-            | ExprKind::Index(left, right)
-            | ExprKind::Assign(left, right, _) => {
-                Self::collect_old_expr_args(left, out);
-                Self::collect_old_expr_args(right, out);
-            }
-            ExprKind::AssignOp(left, _, right) => {
-                Self::collect_old_expr_args(left, out);
-                Self::collect_old_expr_args(right, out);
-            }
-            ExprKind::Unary(_, inner)
-            | ExprKind::Field(inner, _)
-            | ExprKind::TupleField(inner, _)
-            | ExprKind::Try(inner)
-            | ExprKind::Await(inner)
-            | ExprKind::Spawn(inner)
-            | ExprKind::Paren(inner)
-            | ExprKind::Cast(inner, _) => Self::collect_old_expr_args(inner, out),
-            ExprKind::MethodCall(receiver, _, args) => {
-                Self::collect_old_expr_args(receiver, out);
-                for arg in args {
-                    Self::collect_old_expr_args(&arg.value, out);
-                }
-            }
-            ExprKind::Closure(closure) => Self::collect_old_expr_args(&closure.body, out),
-            ExprKind::Tuple(items) | ExprKind::Array(items) => {
-                for item in items {
-                    Self::collect_old_expr_args(item, out);
-                }
-            }
-            ExprKind::ArrayRepeat(value, count) => {
-                Self::collect_old_expr_args(value, out);
-                Self::collect_old_expr_args(count, out);
-            }
-            ExprKind::MapOrSet(entries) => {
-                for entry in entries {
-                    Self::collect_old_expr_args(&entry.key, out);
-                    if let Some(value) = &entry.value {
-                        Self::collect_old_expr_args(value, out);
-                    }
-                }
-            }
-            ExprKind::Range(start, end, _) => {
-                if let Some(start) = start {
-                    Self::collect_old_expr_args(start, out);
-                }
-                if let Some(end) = end {
-                    Self::collect_old_expr_args(end, out);
-                }
-            }
-            _ => {}
-        }
-    }
-
     fn contract_iter_values(&self, iterable: Value) -> Result<Vec<Value>, InterpError> {
         match iterable {
             Value::Array(items) => Ok(items),
@@ -1785,7 +1783,14 @@ impl Interpreter {
             }
 
             let frame = self.current_frame_mut()?;
-            let block = &func.blocks[frame.current_block.0 as usize];
+            let block_index = frame.current_block.0 as usize;
+            let block = &func.blocks[block_index];
+            if let Some(coverage) = self.coverage.as_mut() {
+                let counts = coverage
+                    .entry(func.name.clone())
+                    .or_insert_with(|| vec![0; func.blocks.len()]);
+                counts[block_index] += 1;
+            }
 
             // Execute statements
             for stmt in &block.stmts {
@@ -2027,6 +2032,13 @@ impl Interpreter {
                         self.call_function_with_refs(&callee, arg_vals, ref_binding_list)?
                     } else if let Some(builtin_result) = self.call_builtin(&fn_name, &arg_vals)? {
                         builtin_result
+                    } else if self.program.externs.contains_key(&fn_name) {
+                        return Err(InterpError {
+                            message: format!(
+                                "extern function '{}' can only be called from a compiled build",
+                                fn_name
+                            ),
+                        });
                     } else {
                         return Err(InterpError {
                             message: format!("undefined function: {}", fn_name),
@@ -2172,6 +2184,7 @@ impl Interpreter {
                             captures,
                         } => {
                             let program = Arc::clone(&self.program);
+                            let program_args = Arc::clone(&self.program_args);
                             let func_name_clone = func_name.clone();
                             let captures_clone = captures.clone();
 
@@ -2184,6 +2197,7 @@ impl Interpreter {
                                         Ok(i) => i,
                                         Err(_) => return Value::Unit,
                                     };
+                                    task_interp.program_args = program_args;
 
                                     // Get the closure's implementation function
                                     let func =
@@ -2338,6 +2352,39 @@ impl Interpreter {
                 Ok(Some(Value::Unit))
             }
 
+            // print(fmt, args...) - each `{}` in fmt is replaced by the next argument
+            "__print_fmt" => {
+                let fmt = match args.first() {
+                    Some(Value::Str(s)) => s.clone(),
+                    _ => {
+                        return Err(InterpError {
+                            message: "print() format must be a string".to_string(),
+                        });
+                    }
+                };
+                let segments =
+                    split_format_string(&fmt).map_err(|message| InterpError { message })?;
+                if segments.len() != args.len() {
+                    return Err(InterpError {
+                        message: format!(
+                            "format string has {} placeholder(s) but {} argument(s) were given",
+                            segments.len() - 1,
+                            args.len() - 1
+                        ),
+                    });
+                }
+                let mut out = segments[0].clone();
+                for (val, segment) in args[1..].iter().zip(&segments[1..]) {
+                    match val {
+                        Value::Str(s) => out.push_str(s),
+                        _ => out.push_str(&val.to_string()),
+                    }
+                    out.push_str(segment);
+                }
+                println!("{}", out);
+                Ok(Some(Value::Unit))
+            }
+
             // str(value) -> Str - convert any value to a string
             "str" => {
                 validate_args!(args, 1, "str");
@@ -2507,7 +2554,50 @@ impl Interpreter {
                 }
             }
 
+            // ===== Byte strings =====
+            "bytes_len" => {
+                validate_args!(args, 1, "bytes_len");
+                match &args[0] {
+                    Value::Bytes(bytes) => Ok(Some(Value::Int(bytes.len() as i64))),
+                    _ => Err(InterpError {
+                        message: "bytes_len: expected Bytes".to_string(),
+                    }),
+                }
+            }
+            "bytes_get" => {
+                validate_args!(args, 2, "bytes_get");
+                match (&args[0], &args[1]) {
+                    (Value::Bytes(bytes), Value::Int(i)) => usize::try_from(*i)
+                        .ok()
+                        .and_then(|i| bytes.get(i))
+                        .map(|&b| Some(Value::Int(b as i64)))
+                        .ok_or_else(|| InterpError::index_out_of_bounds(*i as usize, bytes.len())),
+                    _ => Err(InterpError {
+                        message: "bytes_get: expected (Bytes, Int)".to_string(),
+                    }),
+                }
+            }
+
             // ===== String operations =====
+            "str_index" => {
+                validate_args!(args, 2, "str_index");
+                let s = match &args[0] {
+                    Value::Ref(inner) => inner.as_ref(),
+                    other => other,
+                };
+                match (s, &args[1]) {
+                    (Value::Str(s), Value::Int(i)) => usize::try_from(*i)
+                        .ok()
+                        .and_then(|i| s.chars().nth(i))
+                        .map(|c| Some(Value::Char(c)))
+                        .ok_or_else(|| {
+                            InterpError::index_out_of_bounds(*i as usize, s.chars().count())
+                        }),
+                    _ => Err(InterpError {
+                        message: "str_index: expected (Str, Int)".to_string(),
+                    }),
+                }
+            }
             "str_len" => {
                 validate_args!(args, 1, "str_len");
                 let s = match &args[0] {
@@ -3579,6 +3669,7 @@ impl Interpreter {
                     Value::Map(_) => "Map",
                     Value::Closure { .. } => "Closure",
                     Value::Json(_) => "Json",
+                    Value::Bytes(_) => "Bytes",
                     Value::Task(_) => "Task",
                     Value::Future(_) => "Future",
                     Value::Channel(_) => "Channel",
@@ -3709,6 +3800,16 @@ impl Interpreter {
                     }),
                 }
             }
+            "select" => {
+                validate_args!(args, 3, "select");
+                // Both operands were evaluated by the caller; just pick one
+                match &args[0] {
+                    Value::Bool(c) => Ok(Some(if *c { args[1].clone() } else { args[2].clone() })),
+                    _ => Err(InterpError {
+                        message: "select: expected Bool condition".to_string(),
+                    }),
+                }
+            }
             "is_some" => {
                 validate_args!(args, 1, "is_some");
                 // is_some(opt) - returns true if Some, false if None
@@ -3952,9 +4053,26 @@ impl Interpreter {
             // ===== CLI support =====
             "args" => {
                 // args() -> [Str] - command line arguments
-                let args: Vec<Value> = std::env::args().map(Value::Str).collect();
+                let args: Vec<Value> = self.program_args.iter().cloned().map(Value::Str).collect();
                 Ok(Some(Value::Array(args)))
             }
+            "args_count" => Ok(Some(Value::Int(self.program_args.len() as i64))),
+            "args_get" => {
+                validate_args!(args, 1, "args_get");
+                // args_get(i: Int) -> Str, empty when out of range like the
+                // native runtime's null string
+                let arg = match &args[0] {
+                    Value::Int(i) => usize::try_from(*i)
+                        .ok()
+                        .and_then(|i| self.program_args.get(i).cloned()),
+                    _ => {
+                        return Err(InterpError {
+                            message: "args_get: expected Int".to_string(),
+                        });
+                    }
+                };
+                Ok(Some(Value::Str(arg.unwrap_or_default())))
+            }
             "env_get" => {
                 validate_args!(args, 1, "env_get");
                 self.require_capability("env", "env_get")?;
@@ -7276,7 +7394,6 @@ impl Interpreter {
             // ===== TCP/UDP Socket builtins =====
             "tcp_connect" => {
                 validate_args!(args, 2, "tcp_connect");
-                self.require_capability("network", "tcp_connect")?;
                 // tcp_connect(host: Str, port: Int) -> Result[TcpStream, Str]
                 let host = match &args[0] {
                     Value::Str(s) => s.clone(),
@@ -7295,6 +7412,7 @@ impl Interpreter {
                     }
                 };
                 let addr = format!("{}:{}", host, port);
+                self.require_network_address(&addr)?;
                 match std::net::TcpStream::connect(&addr) {
                     Ok(stream) => {
                         let id = self.next_tcp_stream_id;
@@ -11242,6 +11360,11 @@ impl Interpreter {
                     captures: capture_vals,
                 })
             }
+            // A function value is a closure with nothing captured
+            Rvalue::FnRef(name) => Ok(Value::Closure {
+                func_name: name.clone(),
+                captures: vec![],
+            }),
         }
     }
 
@@ -11314,10 +11437,11 @@ impl Interpreter {
         match c {
             Constant::Unit => Value::Unit,
             Constant::Bool(b) => Value::Bool(*b),
-            Constant::Int(n) => Value::Int(*n),
+            Constant::Int(n, _) => Value::Int(*n),
             Constant::Float(n) => Value::Float(*n),
             Constant::Char(c) => Value::Char(*c),
             Constant::Str(s) => Value::Str(s.clone()),
+            Constant::Bytes(bytes) => Value::Bytes(bytes.clone()),
         }
     }
 
@@ -11359,6 +11483,9 @@ impl Interpreter {
             // Bool to int
             (Value::Bool(b), ty) if ty.is_integer() => Ok(Value::Int(if *b { 1 } else { 0 })),
 
+            // Int to bool
+            (Value::Int(n), Ty::Bool) => Ok(Value::Bool(*n != 0)),
+
             // Char to int
             (Value::Char(c), ty) if ty.is_integer() => Ok(Value::Int(*c as i64)),
 
@@ -11382,7 +11509,11 @@ impl Interpreter {
                         message: "division by zero".to_string(),
                     })
                 } else {
-                    Ok(Value::Int(a / b))
+                    a.checked_div(*b)
+                        .map(Value::Int)
+                        .ok_or_else(|| InterpError {
+                            message: "integer overflow in division".to_string(),
+                        })
                 }
             }
             (BinOp::Rem, Value::Int(a), Value::Int(b)) => {
@@ -11391,7 +11522,11 @@ impl Interpreter {
                         message: "remainder by zero".to_string(),
                     })
                 } else {
-                    Ok(Value::Int(a % b))
+                    a.checked_rem(*b)
+                        .map(Value::Int)
+                        .ok_or_else(|| InterpError {
+                            message: "integer overflow in remainder".to_string(),
+                        })
                 }
             }
 
@@ -11516,12 +11651,110 @@ mod tests {
         assert_eq!(result, Value::Int(14));
     }
 
+    #[test]
+    fn test_inferred_let_bindings() {
+        let source = r#"f main() -> Int
+    x := 1 + 2
+    flag := x < 5
+    items: [Int] := []
+    if flag then x + vec_len(items) else 0"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(3));
+    }
+
     #[test]
     fn test_comparison() {
         let result = run_source("f main() -> Bool = 5 > 3").unwrap();
         assert_eq!(result, Value::Bool(true));
     }
 
+    #[test]
+    fn test_logical_ops_short_circuit() {
+        let source = r#"f boom() -> Bool
+    panic("right operand must not be evaluated")
+    true
+
+f main() -> Int
+    a := false && boom()
+    b := true || boom()
+    if a || !b then 1 else 0"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(0));
+    }
+
+    #[test]
+    fn test_if_expression_binding() {
+        let source = r#"f max(a: Int, b: Int) -> Int
+    m := if a > b then a else b
+    m
+
+f main() -> Int
+    max(3, 9) * 10 + max(4, -2)"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(94));
+    }
+
+    #[test]
+    fn test_for_inclusive_range() {
+        let source = r#"f main() -> Int
+    total := 0
+    for i in 1..=5
+        total = total + i
+    total"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(15));
+    }
+
+    #[test]
+    fn test_while_break() {
+        let source = r#"f main() -> Int
+    i := 0
+    wh true
+        if i == 3 then br
+        i = i + 1
+    i"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn test_while_continue() {
+        // Sum of the odd numbers in 1..=9
+        let source = r#"f main() -> Int
+    i := 0
+    total := 0
+    wh i < 9
+        i = i + 1
+        if i % 2 == 0 then ct
+        total = total + i
+    total"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(25));
+    }
+
+    #[test]
+    fn test_user_enum_match() {
+        let source = r#"e Shape
+    Circle(Int)
+    Rect(Int, Int)
+
+f radius(s: Shape) -> Int
+    m s
+        Circle(r) -> r
+        Rect(w, h) -> 0
+
+f main() -> Int
+    radius(Circle(5)) + radius(Rect(2, 3))"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn test_match_int_literals() {
+        let source = r#"f name(n: Int) -> Int
+    m n
+        1 -> 10
+        2 -> 20
+        _ -> 0
+
+f main() -> Int
+    name(1) + name(2) * 10 + name(7)"#;
+        assert_eq!(run_source(source).unwrap(), Value::Int(210));
+    }
+
     #[test]
     fn test_if_expression() {
         let result = run_source("f main() -> Int = if 5 > 3 then 1 else 0").unwrap();
@@ -12480,6 +12713,146 @@ f main() -> Int = same_items([3, 1, 3, 2]).len()
         assert_eq!(result, Value::Int(4));
     }
 
+    /// Run with contracts lowered into MIR checks and the interpreter's own
+    /// contract evaluation turned off, as compiled code sees them.
+    fn run_with_lowered_contracts(source: &str, check: bool) -> Result<Value, String> {
+        let (tokens, _) = Scanner::new(source).scan_all();
+        let ast = Parser::new(&tokens)
+            .parse()
+            .map_err(|e| format!("{:?}", e))?;
+        let program = Lowerer::new()
+            .with_contract_checks(check)
+            .lower(&ast)
+            .map_err(|e| format!("{:?}", e))?;
+        let mut interp = Interpreter::new(program).map_err(|e| e.message)?;
+        interp.set_check_contracts(false);
+        interp.run("main", &[]).map_err(|e| e.message)
+    }
+
+    #[test]
+    fn test_lowered_precondition_fires() {
+        let source = r#"
+@pre(n > 0, "n must be positive")
+f countdown(n: Int) -> Int = n - 1
+
+f main() -> Int = countdown(0)
+"#;
+        let err = run_with_lowered_contracts(source, true).unwrap_err();
+        assert!(
+            err.contains(
+                "Contract violation in 'countdown': n must be positive (condition: n > 0)"
+            ),
+            "unexpected error: {}",
+            err
+        );
+        assert_eq!(
+            run_with_lowered_contracts(source, false),
+            Ok(Value::Int(-1))
+        );
+    }
+
+    #[test]
+    fn test_lowered_postcondition_with_old_and_result() {
+        let ok = r#"
+@post(result == old(x) + 1)
+f inc(x: Int) -> Int
+    x = x + 1
+    x
+
+f main() -> Int = inc(41)
+"#;
+        assert_eq!(run_with_lowered_contracts(ok, true), Ok(Value::Int(42)));
+
+        let bad = r#"
+@post(result > old(x))
+f clamp(x: Int) -> Int
+    if x > 10 then
+        ret 10
+    x + 1
+
+f main() -> Int = clamp(20)
+"#;
+        let err = run_with_lowered_contracts(bad, true).unwrap_err();
+        assert!(
+            err.contains("postcondition failed (condition: result > old(x))"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_lowered_old_snapshots_struct_field() {
+        let template = r#"
+s Counter
+    count: Int
+
+@post(result.count == old(c.count) + 1)
+f incr(c: Counter) -> Counter
+    Counter { count: c.count + STEP }
+
+f main() -> Int = incr(Counter { count: 1 }).count
+"#;
+        let correct = template.replace("STEP", "1");
+        assert_eq!(
+            run_with_lowered_contracts(&correct, true),
+            Ok(Value::Int(2))
+        );
+        assert_eq!(run_source(&correct), Ok(Value::Int(2)));
+
+        let buggy = template.replace("STEP", "2");
+        let err = run_with_lowered_contracts(&buggy, true).unwrap_err();
+        assert!(
+            err.contains("condition: result.count == old(c.count) + 1"),
+            "unexpected error: {}",
+            err
+        );
+        assert!(
+            run_source(&buggy)
+                .unwrap_err()
+                .contains("Contract violation")
+        );
+    }
+
+    #[test]
+    fn test_loop_invariant_violated_on_third_iteration() {
+        let source = r#"
+f main() -> Int
+    i := 0
+    @invariant(i < 2, "i stays below 2")
+    wh i < 5
+        i = i + 1
+    i
+"#;
+        let err = run_source(source).unwrap_err();
+        assert!(
+            err.contains("Contract violation in 'main': i stays below 2 (condition: i < 2)"),
+            "unexpected error: {}",
+            err
+        );
+
+        let (tokens, _) = Scanner::new(source).scan_all();
+        let ast = Parser::new(&tokens).parse().unwrap();
+        let program = Lowerer::new()
+            .with_invariant_checks(false)
+            .lower(&ast)
+            .unwrap();
+        let mut interp = Interpreter::new(program).unwrap();
+        assert_eq!(interp.run("main", &[]).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn test_for_loop_invariant_holds() {
+        let source = r#"
+f main() -> Int
+    total := 0
+    @invariant(total == k * (k - 1) / 2)
+    for k in 0..5
+        total = total + k
+    total
+"#;
+        assert_eq!(run_source(source), Ok(Value::Int(10)));
+    }
+
     #[test]
     fn test_contract_tuple_field_out_of_bounds_error() {
         let source = r#"
@@ -13987,6 +14360,68 @@ f main() -> Int
         );
     }
 
+    #[test]
+    fn test_extern_call_needs_compiled_build() {
+        let err = run_source("extern \"C\" f labs(x: Int) -> Int\n\nf main() -> Int = labs(-3)\n")
+            .unwrap_err();
+        assert!(
+            err.contains("extern function 'labs' can only be called from a compiled build"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_function_stored_in_local() {
+        let result = run_source(
+            r#"
+f double(x: Int) -> Int = x * 2
+
+f apply(g: (Int) -> Int, v: Int) -> Int = g(v)
+
+f main() -> Int
+    g := double
+    g(21) + apply(double, 5)
+"#,
+        )
+        .unwrap();
+        assert_eq!(result, Value::Int(52));
+    }
+
+    #[test]
+    fn test_default_and_named_arguments() {
+        let result = run_source(
+            r#"
+f range_sum(start: Int, stop: Int, step: Int = 1) -> Int
+    total := 0
+    i := start
+    wh i < stop
+        total := total + i
+        i := i + step
+    total
+
+f main() -> Int
+    a := range_sum(0, 5)
+    b := range_sum(stop: 10, start: 0, step: 2)
+    c := range_sum(0, step: 3, stop: 9)
+    a * 10000 + b * 100 + c
+"#,
+        )
+        .unwrap();
+        assert_eq!(result, Value::Int(10 * 10000 + 20 * 100 + 9));
+    }
+
+    #[test]
+    fn test_int_division_overflow_is_an_error() {
+        let source = "f divide(a: Int, b: Int) -> Int = a / b\n\nf main() -> Int\n    divide(-9223372036854775807 - 1, -1)\n";
+        let err = run_source(source).unwrap_err();
+        assert!(err.contains("integer overflow in division"), "{}", err);
+
+        let source = "f rem(a: Int, b: Int) -> Int = a % b\n\nf main() -> Int\n    rem(-9223372036854775807 - 1, -1)\n";
+        let err = run_source(source).unwrap_err();
+        assert!(err.contains("integer overflow in remainder"), "{}", err);
+    }
+
     #[test]
     fn test_file_write_bytes_negative_value() {
         let program = Program::new();
@@ -3,20 +3,43 @@
 //! This module transforms the typed AST into MIR, which is a simpler
 //! representation that's easier to interpret and compile.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 
 use crate::lexer::Span;
 use crate::parser::{
     BinOp as AstBinOp, Block as AstBlock, ElseBranch, Expr, ExprKind, FnBody,
     Function as AstFunction, IfBranch, Item, ItemKind, Literal, LiteralKind, Pattern, PatternKind,
-    SourceFile, StmtKind, UnaryOp as AstUnaryOp,
+    SourceFile, StmtKind, UnaryOp as AstUnaryOp, Visibility, bind_call_args,
 };
 use crate::types::Ty;
 
+use super::capability::{builtin_capability, capability_flag, capability_uses};
+use super::const_eval::ConstEvaluator;
 use super::mir::{
-    BinOp, BlockId, Constant, Function, Local, MirContract, Mutability, Operand, PassMode, Program,
-    Rvalue, Statement, StatementKind, Terminator, UnOp,
+    BinOp, BlockId, CallConv, Constant, ExternFunction, Function, Global, Local, MirContract,
+    Mutability, Operand, PassMode, Program, Rvalue, Statement, StatementKind, Terminator, UnOp,
 };
+use super::optimize::{fold_binop, fold_unop};
+
+/// The string argument of attribute `@name("...")` on `item`, if present.
+fn attr_string(item: &Item, name: &str) -> Option<String> {
+    item.attrs
+        .iter()
+        .find(|a| a.name.name == name)
+        .and_then(|a| a.args.first())
+        .and_then(|arg| match arg.expr.as_deref() {
+            Some(Expr {
+                kind:
+                    ExprKind::Literal(Literal {
+                        kind: LiteralKind::String(value),
+                        ..
+                    }),
+                ..
+            }) => Some(value.clone()),
+            _ => None,
+        })
+}
 
 /// Convert AST PassMode to MIR PassMode.
 fn lower_pass_mode(ast_mode: crate::parser::PassMode) -> PassMode {
@@ -72,10 +95,35 @@ pub struct Lowerer {
     closure_counter: u32,
     /// Function default parameter expressions: fn_name -> list of defaults (None if no default)
     fn_defaults: HashMap<String, Vec<Option<Expr>>>,
+    /// Parameter names of top-level functions, for binding named arguments
+    fn_param_names: HashMap<String, Vec<String>>,
     /// Method to qualified name mapping: method_name -> list of qualified names (Type::method)
     impl_methods: HashMap<String, Vec<String>>,
     /// Function return types for proper call type inference
     fn_return_types: HashMap<String, Ty>,
+    /// Signatures of top-level functions, for referring to them as values
+    fn_types: HashMap<String, Ty>,
+    /// Values of top-level constants, substituted where they are used
+    consts: HashMap<String, Constant>,
+    /// Names of `const f` functions, which constant initializers may call
+    const_fns: HashSet<String>,
+    /// Type aliases: name -> (type parameter names, target type)
+    type_aliases: HashMap<String, (Vec<String>, crate::parser::Type)>,
+    /// Aliases being expanded by `lower_type`, to stop on recursive ones
+    expanding_aliases: RefCell<Vec<String>>,
+    /// Span of the source statement being lowered, attached to emitted MIR
+    current_span: Option<Span>,
+    /// Whether to lower @pre/@post contracts into runtime checks
+    contract_checks: bool,
+    /// Whether to lower loop @invariants into runtime checks
+    invariant_checks: bool,
+    /// Whether to keep calls to the `assert` builtin
+    assertions: bool,
+    /// Entry snapshots of `old(...)` arguments, keyed by the argument's span
+    old_values: HashMap<(usize, usize), Local>,
+    /// Granted capabilities; when set, calls to builtins needing any other
+    /// capability are lowering errors
+    capabilities: Option<HashSet<String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -103,9 +151,116 @@ impl Lowerer {
             fn_defaults: HashMap::new(),
             impl_methods: HashMap::new(),
             fn_return_types: HashMap::new(),
+            fn_types: HashMap::new(),
+            consts: HashMap::new(),
+            const_fns: HashSet::new(),
+            type_aliases: HashMap::new(),
+            expanding_aliases: RefCell::new(Vec::new()),
+            fn_param_names: HashMap::new(),
+            current_span: None,
+            contract_checks: false,
+            invariant_checks: true,
+            assertions: true,
+            old_values: HashMap::new(),
+            capabilities: None,
+        }
+    }
+
+    /// Lower @pre/@post contracts into runtime checks that panic on failure.
+    ///
+    /// The interpreter evaluates contracts itself, so this is only needed
+    /// for compiled code.
+    pub fn with_contract_checks(mut self, enabled: bool) -> Self {
+        self.contract_checks = enabled;
+        self
+    }
+
+    /// Lower loop @invariants into runtime checks (on by default). Unlike
+    /// @pre/@post, the interpreter relies on these checks too.
+    pub fn with_invariant_checks(mut self, enabled: bool) -> Self {
+        self.invariant_checks = enabled;
+        self
+    }
+
+    /// Keep `assert` calls (on by default). When disabled they are dropped
+    /// along with their arguments, independently of contract checks.
+    pub fn with_assertions(mut self, enabled: bool) -> Self {
+        self.assertions = enabled;
+        self
+    }
+
+    /// Reject calls to capability-gated builtins unless their capability is
+    /// in `granted` (`"all"` grants every capability). Off by default, leaving
+    /// the checks to the interpreter at run time.
+    pub fn with_capabilities(mut self, granted: HashSet<String>) -> Self {
+        self.capabilities = Some(granted);
+        self
+    }
+
+    /// Report every call to a builtin whose capability has not been granted.
+    fn check_capabilities(&mut self) {
+        let Some(granted) = &self.capabilities else {
+            return;
+        };
+        if granted.contains("all") {
+            return;
+        }
+        // A `"network:host:port"` grant lets `tcp_connect` reach that address;
+        // whether a call stays within it is only known at run time
+        let scoped_network = granted.iter().any(|g| g.starts_with("network:"));
+        for found in capability_uses(&self.program) {
+            if granted.contains(found.capability)
+                || (scoped_network && found.builtin == "tcp_connect")
+            {
+                continue;
+            }
+            self.errors.push(LowerError {
+                message: format!(
+                    "capability '{}' required for operation '{}' (pass {})",
+                    found.capability,
+                    found.builtin,
+                    capability_flag(found.capability)
+                ),
+                span: found.span.unwrap_or_default(),
+            });
         }
     }
 
+    /// Reject operations a const function cannot perform at compile time:
+    /// calls to anything but other const functions, including IO and
+    /// capability-gated builtins, indirect calls, and tasks.
+    fn check_const_fn(&mut self, name: &str) {
+        let Some(func) = self.program.functions.get(name) else {
+            return;
+        };
+        let mut errors = Vec::new();
+        for block in &func.blocks {
+            let message = match &block.terminator {
+                Some(Terminator::Call { func: callee, .. }) if !self.const_fns.contains(callee) => {
+                    match builtin_capability(callee) {
+                        Some(capability) => format!(
+                            "const fn `{}` cannot call `{}`, which requires capability '{}'",
+                            name, callee, capability
+                        ),
+                        None => format!("const fn `{}` cannot call non-const `{}`", name, callee),
+                    }
+                }
+                Some(Terminator::CallIndirect { .. }) => {
+                    format!("const fn `{}` cannot call function values", name)
+                }
+                Some(Terminator::Spawn { .. } | Terminator::Await { .. }) => {
+                    format!("const fn `{}` cannot spawn or await tasks", name)
+                }
+                _ => continue,
+            };
+            errors.push(LowerError {
+                message,
+                span: block.terminator_span.or(func.span).unwrap_or_default(),
+            });
+        }
+        self.errors.extend(errors);
+    }
+
     /// Get the current block ID, returning an error if none is set.
     fn current_block_id(&self) -> Result<BlockId, LowerError> {
         self.current_block.ok_or_else(|| LowerError {
@@ -163,12 +318,96 @@ impl Lowerer {
                     self.program
                         .enum_variants
                         .insert((enum_name.clone(), variant.name.name.clone()), idx);
+                    let payload = self
+                        .program
+                        .enum_payload_sizes
+                        .entry(enum_name.clone())
+                        .or_insert(0);
+                    *payload = (*payload).max(field_count);
+                }
+            }
+        }
+
+        // Type aliases expand to their targets wherever a type is lowered
+        for item in &source.items {
+            if let ItemKind::TypeAlias(t) = &item.kind
+                && let Some(target) = &t.ty
+            {
+                let params = t
+                    .generics
+                    .iter()
+                    .flat_map(|g| &g.params)
+                    .filter_map(|p| match p {
+                        crate::parser::GenericParam::Type(tp) => Some(tp.name.name.clone()),
+                        crate::parser::GenericParam::Const(_) => None,
+                    })
+                    .collect();
+                self.type_aliases
+                    .insert(t.name.name.clone(), (params, target.clone()));
+            }
+        }
+
+        // Collect function signatures so functions can be used as values
+        // before their definitions are lowered
+        for item in &source.items {
+            if let ItemKind::Function(f) = &item.kind {
+                let params = f.params.iter().map(|p| self.lower_type(&p.ty)).collect();
+                let ret = f
+                    .return_type
+                    .as_ref()
+                    .map(|t| self.lower_type(t))
+                    .unwrap_or(Ty::Unit);
+                self.fn_types
+                    .insert(f.name.name.clone(), Ty::Fn(params, Box::new(ret)));
+                self.fn_param_names.insert(
+                    f.name.name.clone(),
+                    f.params.iter().map(|p| p.name.name.clone()).collect(),
+                );
+                let defaults: Vec<Option<Expr>> =
+                    f.params.iter().map(|p| p.default.clone()).collect();
+                if defaults.iter().any(|d| d.is_some()) {
+                    self.fn_defaults.insert(f.name.name.clone(), defaults);
+                }
+                if f.is_const {
+                    self.const_fns.insert(f.name.name.clone());
+                }
+            }
+        }
+
+        // Lower const functions and evaluate constants in source order, so
+        // each constant may use the constants and const functions above it
+        for item in &source.items {
+            if let ItemKind::Function(f) = &item.kind
+                && f.is_const
+            {
+                self.lower_item(item);
+                self.check_const_fn(&f.name.name);
+                self.vars.clear();
+            }
+            if let ItemKind::Const(c) = &item.kind {
+                match self.eval_const(&c.value) {
+                    Ok(value) => {
+                        self.consts.insert(c.name.name.clone(), value.clone());
+                        self.program.globals.insert(
+                            c.name.name.clone(),
+                            Global {
+                                name: c.name.name.clone(),
+                                value,
+                            },
+                        );
+                    }
+                    Err((message, span)) => {
+                        self.error(format!("constant `{}`: {}", c.name.name, message), span)
+                    }
                 }
             }
         }
 
         // Second pass: lower items (functions, impls, etc.)
         for item in &source.items {
+            if matches!(&item.kind, ItemKind::Function(f) if f.is_const) {
+                continue;
+            }
             self.lower_item(item);
         }
 
@@ -177,6 +416,8 @@ impl Lowerer {
             self.program.entry = Some("main".to_string());
         }
 
+        self.check_capabilities();
+
         if self.errors.is_empty() {
             Ok(self.program)
         } else {
@@ -184,10 +425,47 @@ impl Lowerer {
         }
     }
 
+    /// The calling convention from an item's `@callconv`, C by default. The
+    /// parser has already rejected unknown names.
+    fn lower_call_conv(&self, item: &Item) -> CallConv {
+        attr_string(item, "callconv")
+            .and_then(|name| CallConv::from_name(&name))
+            .unwrap_or_default()
+    }
+
     fn lower_item(&mut self, item: &Item) {
         match &item.kind {
+            ItemKind::Function(f) if f.abi.is_some() => {
+                let extern_fn = ExternFunction {
+                    name: f.name.name.clone(),
+                    params: f.params.iter().map(|p| self.lower_type(&p.ty)).collect(),
+                    return_ty: f
+                        .return_type
+                        .as_ref()
+                        .map(|t| self.lower_type(t))
+                        .unwrap_or(Ty::Unit),
+                    call_conv: self.lower_call_conv(item),
+                };
+                self.program
+                    .externs
+                    .insert(extern_fn.name.clone(), extern_fn);
+            }
             ItemKind::Function(f) => {
-                if let Some(mir_fn) = self.lower_function(f) {
+                if let Some(mut mir_fn) = self.lower_function(f) {
+                    mir_fn.no_mangle = item.attrs.iter().any(|a| a.name.name == "no_mangle");
+                    mir_fn.section = attr_string(item, "section");
+                    mir_fn.exported = match attr_string(item, "visibility").as_deref() {
+                        Some(visibility) => visibility == "default",
+                        None => mir_fn.no_mangle && f.visibility == Visibility::Public,
+                    };
+                    mir_fn.is_const = f.is_const;
+                    mir_fn.call_conv = self.lower_call_conv(item);
+                    if mir_fn.name == "main" && mir_fn.call_conv != CallConv::C {
+                        self.error(
+                            "`main` must use the C calling convention".to_string(),
+                            item.span,
+                        );
+                    }
                     self.program.functions.insert(mir_fn.name.clone(), mir_fn);
                 }
             }
@@ -195,7 +473,7 @@ impl Lowerer {
                 // Lower methods in impl block
                 for impl_item in &impl_block.items {
                     if let crate::parser::ImplItem::Function(f) = impl_item
-                        && let Some(mir_fn) = self.lower_function(f)
+                        && let Some(mut mir_fn) = self.lower_function(f)
                     {
                         // Use qualified name for methods
                         let qualified_name = format!(
@@ -208,6 +486,7 @@ impl Lowerer {
                             .entry(mir_fn.name.clone())
                             .or_default()
                             .push(qualified_name.clone());
+                        mir_fn.name = qualified_name.clone();
                         self.program.functions.insert(qualified_name, mir_fn);
                     }
                 }
@@ -251,6 +530,9 @@ impl Lowerer {
                 crate::parser::ast::LiteralKind::Int(n) => n.to_string(),
                 crate::parser::ast::LiteralKind::Float(f) => f.to_string(),
                 crate::parser::ast::LiteralKind::String(s) => format!("\"{}\"", s),
+                crate::parser::ast::LiteralKind::Bytes(bytes) => {
+                    crate::parser::ast::byte_string_source(bytes)
+                }
                 crate::parser::ast::LiteralKind::Char(c) => format!("'{}'", c),
                 crate::parser::ast::LiteralKind::Bool(b) => b.to_string(),
                 crate::parser::ast::LiteralKind::None => "None".to_string(),
@@ -286,6 +568,7 @@ impl Lowerer {
             }
             ExprKind::Unary(op, operand) => {
                 let op_str = match op {
+                    UnaryOp::Plus => "+",
                     UnaryOp::Neg => "-",
                     UnaryOp::Not => "!",
                     UnaryOp::Ref => "&",
@@ -400,12 +683,16 @@ impl Lowerer {
 
         // Create function
         let mut mir_fn = Function::new(f.name.name.clone(), vec![], return_ty.clone());
+        mir_fn.span = Some(f.span);
+        self.current_span = Some(f.span);
 
         // Add parameters
         for param in &f.params {
             let ty = self.lower_type(&param.ty);
             let local = mir_fn.add_local(ty.clone(), Some(param.name.name.clone()));
             mir_fn.params.push((local, ty.clone()));
+            self.var_full_types
+                .insert(param.name.name.clone(), ty.clone());
             mir_fn.param_names.push((param.name.name.clone(), ty));
             mir_fn
                 .param_pass_modes
@@ -420,12 +707,26 @@ impl Lowerer {
         self.current_fn = Some(mir_fn);
         self.current_block = Some(entry);
 
+        if self.contract_checks {
+            for contract in &f.preconditions {
+                self.lower_contract_check(&f.name.name, contract, "precondition failed");
+            }
+            self.snapshot_old_values(f);
+        }
+
         // Lower body
         let result = match body {
             FnBody::Expr(expr) => self.lower_expr(expr),
             FnBody::Block(block) => self.lower_block(block),
         };
 
+        // The implicit return belongs to the function's tail statement
+        if let FnBody::Block(block) = body
+            && let Some(last) = block.stmts.last()
+        {
+            self.current_span = Some(last.span);
+        }
+
         // Add return
         if let Some(result) = result {
             let block = self.current_block_id().ok()?;
@@ -451,6 +752,10 @@ impl Lowerer {
             }
         }
 
+        if self.contract_checks {
+            self.lower_postcondition_checks(f, &return_ty);
+        }
+
         // Copy contracts from AST to MIR
         let mut mir_fn = self.current_fn.take()?;
         for contract in &f.preconditions {
@@ -472,11 +777,157 @@ impl Lowerer {
         Some(mir_fn)
     }
 
+    /// Whether a contract can be lowered to MIR. Quantifiers are only
+    /// understood by the interpreter's contract evaluator.
+    fn is_lowerable_contract(&self, contract: &crate::parser::Contract) -> bool {
+        let text = self.expr_to_string(&contract.condition);
+        !text.contains("forall ") && !text.contains("exists ")
+    }
+
+    /// Branch on a contract condition, panicking with the same message the
+    /// interpreter reports when it is false.
+    fn lower_contract_check(
+        &mut self,
+        fn_name: &str,
+        contract: &crate::parser::Contract,
+        default_message: &str,
+    ) {
+        if !self.is_lowerable_contract(contract) {
+            return;
+        }
+        let saved_span = self.current_span;
+        self.current_span = Some(contract.condition.span);
+        let Some(cond) = self.lower_expr(&contract.condition) else {
+            self.current_span = saved_span;
+            return;
+        };
+
+        let ok_block = self.new_block();
+        let fail_block = self.new_block();
+        let dead_block = self.new_block();
+        self.terminate(Terminator::If {
+            cond,
+            then_block: ok_block,
+            else_block: fail_block,
+        });
+
+        let pattern = contract
+            .pattern_name
+            .as_ref()
+            .map(|p| format!(" [@{}]", p))
+            .unwrap_or_default();
+        let message = format!(
+            "Contract violation{} in '{}': {} (condition: {})",
+            pattern,
+            fn_name,
+            contract.message.as_deref().unwrap_or(default_message),
+            self.expr_to_string(&contract.condition)
+        );
+        self.current_block = Some(fail_block);
+        self.terminate(Terminator::Call {
+            func: "panic".to_string(),
+            args: vec![Operand::Constant(Constant::Str(message))],
+            arg_pass_modes: vec![],
+            dest: None,
+            next: dead_block,
+        });
+        self.current_block = Some(dead_block);
+        self.terminate(Terminator::Unreachable);
+
+        self.current_block = Some(ok_block);
+        self.current_span = saved_span;
+    }
+
+    /// Check a loop's `@invariant`s at the top of an iteration.
+    fn lower_loop_invariants(&mut self, invariants: &[crate::parser::Contract]) {
+        if !self.invariant_checks || invariants.is_empty() {
+            return;
+        }
+        let fn_name = match self.current_function() {
+            Ok(f) => f.name.clone(),
+            Err(_) => return,
+        };
+        for invariant in invariants {
+            self.lower_contract_check(&fn_name, invariant, "loop invariant failed");
+        }
+    }
+
+    /// Evaluate each `old(...)` argument of the @post contracts at entry
+    /// into a hidden local the postcondition check reads back.
+    fn snapshot_old_values(&mut self, f: &AstFunction) {
+        self.old_values.clear();
+        let mut old_args = Vec::new();
+        for contract in &f.postconditions {
+            if self.is_lowerable_contract(contract) {
+                contract.condition.collect_old_args(&mut old_args);
+            }
+        }
+        for arg in old_args {
+            let key = (arg.span.start, arg.span.end);
+            if self.old_values.contains_key(&key) {
+                continue;
+            }
+            let Some(value) = self.lower_expr(arg) else {
+                continue;
+            };
+            let snapshot = self.new_temp(self.operand_type(&value));
+            self.emit(StatementKind::Assign(snapshot, Rvalue::Use(value)));
+            self.old_values.insert(key, snapshot);
+        }
+    }
+
+    /// Route every return through a block that checks the postconditions,
+    /// with `result` bound to the returned value.
+    fn lower_postcondition_checks(&mut self, f: &AstFunction, return_ty: &Ty) {
+        if !f
+            .postconditions
+            .iter()
+            .any(|c| self.is_lowerable_contract(c))
+        {
+            return;
+        }
+        let result_local = self.new_local(return_ty.clone(), Some("result".to_string()));
+        let check_block = self.new_block();
+
+        let Ok(func) = self.current_function_mut() else {
+            return;
+        };
+        let mut returns_value = false;
+        for block in &mut func.blocks {
+            if let Some(Terminator::Return(value)) = &block.terminator {
+                if let Some(op) = value.clone() {
+                    returns_value = true;
+                    block.stmts.push(Statement {
+                        kind: StatementKind::Assign(result_local, Rvalue::Use(op)),
+                        span: block.terminator_span,
+                    });
+                }
+                block.terminator = Some(Terminator::Goto(check_block));
+            }
+        }
+
+        self.current_block = Some(check_block);
+        let saved_result = self.vars.insert("result".to_string(), result_local);
+        for contract in &f.postconditions {
+            self.lower_contract_check(&f.name.name, contract, "postcondition failed");
+        }
+        match saved_result {
+            Some(local) => self.vars.insert("result".to_string(), local),
+            None => self.vars.remove("result"),
+        };
+        self.old_values.clear();
+
+        let value = returns_value.then_some(Operand::Local(result_local));
+        self.terminate(Terminator::Return(value));
+    }
+
     fn lower_block(&mut self, block: &AstBlock) -> Option<Operand> {
         let mut last_value = None;
+        let saved_span = self.current_span;
 
         for (i, stmt) in block.stmts.iter().enumerate() {
             let is_last = i == block.stmts.len() - 1;
+            self.current_span = Some(stmt.span);
 
             match &stmt.kind {
                 StmtKind::Let(let_stmt) => {
@@ -491,6 +942,15 @@ impl Lowerer {
                         {
                             self.var_types.insert(ident.name.clone(), type_name);
                         }
+                        // An annotated binding takes its declared type
+                        if let Some(ty) = &let_stmt.ty
+                            && let PatternKind::Ident(ident, _, _) = &let_stmt.pattern.kind
+                            && !self.vars.contains_key(&ident.name)
+                        {
+                            let ty = self.lower_type(ty);
+                            let local = self.new_local(ty, Some(ident.name.clone()));
+                            self.vars.insert(ident.name.clone(), local);
+                        }
                         self.bind_pattern(&let_stmt.pattern, op);
                     }
                     last_value = None;
@@ -511,16 +971,24 @@ impl Lowerer {
             }
         }
 
+        self.current_span = saved_span;
         last_value
     }
 
     fn lower_expr(&mut self, expr: &Expr) -> Option<Operand> {
+        // Length and indexing of constant strings need no runtime call
+        if let Some(Ok(value)) = self.fold_str_query(expr) {
+            return Some(Operand::Constant(value));
+        }
+
         match &expr.kind {
             ExprKind::Literal(lit) => Some(Operand::Constant(self.lower_literal(lit))),
 
             ExprKind::Ident(ident) => {
                 if let Some(&local) = self.vars.get(&ident.name) {
                     Some(Operand::Local(local))
+                } else if let Some(value) = self.consts.get(&ident.name) {
+                    Some(Operand::Constant(value.clone()))
                 } else {
                     // Check if it's a unit enum variant (like None)
                     match ident.name.as_str() {
@@ -558,6 +1026,16 @@ impl Lowerer {
                                 return Some(Operand::Local(result));
                             }
 
+                            // A named function used as a value
+                            if let Some(fn_ty) = self.fn_types.get(&ident.name).cloned() {
+                                let result = self.new_temp(fn_ty);
+                                self.emit(StatementKind::Assign(
+                                    result,
+                                    Rvalue::FnRef(ident.name.clone()),
+                                ));
+                                return Some(Operand::Local(result));
+                            }
+
                             // Check for similar variable names to provide helpful suggestions
                             let similar = self.find_similar_name(&ident.name);
                             let msg = if let Some(suggestion) = similar {
@@ -661,6 +1139,8 @@ impl Lowerer {
             }
 
             ExprKind::Unary(op, operand) => match op {
+                // Unary plus is the identity
+                AstUnaryOp::Plus => self.lower_expr(operand),
                 AstUnaryOp::Neg => {
                     let operand_ty = self.infer_expr_type(operand);
                     let op = self.lower_expr(operand)?;
@@ -717,7 +1197,7 @@ impl Lowerer {
                 AstUnaryOp::Deref => {
                     let operand_ty = self.infer_expr_type(operand);
                     let inner_ty = match operand_ty {
-                        Ty::Ref(inner, _) => *inner,
+                        Ty::Ref(inner, _) | Ty::Ptr(inner, _) | Ty::RawPtr(inner) => *inner,
                         _ => Ty::Unit,
                     };
                     let op = self.lower_expr(operand)?;
@@ -728,12 +1208,45 @@ impl Lowerer {
             },
 
             ExprKind::Call(callee, args) => {
+                // Elided assertions evaluate nothing
+                if !self.assertions
+                    && let ExprKind::Ident(ident) = &callee.kind
+                    && ident.name == "assert"
+                    && !self.fn_types.contains_key("assert")
+                    && !self.vars.contains_key("assert")
+                {
+                    return Some(Operand::Constant(Constant::Unit));
+                }
+
+                // old(e) in a lowered @post reads the value snapshotted at entry
+                if let ExprKind::Ident(ident) = &callee.kind
+                    && ident.name == "old"
+                    && args.len() == 1
+                    && let Some(&snapshot) = self
+                        .old_values
+                        .get(&(args[0].value.span.start, args[0].value.span.end))
+                {
+                    return Some(Operand::Local(snapshot));
+                }
+
                 // Check if this is an enum constructor call like Some(x) or Ok(x)
                 if let ExprKind::Ident(ident) = &callee.kind {
                     let (is_enum, type_name, variant) = match ident.name.as_str() {
                         "Some" => (true, "Option".to_string(), "Some".to_string()),
                         "Ok" => (true, "Result".to_string(), "Ok".to_string()),
                         "Err" => (true, "Result".to_string(), "Err".to_string()),
+                        // User-defined tuple variant like Circle(5), unless shadowed
+                        // by a function or local of the same name
+                        name if !self.program.functions.contains_key(name)
+                            && !self.vars.contains_key(name) =>
+                        {
+                            match self.enum_variants.get(name) {
+                                Some((enum_name, count)) if *count > 0 => {
+                                    (true, enum_name.clone(), name.to_string())
+                                }
+                                _ => (false, String::new(), String::new()),
+                            }
+                        }
                         _ => (false, String::new(), String::new()),
                     };
 
@@ -816,18 +1329,85 @@ impl Lowerer {
                     _ => (false, None), // Expression that evaluates to closure
                 };
 
+                // `println` is an alias for `print`; with more than one
+                // argument the first is a format string
+                let func_name = match func_name {
+                    Some(name)
+                        if matches!(name.as_str(), "print" | "println")
+                            && !self.program.functions.contains_key(&name) =>
+                    {
+                        if args.len() > 1 {
+                            Some("__print_fmt".to_string())
+                        } else {
+                            Some("print".to_string())
+                        }
+                    }
+                    other => other,
+                };
+
                 // Lower arguments
                 let mut mir_args = Vec::new();
                 let mut mir_arg_pass_modes: Vec<PassMode> = Vec::new();
-                for arg in args {
-                    if let Some(op) = self.lower_expr(&arg.value) {
-                        mir_args.push(op);
-                        mir_arg_pass_modes.push(lower_pass_mode(arg.pass_mode));
+                let named = args.iter().any(|a| a.name.is_some());
+                if named
+                    && is_direct
+                    && let Some(fn_name_ref) = &func_name
+                    && let Some(params) = self.fn_param_names.get(fn_name_ref).cloned()
+                {
+                    // Evaluate arguments in source order, then place them in
+                    // parameter order, filling omitted ones from defaults
+                    let mut lowered = Vec::new();
+                    for arg in args {
+                        lowered.push(self.lower_expr(&arg.value));
+                    }
+                    match bind_call_args(fn_name_ref, &params, args) {
+                        Ok(slots) => {
+                            let defaults = self.fn_defaults.get(fn_name_ref).cloned();
+                            for (i, slot) in slots.into_iter().enumerate() {
+                                let (op, mode) = match slot {
+                                    Some(a) => (lowered[a].clone(), args[a].pass_mode),
+                                    None => {
+                                        let default = defaults
+                                            .as_ref()
+                                            .and_then(|d| d.get(i).cloned().flatten());
+                                        match default {
+                                            Some(default_expr) => (
+                                                self.lower_expr(&default_expr),
+                                                crate::parser::PassMode::Owned,
+                                            ),
+                                            None => {
+                                                self.error(
+                                                    format!(
+                                                        "missing argument for parameter '{}' of '{}'",
+                                                        params[i], fn_name_ref
+                                                    ),
+                                                    expr.span,
+                                                );
+                                                (None, crate::parser::PassMode::Owned)
+                                            }
+                                        }
+                                    }
+                                };
+                                if let Some(op) = op {
+                                    mir_args.push(op);
+                                    mir_arg_pass_modes.push(lower_pass_mode(mode));
+                                }
+                            }
+                        }
+                        Err((message, span)) => self.error(message, span),
+                    }
+                } else {
+                    for arg in args {
+                        if let Some(op) = self.lower_expr(&arg.value) {
+                            mir_args.push(op);
+                            mir_arg_pass_modes.push(lower_pass_mode(arg.pass_mode));
+                        }
                     }
                 }
 
                 // Fill in default arguments if needed
                 if is_direct
+                    && !named
                     && let Some(fn_name_ref) = &func_name
                     && let Some(defaults) = self.fn_defaults.get(fn_name_ref).cloned()
                 {
@@ -841,8 +1421,26 @@ impl Lowerer {
                     }
                 }
 
+                // Builtin assert reports where it failed
+                if func_name.as_deref() == Some("assert")
+                    && !self.fn_types.contains_key("assert")
+                    && !self.program.functions.contains_key("assert")
+                {
+                    let location = format!("at {}:{}", expr.span.line, expr.span.column);
+                    match mir_args.get_mut(1) {
+                        Some(Operand::Constant(Constant::Str(msg))) => {
+                            *msg = format!("{} ({})", msg, location);
+                        }
+                        Some(_) => {}
+                        None => mir_args.push(Operand::Constant(Constant::Str(location))),
+                    }
+                }
+
                 // Get return type for the function
-                let return_ty = if let Some(ref name) = func_name {
+                let return_ty = if func_name.as_deref() == Some("select") && mir_args.len() == 3 {
+                    // select[T] returns the type of its operands
+                    self.operand_type(&mir_args[1])
+                } else if let Some(ref name) = func_name {
                     self.get_function_return_type(name)
                 } else {
                     self.infer_expr_type(expr)
@@ -925,18 +1523,20 @@ impl Lowerer {
 
             ExprKind::Match(scrutinee, arms) => self.lower_match(scrutinee, arms, expr.span),
 
-            ExprKind::For(label, pattern, iter, body) => self.lower_for(
+            ExprKind::For(label, pattern, iter, body, invariants) => self.lower_for(
                 label.as_ref().map(|l| l.name.clone()),
                 pattern,
                 iter,
                 body,
+                invariants,
                 expr.span,
             ),
 
-            ExprKind::While(label, cond, body) => self.lower_while(
+            ExprKind::While(label, cond, body, invariants) => self.lower_while(
                 label.as_ref().map(|l| l.name.clone()),
                 cond,
                 body,
+                invariants,
                 expr.span,
             ),
 
@@ -1038,14 +1638,34 @@ impl Lowerer {
             ExprKind::Index(base, index) => {
                 // Infer element type from base array/string type
                 let base_ty = self.infer_expr_type(base);
-                let elem_ty = match base_ty {
-                    Ty::List(inner) => *inner,
+                let elem_ty = match &base_ty {
+                    Ty::List(inner) => (**inner).clone(),
                     Ty::Str => Ty::Char,
+                    Ty::Bytes => Ty::U8,
                     _ => Ty::Unit,
                 };
                 let base_op = self.lower_expr(base)?;
                 let index_op = self.lower_expr(index)?;
                 let result = self.new_temp(elem_ty);
+                let builtin = match base_ty {
+                    // b[i] and s[i] are the bounds-checked bytes_get and
+                    // str_index builtins
+                    Ty::Bytes => Some("bytes_get"),
+                    Ty::Str => Some("str_index"),
+                    _ => None,
+                };
+                if let Some(builtin) = builtin {
+                    let next_block = self.new_block();
+                    self.terminate(Terminator::Call {
+                        func: builtin.to_string(),
+                        args: vec![base_op, index_op],
+                        arg_pass_modes: vec![],
+                        dest: Some(result),
+                        next: next_block,
+                    });
+                    self.current_block = Some(next_block);
+                    return Some(Operand::Local(result));
+                }
                 self.emit(StatementKind::Assign(
                     result,
                     Rvalue::Index(base_op, index_op),
@@ -1236,6 +1856,7 @@ impl Lowerer {
                 // Create the lifted function with signature: fn(captures..., params...)
                 let mut params: Vec<(Local, Ty)> = Vec::new();
                 let mut new_fn = Function::new(func_name.clone(), vec![], return_ty.clone());
+                new_fn.span = Some(expr.span);
 
                 // Add captured variables as parameters first
                 for var_name in &free_vars {
@@ -1384,7 +2005,7 @@ impl Lowerer {
                         Rvalue::BinaryOp(
                             BinOp::Eq,
                             Operand::Copy(disc),
-                            Operand::Constant(Constant::Int(0)),
+                            Operand::Constant(Constant::Int(0, Ty::Int)),
                         ),
                     ));
                 } else {
@@ -1394,7 +2015,7 @@ impl Lowerer {
                         Rvalue::BinaryOp(
                             BinOp::Gt,
                             Operand::Copy(disc),
-                            Operand::Constant(Constant::Int(0)),
+                            Operand::Constant(Constant::Int(0, Ty::Int)),
                         ),
                     ));
                 }
@@ -1451,7 +2072,7 @@ impl Lowerer {
                     Rvalue::BinaryOp(
                         BinOp::Gt,
                         Operand::Copy(disc),
-                        Operand::Constant(Constant::Int(0)),
+                        Operand::Constant(Constant::Int(0, Ty::Int)),
                     ),
                 ));
                 self.terminate(Terminator::If {
@@ -1617,7 +2238,7 @@ impl Lowerer {
                         let exp_local = self.new_temp(Ty::Int);
                         self.emit(StatementKind::Assign(
                             exp_local,
-                            Rvalue::Use(Operand::Constant(Constant::Int(expected))),
+                            Rvalue::Use(Operand::Constant(Constant::Int(expected, Ty::Int))),
                         ));
                         let cond = self.new_temp(Ty::Bool);
                         self.emit(StatementKind::Assign(
@@ -1658,7 +2279,7 @@ impl Lowerer {
                         let exp_local = self.new_temp(Ty::Int);
                         self.emit(StatementKind::Assign(
                             exp_local,
-                            Rvalue::Use(Operand::Constant(Constant::Int(expected))),
+                            Rvalue::Use(Operand::Constant(Constant::Int(expected, Ty::Int))),
                         ));
                         let cond = self.new_temp(Ty::Bool);
                         self.emit(StatementKind::Assign(
@@ -1707,12 +2328,12 @@ impl Lowerer {
                 let start_val = if let Some(s) = start {
                     self.lower_expr(s)?
                 } else {
-                    Operand::Constant(Constant::Int(0))
+                    Operand::Constant(Constant::Int(0, Ty::Int))
                 };
                 let end_val = if let Some(e) = end {
                     self.lower_expr(e)?
                 } else {
-                    Operand::Constant(Constant::Int(i64::MAX))
+                    Operand::Constant(Constant::Int(i64::MAX, Ty::Int))
                 };
                 let incl_val = Operand::Constant(Constant::Bool(*inclusive));
 
@@ -1967,7 +2588,7 @@ impl Lowerer {
                         let expected_local = self.new_temp(Ty::Int);
                         self.emit(StatementKind::Assign(
                             expected_local,
-                            Rvalue::Use(Operand::Constant(Constant::Int(expected_disc))),
+                            Rvalue::Use(Operand::Constant(Constant::Int(expected_disc, Ty::Int))),
                         ));
 
                         let cond_local = self.new_temp(Ty::Bool);
@@ -2004,7 +2625,7 @@ impl Lowerer {
                     let lit_local = self.new_temp(Ty::Int);
                     self.emit(StatementKind::Assign(
                         lit_local,
-                        Rvalue::Use(Operand::Constant(Constant::Int(*n as i64))),
+                        Rvalue::Use(Operand::Constant(Constant::Int(*n as i64, Ty::Int))),
                     ));
                     let cond_local = self.new_temp(Ty::Bool);
                     self.emit(StatementKind::Assign(
@@ -2148,7 +2769,7 @@ impl Lowerer {
                     let expected = self.new_temp(Ty::Int);
                     self.emit(StatementKind::Assign(
                         expected,
-                        Rvalue::Use(Operand::Constant(Constant::Int(variant_disc))),
+                        Rvalue::Use(Operand::Constant(Constant::Int(variant_disc, Ty::Int))),
                     ));
 
                     let cond = self.new_temp(Ty::Bool);
@@ -2245,7 +2866,10 @@ impl Lowerer {
                                 let lit_local = self.new_temp(Ty::Int);
                                 self.emit(StatementKind::Assign(
                                     lit_local,
-                                    Rvalue::Use(Operand::Constant(Constant::Int(*n as i64))),
+                                    Rvalue::Use(Operand::Constant(Constant::Int(
+                                        *n as i64,
+                                        Ty::Int,
+                                    ))),
                                 ));
                                 let cond_local = self.new_temp(Ty::Bool);
                                 self.emit(StatementKind::Assign(
@@ -2280,7 +2904,10 @@ impl Lowerer {
                                     let exp = self.new_temp(Ty::Int);
                                     self.emit(StatementKind::Assign(
                                         exp,
-                                        Rvalue::Use(Operand::Constant(Constant::Int(exp_disc))),
+                                        Rvalue::Use(Operand::Constant(Constant::Int(
+                                            exp_disc,
+                                            Ty::Int,
+                                        ))),
                                     ));
                                     let cond = self.new_temp(Ty::Bool);
                                     self.emit(StatementKind::Assign(
@@ -2333,7 +2960,7 @@ impl Lowerer {
                         let start_val = self.new_temp(Ty::Int);
                         self.emit(StatementKind::Assign(
                             start_val,
-                            Rvalue::Use(Operand::Constant(Constant::Int(*n as i64))),
+                            Rvalue::Use(Operand::Constant(Constant::Int(*n as i64, Ty::Int))),
                         ));
                         let ge_cond = self.new_temp(Ty::Bool);
                         self.emit(StatementKind::Assign(
@@ -2356,7 +2983,7 @@ impl Lowerer {
                         let end_val = self.new_temp(Ty::Int);
                         self.emit(StatementKind::Assign(
                             end_val,
-                            Rvalue::Use(Operand::Constant(Constant::Int(*n as i64))),
+                            Rvalue::Use(Operand::Constant(Constant::Int(*n as i64, Ty::Int))),
                         ));
                         let cmp_op = if *inclusive { BinOp::Le } else { BinOp::Lt };
                         let le_cond = self.new_temp(Ty::Bool);
@@ -2484,7 +3111,17 @@ impl Lowerer {
             "Some" => 1,
             "Ok" => 0,
             "Err" => 1,
-            // For user-defined enums, use FNV-1a hash to avoid collisions
+            // User-defined enums use the variant's index in its declaration,
+            // matching the registry the interpreter and codegen read
+            _ if let Some(idx) = self.enum_variants.get(variant).and_then(|(enum_name, _)| {
+                self.program
+                    .enum_variants
+                    .get(&(enum_name.clone(), variant.to_string()))
+            }) =>
+            {
+                *idx as i64
+            }
+            // Unregistered variants fall back to an FNV-1a hash to avoid collisions
             // (ASCII sum would collide e.g., "ab" == "ba")
             _ => {
                 const FNV_OFFSET: u64 = 14695981039346656037;
@@ -2625,6 +3262,7 @@ impl Lowerer {
         pattern: &Pattern,
         iter: &Expr,
         body: &AstBlock,
+        invariants: &[crate::parser::Contract],
         _span: Span,
     ) -> Option<Operand> {
         // For loops can iterate over:
@@ -2636,10 +3274,9 @@ impl Lowerer {
             return self.lower_for_range(
                 label.clone(),
                 pattern,
-                start_opt,
-                end_opt,
-                *inclusive,
+                (start_opt, end_opt, *inclusive),
                 body,
+                invariants,
             );
         }
 
@@ -2662,7 +3299,7 @@ impl Lowerer {
         let idx_local = self.new_temp(Ty::Int);
         self.emit(StatementKind::Assign(
             idx_local,
-            Rvalue::Use(Operand::Constant(Constant::Int(0))),
+            Rvalue::Use(Operand::Constant(Constant::Int(0, Ty::Int))),
         ));
 
         // Get array length
@@ -2764,6 +3401,8 @@ impl Lowerer {
             }
         }
 
+        self.lower_loop_invariants(invariants);
+
         // Execute loop body
         self.lower_block(body);
 
@@ -2785,7 +3424,7 @@ impl Lowerer {
             Rvalue::BinaryOp(
                 BinOp::Add,
                 Operand::Copy(idx_local),
-                Operand::Constant(Constant::Int(1)),
+                Operand::Constant(Constant::Int(1, Ty::Int)),
             ),
         ));
         self.terminate(Terminator::Goto(cond_block));
@@ -2800,16 +3439,15 @@ impl Lowerer {
         &mut self,
         label: Option<String>,
         pattern: &Pattern,
-        start_opt: &Option<Box<Expr>>,
-        end_opt: &Option<Box<Expr>>,
-        inclusive: bool,
+        (start_opt, end_opt, inclusive): (&Option<Box<Expr>>, &Option<Box<Expr>>, bool),
         body: &AstBlock,
+        invariants: &[crate::parser::Contract],
     ) -> Option<Operand> {
         // Get the start value (default to 0 if not specified)
         let start_val = if let Some(start_expr) = start_opt {
             self.lower_expr(start_expr)?
         } else {
-            Operand::Constant(Constant::Int(0))
+            Operand::Constant(Constant::Int(0, Ty::Int))
         };
 
         // Get the end value (required for iteration)
@@ -2872,6 +3510,8 @@ impl Lowerer {
             self.vars.insert(ident.name.clone(), var_local);
         }
 
+        self.lower_loop_invariants(invariants);
+
         // Execute loop body
         self.lower_block(body);
 
@@ -2893,7 +3533,7 @@ impl Lowerer {
             Rvalue::BinaryOp(
                 BinOp::Add,
                 Operand::Copy(idx_local),
-                Operand::Constant(Constant::Int(1)),
+                Operand::Constant(Constant::Int(1, Ty::Int)),
             ),
         ));
         self.terminate(Terminator::Goto(cond_block));
@@ -2908,6 +3548,7 @@ impl Lowerer {
         label: Option<String>,
         cond: &Expr,
         body: &AstBlock,
+        invariants: &[crate::parser::Contract],
         _span: Span,
     ) -> Option<Operand> {
         let cond_block = self.new_block();
@@ -2925,8 +3566,9 @@ impl Lowerer {
         // Jump to condition check
         self.terminate(Terminator::Goto(cond_block));
 
-        // Condition block
+        // Condition block, preceded by the invariant checks
         self.current_block = Some(cond_block);
+        self.lower_loop_invariants(invariants);
         let cond_val = self.lower_expr(cond);
         if let Some(c) = cond_val {
             self.terminate(Terminator::If {
@@ -3002,7 +3644,13 @@ impl Lowerer {
                 if let Some(&existing_local) = self.vars.get(&ident.name) {
                     self.emit(StatementKind::Assign(existing_local, Rvalue::Use(value)));
                 } else {
-                    let local = self.new_local(Ty::Int, Some(ident.name.clone()));
+                    // Give the local the type of its initializer; temporaries whose
+                    // type is unknown are still typed `()`, which falls back to Int.
+                    let ty = match self.operand_type(&value) {
+                        Ty::Unit => Ty::Int,
+                        ty => ty,
+                    };
+                    let local = self.new_local(ty, Some(ident.name.clone()));
                     self.vars.insert(ident.name.clone(), local);
                     self.emit(StatementKind::Assign(local, Rvalue::Use(value)));
                 }
@@ -3028,15 +3676,177 @@ impl Lowerer {
 
     fn lower_literal(&self, lit: &Literal) -> Constant {
         match &lit.kind {
-            LiteralKind::Int(n) => Constant::Int(*n as i64),
+            LiteralKind::Int(n) => Constant::Int(*n as i64, Ty::Int),
             LiteralKind::Float(n) => Constant::Float(*n),
             LiteralKind::String(s) => Constant::Str(s.clone()),
+            LiteralKind::Bytes(bytes) => Constant::Bytes(bytes.clone()),
             LiteralKind::Char(c) => Constant::Char(*c),
             LiteralKind::Bool(b) => Constant::Bool(*b),
             LiteralKind::None => Constant::Unit,
         }
     }
 
+    /// Evaluate a constant initializer at compile time, folding operators
+    /// with the same rules as the MIR constant-folding pass.
+    fn eval_const(&self, expr: &Expr) -> Result<Constant, (String, Span)> {
+        match &expr.kind {
+            ExprKind::Literal(lit) if !matches!(lit.kind, LiteralKind::None) => {
+                Ok(self.lower_literal(lit))
+            }
+            ExprKind::Paren(inner) => self.eval_const(inner),
+            ExprKind::Ident(ident) => self
+                .consts
+                .get(&ident.name)
+                .filter(|_| !self.vars.contains_key(&ident.name))
+                .cloned()
+                .ok_or_else(|| (format!("`{}` is not a constant", ident.name), ident.span)),
+            ExprKind::Call(callee, args)
+                if matches!(&callee.kind, ExprKind::Ident(id)
+                    if self.const_fns.contains(&id.name) && !self.vars.contains_key(&id.name)) =>
+            {
+                let ExprKind::Ident(id) = &callee.kind else {
+                    unreachable!()
+                };
+                self.eval_const_call(&id.name, args, expr.span)
+            }
+            ExprKind::Call(..) | ExprKind::MethodCall(..) | ExprKind::Index(..) => {
+                self.fold_str_query(expr).unwrap_or_else(|| {
+                    Err((
+                        "initializer is not a constant expression".to_string(),
+                        expr.span,
+                    ))
+                })
+            }
+            ExprKind::Unary(AstUnaryOp::Plus, operand) => self.eval_const(operand),
+            ExprKind::Unary(op, operand) => {
+                let op = match op {
+                    AstUnaryOp::Neg => UnOp::Neg,
+                    AstUnaryOp::Not => UnOp::Not,
+                    _ => {
+                        return Err((
+                            "initializer is not a constant expression".to_string(),
+                            expr.span,
+                        ));
+                    }
+                };
+                let value = self.eval_const(operand)?;
+                fold_unop(op, &value).ok_or_else(|| {
+                    (
+                        "cannot evaluate this operation at compile time".to_string(),
+                        expr.span,
+                    )
+                })
+            }
+            ExprKind::Binary(left, op, right) => {
+                let left = self.eval_const(left)?;
+                let right = self.eval_const(right)?;
+                let op = self.lower_bin_op(*op);
+                let zero = match right {
+                    Constant::Int(n, _) => n == 0,
+                    Constant::Float(f) => f == 0.0,
+                    _ => false,
+                };
+                if matches!(op, BinOp::Div | BinOp::Rem) && zero {
+                    return Err(("division by zero".to_string(), expr.span));
+                }
+                fold_binop(op, &left, &right).ok_or_else(|| {
+                    (
+                        "cannot evaluate this operation at compile time".to_string(),
+                        expr.span,
+                    )
+                })
+            }
+            _ => Err((
+                "initializer is not a constant expression".to_string(),
+                expr.span,
+            )),
+        }
+    }
+
+    /// Evaluate a call to const function `name` by running its MIR with
+    /// constant arguments.
+    fn eval_const_call(
+        &self,
+        name: &str,
+        args: &[crate::parser::Arg],
+        span: Span,
+    ) -> Result<Constant, (String, Span)> {
+        let params = self.fn_param_names.get(name).cloned().unwrap_or_default();
+        let defaults = self.fn_defaults.get(name);
+        let slots = bind_call_args(name, &params, args)?;
+        let mut values = Vec::new();
+        for (i, slot) in slots.into_iter().enumerate() {
+            let value = match slot {
+                Some(a) => self.eval_const(&args[a].value)?,
+                None => match defaults.and_then(|d| d.get(i)).and_then(Option::as_ref) {
+                    Some(default) => self.eval_const(default)?,
+                    None => {
+                        return Err((
+                            format!(
+                                "missing argument for parameter '{}' of '{}'",
+                                params[i], name
+                            ),
+                            span,
+                        ));
+                    }
+                },
+            };
+            values.push(value);
+        }
+        ConstEvaluator::new(&self.program)
+            .call(name, values)
+            .map_err(|e| (format!("evaluating `{}`: {}", name, e), span))
+    }
+
+    /// Fold `str_len(s)`, `s.len()` and `s[i]` when `s` and `i` are
+    /// compile-time constants. Returns `None` for any other expression, or
+    /// when an operand is only known at runtime.
+    fn fold_str_query(&self, expr: &Expr) -> Option<Result<Constant, (String, Span)>> {
+        let is_builtin =
+            |name: &str| !self.fn_types.contains_key(name) && !self.vars.contains_key(name);
+        let (string, index) = match &expr.kind {
+            ExprKind::Call(callee, args)
+                if args.len() == 1
+                    && matches!(&callee.kind, ExprKind::Ident(id)
+                        if id.name == "str_len" && is_builtin("str_len")) =>
+            {
+                (&args[0].value, None)
+            }
+            ExprKind::MethodCall(receiver, method, args)
+                if method.name == "len" && args.is_empty() =>
+            {
+                (receiver.as_ref(), None)
+            }
+            ExprKind::Index(base, index) => (base.as_ref(), Some(index.as_ref())),
+            _ => return None,
+        };
+        let Ok(Constant::Str(s)) = self.eval_const(string) else {
+            return None;
+        };
+        let Some(index) = index else {
+            return Some(Ok(Constant::Int(s.len() as i64, Ty::Int)));
+        };
+        let Ok(Constant::Int(i, _)) = self.eval_const(index) else {
+            return None;
+        };
+        Some(
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| s.chars().nth(i))
+                .map(Constant::Char)
+                .ok_or_else(|| {
+                    (
+                        format!(
+                            "string index {} is out of bounds for length {}",
+                            i,
+                            s.chars().count()
+                        ),
+                        expr.span,
+                    )
+                }),
+        )
+    }
+
     fn lower_bin_op(&self, op: AstBinOp) -> BinOp {
         match op {
             AstBinOp::Add => BinOp::Add,
@@ -3100,6 +3910,7 @@ impl Lowerer {
                     "Int" => Ty::Int,
                     "Bool" => Ty::Bool,
                     "Str" => Ty::Str,
+                    "Bytes" => Ty::Bytes,
                     "Float" => Ty::Float,
                     "Char" => Ty::Char,
                     "Unit" | "()" => Ty::Unit,
@@ -3134,6 +3945,7 @@ impl Lowerer {
                         let err_ty = type_args.get(1).cloned().unwrap_or(Ty::Str);
                         Ty::Result(Box::new(ok_ty), Box::new(err_ty))
                     }
+                    _ if self.type_aliases.contains_key(name) => self.lower_alias(name, type_args),
                     // User-defined type
                     _ => Ty::Named(TypeId::new(name.clone()), type_args),
                 }
@@ -3175,9 +3987,14 @@ impl Lowerer {
                 Ty::Fn(param_tys, Box::new(ret_ty))
             }
 
-            AstTypeKind::Array(inner, _size_expr) => {
-                // For now, use a default size - proper const evaluation would be needed
-                Ty::Array(Box::new(self.lower_type(inner)), 0)
+            AstTypeKind::Array(inner, size_expr) => {
+                // Sizes may be constants or const function calls; anything
+                // else keeps the default size
+                let size = match self.eval_const(size_expr) {
+                    Ok(Constant::Int(n, _)) => usize::try_from(n).unwrap_or(0),
+                    _ => 0,
+                };
+                Ty::Array(Box::new(self.lower_type(inner)), size)
             }
 
             AstTypeKind::Map(key, value) => Ty::Map(
@@ -3229,6 +4046,23 @@ impl Lowerer {
     // ========================================================================
 
     /// Infer the type of an expression (for MIR type propagation)
+    /// Lower alias `name` applied to `args` to its target type. The type
+    /// checker rejects recursive aliases; here they just become errors.
+    fn lower_alias(&self, name: &str, args: Vec<Ty>) -> Ty {
+        let Some((params, target)) = self.type_aliases.get(name) else {
+            return Ty::Error;
+        };
+        if self.expanding_aliases.borrow().iter().any(|a| a == name) {
+            return Ty::Error;
+        }
+        self.expanding_aliases.borrow_mut().push(name.to_string());
+        let ty = self.lower_type(target);
+        self.expanding_aliases.borrow_mut().pop();
+
+        let subst: HashMap<String, Ty> = params.iter().cloned().zip(args).collect();
+        ty.substitute_named(&subst)
+    }
+
     fn infer_expr_type(&self, expr: &Expr) -> Ty {
         match &expr.kind {
             ExprKind::Literal(lit) => self.literal_type(lit),
@@ -3238,6 +4072,9 @@ impl Lowerer {
                 if let Some(ty) = self.var_full_types.get(&ident.name) {
                     return ty.clone();
                 }
+                if let Some(value) = self.consts.get(&ident.name) {
+                    return value.ty();
+                }
                 // Check for known enum variants
                 if let Some((enum_name, _)) = self.enum_variants.get(&ident.name) {
                     return Ty::Named(crate::types::TypeId::new(enum_name), vec![]);
@@ -3276,7 +4113,7 @@ impl Lowerer {
 
             ExprKind::Unary(op, operand) => match op {
                 AstUnaryOp::Not => Ty::Bool,
-                AstUnaryOp::Neg => self.infer_expr_type(operand),
+                AstUnaryOp::Plus | AstUnaryOp::Neg => self.infer_expr_type(operand),
                 AstUnaryOp::Ref => Ty::Ref(
                     Box::new(self.infer_expr_type(operand)),
                     crate::types::Mutability::Immutable,
@@ -3286,14 +4123,17 @@ impl Lowerer {
                     crate::types::Mutability::Mutable,
                 ),
                 AstUnaryOp::Deref => match self.infer_expr_type(operand) {
-                    Ty::Ref(inner, _) => *inner,
+                    Ty::Ref(inner, _) | Ty::Ptr(inner, _) | Ty::RawPtr(inner) => *inner,
                     _ => Ty::Unit,
                 },
             },
 
-            ExprKind::Call(callee, _args) => {
+            ExprKind::Call(callee, args) => {
                 // Try to get function return type
                 if let ExprKind::Ident(ident) = &callee.kind {
+                    if ident.name == "select" && args.len() == 3 {
+                        return self.infer_expr_type(&args[1].value);
+                    }
                     return self.get_function_return_type(&ident.name);
                 }
                 if let ExprKind::Path(path) = &callee.kind {
@@ -3327,6 +4167,7 @@ impl Lowerer {
             ExprKind::Index(arr, _idx) => match self.infer_expr_type(arr) {
                 Ty::List(elem) => *elem,
                 Ty::Str => Ty::Char,
+                Ty::Bytes => Ty::U8,
                 _ => Ty::Unit,
             },
 
@@ -3410,6 +4251,7 @@ impl Lowerer {
             LiteralKind::Int(_) => Ty::Int,
             LiteralKind::Float(_) => Ty::Float,
             LiteralKind::String(_) => Ty::Str,
+            LiteralKind::Bytes(_) => Ty::Bytes,
             LiteralKind::Char(_) => Ty::Char,
             LiteralKind::Bool(_) => Ty::Bool,
             LiteralKind::None => Ty::Option(Box::new(Ty::Unit)),
@@ -3447,7 +4289,9 @@ impl Lowerer {
         // Builtin function return types
         match name {
             // Vector operations
-            "vec_len" | "str_len" | "map_len" => Ty::Int,
+            "vec_len" | "str_len" | "map_len" | "bytes_len" => Ty::Int,
+            "bytes_get" => Ty::U8,
+            "str_index" => Ty::Char,
             "vec_get" => Ty::Option(Box::new(Ty::Unit)),
             "vec_push" | "vec_pop" | "vec_clear" => Ty::Unit,
             "vec_is_empty" | "str_contains" | "str_starts_with" | "str_ends_with" => Ty::Bool,
@@ -3476,7 +4320,7 @@ impl Lowerer {
             }
 
             // I/O operations
-            "print" | "println" | "eprint" | "eprintln" => Ty::Unit,
+            "print" | "println" | "eprint" | "eprintln" | "__print_fmt" => Ty::Unit,
             "read_line" => Ty::Result(Box::new(Ty::Str), Box::new(Ty::Str)),
             "read_file" | "write_file" => Ty::Result(Box::new(Ty::Unit), Box::new(Ty::Str)),
 
@@ -3564,6 +4408,7 @@ impl Lowerer {
                 return;
             }
         };
+        let span = self.current_span;
         let func = match self.current_function_mut() {
             Ok(f) => f,
             Err(e) => {
@@ -3571,7 +4416,7 @@ impl Lowerer {
                 return;
             }
         };
-        func.block_mut(block).push(Statement { kind });
+        func.block_mut(block).push(Statement { kind, span });
     }
 
     fn terminate(&mut self, term: Terminator) {
@@ -3582,6 +4427,7 @@ impl Lowerer {
                 return;
             }
         };
+        let span = self.current_span;
         let func = match self.current_function_mut() {
             Ok(f) => f,
             Err(e) => {
@@ -3589,7 +4435,9 @@ impl Lowerer {
                 return;
             }
         };
-        func.block_mut(block).terminate(term);
+        let bb = func.block_mut(block);
+        bb.terminate(term);
+        bb.terminator_span = span;
     }
 
     fn error(&mut self, message: String, span: Span) {
@@ -3789,6 +4637,8 @@ impl Lowerer {
         matches!(
             name,
             "print"
+                | "println"
+                | "__print_fmt"
                 | "vec_new"
                 | "vec_push"
                 | "vec_pop"
@@ -3822,6 +4672,7 @@ impl Lowerer {
                 | "char_is_alphanumeric"
                 | "char_is_whitespace"
                 | "char_to_int"
+                | "select"
         )
     }
 
@@ -3882,6 +4733,266 @@ mod tests {
         assert!(program.functions.contains_key("countdown"));
     }
 
+    #[test]
+    fn test_recursive_calls() {
+        let program = lower_source(
+            r#"f is_even(n: Int) -> Bool
+    if n == 0 then true else is_odd(n - 1)
+
+f is_odd(n: Int) -> Bool
+    if n == 0 then false else is_even(n - 1)"#,
+        )
+        .unwrap();
+        let calls = |name: &str| {
+            program.functions[name]
+                .blocks
+                .iter()
+                .filter_map(|b| match &b.terminator {
+                    Some(Terminator::Call { func, .. }) => Some(func.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(calls("is_even"), vec!["is_odd".to_string()]);
+        assert_eq!(calls("is_odd"), vec!["is_even".to_string()]);
+    }
+
+    #[test]
+    fn test_logical_and_lowers_to_branch() {
+        let program = lower_source(
+            r#"f check(a: Bool, b: Bool) -> Bool
+    a && b"#,
+        )
+        .unwrap();
+        let func = &program.functions["check"];
+        let has_logical_binop = func.blocks.iter().flat_map(|b| &b.stmts).any(|s| {
+            matches!(
+                &s.kind,
+                StatementKind::Assign(_, Rvalue::BinaryOp(BinOp::And | BinOp::Or, _, _))
+            )
+        });
+        assert!(!has_logical_binop);
+        assert!(
+            func.blocks
+                .iter()
+                .any(|b| matches!(b.terminator, Some(Terminator::If { .. })))
+        );
+    }
+
+    #[test]
+    fn test_user_enum_registry() {
+        let program = lower_source(
+            r#"e Shape
+    Circle(Int)
+    Rect(Int, Int)
+    Empty
+
+f make() -> Shape
+    Rect(2, 3)"#,
+        )
+        .unwrap();
+        let key = |v: &str| ("Shape".to_string(), v.to_string());
+        assert_eq!(program.enum_variants.get(&key("Circle")), Some(&0));
+        assert_eq!(program.enum_variants.get(&key("Empty")), Some(&2));
+        assert_eq!(program.enum_payload_sizes.get("Shape"), Some(&2));
+
+        let constructs_rect = program.functions["make"]
+            .blocks
+            .iter()
+            .flat_map(|b| &b.stmts)
+            .any(|s| {
+                matches!(
+                    &s.kind,
+                    StatementKind::Assign(_, Rvalue::Enum { type_name, variant, fields })
+                        if type_name == "Shape" && variant == "Rect" && fields.len() == 2
+                )
+            });
+        assert!(constructs_rect);
+    }
+
+    #[test]
+    fn test_let_local_types() {
+        let program = lower_source(
+            r#"f example(a: Int) -> Int
+    x := a + 1
+    flag := x < 5
+    items: [Int] := []
+    if flag then x else vec_len(items)"#,
+        )
+        .unwrap();
+        let local_ty = |name: &str| {
+            program.functions["example"]
+                .locals
+                .iter()
+                .find(|l| l.name.as_deref() == Some(name))
+                .map(|l| l.ty.clone())
+        };
+        assert_eq!(local_ty("x"), Some(Ty::Int));
+        assert_eq!(local_ty("flag"), Some(Ty::Bool));
+        assert_eq!(local_ty("items"), Some(Ty::List(Box::new(Ty::Int))));
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let program = lower_source(
+            r#"f example(a: Int) -> Int
+    x := a + 1
+    y := x * 2
+    y"#,
+        )
+        .unwrap();
+        let func = &program.functions["example"];
+        assert_eq!(func.span.map(|s| s.line), Some(1));
+        let lines: Vec<usize> = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.stmts)
+            .filter_map(|s| s.span.map(|span| span.line))
+            .collect();
+        assert!(lines.contains(&2), "lines: {:?}", lines);
+        assert!(lines.contains(&3), "lines: {:?}", lines);
+        let entry = func.block(func.entry_block);
+        assert_eq!(entry.terminator_span.map(|s| s.line), Some(4));
+    }
+
+    #[test]
+    fn test_no_mangle_attribute() {
+        let program =
+            lower_source("@no_mangle\nf exported() -> Int = 1\n\nf internal() -> Int = 2\n")
+                .unwrap();
+        assert!(program.functions["exported"].no_mangle);
+        assert!(!program.functions["internal"].no_mangle);
+    }
+
+    #[test]
+    fn test_callconv_attribute() {
+        let program = lower_source(
+            "@callconv(\"fast\")\nf step(x: Int) -> Int = x + 1\n\n@callconv(\"fast\")\nextern \"C\" f ext_step(x: Int) -> Int\n\nf main() -> Int = step(1)\n",
+        )
+        .unwrap();
+        assert_eq!(program.functions["step"].call_conv, CallConv::Fast);
+        assert_eq!(program.functions["main"].call_conv, CallConv::C);
+        assert_eq!(program.externs["ext_step"].call_conv, CallConv::Fast);
+
+        let errors = lower_source("@callconv(\"fast\")\nf main() -> Int = 0\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "`main` must use the C calling convention"
+        );
+    }
+
+    #[test]
+    fn test_constants_are_evaluated() {
+        let program = lower_source(
+            "SECONDS :: 60 * 60\nHALF :: -(SECONDS / 2)\nf main() -> Int = SECONDS + HALF\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            program.globals["SECONDS"].value,
+            Constant::Int(3600, Ty::Int)
+        ));
+        assert!(matches!(
+            program.globals["HALF"].value,
+            Constant::Int(-1800, Ty::Int)
+        ));
+
+        let errors = lower_source("BAD :: 1 / (2 - 2)\n").unwrap_err();
+        assert_eq!(errors[0].message, "constant `BAD`: division by zero");
+        let errors = lower_source("f one() -> Int = 1\nBAD :: one() + 1\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "constant `BAD`: initializer is not a constant expression"
+        );
+        let errors = lower_source("BAD :: LATER + 1\nLATER :: 1\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "constant `BAD`: `LATER` is not a constant"
+        );
+    }
+
+    #[test]
+    fn test_const_fn_initializes_global() {
+        let program = lower_source(
+            "const f square(x: Int) -> Int = x * x\n\nNINE :: square(3)\n\nf table() -> [Int; square(2)] = [1, 2, 3, 4]\n",
+        )
+        .unwrap();
+        assert!(program.functions["square"].is_const);
+        assert!(matches!(
+            program.globals["NINE"].value,
+            Constant::Int(9, Ty::Int)
+        ));
+        assert!(matches!(
+            program.functions["table"].return_ty,
+            Ty::Array(_, 4)
+        ));
+
+        let errors =
+            lower_source("const f shout(x: Int) -> Int\n    print(x)\n    x\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "const fn `shout` cannot call non-const `print`"
+        );
+        let errors = lower_source("const f home() -> Str = env_get(\"HOME\")\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "const fn `home` cannot call `env_get`, which requires capability 'env'"
+        );
+    }
+
+    #[test]
+    fn test_type_aliases_expand() {
+        let program = lower_source(
+            "type Celsius = Float\ntype Pair[T] = (T, T)\n\nf warm(c: Celsius) -> Celsius = c\n\nf swap(p: Pair[Int]) -> Pair[Int] = (p.1, p.0)\n",
+        )
+        .unwrap();
+        let warm = &program.functions["warm"];
+        assert_eq!(warm.params[0].1, Ty::Float);
+        assert_eq!(warm.return_ty, Ty::Float);
+        let swap = &program.functions["swap"];
+        assert_eq!(swap.params[0].1, Ty::Tuple(vec![Ty::Int, Ty::Int]));
+    }
+
+    #[test]
+    fn test_section_attribute() {
+        let program =
+            lower_source("@section(\".text.boot\")\nf boot() -> Int = 1\n\nf other() -> Int = 2\n")
+                .unwrap();
+        assert_eq!(
+            program.functions["boot"].section.as_deref(),
+            Some(".text.boot")
+        );
+        assert_eq!(program.functions["other"].section, None);
+    }
+
+    #[test]
+    fn test_deref_pointer_type() {
+        let program = lower_source("f read(p: *Int) -> Int = *p\n").unwrap();
+        let func = &program.functions["read"];
+        let deref_ty = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.stmts)
+            .find_map(|s| match &s.kind {
+                StatementKind::Assign(local, Rvalue::Deref(_)) => {
+                    Some(func.locals[local.0 as usize].ty.clone())
+                }
+                _ => None,
+            })
+            .expect("expected a deref");
+        assert_eq!(deref_ty, Ty::Int);
+    }
+
+    #[test]
+    fn test_extern_declaration() {
+        let program =
+            lower_source("extern \"C\" f labs(x: Int) -> Int\n\nf main() -> Int = labs(-3)\n")
+                .unwrap();
+        let ext = &program.externs["labs"];
+        assert_eq!(ext.params, vec![Ty::Int]);
+        assert_eq!(ext.return_ty, Ty::Int);
+        assert!(!program.functions.contains_key("labs"));
+    }
+
     #[test]
     fn test_let_binding() {
         let program = lower_source(
@@ -14,6 +14,7 @@
 use std::collections::HashMap;
 use std::fmt;
 
+use crate::lexer::Span;
 use crate::types::Ty;
 
 /// A unique identifier for a function in MIR.
@@ -47,6 +48,12 @@ pub struct Program {
     pub entry: Option<String>,
     /// Enum variant registry: maps (enum_name, variant_name) -> variant index
     pub enum_variants: HashMap<(String, String), usize>,
+    /// Enum payload sizes: maps enum_name -> field count of its largest variant
+    pub enum_payload_sizes: HashMap<String, usize>,
+    /// Foreign functions declared with `extern "C"`, resolved at link time
+    pub externs: HashMap<String, ExternFunction>,
+    /// Top-level constants (`N :: 10 * 60`), already evaluated
+    pub globals: HashMap<String, Global>,
 }
 
 impl Program {
@@ -55,10 +62,30 @@ impl Program {
             functions: HashMap::new(),
             entry: None,
             enum_variants: HashMap::new(),
+            enum_payload_sizes: HashMap::new(),
+            externs: HashMap::new(),
+            globals: HashMap::new(),
         }
     }
 }
 
+/// A top-level constant whose initializer was evaluated at compile time.
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub value: Constant,
+}
+
+/// Signature of a foreign function declared with `extern "C"`.
+#[derive(Debug, Clone)]
+pub struct ExternFunction {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub return_ty: Ty,
+    /// Calling convention the foreign function expects
+    pub call_conv: CallConv,
+}
+
 impl Default for Program {
     fn default() -> Self {
         Self::new()
@@ -107,9 +134,69 @@ pub struct Function {
     pub preconditions: Vec<MirContract>,
     /// Postconditions (@post) - checked at function exit
     pub postconditions: Vec<MirContract>,
+    /// Source location of the function definition, if known
+    pub span: Option<Span>,
+    /// Keep `name` as the native symbol instead of mangling it (`@no_mangle`)
+    pub no_mangle: bool,
+    /// Object file section to place the function in (`@section("...")`)
+    pub section: Option<String>,
+    /// Declared `const f`, so constant initializers may call it
+    pub is_const: bool,
+    /// Keep the native symbol visible outside the object file; other
+    /// functions get hidden visibility. Set for `pub @no_mangle` functions
+    /// and by `@visibility("default")`.
+    pub exported: bool,
+    /// Native calling convention (`@callconv("...")`)
+    pub call_conv: CallConv,
+}
+
+/// Calling convention of a function in native builds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CallConv {
+    /// The platform C convention
+    #[default]
+    C,
+    /// LLVM's `fastcc`, for calls between FORMA functions only
+    Fast,
+}
+
+impl CallConv {
+    /// Parse the name used in `@callconv("...")`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "C" => Some(CallConv::C),
+            "fast" => Some(CallConv::Fast),
+            _ => None,
+        }
+    }
+}
+
+/// Native symbol name for a MIR function name.
+///
+/// Each `::`-separated path segment is prefixed with its length after a
+/// leading `_F`, so `helper` becomes `_F6helper` and `math::helper` becomes
+/// `_F4math6helper`. This keeps FORMA functions from colliding with each
+/// other across modules and impls, and with C symbols such as `exit`.
+pub fn mangle_symbol(name: &str) -> String {
+    let mut symbol = String::from("_F");
+    for segment in name.split("::") {
+        symbol.push_str(&segment.len().to_string());
+        symbol.push_str(segment);
+    }
+    symbol
 }
 
 impl Function {
+    /// Native symbol for this function under its program key `name`: the
+    /// mangled form, except for `main` and `@no_mangle` functions.
+    pub fn symbol(&self, name: &str) -> String {
+        if self.no_mangle || name == "main" {
+            name.to_string()
+        } else {
+            mangle_symbol(name)
+        }
+    }
+
     pub fn new(name: String, params: Vec<(Local, Ty)>, return_ty: Ty) -> Self {
         Self {
             name,
@@ -122,6 +209,12 @@ impl Function {
             entry_block: BlockId(0),
             preconditions: Vec::new(),
             postconditions: Vec::new(),
+            span: None,
+            no_mangle: false,
+            section: None,
+            is_const: false,
+            exported: false,
+            call_conv: CallConv::C,
         }
     }
 
@@ -163,6 +256,8 @@ pub struct BasicBlock {
     pub id: BlockId,
     pub stmts: Vec<Statement>,
     pub terminator: Option<Terminator>,
+    /// Source location of the terminator, if known
+    pub terminator_span: Option<Span>,
 }
 
 impl BasicBlock {
@@ -171,6 +266,7 @@ impl BasicBlock {
             id,
             stmts: Vec::new(),
             terminator: None,
+            terminator_span: None,
         }
     }
 
@@ -187,6 +283,8 @@ impl BasicBlock {
 #[derive(Debug, Clone)]
 pub struct Statement {
     pub kind: StatementKind,
+    /// Source location the statement was lowered from, if known
+    pub span: Option<Span>,
 }
 
 #[derive(Debug, Clone)]
@@ -262,6 +360,8 @@ pub enum Rvalue {
         func_name: String,
         captures: Vec<Operand>,
     },
+    /// Pointer to a named function, used as a first-class value
+    FnRef(String),
 }
 
 /// An operand - something that can be used as input.
@@ -282,10 +382,12 @@ pub enum Operand {
 pub enum Constant {
     Unit,
     Bool(bool),
-    Int(i64),
+    /// An integer of the given integer type
+    Int(i64, Ty),
     Float(f64),
     Char(char),
     Str(String),
+    Bytes(Vec<u8>),
 }
 
 impl Constant {
@@ -293,10 +395,11 @@ impl Constant {
         match self {
             Constant::Unit => Ty::Unit,
             Constant::Bool(_) => Ty::Bool,
-            Constant::Int(_) => Ty::Int,
+            Constant::Int(_, ty) => ty.clone(),
             Constant::Float(_) => Ty::Float,
             Constant::Char(_) => Ty::Char,
             Constant::Str(_) => Ty::Str,
+            Constant::Bytes(_) => Ty::Bytes,
         }
     }
 }
@@ -526,6 +629,7 @@ impl fmt::Display for Rvalue {
             Rvalue::TupleField(op, idx) => write!(f, "{}.{}", op, idx),
             Rvalue::Index(base, idx) => write!(f, "{}[{}]", base, idx),
             Rvalue::Cast(op, ty) => write!(f, "{} as {}", op, ty),
+            Rvalue::FnRef(name) => write!(f, "fn {}", name),
             Rvalue::Closure {
                 func_name,
                 captures,
@@ -559,10 +663,13 @@ impl fmt::Display for Constant {
         match self {
             Constant::Unit => write!(f, "()"),
             Constant::Bool(b) => write!(f, "{}", b),
-            Constant::Int(n) => write!(f, "{}", n),
+            Constant::Int(n, _) => write!(f, "{}", n),
             Constant::Float(n) => write!(f, "{}", n),
             Constant::Char(c) => write!(f, "'{}'", c),
             Constant::Str(s) => write!(f, "\"{}\"", s),
+            Constant::Bytes(bytes) => {
+                write!(f, "{}", crate::parser::ast::byte_string_source(bytes))
+            }
         }
     }
 }
@@ -668,6 +775,7 @@ mod tests {
                 result,
                 Rvalue::BinaryOp(BinOp::Add, Operand::Local(a), Operand::Local(b)),
             ),
+            span: None,
         });
 
         // Terminate with return
@@ -699,9 +807,10 @@ mod tests {
                 Rvalue::BinaryOp(
                     BinOp::Lt,
                     Operand::Local(x),
-                    Operand::Constant(Constant::Int(0)),
+                    Operand::Constant(Constant::Int(0, Ty::Int)),
                 ),
             ),
+            span: None,
         });
         func.block_mut(entry).terminate(Terminator::If {
             cond: Operand::Local(cond),
@@ -712,12 +821,14 @@ mod tests {
         // Then: result = -x
         func.block_mut(then_block).push(Statement {
             kind: StatementKind::Assign(result, Rvalue::UnaryOp(UnOp::Neg, Operand::Local(x))),
+            span: None,
         });
         func.block_mut(then_block).terminate(Terminator::Goto(exit));
 
         // Else: result = x
         func.block_mut(else_block).push(Statement {
             kind: StatementKind::Assign(result, Rvalue::Use(Operand::Local(x))),
+            span: None,
         });
         func.block_mut(else_block).terminate(Terminator::Goto(exit));
 
@@ -17,6 +17,9 @@
 //! - [`mir`]: MIR data structures
 //! - [`lower`]: AST to MIR lowering
 //! - [`interp`]: Simple MIR interpreter
+//! - [`sourcemap`]: Maps from native code back to source lines
+//! - [`capability`]: Capabilities required by builtins
+//! - [`const_eval`]: Compile-time evaluation of const functions
 //!
 //! # Example
 //!
@@ -27,14 +30,18 @@
 //! let result = Interpreter::new().run(&mir, "main", &[])?;
 //! ```
 
+pub mod capability;
+pub mod const_eval;
 pub mod interp;
 pub mod lower;
 pub mod mir;
 pub mod optimize;
+pub mod sourcemap;
 
 pub use interp::{InterpError, Interpreter, RuntimeError, Value};
 pub use lower::{LowerError, Lowerer};
 pub use mir::{
-    BasicBlock, BinOp, BlockId, Constant, Function, Local, LocalDecl, MirContract, Mutability,
-    Operand, Program, Rvalue, Statement, StatementKind, Terminator, UnOp,
+    BasicBlock, BinOp, BlockId, CallConv, Constant, ExternFunction, Function, Global, Local,
+    LocalDecl, MirContract, Mutability, Operand, Program, Rvalue, Statement, StatementKind,
+    Terminator, UnOp, mangle_symbol,
 };
@@ -1,15 +1,24 @@
 //! MIR optimization passes.
 //!
-//! Runs between lowering and interpretation/codegen. Four passes run in rounds
+//! Runs between lowering and interpretation/codegen. Five passes run in rounds
 //! to a fixed point (or max 3 rounds):
 //!
 //! 1. **Constant folding** — evaluate constant expressions at compile time
-//! 2. **Copy propagation** — block-local forward propagation of copy temps
-//! 3. **Dead block elimination** — remove unreachable blocks, simplify constant branches
-//! 4. **Peephole optimizations** — local pattern replacements within a block
+//! 2. **Constant propagation** — carry locals holding constants across blocks
+//! 3. **Copy propagation** — block-local forward propagation of copy temps,
+//!    then removal of the copies left unread
+//! 4. **Dead block elimination** — merge straight-line blocks, remove unreachable
+//!    blocks, simplify constant branches
+//! 5. **Peephole optimizations** — local pattern replacements within a block
+//!
+//! It also provides MIR checks: [`validate_mir`] for structural invariants and
+//! [`find_uninitialized_uses`] for reads of locals that may not be assigned yet.
 
 use std::collections::{HashMap, HashSet};
 
+use crate::lexer::Span;
+use crate::types::Ty;
+
 use super::mir::{
     BasicBlock, BinOp, BlockId, Constant, Function, Local, Operand, Program, Rvalue, Statement,
     StatementKind, Terminator, UnOp,
@@ -19,8 +28,11 @@ use super::mir::{
 #[derive(Debug, Default, Clone)]
 pub struct OptStats {
     pub constants_folded: usize,
+    pub constants_propagated: usize,
     pub copies_propagated: usize,
+    pub dead_copies_removed: usize,
     pub dead_blocks_removed: usize,
+    pub blocks_merged: usize,
     pub branches_simplified: usize,
     pub peepholes_applied: usize,
     pub nops_removed: usize,
@@ -29,8 +41,11 @@ pub struct OptStats {
 impl OptStats {
     pub fn total(&self) -> usize {
         self.constants_folded
+            + self.constants_propagated
             + self.copies_propagated
+            + self.dead_copies_removed
             + self.dead_blocks_removed
+            + self.blocks_merged
             + self.branches_simplified
             + self.peepholes_applied
             + self.nops_removed
@@ -38,8 +53,11 @@ impl OptStats {
 
     fn merge(&mut self, other: &OptStats) {
         self.constants_folded += other.constants_folded;
+        self.constants_propagated += other.constants_propagated;
         self.copies_propagated += other.copies_propagated;
+        self.dead_copies_removed += other.dead_copies_removed;
         self.dead_blocks_removed += other.dead_blocks_removed;
+        self.blocks_merged += other.blocks_merged;
         self.branches_simplified += other.branches_simplified;
         self.peepholes_applied += other.peepholes_applied;
         self.nops_removed += other.nops_removed;
@@ -50,10 +68,13 @@ impl std::fmt::Display for OptStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "constants_folded={}, copies_propagated={}, dead_blocks={}, branches_simplified={}, peepholes={}, nops={}",
+            "constants_folded={}, constants_propagated={}, copies_propagated={}, dead_copies={}, dead_blocks={}, blocks_merged={}, branches_simplified={}, peepholes={}, nops={}",
             self.constants_folded,
+            self.constants_propagated,
             self.copies_propagated,
+            self.dead_copies_removed,
             self.dead_blocks_removed,
+            self.blocks_merged,
             self.branches_simplified,
             self.peepholes_applied,
             self.nops_removed,
@@ -74,7 +95,10 @@ pub fn optimize(program: &mut Program) -> OptStats {
         for func in program.functions.values_mut() {
             let mut s = OptStats::default();
             constant_fold(func, &mut s);
+            propagate_constants(func, &mut s);
             copy_propagate(func, &mut s);
+            remove_dead_copies(func, &mut s);
+            merge_blocks(func, &mut s);
             dead_block_eliminate(func, &mut s);
             peephole(func, &mut s);
             round_stats.merge(&s);
@@ -216,7 +240,7 @@ fn constant_fold(func: &mut Function, stats: &mut OptStats) {
                     stats.branches_simplified += 1;
                 }
                 Terminator::Switch {
-                    operand: Operand::Constant(Constant::Int(val)),
+                    operand: Operand::Constant(Constant::Int(val, _)),
                     targets,
                     default,
                 } => {
@@ -257,52 +281,57 @@ fn operand_as_constant(op: &Operand) -> Option<&Constant> {
     }
 }
 
-fn fold_binop(op: BinOp, left: &Constant, right: &Constant) -> Option<Constant> {
+pub(crate) fn fold_binop(op: BinOp, left: &Constant, right: &Constant) -> Option<Constant> {
     match (left, right) {
-        // Integer arithmetic
-        (Constant::Int(a), Constant::Int(b)) => match op {
-            BinOp::Add => a.checked_add(*b).map(Constant::Int),
-            BinOp::Sub => a.checked_sub(*b).map(Constant::Int),
-            BinOp::Mul => a.checked_mul(*b).map(Constant::Int),
-            BinOp::Div => {
-                if *b == 0 {
-                    None // leave div-by-zero as runtime
-                } else {
-                    a.checked_div(*b).map(Constant::Int)
+        // Integer arithmetic, kept only when the result fits the type
+        (Constant::Int(a, a_ty), Constant::Int(b, b_ty)) => {
+            let ty = int_binop_ty(a_ty, b_ty)?;
+            let int = |n: i64| int_fits(n, ty).then(|| Constant::Int(n, ty.clone()));
+            let bits = i64::from(ty.numeric_bits().unwrap_or(64).min(64));
+            match op {
+                BinOp::Add => a.checked_add(*b).and_then(int),
+                BinOp::Sub => a.checked_sub(*b).and_then(int),
+                BinOp::Mul => a.checked_mul(*b).and_then(int),
+                BinOp::Div => {
+                    if *b == 0 {
+                        None // leave div-by-zero as runtime
+                    } else {
+                        a.checked_div(*b).and_then(int)
+                    }
                 }
-            }
-            BinOp::Rem => {
-                if *b == 0 {
-                    None
-                } else {
-                    a.checked_rem(*b).map(Constant::Int)
+                BinOp::Rem => {
+                    if *b == 0 {
+                        None
+                    } else {
+                        a.checked_rem(*b).and_then(int)
+                    }
                 }
-            }
-            BinOp::Eq => Some(Constant::Bool(a == b)),
-            BinOp::Ne => Some(Constant::Bool(a != b)),
-            BinOp::Lt => Some(Constant::Bool(a < b)),
-            BinOp::Le => Some(Constant::Bool(a <= b)),
-            BinOp::Gt => Some(Constant::Bool(a > b)),
-            BinOp::Ge => Some(Constant::Bool(a >= b)),
-            BinOp::BitAnd => Some(Constant::Int(a & b)),
-            BinOp::BitOr => Some(Constant::Int(a | b)),
-            BinOp::BitXor => Some(Constant::Int(a ^ b)),
-            BinOp::Shl => {
-                if *b < 0 || *b >= 64 {
-                    None // invalid shift
-                } else {
-                    Some(Constant::Int(a << b))
+                BinOp::Eq => Some(Constant::Bool(a == b)),
+                BinOp::Ne => Some(Constant::Bool(a != b)),
+                BinOp::Lt => Some(Constant::Bool(a < b)),
+                BinOp::Le => Some(Constant::Bool(a <= b)),
+                BinOp::Gt => Some(Constant::Bool(a > b)),
+                BinOp::Ge => Some(Constant::Bool(a >= b)),
+                BinOp::BitAnd => int(a & b),
+                BinOp::BitOr => int(a | b),
+                BinOp::BitXor => int(a ^ b),
+                BinOp::Shl => {
+                    if *b < 0 || *b >= bits {
+                        None // invalid shift
+                    } else {
+                        int(a << b)
+                    }
                 }
-            }
-            BinOp::Shr => {
-                if *b < 0 || *b >= 64 {
-                    None
-                } else {
-                    Some(Constant::Int(a >> b))
+                BinOp::Shr => {
+                    if *b < 0 || *b >= bits {
+                        None
+                    } else {
+                        int(a >> b)
+                    }
                 }
+                _ => None,
             }
-            _ => None,
-        },
+        }
         // Float arithmetic
         (Constant::Float(a), Constant::Float(b)) => match op {
             BinOp::Add => Some(Constant::Float(a + b)),
@@ -351,31 +380,350 @@ fn fold_binop(op: BinOp, left: &Constant, right: &Constant) -> Option<Constant>
     }
 }
 
-fn fold_unop(op: UnOp, c: &Constant) -> Option<Constant> {
+/// The type of an integer operation on `a` and `b`. An `Int` operand, such
+/// as an integer literal, takes the other operand's type.
+fn int_binop_ty<'a>(a: &'a Ty, b: &'a Ty) -> Option<&'a Ty> {
+    match (a, b) {
+        _ if a == b => Some(a),
+        (Ty::Int, other) | (other, Ty::Int) => Some(other),
+        _ => None,
+    }
+}
+
+/// Whether `n` is a value of the integer type `ty`. Unsigned 64- and 128-bit
+/// values are only folded while they stay non-negative.
+fn int_fits(n: i64, ty: &Ty) -> bool {
+    match ty {
+        Ty::I8 => i8::try_from(n).is_ok(),
+        Ty::I16 => i16::try_from(n).is_ok(),
+        Ty::I32 => i32::try_from(n).is_ok(),
+        Ty::U8 => u8::try_from(n).is_ok(),
+        Ty::U16 => u16::try_from(n).is_ok(),
+        Ty::U32 => u32::try_from(n).is_ok(),
+        Ty::UInt | Ty::U64 | Ty::U128 | Ty::Usize => n >= 0,
+        _ => true,
+    }
+}
+
+pub(crate) fn fold_unop(op: UnOp, c: &Constant) -> Option<Constant> {
     match (op, c) {
-        (UnOp::Neg, Constant::Int(n)) => n.checked_neg().map(Constant::Int),
+        (UnOp::Neg, Constant::Int(n, ty)) => n
+            .checked_neg()
+            .filter(|n| int_fits(*n, ty))
+            .map(|n| Constant::Int(n, ty.clone())),
         (UnOp::Neg, Constant::Float(n)) => Some(Constant::Float(-n)),
         (UnOp::Not, Constant::Bool(b)) => Some(Constant::Bool(!b)),
-        (UnOp::BitNot, Constant::Int(n)) => Some(Constant::Int(!n)),
+        (UnOp::BitNot, Constant::Int(n, ty)) => {
+            // Narrow unsigned values keep only their own bits
+            let n = match ty {
+                Ty::U8 => !n & 0xFF,
+                Ty::U16 => !n & 0xFFFF,
+                Ty::U32 => !n & 0xFFFF_FFFF,
+                _ => !n,
+            };
+            int_fits(n, ty).then(|| Constant::Int(n, ty.clone()))
+        }
         _ => None,
     }
 }
 
 // ---------------------------------------------------------------------------
-// Pass 2: Copy Propagation
+// Pass 2: Constant Propagation
+// ---------------------------------------------------------------------------
+
+/// Locals known to hold a constant. A local that is absent may hold anything.
+type ConstEnv = HashMap<Local, Constant>;
+
+/// Sparse conditional constant propagation.
+///
+/// Forward dataflow over the CFG tracking which locals hold a known scalar
+/// constant. Where paths join, a local stays known only if every reached
+/// predecessor agrees on its value, and a branch on a known condition only
+/// reaches the taken successor, so the other side cannot spoil the join.
+/// Uses of known locals are then replaced by the constant, and operations
+/// whose operands all become constant are folded.
+///
+/// Parameters, locals whose address is taken and locals passed to a call
+/// (which may take them by `ref mut`) are never tracked, nor are locals whose
+/// type differs from the constant's, such as an `I32` holding an `Int` literal.
+fn propagate_constants(func: &mut Function, stats: &mut OptStats) {
+    let num_blocks = func.blocks.len();
+    if func.entry_block.0 as usize >= num_blocks {
+        return;
+    }
+    let tracking = ConstTracking::new(func);
+
+    // Entry states; None means the block has not been reached yet
+    let mut entry_states: Vec<Option<ConstEnv>> = vec![None; num_blocks];
+    entry_states[func.entry_block.0 as usize] = Some(ConstEnv::new());
+
+    let mut worklist = vec![func.entry_block];
+    while let Some(bid) = worklist.pop() {
+        let Some(mut env) = entry_states[bid.0 as usize].clone() else {
+            continue;
+        };
+        let block = func.block(bid);
+        for stmt in &block.stmts {
+            tracking.transfer_stmt(&stmt.kind, &mut env);
+        }
+        let Some(term) = &block.terminator else {
+            continue;
+        };
+        tracking.transfer_terminator(term, &mut env);
+
+        for succ in const_successors(term, &env) {
+            let Some(entry) = entry_states.get_mut(succ.0 as usize) else {
+                continue;
+            };
+            let changed = match entry {
+                None => {
+                    *entry = Some(env.clone());
+                    true
+                }
+                Some(existing) => {
+                    let before = existing.len();
+                    existing.retain(|local, c| {
+                        env.get(local).is_some_and(|other| same_constant(c, other))
+                    });
+                    existing.len() != before
+                }
+            };
+            if changed {
+                worklist.push(succ);
+            }
+        }
+    }
+
+    for (block, entry) in func.blocks.iter_mut().zip(entry_states) {
+        let Some(mut env) = entry else {
+            continue;
+        };
+        for stmt in &mut block.stmts {
+            match &mut stmt.kind {
+                StatementKind::Assign(_, rvalue) => {
+                    stats.constants_propagated += substitute_constants_rvalue(rvalue, &env);
+                    if let Some(c) = try_fold_rvalue(rvalue) {
+                        *rvalue = Rvalue::Use(Operand::Constant(c));
+                        stats.constants_folded += 1;
+                    }
+                }
+                StatementKind::IndexAssign(_, idx, val) => {
+                    stats.constants_propagated += substitute_constant(idx, &env);
+                    stats.constants_propagated += substitute_constant(val, &env);
+                }
+                StatementKind::Nop => {}
+            }
+            tracking.transfer_stmt(&stmt.kind, &mut env);
+        }
+        // Call arguments keep their locals, which ref parameters bind to
+        match &mut block.terminator {
+            Some(Terminator::Return(Some(op)))
+            | Some(Terminator::If { cond: op, .. })
+            | Some(Terminator::Switch { operand: op, .. }) => {
+                stats.constants_propagated += substitute_constant(op, &env);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Which locals constant propagation may track.
+struct ConstTracking {
+    untracked: HashSet<Local>,
+    local_tys: Vec<Ty>,
+}
+
+impl ConstTracking {
+    fn new(func: &Function) -> Self {
+        let mut untracked: HashSet<Local> = func.params.iter().map(|(l, _)| *l).collect();
+        for block in &func.blocks {
+            for stmt in &block.stmts {
+                if let StatementKind::Assign(_, Rvalue::Ref(local, _)) = &stmt.kind {
+                    untracked.insert(*local);
+                }
+            }
+        }
+        Self {
+            untracked,
+            local_tys: func.locals.iter().map(|decl| decl.ty.clone()).collect(),
+        }
+    }
+
+    /// Record that `dest` now holds `value`, or something unknown.
+    fn assign(&self, env: &mut ConstEnv, dest: Local, value: Option<Constant>) {
+        match value {
+            Some(c)
+                if matches!(
+                    c,
+                    Constant::Bool(_) | Constant::Int(..) | Constant::Float(_) | Constant::Char(_)
+                ) && !self.untracked.contains(&dest)
+                    && self.local_tys.get(dest.0 as usize) == Some(&c.ty()) =>
+            {
+                env.insert(dest, c);
+            }
+            _ => {
+                env.remove(&dest);
+            }
+        }
+    }
+
+    fn transfer_stmt(&self, kind: &StatementKind, env: &mut ConstEnv) {
+        match kind {
+            StatementKind::Assign(dest, rvalue) => {
+                let value = const_rvalue(rvalue, env);
+                self.assign(env, *dest, value);
+            }
+            StatementKind::IndexAssign(target, _, _) => {
+                env.remove(target);
+            }
+            StatementKind::Nop => {}
+        }
+    }
+
+    fn transfer_terminator(&self, term: &Terminator, env: &mut ConstEnv) {
+        let (args, dest): (&[Operand], Option<Local>) = match term {
+            Terminator::Call { args, dest, .. } | Terminator::CallIndirect { args, dest, .. } => {
+                (args, *dest)
+            }
+            Terminator::Spawn { dest, .. } | Terminator::Await { dest, .. } => (&[], *dest),
+            _ => (&[], None),
+        };
+        for local in args.iter().filter_map(operand_local) {
+            env.remove(&local);
+        }
+        if let Some(dest) = dest {
+            env.remove(&dest);
+        }
+    }
+}
+
+/// The constant `op` holds under `env`, if known.
+fn const_operand(op: &Operand, env: &ConstEnv) -> Option<Constant> {
+    match op {
+        Operand::Constant(c) => Some(c.clone()),
+        Operand::Copy(l) | Operand::Local(l) | Operand::Move(l) => env.get(l).cloned(),
+    }
+}
+
+/// The constant `rvalue` evaluates to under `env`, if known.
+fn const_rvalue(rvalue: &Rvalue, env: &ConstEnv) -> Option<Constant> {
+    match rvalue {
+        Rvalue::Use(op) => const_operand(op, env),
+        Rvalue::BinaryOp(op, l, r) => {
+            fold_binop(*op, &const_operand(l, env)?, &const_operand(r, env)?)
+        }
+        Rvalue::UnaryOp(op, operand) => fold_unop(*op, &const_operand(operand, env)?),
+        _ => None,
+    }
+}
+
+/// Whether two constants are the same value. Floats compare by bit pattern,
+/// so `0.0` and `-0.0` differ and a NaN equals itself.
+fn same_constant(a: &Constant, b: &Constant) -> bool {
+    match (a, b) {
+        (Constant::Unit, Constant::Unit) => true,
+        (Constant::Bool(a), Constant::Bool(b)) => a == b,
+        (Constant::Int(a, a_ty), Constant::Int(b, b_ty)) => a == b && a_ty == b_ty,
+        (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+        (Constant::Char(a), Constant::Char(b)) => a == b,
+        (Constant::Str(a), Constant::Str(b)) => a == b,
+        (Constant::Bytes(a), Constant::Bytes(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Successors a terminator can reach under `env`: only the taken one when the
+/// condition or discriminant is known.
+fn const_successors(term: &Terminator, env: &ConstEnv) -> Vec<BlockId> {
+    match term {
+        Terminator::If {
+            cond,
+            then_block,
+            else_block,
+        } => match const_operand(cond, env) {
+            Some(Constant::Bool(true)) => vec![*then_block],
+            Some(Constant::Bool(false)) => vec![*else_block],
+            _ => terminator_successors(term),
+        },
+        Terminator::Switch {
+            operand,
+            targets,
+            default,
+        } => match const_operand(operand, env) {
+            Some(Constant::Int(val, _)) => vec![
+                targets
+                    .iter()
+                    .find(|(v, _)| *v == val)
+                    .map(|(_, bid)| *bid)
+                    .unwrap_or(*default),
+            ],
+            _ => terminator_successors(term),
+        },
+        _ => terminator_successors(term),
+    }
+}
+
+/// Replace a known local with its constant. Returns 1 if it was replaced.
+fn substitute_constant(op: &mut Operand, env: &ConstEnv) -> usize {
+    if let Operand::Copy(l) | Operand::Local(l) | Operand::Move(l) = op
+        && let Some(c) = env.get(l)
+    {
+        *op = Operand::Constant(c.clone());
+        return 1;
+    }
+    0
+}
+
+/// Replace known locals among an rvalue's operands. Closure captures and
+/// place-based rvalues are left alone. Returns the number replaced.
+fn substitute_constants_rvalue(rvalue: &mut Rvalue, env: &ConstEnv) -> usize {
+    match rvalue {
+        Rvalue::Use(op) | Rvalue::UnaryOp(_, op) | Rvalue::Cast(op, _) => {
+            substitute_constant(op, env)
+        }
+        Rvalue::BinaryOp(_, l, r) => substitute_constant(l, env) + substitute_constant(r, env),
+        Rvalue::Index(_, idx) => substitute_constant(idx, env),
+        Rvalue::Tuple(ops) | Rvalue::Array(ops) | Rvalue::Enum { fields: ops, .. } => {
+            ops.iter_mut().map(|op| substitute_constant(op, env)).sum()
+        }
+        Rvalue::Struct(_, fields) => fields
+            .iter_mut()
+            .map(|(_, op)| substitute_constant(op, env))
+            .sum(),
+        Rvalue::Ref(_, _)
+        | Rvalue::Deref(_)
+        | Rvalue::Discriminant(_)
+        | Rvalue::EnumField(_, _)
+        | Rvalue::Field(_, _)
+        | Rvalue::TupleField(_, _)
+        | Rvalue::Closure { .. }
+        | Rvalue::FnRef(_) => 0,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Pass 3: Copy Propagation
 // ---------------------------------------------------------------------------
 
+/// Copy temps in scope, mapped to the operand they copied.
+type CopySubst = HashMap<Local, Operand>;
+
 /// Eliminate redundant temporaries by replacing uses of a copy with the original.
 ///
 /// Uses block-local forward propagation: each block starts with an empty
 /// substitution map, so reassignments in other blocks cannot cause unsound
 /// cross-block substitutions.
+///
+/// A `Move` into a temp is only propagated when the temp has a single use,
+/// which then moves from the original instead; the original may not be read
+/// again after a move, so it cannot stand in for several uses.
 fn copy_propagate(func: &mut Function, stats: &mut OptStats) {
     let locals = &func.locals;
+    let uses = count_local_uses(&func.blocks);
     let mut total_count = 0usize;
 
     for block in &mut func.blocks {
-        let mut subst: HashMap<Local, Local> = HashMap::new();
+        let mut subst = CopySubst::new();
 
         for stmt in &mut block.stmts {
             // Step 1: Substitute operands in this statement using the current map
@@ -387,17 +735,19 @@ fn copy_propagate(func: &mut Function, stats: &mut OptStats) {
                     let dest_local = *dest;
 
                     // Invalidate any mapping where dest or src equals the assigned local
-                    subst.retain(|d, s| *d != dest_local && *s != dest_local);
+                    subst.retain(|d, s| *d != dest_local && operand_local(s) != Some(dest_local));
 
                     // If this is a simple copy to a compiler temp, add to map
                     if let Rvalue::Use(operand) = rvalue {
                         let dest_idx = dest_local.0 as usize;
                         let is_temp = dest_idx < locals.len() && locals[dest_idx].name.is_none();
-                        if is_temp
-                            && let Operand::Copy(src) | Operand::Local(src) | Operand::Move(src) =
-                                operand
-                        {
-                            subst.insert(dest_local, *src);
+                        let propagates = match operand {
+                            Operand::Copy(_) | Operand::Local(_) => true,
+                            Operand::Move(_) => uses.get(&dest_local) == Some(&1),
+                            Operand::Constant(_) => false,
+                        };
+                        if is_temp && propagates {
+                            subst.insert(dest_local, operand.clone());
                         }
                     }
                 }
@@ -405,7 +755,7 @@ fn copy_propagate(func: &mut Function, stats: &mut OptStats) {
                     // Mutating an element of `local` — invalidate any mapping
                     // involving this local to prevent unsound substitution.
                     let written = *local;
-                    subst.retain(|d, s| *d != written && *s != written);
+                    subst.retain(|d, s| *d != written && operand_local(s) != Some(written));
                 }
                 StatementKind::Nop => {}
             }
@@ -420,8 +770,73 @@ fn copy_propagate(func: &mut Function, stats: &mut OptStats) {
     stats.copies_propagated += total_count;
 }
 
+/// Drop assignments of a plain value to a compiler temp that nothing reads,
+/// such as the copies copy propagation has bypassed. Dropping one copy can
+/// leave the copy it read from unused, so this repeats until nothing changes.
+fn remove_dead_copies(func: &mut Function, stats: &mut OptStats) {
+    loop {
+        let uses = count_local_uses(&func.blocks);
+        let mut removed = 0;
+        for block in &mut func.blocks {
+            for stmt in &mut block.stmts {
+                if let StatementKind::Assign(dest, Rvalue::Use(_)) = &stmt.kind
+                    && func
+                        .locals
+                        .get(dest.0 as usize)
+                        .is_some_and(|decl| decl.name.is_none())
+                    && !uses.contains_key(dest)
+                {
+                    stmt.kind = StatementKind::Nop;
+                    removed += 1;
+                }
+            }
+        }
+        if removed == 0 {
+            break;
+        }
+        stats.dead_copies_removed += removed;
+    }
+}
+
+/// How many times each local is read anywhere in `blocks`, including as the
+/// target of an element assignment or the place of a reference.
+fn count_local_uses(blocks: &[BasicBlock]) -> HashMap<Local, usize> {
+    let mut counts = HashMap::new();
+    for block in blocks {
+        for stmt in &block.stmts {
+            count_operand_uses(&stmt.kind, &mut counts);
+            match &stmt.kind {
+                StatementKind::Assign(
+                    _,
+                    Rvalue::Ref(l, _) | Rvalue::Discriminant(l) | Rvalue::EnumField(l, _),
+                )
+                | StatementKind::IndexAssign(l, _, _) => {
+                    *counts.entry(*l).or_insert(0) += 1;
+                }
+                _ => {}
+            }
+        }
+        let operands: Vec<&Operand> = match &block.terminator {
+            Some(Terminator::Return(op)) => op.iter().collect(),
+            Some(Terminator::If { cond, .. }) => vec![cond],
+            Some(Terminator::Switch { operand, .. }) => vec![operand],
+            Some(Terminator::Call { args, .. }) => args.iter().collect(),
+            Some(Terminator::CallIndirect { callee, args, .. }) => {
+                std::iter::once(callee).chain(args).collect()
+            }
+            Some(Terminator::Spawn { expr, .. }) => vec![expr],
+            Some(Terminator::Await { task, .. }) => vec![task],
+            Some(Terminator::Goto(_) | Terminator::Unreachable) | None => vec![],
+        };
+        for op in operands {
+            count_single_use(op, &mut counts);
+        }
+    }
+    counts
+}
+
 /// Substitute locals in a statement's operands. Returns number of substitutions made.
-fn substitute_stmt(stmt: &mut Statement, subst: &HashMap<Local, Local>) -> usize {
+fn substitute_stmt(stmt: &mut Statement, subst: &CopySubst) -> usize {
     let mut count = 0;
     match &mut stmt.kind {
         StatementKind::Assign(_, rvalue) => {
@@ -436,7 +851,7 @@ fn substitute_stmt(stmt: &mut Statement, subst: &HashMap<Local, Local>) -> usize
     count
 }
 
-fn substitute_rvalue(rvalue: &mut Rvalue, subst: &HashMap<Local, Local>) -> usize {
+fn substitute_rvalue(rvalue: &mut Rvalue, subst: &CopySubst) -> usize {
     let mut count = 0;
     match rvalue {
         Rvalue::Use(op) => count += substitute_operand(op, subst),
@@ -473,39 +888,32 @@ fn substitute_rvalue(rvalue: &mut Rvalue, subst: &HashMap<Local, Local>) -> usiz
                 count += substitute_operand(op, subst);
             }
         }
-        Rvalue::Ref(_, _) | Rvalue::Discriminant(_) | Rvalue::EnumField(_, _) => {
-            // These reference locals directly, not operands — don't substitute
+        Rvalue::Ref(_, _)
+        | Rvalue::Discriminant(_)
+        | Rvalue::EnumField(_, _)
+        | Rvalue::FnRef(_) => {
+            // These reference locals or functions directly, not operands — don't substitute
         }
     }
     count
 }
 
-fn substitute_operand(op: &mut Operand, subst: &HashMap<Local, Local>) -> usize {
-    match op {
-        Operand::Copy(local) => {
-            if let Some(&replacement) = subst.get(local) {
-                *local = replacement;
-                return 1;
-            }
-        }
-        Operand::Local(local) => {
-            if let Some(&replacement) = subst.get(local) {
-                *local = replacement;
-                return 1;
-            }
-        }
-        Operand::Move(local) => {
-            if let Some(&replacement) = subst.get(local) {
-                *local = replacement;
-                return 1;
-            }
-        }
-        Operand::Constant(_) => {}
+fn substitute_operand(op: &mut Operand, subst: &CopySubst) -> usize {
+    let Some(local) = operand_local(op) else {
+        return 0;
+    };
+    match subst.get(&local) {
+        Some(Operand::Move(src)) => *op = Operand::Move(*src),
+        Some(Operand::Copy(src) | Operand::Local(src)) => match op {
+            Operand::Copy(l) | Operand::Local(l) | Operand::Move(l) => *l = *src,
+            Operand::Constant(_) => {}
+        },
+        _ => return 0,
     }
-    0
+    1
 }
 
-fn substitute_terminator(term: &mut Terminator, subst: &HashMap<Local, Local>) -> usize {
+fn substitute_terminator(term: &mut Terminator, subst: &CopySubst) -> usize {
     let mut count = 0;
     match term {
         Terminator::Return(Some(op)) => {
@@ -540,7 +948,7 @@ fn substitute_terminator(term: &mut Terminator, subst: &HashMap<Local, Local>) -
 }
 
 // ---------------------------------------------------------------------------
-// Pass 3: Dead Block Elimination
+// Pass 4: Dead Block Elimination
 // ---------------------------------------------------------------------------
 
 /// Remove unreachable blocks and remap BlockIds.
@@ -607,6 +1015,41 @@ fn dead_block_eliminate(func: &mut Function, stats: &mut OptStats) {
     func.blocks = new_blocks;
 }
 
+/// Merge a block ending in `Goto(bbX)` with bbX when bbX has no other
+/// predecessor, following chains of such blocks. A merged-away block is left
+/// unreachable for the reachability sweep to remove.
+fn merge_blocks(func: &mut Function, stats: &mut OptStats) {
+    let mut predecessors: HashMap<BlockId, usize> = HashMap::new();
+    for term in func.blocks.iter().filter_map(|b| b.terminator.as_ref()) {
+        for succ in terminator_successors(term) {
+            *predecessors.entry(succ).or_insert(0) += 1;
+        }
+    }
+
+    for idx in 0..func.blocks.len() {
+        while let Some(Terminator::Goto(target)) = func.blocks[idx].terminator
+            && target.0 as usize != idx
+            && target != func.entry_block
+            && (target.0 as usize) < func.blocks.len()
+            && predecessors.get(&target) == Some(&1)
+        {
+            // The successor's outgoing edges move to this block, so the
+            // predecessor counts stay the same
+            let successor = &mut func.blocks[target.0 as usize];
+            let stmts = std::mem::take(&mut successor.stmts);
+            let terminator = successor.terminator.replace(Terminator::Unreachable);
+            let terminator_span = successor.terminator_span.take();
+            predecessors.remove(&target);
+
+            let block = &mut func.blocks[idx];
+            block.stmts.extend(stmts);
+            block.terminator = terminator;
+            block.terminator_span = terminator_span;
+            stats.blocks_merged += 1;
+        }
+    }
+}
+
 /// Jump-thread: replace Goto(bbX) where bbX is empty with Goto(bbY) → Goto(bbY).
 fn jump_thread(func: &mut Function) {
     // Build a map of "empty goto" blocks: block with no statements and Goto terminator
@@ -723,7 +1166,7 @@ fn remap_terminator(term: &mut Terminator, remap: &HashMap<BlockId, BlockId>) {
 }
 
 // ---------------------------------------------------------------------------
-// Pass 4: Peephole Optimizations
+// Pass 5: Peephole Optimizations
 // ---------------------------------------------------------------------------
 
 /// Local pattern replacements within a single block.
@@ -785,8 +1228,11 @@ fn try_identity_fold(rvalue: &Rvalue) -> Option<Rvalue> {
                 return Some(Rvalue::Use(right.clone()));
             }
             // x * 0 → 0, 0 * x → 0
-            if is_int_zero(right) || is_int_zero(left) {
-                return Some(Rvalue::Use(Operand::Constant(Constant::Int(0))));
+            if is_int_zero(right) {
+                return Some(Rvalue::Use(right.clone()));
+            }
+            if is_int_zero(left) {
+                return Some(Rvalue::Use(left.clone()));
             }
             None
         }
@@ -802,11 +1248,11 @@ fn try_identity_fold(rvalue: &Rvalue) -> Option<Rvalue> {
 }
 
 fn is_int_zero(op: &Operand) -> bool {
-    matches!(op, Operand::Constant(Constant::Int(0)))
+    matches!(op, Operand::Constant(Constant::Int(0, _)))
 }
 
 fn is_int_one(op: &Operand) -> bool {
-    matches!(op, Operand::Constant(Constant::Int(1)))
+    matches!(op, Operand::Constant(Constant::Int(1, _)))
 }
 
 /// Double negation elimination.
@@ -918,7 +1364,10 @@ fn count_rvalue_uses(rvalue: &Rvalue, counts: &mut HashMap<Local, usize>) {
                 count_single_use(op, counts);
             }
         }
-        Rvalue::Ref(_, _) | Rvalue::Discriminant(_) | Rvalue::EnumField(_, _) => {}
+        Rvalue::Ref(_, _)
+        | Rvalue::Discriminant(_)
+        | Rvalue::EnumField(_, _)
+        | Rvalue::FnRef(_) => {}
     }
 }
 
@@ -975,6 +1424,167 @@ fn peephole_return_temp(
     }
 }
 
+// ---------------------------------------------------------------------------
+// Definite Assignment
+// ---------------------------------------------------------------------------
+
+/// A read of a local that is not assigned on every path reaching it.
+#[derive(Debug, Clone)]
+pub struct UninitializedUse {
+    pub local: Local,
+    /// Source location of the statement or terminator doing the read
+    pub span: Option<Span>,
+}
+
+/// Find reads of locals that may happen before any write.
+///
+/// Forward dataflow over the CFG: a local is definitely assigned at a point
+/// if every path from the entry assigns it first. Parameters start assigned,
+/// as do `Unit` locals, which carry no data to read. Taking a reference does
+/// not count as a read, since the callee may write through it.
+pub fn find_uninitialized_uses(func: &Function) -> Vec<UninitializedUse> {
+    let num_blocks = func.blocks.len();
+    if func.entry_block.0 as usize >= num_blocks {
+        return Vec::new();
+    }
+
+    // Assigned-on-entry sets; None means the block has not been reached yet
+    let mut entry_states: Vec<Option<Vec<bool>>> = vec![None; num_blocks];
+    let mut start: Vec<bool> = func.locals.iter().map(|l| l.ty == Ty::Unit).collect();
+    for (local, _) in &func.params {
+        if let Some(slot) = start.get_mut(local.0 as usize) {
+            *slot = true;
+        }
+    }
+    entry_states[func.entry_block.0 as usize] = Some(start);
+
+    let mut worklist = vec![func.entry_block];
+    while let Some(bid) = worklist.pop() {
+        let block = func.block(bid);
+        let Some(mut state) = entry_states[bid.0 as usize].clone() else {
+            continue;
+        };
+        transfer_block(block, &mut state, &mut |_, _| {});
+        let Some(term) = &block.terminator else {
+            continue;
+        };
+        for succ in terminator_successors(term) {
+            let Some(entry) = entry_states.get_mut(succ.0 as usize) else {
+                continue;
+            };
+            let changed = match entry {
+                None => {
+                    *entry = Some(state.clone());
+                    true
+                }
+                Some(existing) => {
+                    let mut changed = false;
+                    for (e, s) in existing.iter_mut().zip(&state) {
+                        if *e && !*s {
+                            *e = false;
+                            changed = true;
+                        }
+                    }
+                    changed
+                }
+            };
+            if changed {
+                worklist.push(succ);
+            }
+        }
+    }
+
+    let mut uses = Vec::new();
+    for (block, entry) in func.blocks.iter().zip(&entry_states) {
+        let Some(mut state) = entry.clone() else {
+            continue;
+        };
+        transfer_block(block, &mut state, &mut |local, span| {
+            uses.push(UninitializedUse { local, span });
+        });
+    }
+    uses
+}
+
+/// Apply a block's assignments to `state`, reporting reads of unassigned locals.
+fn transfer_block(
+    block: &BasicBlock,
+    state: &mut [bool],
+    report: &mut dyn FnMut(Local, Option<Span>),
+) {
+    let mut read = |local: Local, span: Option<Span>, state: &[bool]| {
+        if !state.get(local.0 as usize).copied().unwrap_or(true) {
+            report(local, span);
+        }
+    };
+
+    for stmt in &block.stmts {
+        match &stmt.kind {
+            StatementKind::Assign(dest, rvalue) => {
+                for local in rvalue_reads(rvalue) {
+                    read(local, stmt.span, state);
+                }
+                if let Some(slot) = state.get_mut(dest.0 as usize) {
+                    *slot = true;
+                }
+            }
+            StatementKind::IndexAssign(target, idx, val) => {
+                for local in [*target]
+                    .into_iter()
+                    .chain(operand_local(idx))
+                    .chain(operand_local(val))
+                {
+                    read(local, stmt.span, state);
+                }
+            }
+            StatementKind::Nop => {}
+        }
+    }
+
+    let Some(term) = &block.terminator else {
+        return;
+    };
+    let span = block.terminator_span;
+    let (operands, dest): (Vec<&Operand>, Option<Local>) = match term {
+        Terminator::Return(op) => (op.iter().collect(), None),
+        Terminator::If { cond, .. } => (vec![cond], None),
+        Terminator::Switch { operand, .. } => (vec![operand], None),
+        Terminator::Call { args, dest, .. } => (args.iter().collect(), *dest),
+        Terminator::CallIndirect {
+            callee, args, dest, ..
+        } => (std::iter::once(callee).chain(args).collect(), *dest),
+        Terminator::Spawn { expr, dest, .. } => (vec![expr], *dest),
+        Terminator::Await { task, dest, .. } => (vec![task], *dest),
+        Terminator::Goto(_) | Terminator::Unreachable => (vec![], None),
+    };
+    for op in operands {
+        if let Some(local) = operand_local(op) {
+            read(local, span, state);
+        }
+    }
+    if let Some(slot) = dest.and_then(|d| state.get_mut(d.0 as usize)) {
+        *slot = true;
+    }
+}
+
+/// Locals an rvalue reads.
+fn rvalue_reads(rvalue: &Rvalue) -> Vec<Local> {
+    let mut counts = HashMap::new();
+    count_rvalue_uses(rvalue, &mut counts);
+    let mut locals: Vec<Local> = counts.into_keys().collect();
+    if let Rvalue::Discriminant(l) | Rvalue::EnumField(l, _) = rvalue {
+        locals.push(*l);
+    }
+    locals
+}
+
+fn operand_local(op: &Operand) -> Option<Local> {
+    match op {
+        Operand::Copy(l) | Operand::Local(l) | Operand::Move(l) => Some(*l),
+        Operand::Constant(_) => None,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -982,19 +1592,21 @@ fn peephole_return_temp(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mir::mir::{LocalDecl, Statement};
+    use crate::mir::mir::{CallConv, LocalDecl, Statement};
     use crate::types::Ty;
 
     /// Helper: make a statement
     fn assign(local: u32, rvalue: Rvalue) -> Statement {
         Statement {
             kind: StatementKind::Assign(Local(local), rvalue),
+            span: None,
         }
     }
 
     fn nop() -> Statement {
         Statement {
             kind: StatementKind::Nop,
+            span: None,
         }
     }
 
@@ -1010,6 +1622,7 @@ mod tests {
             id: BlockId(id),
             stmts,
             terminator: Some(term),
+            terminator_span: None,
         }
     }
 
@@ -1025,6 +1638,12 @@ mod tests {
             entry_block: BlockId(0),
             preconditions: vec![],
             postconditions: vec![],
+            span: None,
+            no_mangle: false,
+            section: None,
+            is_const: false,
+            exported: false,
+            call_conv: CallConv::C,
         }
     }
 
@@ -1032,48 +1651,80 @@ mod tests {
 
     #[test]
     fn test_fold_int_add() {
-        let result = fold_binop(BinOp::Add, &Constant::Int(1), &Constant::Int(2));
-        assert!(matches!(result, Some(Constant::Int(3))));
+        let result = fold_binop(
+            BinOp::Add,
+            &Constant::Int(1, Ty::Int),
+            &Constant::Int(2, Ty::Int),
+        );
+        assert!(matches!(result, Some(Constant::Int(3, Ty::Int))));
     }
 
     #[test]
     fn test_fold_int_sub() {
-        let result = fold_binop(BinOp::Sub, &Constant::Int(10), &Constant::Int(3));
-        assert!(matches!(result, Some(Constant::Int(7))));
+        let result = fold_binop(
+            BinOp::Sub,
+            &Constant::Int(10, Ty::Int),
+            &Constant::Int(3, Ty::Int),
+        );
+        assert!(matches!(result, Some(Constant::Int(7, Ty::Int))));
     }
 
     #[test]
     fn test_fold_int_mul() {
-        let result = fold_binop(BinOp::Mul, &Constant::Int(4), &Constant::Int(5));
-        assert!(matches!(result, Some(Constant::Int(20))));
+        let result = fold_binop(
+            BinOp::Mul,
+            &Constant::Int(4, Ty::Int),
+            &Constant::Int(5, Ty::Int),
+        );
+        assert!(matches!(result, Some(Constant::Int(20, Ty::Int))));
     }
 
     #[test]
     fn test_fold_int_div() {
-        let result = fold_binop(BinOp::Div, &Constant::Int(10), &Constant::Int(3));
-        assert!(matches!(result, Some(Constant::Int(3))));
+        let result = fold_binop(
+            BinOp::Div,
+            &Constant::Int(10, Ty::Int),
+            &Constant::Int(3, Ty::Int),
+        );
+        assert!(matches!(result, Some(Constant::Int(3, Ty::Int))));
     }
 
     #[test]
     fn test_fold_div_by_zero_not_folded() {
-        let result = fold_binop(BinOp::Div, &Constant::Int(10), &Constant::Int(0));
+        let result = fold_binop(
+            BinOp::Div,
+            &Constant::Int(10, Ty::Int),
+            &Constant::Int(0, Ty::Int),
+        );
         assert!(result.is_none());
     }
 
     #[test]
     fn test_fold_rem_by_zero_not_folded() {
-        let result = fold_binop(BinOp::Rem, &Constant::Int(10), &Constant::Int(0));
+        let result = fold_binop(
+            BinOp::Rem,
+            &Constant::Int(10, Ty::Int),
+            &Constant::Int(0, Ty::Int),
+        );
         assert!(result.is_none());
     }
 
     #[test]
     fn test_fold_int_comparison() {
         assert!(matches!(
-            fold_binop(BinOp::Lt, &Constant::Int(1), &Constant::Int(2)),
+            fold_binop(
+                BinOp::Lt,
+                &Constant::Int(1, Ty::Int),
+                &Constant::Int(2, Ty::Int)
+            ),
             Some(Constant::Bool(true))
         ));
         assert!(matches!(
-            fold_binop(BinOp::Ge, &Constant::Int(1), &Constant::Int(2)),
+            fold_binop(
+                BinOp::Ge,
+                &Constant::Int(1, Ty::Int),
+                &Constant::Int(2, Ty::Int)
+            ),
             Some(Constant::Bool(false))
         ));
     }
@@ -1093,8 +1744,8 @@ mod tests {
     #[test]
     fn test_fold_unary_neg() {
         assert!(matches!(
-            fold_unop(UnOp::Neg, &Constant::Int(5)),
-            Some(Constant::Int(-5))
+            fold_unop(UnOp::Neg, &Constant::Int(5, Ty::Int)),
+            Some(Constant::Int(-5, Ty::Int))
         ));
     }
 
@@ -1108,14 +1759,53 @@ mod tests {
 
     #[test]
     fn test_fold_overflow_not_folded() {
-        let result = fold_binop(BinOp::Add, &Constant::Int(i64::MAX), &Constant::Int(1));
+        let result = fold_binop(
+            BinOp::Add,
+            &Constant::Int(i64::MAX, Ty::Int),
+            &Constant::Int(1, Ty::Int),
+        );
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_fold_keeps_sized_int_type() {
+        let result = fold_binop(
+            BinOp::Add,
+            &Constant::Int(100, Ty::I8),
+            &Constant::Int(27, Ty::Int),
+        );
+        assert!(matches!(result, Some(Constant::Int(127, Ty::I8))));
+        // Out of range for the type: left to the runtime
+        let result = fold_binop(
+            BinOp::Add,
+            &Constant::Int(100, Ty::I8),
+            &Constant::Int(28, Ty::Int),
+        );
         assert!(result.is_none());
+        assert!(matches!(
+            fold_unop(UnOp::BitNot, &Constant::Int(0, Ty::U8)),
+            Some(Constant::Int(0xFF, Ty::U8))
+        ));
     }
 
     #[test]
     fn test_fold_invalid_shift_not_folded() {
-        assert!(fold_binop(BinOp::Shl, &Constant::Int(1), &Constant::Int(64)).is_none());
-        assert!(fold_binop(BinOp::Shr, &Constant::Int(1), &Constant::Int(-1)).is_none());
+        assert!(
+            fold_binop(
+                BinOp::Shl,
+                &Constant::Int(1, Ty::Int),
+                &Constant::Int(64, Ty::Int)
+            )
+            .is_none()
+        );
+        assert!(
+            fold_binop(
+                BinOp::Shr,
+                &Constant::Int(1, Ty::Int),
+                &Constant::Int(-1, Ty::Int)
+            )
+            .is_none()
+        );
     }
 
     // ---- Constant Folding: full pass on function ----
@@ -1127,8 +1817,8 @@ mod tests {
             0,
             Rvalue::BinaryOp(
                 BinOp::Add,
-                Operand::Constant(Constant::Int(10)),
-                Operand::Constant(Constant::Int(20)),
+                Operand::Constant(Constant::Int(10, Ty::Int)),
+                Operand::Constant(Constant::Int(20, Ty::Int)),
             ),
         )];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(0)))));
@@ -1139,8 +1829,10 @@ mod tests {
         assert_eq!(stats.constants_folded, 1);
 
         // Check the statement was replaced
-        if let StatementKind::Assign(_, Rvalue::Use(Operand::Constant(Constant::Int(30)))) =
-            &func.blocks[0].stmts[0].kind
+        if let StatementKind::Assign(
+            _,
+            Rvalue::Use(Operand::Constant(Constant::Int(30, Ty::Int))),
+        ) = &func.blocks[0].stmts[0].kind
         {
             // good
         } else {
@@ -1164,12 +1856,12 @@ mod tests {
             make_block(
                 1,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(1)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(1, Ty::Int)))),
             ),
             make_block(
                 2,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(2)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(2, Ty::Int)))),
             ),
         ];
         let mut func = make_function(locals, blocks);
@@ -1183,6 +1875,237 @@ mod tests {
         ));
     }
 
+    // ---- Constant Propagation ----
+
+    fn bool_local(name: Option<&str>) -> LocalDecl {
+        LocalDecl {
+            ty: Ty::Bool,
+            name: name.map(|s| s.to_string()),
+        }
+    }
+
+    /// bb0 branches on `cond` to bb1 and bb2, which assign `_1` and join in
+    /// bb3: `_2 = Add(_1, 1); return _2`. `_0` is a Bool.
+    fn diamond(cond: Operand, then_value: i64, else_value: i64) -> Function {
+        let add_one = |id| {
+            make_block(
+                id,
+                vec![assign(
+                    2,
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        Operand::Copy(Local(1)),
+                        Operand::Constant(Constant::Int(1, Ty::Int)),
+                    ),
+                )],
+                Terminator::Return(Some(Operand::Copy(Local(2)))),
+            )
+        };
+        let set = |id, value| {
+            make_block(
+                id,
+                vec![assign(
+                    1,
+                    Rvalue::Use(Operand::Constant(Constant::Int(value, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(3)),
+            )
+        };
+        let blocks = vec![
+            make_block(
+                0,
+                vec![],
+                Terminator::If {
+                    cond,
+                    then_block: BlockId(1),
+                    else_block: BlockId(2),
+                },
+            ),
+            set(1, then_value),
+            set(2, else_value),
+            add_one(3),
+        ];
+        make_function(
+            vec![
+                bool_local(Some("flag")),
+                make_local(Some("x")),
+                make_local(None),
+            ],
+            blocks,
+        )
+    }
+
+    fn assigned_constant(func: &Function, block: usize, stmt: usize) -> Option<i64> {
+        match &func.blocks[block].stmts[stmt].kind {
+            StatementKind::Assign(_, Rvalue::Use(Operand::Constant(Constant::Int(n, _)))) => {
+                Some(*n)
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_const_prop_across_blocks() {
+        // bb0: _1 = const 5; goto bb1
+        // bb1: _2 = Add(_1, const 3); return _2
+        let blocks = vec![
+            make_block(
+                0,
+                vec![assign(
+                    1,
+                    Rvalue::Use(Operand::Constant(Constant::Int(5, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(1)),
+            ),
+            make_block(
+                1,
+                vec![assign(
+                    2,
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        Operand::Copy(Local(1)),
+                        Operand::Constant(Constant::Int(3, Ty::Int)),
+                    ),
+                )],
+                Terminator::Return(Some(Operand::Copy(Local(2)))),
+            ),
+        ];
+        let locals = vec![make_local(None), make_local(None), make_local(None)];
+        let mut func = make_function(locals, blocks);
+
+        let mut stats = OptStats::default();
+        propagate_constants(&mut func, &mut stats);
+
+        assert_eq!(assigned_constant(&func, 1, 0), Some(8));
+        assert!(matches!(
+            func.blocks[1].terminator,
+            Some(Terminator::Return(Some(Operand::Constant(Constant::Int(
+                8,
+                Ty::Int
+            )))))
+        ));
+        assert_eq!(stats.constants_propagated, 2);
+    }
+
+    #[test]
+    fn test_const_prop_join_of_differing_values_is_unknown() {
+        let mut func = diamond(Operand::Copy(Local(0)), 5, 6);
+        func.params = vec![(Local(0), Ty::Bool)];
+        let mut stats = OptStats::default();
+        propagate_constants(&mut func, &mut stats);
+        assert_eq!(assigned_constant(&func, 3, 0), None);
+        assert_eq!(stats.constants_propagated, 0);
+
+        // Both sides agree, so the join keeps the value
+        let mut func = diamond(Operand::Copy(Local(0)), 5, 5);
+        func.params = vec![(Local(0), Ty::Bool)];
+        propagate_constants(&mut func, &mut OptStats::default());
+        assert_eq!(assigned_constant(&func, 3, 0), Some(6));
+    }
+
+    #[test]
+    fn test_const_prop_ignores_untaken_branch() {
+        // `_0 = true` makes bb2 unreachable, so its `_1 = 6` never joins
+        let mut func = diamond(Operand::Copy(Local(0)), 5, 6);
+        func.blocks[0].stmts.push(assign(
+            0,
+            Rvalue::Use(Operand::Constant(Constant::Bool(true))),
+        ));
+        propagate_constants(&mut func, &mut OptStats::default());
+        assert_eq!(assigned_constant(&func, 3, 0), Some(6));
+        assert!(matches!(
+            func.blocks[0].terminator,
+            Some(Terminator::If {
+                cond: Operand::Constant(Constant::Bool(true)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_const_prop_loop_variable_is_unknown() {
+        // bb0: _1 = 0; goto bb1
+        // bb1: if _0 then bb2 else bb3
+        // bb2: _1 = Add(_1, 1); goto bb1
+        // bb3: return _1
+        let blocks = vec![
+            make_block(
+                0,
+                vec![assign(
+                    1,
+                    Rvalue::Use(Operand::Constant(Constant::Int(0, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(1)),
+            ),
+            make_block(
+                1,
+                vec![],
+                Terminator::If {
+                    cond: Operand::Copy(Local(0)),
+                    then_block: BlockId(2),
+                    else_block: BlockId(3),
+                },
+            ),
+            make_block(
+                2,
+                vec![assign(
+                    1,
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        Operand::Copy(Local(1)),
+                        Operand::Constant(Constant::Int(1, Ty::Int)),
+                    ),
+                )],
+                Terminator::Goto(BlockId(1)),
+            ),
+            make_block(3, vec![], Terminator::Return(Some(Operand::Copy(Local(1))))),
+        ];
+        let mut func = make_function(
+            vec![bool_local(Some("more")), make_local(Some("i"))],
+            blocks,
+        );
+        func.params = vec![(Local(0), Ty::Bool)];
+
+        let mut stats = OptStats::default();
+        propagate_constants(&mut func, &mut stats);
+        assert_eq!(stats.constants_propagated, 0);
+        assert!(matches!(
+            func.blocks[3].terminator,
+            Some(Terminator::Return(Some(Operand::Copy(Local(1)))))
+        ));
+    }
+
+    #[test]
+    fn test_const_prop_call_argument_is_unknown_after_call() {
+        // The callee may take `_1` by `ref mut`
+        let blocks = vec![
+            make_block(
+                0,
+                vec![assign(
+                    1,
+                    Rvalue::Use(Operand::Constant(Constant::Int(5, Ty::Int))),
+                )],
+                Terminator::Call {
+                    func: "bump".to_string(),
+                    args: vec![Operand::Copy(Local(1))],
+                    arg_pass_modes: vec![crate::mir::mir::PassMode::RefMut],
+                    dest: None,
+                    next: BlockId(1),
+                },
+            ),
+            make_block(1, vec![], Terminator::Return(Some(Operand::Copy(Local(1))))),
+        ];
+        let mut func = make_function(vec![make_local(None), make_local(Some("n"))], blocks);
+
+        let mut stats = OptStats::default();
+        propagate_constants(&mut func, &mut stats);
+        assert_eq!(stats.constants_propagated, 0);
+        assert!(matches!(
+            &func.blocks[0].terminator,
+            Some(Terminator::Call { args, .. }) if matches!(args[0], Operand::Copy(Local(1)))
+        ));
+    }
+
     // ---- Copy Propagation ----
 
     #[test]
@@ -1264,7 +2187,10 @@ mod tests {
         ];
         let stmts = vec![
             assign(1, Rvalue::Use(Operand::Copy(Local(0)))),
-            assign(0, Rvalue::Use(Operand::Constant(Constant::Int(999)))),
+            assign(
+                0,
+                Rvalue::Use(Operand::Constant(Constant::Int(999, Ty::Int))),
+            ),
         ];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(1)))));
         let mut func = make_function(locals, vec![block]);
@@ -1334,7 +2260,10 @@ mod tests {
         ];
         let stmts = vec![
             assign(1, Rvalue::Use(Operand::Copy(Local(0)))),
-            assign(1, Rvalue::Use(Operand::Constant(Constant::Int(42)))),
+            assign(
+                1,
+                Rvalue::Use(Operand::Constant(Constant::Int(42, Ty::Int))),
+            ),
             assign(2, Rvalue::Use(Operand::Copy(Local(1)))),
         ];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(2)))));
@@ -1405,9 +2334,10 @@ mod tests {
             Statement {
                 kind: StatementKind::IndexAssign(
                     Local(0),
-                    Operand::Constant(Constant::Int(0)),
-                    Operand::Constant(Constant::Int(999)),
+                    Operand::Constant(Constant::Int(0, Ty::Int)),
+                    Operand::Constant(Constant::Int(999, Ty::Int)),
                 ),
+                span: None,
             },
         ];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(1)))));
@@ -1431,6 +2361,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_copy_chain_collapses_in_mir_dump() {
+        // _1 = _0; _2 = _1; _3 = _2; _4 = _3 Mul _3; return _4
+        let locals = vec![
+            make_local(Some("x")),
+            make_local(None),
+            make_local(None),
+            make_local(None),
+            make_local(None),
+        ];
+        let stmts = vec![
+            assign(1, Rvalue::Use(Operand::Copy(Local(0)))),
+            assign(2, Rvalue::Use(Operand::Copy(Local(1)))),
+            assign(3, Rvalue::Use(Operand::Copy(Local(2)))),
+            assign(
+                4,
+                Rvalue::BinaryOp(BinOp::Mul, Operand::Copy(Local(3)), Operand::Copy(Local(3))),
+            ),
+        ];
+        let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(4)))));
+        let mut func = make_function(locals, vec![block]);
+        func.params = vec![(Local(0), Ty::Int)];
+
+        let mut program = Program::new();
+        program.functions.insert("test".to_string(), func);
+        let stats = optimize(&mut program);
+        assert_eq!(stats.dead_copies_removed, 3);
+
+        let dump = program.functions["test"].to_string();
+        assert!(dump.contains("_4 = copy _0 Mul copy _0"), "{}", dump);
+        for copy in ["_1 =", "_2 =", "_3 ="] {
+            assert!(!dump.contains(copy), "{}", dump);
+        }
+    }
+
+    #[test]
+    fn test_copy_prop_move_needs_single_use() {
+        // _1 = move _0; _2 = _1 Add 1 → _2 = move _0 Add 1
+        let add_one = |operand| {
+            Rvalue::BinaryOp(
+                BinOp::Add,
+                operand,
+                Operand::Constant(Constant::Int(1, Ty::Int)),
+            )
+        };
+        let locals = vec![make_local(Some("x")), make_local(None), make_local(None)];
+        let stmts = vec![
+            assign(1, Rvalue::Use(Operand::Move(Local(0)))),
+            assign(2, add_one(Operand::Copy(Local(1)))),
+        ];
+        let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(2)))));
+        let mut func = make_function(locals.clone(), vec![block]);
+        copy_propagate(&mut func, &mut OptStats::default());
+        assert!(matches!(
+            func.blocks[0].stmts[1].kind,
+            StatementKind::Assign(_, Rvalue::BinaryOp(_, Operand::Move(Local(0)), _))
+        ));
+
+        // A second use would read `_0` after it was moved, so `_1` stays
+        let stmts = vec![
+            assign(1, Rvalue::Use(Operand::Move(Local(0)))),
+            assign(2, add_one(Operand::Copy(Local(1)))),
+        ];
+        let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(1)))));
+        let mut func = make_function(locals, vec![block]);
+        let mut stats = OptStats::default();
+        copy_propagate(&mut func, &mut stats);
+        assert_eq!(stats.copies_propagated, 0);
+    }
+
     // ---- Dead Block Elimination ----
 
     #[test]
@@ -1440,12 +2440,12 @@ mod tests {
             make_block(
                 0,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(0)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(0, Ty::Int)))),
             ),
             make_block(
                 1,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(1)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(1, Ty::Int)))),
             ), // unreachable
         ];
         let mut func = make_function(locals, blocks);
@@ -1465,12 +2465,12 @@ mod tests {
             make_block(
                 1,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(1)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(1, Ty::Int)))),
             ),
             make_block(
                 2,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(2)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(2, Ty::Int)))),
             ),
         ];
         let mut func = make_function(locals, blocks);
@@ -1498,7 +2498,7 @@ mod tests {
             make_block(
                 2,
                 vec![],
-                Terminator::Return(Some(Operand::Constant(Constant::Int(0)))),
+                Terminator::Return(Some(Operand::Constant(Constant::Int(0, Ty::Int)))),
             ),
         ];
         let mut func = make_function(locals, blocks);
@@ -1513,6 +2513,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_merge_goto_chain() {
+        // bb0 → bb1 → bb2 → bb3, each the only predecessor of the next
+        let step = |id: u32, value: i64| {
+            make_block(
+                id,
+                vec![assign(
+                    0,
+                    Rvalue::Use(Operand::Constant(Constant::Int(value, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(id + 1)),
+            )
+        };
+        let blocks = vec![
+            step(0, 1),
+            step(1, 2),
+            step(2, 3),
+            make_block(3, vec![], Terminator::Return(Some(Operand::Copy(Local(0))))),
+        ];
+        let mut func = make_function(vec![make_local(Some("x"))], blocks);
+
+        let mut stats = OptStats::default();
+        merge_blocks(&mut func, &mut stats);
+        dead_block_eliminate(&mut func, &mut stats);
+        assert_eq!(stats.blocks_merged, 3);
+        assert_eq!(func.blocks.len(), 1);
+        assert_eq!(func.blocks[0].stmts.len(), 3);
+        assert!(matches!(
+            func.blocks[0].terminator,
+            Some(Terminator::Return(Some(Operand::Copy(Local(0)))))
+        ));
+    }
+
+    #[test]
+    fn test_merge_skips_shared_targets() {
+        // bb3 is reached from both arms, so neither arm merges into it
+        let blocks = vec![
+            make_block(
+                0,
+                vec![],
+                Terminator::If {
+                    cond: Operand::Copy(Local(0)),
+                    then_block: BlockId(1),
+                    else_block: BlockId(2),
+                },
+            ),
+            make_block(1, vec![nop()], Terminator::Goto(BlockId(3))),
+            make_block(2, vec![nop()], Terminator::Goto(BlockId(3))),
+            make_block(3, vec![], Terminator::Return(None)),
+        ];
+        let mut func = make_function(vec![make_local(Some("flag"))], blocks);
+
+        let mut stats = OptStats::default();
+        merge_blocks(&mut func, &mut stats);
+        assert_eq!(stats.blocks_merged, 0);
+        assert!(matches!(
+            func.blocks[1].terminator,
+            Some(Terminator::Goto(BlockId(3)))
+        ));
+        assert!(matches!(
+            func.blocks[3].terminator,
+            Some(Terminator::Return(None))
+        ));
+    }
+
     // ---- Peephole ----
 
     #[test]
@@ -1520,7 +2585,7 @@ mod tests {
         let locals = vec![make_local(None)];
         let stmts = vec![
             nop(),
-            assign(0, Rvalue::Use(Operand::Constant(Constant::Int(1)))),
+            assign(0, Rvalue::Use(Operand::Constant(Constant::Int(1, Ty::Int)))),
             nop(),
         ];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(0)))));
@@ -1540,14 +2605,14 @@ mod tests {
             Rvalue::BinaryOp(
                 BinOp::Add,
                 Operand::Copy(Local(0)),
-                Operand::Constant(Constant::Int(0)),
+                Operand::Constant(Constant::Int(0, Ty::Int)),
             ),
         )];
         // Use a non-temp return so return-temp peephole doesn't also fire
         let block = make_block(
             0,
             stmts,
-            Terminator::Return(Some(Operand::Constant(Constant::Int(99)))),
+            Terminator::Return(Some(Operand::Constant(Constant::Int(99, Ty::Int)))),
         );
         let mut func = make_function(locals, vec![block]);
 
@@ -1571,14 +2636,14 @@ mod tests {
             1,
             Rvalue::BinaryOp(
                 BinOp::Mul,
-                Operand::Constant(Constant::Int(1)),
+                Operand::Constant(Constant::Int(1, Ty::Int)),
                 Operand::Copy(Local(0)),
             ),
         )];
         let block = make_block(
             0,
             stmts,
-            Terminator::Return(Some(Operand::Constant(Constant::Int(99)))),
+            Terminator::Return(Some(Operand::Constant(Constant::Int(99, Ty::Int)))),
         );
         let mut func = make_function(locals, vec![block]);
 
@@ -1603,13 +2668,13 @@ mod tests {
             Rvalue::BinaryOp(
                 BinOp::Mul,
                 Operand::Copy(Local(0)),
-                Operand::Constant(Constant::Int(0)),
+                Operand::Constant(Constant::Int(0, Ty::Int)),
             ),
         )];
         let block = make_block(
             0,
             stmts,
-            Terminator::Return(Some(Operand::Constant(Constant::Int(99)))),
+            Terminator::Return(Some(Operand::Constant(Constant::Int(99, Ty::Int)))),
         );
         let mut func = make_function(locals, vec![block]);
 
@@ -1617,7 +2682,7 @@ mod tests {
         peephole(&mut func, &mut stats);
         assert_eq!(stats.peepholes_applied, 1);
 
-        if let StatementKind::Assign(_, Rvalue::Use(Operand::Constant(Constant::Int(0)))) =
+        if let StatementKind::Assign(_, Rvalue::Use(Operand::Constant(Constant::Int(0, Ty::Int)))) =
             &func.blocks[0].stmts[0].kind
         {
             // good
@@ -1630,7 +2695,10 @@ mod tests {
     fn test_peephole_return_temp() {
         // Assign(_1, Use(Constant(42))); Return(Copy(_1)) → Return(Constant(42))
         let locals = vec![make_local(None), make_local(None)];
-        let stmts = vec![assign(1, Rvalue::Use(Operand::Constant(Constant::Int(42))))];
+        let stmts = vec![assign(
+            1,
+            Rvalue::Use(Operand::Constant(Constant::Int(42, Ty::Int))),
+        )];
         let block = make_block(0, stmts, Terminator::Return(Some(Operand::Copy(Local(1)))));
         let mut func = make_function(locals, vec![block]);
 
@@ -1638,7 +2706,7 @@ mod tests {
         peephole(&mut func, &mut stats);
         assert!(stats.peepholes_applied > 0);
 
-        if let Some(Terminator::Return(Some(Operand::Constant(Constant::Int(42))))) =
+        if let Some(Terminator::Return(Some(Operand::Constant(Constant::Int(42, Ty::Int))))) =
             &func.blocks[0].terminator
         {
             // good
@@ -1658,8 +2726,8 @@ mod tests {
                 0,
                 Rvalue::BinaryOp(
                     BinOp::Add,
-                    Operand::Constant(Constant::Int(1)),
-                    Operand::Constant(Constant::Int(2)),
+                    Operand::Constant(Constant::Int(1, Ty::Int)),
+                    Operand::Constant(Constant::Int(2, Ty::Int)),
                 ),
             )],
             Terminator::Return(Some(Operand::Copy(Local(0)))),
@@ -1692,7 +2760,7 @@ mod tests {
         let blocks = vec![make_block(
             0,
             vec![],
-            Terminator::Return(Some(Operand::Constant(Constant::Int(0)))),
+            Terminator::Return(Some(Operand::Constant(Constant::Int(0, Ty::Int)))),
         )];
         let func = make_function(locals, blocks);
 
@@ -1713,7 +2781,7 @@ mod tests {
         let blocks = vec![make_block(
             0,
             vec![],
-            Terminator::Return(Some(Operand::Constant(Constant::Int(0)))),
+            Terminator::Return(Some(Operand::Constant(Constant::Int(0, Ty::Int)))),
         )];
         let mut func = make_function(locals, blocks);
         func.entry_block = BlockId(99);
@@ -1737,4 +2805,142 @@ mod tests {
         let errors = validate_mir(&program);
         assert!(!errors.is_empty());
     }
+
+    // ---- Definite Assignment ----
+
+    fn copy(local: u32) -> Operand {
+        Operand::Copy(Local(local))
+    }
+
+    #[test]
+    fn test_read_before_write_is_reported() {
+        // _1 = _0 + 1 with _0 never assigned
+        let locals = vec![make_local(Some("x")), make_local(None)];
+        let blocks = vec![make_block(
+            0,
+            vec![assign(
+                1,
+                Rvalue::BinaryOp(
+                    BinOp::Add,
+                    copy(0),
+                    Operand::Constant(Constant::Int(1, Ty::Int)),
+                ),
+            )],
+            Terminator::Return(Some(copy(1))),
+        )];
+        let uses = find_uninitialized_uses(&make_function(locals, blocks));
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].local, Local(0));
+    }
+
+    #[test]
+    fn test_assignment_on_one_branch_is_reported() {
+        let locals = vec![make_local(Some("x"))];
+        let blocks = vec![
+            make_block(
+                0,
+                vec![],
+                Terminator::If {
+                    cond: Operand::Constant(Constant::Bool(true)),
+                    then_block: BlockId(1),
+                    else_block: BlockId(2),
+                },
+            ),
+            make_block(
+                1,
+                vec![assign(
+                    0,
+                    Rvalue::Use(Operand::Constant(Constant::Int(1, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(3)),
+            ),
+            make_block(2, vec![], Terminator::Goto(BlockId(3))),
+            make_block(3, vec![], Terminator::Return(Some(copy(0)))),
+        ];
+        let uses = find_uninitialized_uses(&make_function(locals, blocks));
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].local, Local(0));
+    }
+
+    #[test]
+    fn test_assignment_on_every_path_is_accepted() {
+        // Assigned on both branches, then read in a loop that reassigns it
+        let locals = vec![make_local(Some("x")), make_local(None)];
+        let blocks = vec![
+            make_block(
+                0,
+                vec![],
+                Terminator::If {
+                    cond: Operand::Constant(Constant::Bool(true)),
+                    then_block: BlockId(1),
+                    else_block: BlockId(2),
+                },
+            ),
+            make_block(
+                1,
+                vec![assign(
+                    0,
+                    Rvalue::Use(Operand::Constant(Constant::Int(1, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(3)),
+            ),
+            make_block(
+                2,
+                vec![assign(
+                    0,
+                    Rvalue::Use(Operand::Constant(Constant::Int(2, Ty::Int))),
+                )],
+                Terminator::Goto(BlockId(3)),
+            ),
+            make_block(
+                3,
+                vec![assign(
+                    1,
+                    Rvalue::BinaryOp(
+                        BinOp::Lt,
+                        copy(0),
+                        Operand::Constant(Constant::Int(10, Ty::Int)),
+                    ),
+                )],
+                Terminator::If {
+                    cond: copy(1),
+                    then_block: BlockId(4),
+                    else_block: BlockId(5),
+                },
+            ),
+            make_block(
+                4,
+                vec![assign(
+                    0,
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        copy(0),
+                        Operand::Constant(Constant::Int(1, Ty::Int)),
+                    ),
+                )],
+                Terminator::Goto(BlockId(3)),
+            ),
+            make_block(5, vec![], Terminator::Return(Some(copy(0)))),
+        ];
+        assert!(find_uninitialized_uses(&make_function(locals, blocks)).is_empty());
+    }
+
+    #[test]
+    fn test_params_and_unit_locals_count_as_assigned() {
+        let locals = vec![
+            make_local(Some("n")),
+            LocalDecl {
+                ty: Ty::Unit,
+                name: None,
+            },
+        ];
+        let blocks = vec![make_block(
+            0,
+            vec![assign(1, Rvalue::Use(copy(1)))],
+            Terminator::Return(Some(copy(0))),
+        )];
+        let mut func = make_function(locals, blocks);
+        func.params = vec![(Local(0), Ty::Int)];
+        assert!(find_uninitialized_uses(&func).is_empty());
+    }
 }
@@ -0,0 +1,177 @@
+//! Source maps from native code back to FORMA lines.
+//!
+//! A [`SourceMap`] lists each function compiled from a file with its native
+//! symbol and, for every basic block, the range of source lines its
+//! statements came from. Block labels match the `bb_N` blocks the LLVM
+//! backend emits, so tools that don't read DWARF can still attribute samples
+//! or coverage counters to FORMA source.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::lexer::Span;
+use crate::parser::{ImplItem, ItemKind, SourceFile, TypeKind};
+
+use super::mir::{Function, Program};
+
+/// JSON sidecar written by `forma build --emit sourcemap`.
+#[derive(Debug, Serialize)]
+pub struct SourceMap {
+    pub version: u32,
+    pub file: String,
+    pub functions: Vec<FunctionMap>,
+}
+
+/// Line ranges for one function.
+#[derive(Debug, Serialize)]
+pub struct FunctionMap {
+    pub name: String,
+    pub symbol: String,
+    pub line: usize,
+    pub end_line: usize,
+    pub blocks: Vec<BlockMap>,
+}
+
+/// Line range covered by one basic block.
+#[derive(Debug, Serialize)]
+pub struct BlockMap {
+    pub label: String,
+    pub line: usize,
+    pub end_line: usize,
+}
+
+impl SourceMap {
+    /// Map the functions of `program` that are defined in `ast`, the parsed
+    /// contents of `file`. Functions pulled in from imported modules are left
+    /// out because their spans point into other files.
+    pub fn new(file: &str, source: &str, ast: &SourceFile, program: &Program) -> Self {
+        let defined = defined_functions(ast);
+        let mut functions: Vec<FunctionMap> = program
+            .functions
+            .iter()
+            .filter(|(name, _)| defined.contains(name.as_str()))
+            .filter_map(|(name, func)| map_function(name, func, source))
+            .collect();
+        functions.sort_by(|a, b| (a.line, &a.name).cmp(&(b.line, &b.name)));
+
+        Self {
+            version: 1,
+            file: file.to_string(),
+            functions,
+        }
+    }
+}
+
+/// Program keys of the functions and methods declared in `ast`.
+fn defined_functions(ast: &SourceFile) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for item in &ast.items {
+        match &item.kind {
+            ItemKind::Function(f) if f.body.is_some() => {
+                names.insert(f.name.name.clone());
+            }
+            ItemKind::Impl(impl_block) => {
+                let TypeKind::Path(path) = &impl_block.self_type.kind else {
+                    continue;
+                };
+                let type_name = path
+                    .segments
+                    .iter()
+                    .map(|s| s.name.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join("::");
+                for impl_item in &impl_block.items {
+                    if let ImplItem::Function(f) = impl_item {
+                        names.insert(format!("{}::{}", type_name, f.name.name));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+fn map_function(name: &str, func: &Function, source: &str) -> Option<FunctionMap> {
+    let line_range = |span: Span| (span.line, end_line(source, span));
+
+    let blocks: Vec<BlockMap> = func
+        .blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| {
+            let (line, end_line) = block
+                .stmts
+                .iter()
+                .filter_map(|stmt| stmt.span)
+                .chain(block.terminator_span)
+                .map(line_range)
+                .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))?;
+            Some(BlockMap {
+                label: format!("bb_{}", i),
+                line,
+                end_line,
+            })
+        })
+        .collect();
+
+    let (line, end_line) = func
+        .span
+        .map(line_range)
+        .into_iter()
+        .chain(blocks.iter().map(|b| (b.line, b.end_line)))
+        .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))?;
+
+    Some(FunctionMap {
+        name: name.to_string(),
+        symbol: func.symbol(name),
+        line,
+        end_line,
+        blocks,
+    })
+}
+
+/// Last line holding part of `span`, ignoring trailing whitespace that
+/// spans often include up to the next item.
+fn end_line(source: &str, span: Span) -> usize {
+    match source.get(span.start..span.end) {
+        Some(text) => span.line + text.trim_end().matches('\n').count(),
+        None => span.line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lowerer, Parser, Scanner};
+
+    #[test]
+    fn test_two_function_source_map() {
+        let source = "f double(n: Int) -> Int\n    m := n * 2\n    m\n\nf main() -> Int\n    x := double(21)\n    print(x)\n    0\n";
+        let (tokens, _) = Scanner::new(source).scan_all();
+        let ast = Parser::new(&tokens).parse().unwrap();
+        let program = Lowerer::new().lower(&ast).unwrap();
+        let map = SourceMap::new("two.forma", source, &ast, &program);
+
+        assert_eq!(map.file, "two.forma");
+        let names: Vec<&str> = map.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["double", "main"]);
+
+        let double = &map.functions[0];
+        assert_eq!(double.symbol, "_F6double");
+        assert_eq!((double.line, double.end_line), (1, 3));
+        let main = &map.functions[1];
+        assert_eq!(main.symbol, "main");
+        assert_eq!((main.line, main.end_line), (5, 8));
+        assert_eq!(main.blocks[0].label, "bb_0");
+        for block in double.blocks.iter().chain(&main.blocks) {
+            assert!(block.line <= block.end_line);
+        }
+        assert!(
+            main.blocks
+                .iter()
+                .any(|b| (b.line..=b.end_line).contains(&6))
+        );
+    }
+}
@@ -4,12 +4,14 @@
 //! It supports the `us` (use) statement syntax:
 //! - `us std.core` -> looks for `std/core.forma`
 //! - `us my_module` -> looks for `my_module.forma`
+//! - `us my_module.helper` -> imports `helper` from `my_module.forma` when
+//!   there is no `my_module/helper.forma`
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use crate::lexer::{Scanner, Span};
-use crate::parser::{Item, ItemKind, Parser, SourceFile, UseTree};
+use crate::parser::{Item, ItemKind, Parser, SourceFile, UseTree, Visibility};
 
 /// Error during module loading.
 #[derive(Debug, Clone)]
@@ -243,6 +245,59 @@ impl ModuleLoader {
         })
     }
 
+    /// Resolve the file for a `us` path.
+    ///
+    /// A path that does not name a module file may name an item inside one
+    /// (`us lib.helper`); the item's name is then returned with the file.
+    fn find_use_target(
+        &self,
+        module_path: &[String],
+    ) -> Result<(PathBuf, Option<String>), ModuleError> {
+        match self.find_module_file(module_path) {
+            Ok(path) => Ok((path, None)),
+            Err(e) => {
+                if let Some((item, parent)) = module_path.split_last()
+                    && !parent.is_empty()
+                    && let Ok(path) = self.find_module_file(parent)
+                {
+                    return Ok((path, Some(item.clone())));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Check that an item imported by name is declared `pub` in its (loaded) module.
+    fn check_imported_item(
+        &self,
+        path: &Path,
+        module_path: &[String],
+        item: Option<&str>,
+    ) -> Result<(), ModuleError> {
+        let Some(item) = item else {
+            return Ok(());
+        };
+        let module_name = module_path[..module_path.len() - 1].join(".");
+        let declared = self.loaded.get(path).and_then(|m| {
+            m.items
+                .iter()
+                .find(|i| item_name(i) == Some(item))
+                .map(item_visibility)
+        });
+        let message = match declared {
+            Some(Visibility::Private) => {
+                format!("'{}' is private to module '{}'", item, module_name)
+            }
+            Some(_) => return Ok(()),
+            None => format!("module '{}' has no item named '{}'", module_name, item),
+        };
+        Err(ModuleError {
+            message,
+            path: Some(path.to_path_buf()),
+            span: None,
+        })
+    }
+
     /// Load all modules referenced by use statements in the given AST.
     /// Returns the combined items from all loaded modules, including transitive imports.
     pub fn load_imports(&mut self, ast: &SourceFile) -> Result<Vec<Item>, ModuleError> {
@@ -255,11 +310,15 @@ impl ModuleLoader {
                 Self::extract_use_paths(&use_item.tree, &[], &mut paths);
 
                 for module_path in paths {
-                    let file_path = self.find_module_file(&module_path).map_err(|mut e| {
-                        e.span = Some(import_span);
-                        e
-                    })?;
+                    let (file_path, item) =
+                        self.find_use_target(&module_path).map_err(|mut e| {
+                            e.span = Some(import_span);
+                            e
+                        })?;
                     self.load_module_recursive(&file_path, &mut all_imported_items)
+                        .and_then(|()| {
+                            self.check_imported_item(&file_path, &module_path, item.as_deref())
+                        })
                         .map_err(|mut e| {
                             if e.span.is_none() {
                                 e.span = Some(import_span);
@@ -273,6 +332,13 @@ impl ModuleLoader {
         Ok(all_imported_items)
     }
 
+    /// Paths of every module loaded so far, in sorted order.
+    pub fn loaded_paths(&self) -> Vec<&Path> {
+        let mut paths: Vec<&Path> = self.loaded.keys().map(PathBuf::as_path).collect();
+        paths.sort();
+        paths
+    }
+
     /// Recursively load a module and its transitive imports.
     /// Uses the `loading` set for cycle detection during transitive resolution.
     fn load_module_recursive(
@@ -322,14 +388,16 @@ impl ModuleLoader {
                 let mut paths = Vec::new();
                 Self::extract_use_paths(&use_decl.tree, &[], &mut paths);
                 for module_path in paths {
-                    let dep_path = match self.find_module_file(&module_path) {
-                        Ok(p) => p,
+                    let (dep_path, item) = match self.find_use_target(&module_path) {
+                        Ok(target) => target,
                         Err(e) => {
                             self.loading.remove(&path_buf);
                             return Err(e);
                         }
                     };
-                    if let Err(e) = self.load_module_recursive(&dep_path, items) {
+                    if let Err(e) = self.load_module_recursive(&dep_path, items).and_then(|()| {
+                        self.check_imported_item(&dep_path, &module_path, item.as_deref())
+                    }) {
                         self.loading.remove(&path_buf);
                         return Err(e);
                     }
@@ -381,6 +449,36 @@ impl ModuleLoader {
     }
 }
 
+/// The name an item declares, if any.
+fn item_name(item: &Item) -> Option<&str> {
+    let name = match &item.kind {
+        ItemKind::Function(f) => &f.name,
+        ItemKind::Struct(s) => &s.name,
+        ItemKind::Enum(e) => &e.name,
+        ItemKind::Trait(t) => &t.name,
+        ItemKind::TypeAlias(t) => &t.name,
+        ItemKind::Module(m) => &m.name,
+        ItemKind::Const(c) => &c.name,
+        ItemKind::Impl(_) | ItemKind::Use(_) => return None,
+    };
+    Some(&name.name)
+}
+
+/// The visibility an item was declared with.
+fn item_visibility(item: &Item) -> Visibility {
+    match &item.kind {
+        ItemKind::Function(f) => f.visibility,
+        ItemKind::Struct(s) => s.visibility,
+        ItemKind::Enum(e) => e.visibility,
+        ItemKind::Trait(t) => t.visibility,
+        // Type aliases take no visibility modifier, so they are always importable
+        ItemKind::TypeAlias(_) => Visibility::Public,
+        ItemKind::Module(m) => m.visibility,
+        ItemKind::Const(c) => c.visibility,
+        ItemKind::Impl(_) | ItemKind::Use(_) => Visibility::Private,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -511,4 +609,58 @@ mod tests {
             "lex error should not poison cycle detection"
         );
     }
+
+    #[test]
+    fn test_import_single_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+
+        write_temp_file(base, "lib.forma", "pub f double(x: Int) -> Int = x * 2\n");
+        write_temp_file(
+            base,
+            "main.forma",
+            "us lib.double\nf main() -> Int = double(21)\n",
+        );
+
+        let main_path = base.join("main.forma");
+        let mut loader = ModuleLoader::from_source_file(&main_path);
+        let ast = loader
+            .load_with_dependencies(&main_path)
+            .expect("importing an item by name should succeed");
+        assert!(ast.items.iter().any(|i| item_name(i) == Some("double")));
+    }
+
+    #[test]
+    fn test_import_unknown_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+
+        write_temp_file(base, "lib.forma", "f double(x: Int) -> Int = x * 2\n");
+        write_temp_file(base, "main.forma", "us lib.triple\nf main() -> Int = 0\n");
+
+        let main_path = base.join("main.forma");
+        let mut loader = ModuleLoader::from_source_file(&main_path);
+        let err = loader.load_with_dependencies(&main_path).unwrap_err();
+        assert_eq!(err.message, "module 'lib' has no item named 'triple'");
+        assert!(err.span.is_some());
+    }
+
+    #[test]
+    fn test_import_private_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+
+        write_temp_file(
+            base,
+            "lib.forma",
+            "f secret() -> Int = 7\npub f reveal() -> Int = secret()\n",
+        );
+        write_temp_file(base, "main.forma", "us lib.secret\nf main() -> Int = 0\n");
+
+        let main_path = base.join("main.forma");
+        let mut loader = ModuleLoader::from_source_file(&main_path);
+        let err = loader.load_with_dependencies(&main_path).unwrap_err();
+        assert_eq!(err.message, "'secret' is private to module 'lib'");
+        assert_eq!(err.span.map(|s| s.line), Some(1));
+    }
 }
@@ -2,24 +2,26 @@
 //!
 //! This module defines all AST nodes that represent parsed FORMA programs.
 
+use serde::Serialize;
+
 use crate::lexer::Span;
 
 /// A complete source file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SourceFile {
     pub items: Vec<Item>,
     pub span: Span,
 }
 
 /// Top-level items in a source file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Item {
     pub kind: ItemKind,
     pub attrs: Vec<Attribute>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ItemKind {
     Function(Function),
     Struct(Struct),
@@ -33,14 +35,14 @@ pub enum ItemKind {
 }
 
 /// An attribute like `@test` or `@derive(Debug, Clone)`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Attribute {
     pub name: Ident,
     pub args: Vec<AttrArg>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AttrArg {
     pub name: Ident,
     pub value: Option<Literal>,
@@ -50,7 +52,7 @@ pub struct AttrArg {
 }
 
 /// A contract (precondition or postcondition) for a function.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Contract {
     /// The condition expression that must be true
     pub condition: Box<Expr>,
@@ -62,7 +64,7 @@ pub struct Contract {
 }
 
 /// A function definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Function {
     pub name: Ident,
     pub generics: Option<Generics>,
@@ -71,7 +73,11 @@ pub struct Function {
     pub body: Option<FnBody>,
     pub is_async: bool,
     pub is_unsafe: bool,
+    /// `const f`: evaluable at compile time, in constant initializers
+    pub is_const: bool,
     pub visibility: Visibility,
+    /// ABI of an `extern "C"` declaration, which has no body
+    pub abi: Option<String>,
     /// Preconditions (@pre) - checked at function entry
     pub preconditions: Vec<Contract>,
     /// Postconditions (@post) - checked at function exit
@@ -79,7 +85,7 @@ pub struct Function {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum FnBody {
     /// Single expression: `f foo -> Int = 42`
     Expr(Box<Expr>),
@@ -88,7 +94,7 @@ pub enum FnBody {
 }
 
 /// How a parameter is passed: by value (owned), by shared reference, or by mutable reference.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub enum PassMode {
     /// Pass by value (default)
     #[default]
@@ -99,7 +105,7 @@ pub enum PassMode {
     RefMut,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Param {
     pub name: Ident,
     pub ty: Type,
@@ -109,7 +115,7 @@ pub struct Param {
 }
 
 /// A struct definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Struct {
     pub name: Ident,
     pub generics: Option<Generics>,
@@ -118,7 +124,7 @@ pub struct Struct {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum StructKind {
     /// Named fields: `s Point { x: Int, y: Int }`
     Named(Vec<Field>),
@@ -128,7 +134,7 @@ pub enum StructKind {
     Unit,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Field {
     pub name: Ident,
     pub ty: Type,
@@ -138,7 +144,7 @@ pub struct Field {
 }
 
 /// An enum definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Enum {
     pub name: Ident,
     pub generics: Option<Generics>,
@@ -147,14 +153,14 @@ pub struct Enum {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Variant {
     pub name: Ident,
     pub kind: VariantKind,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum VariantKind {
     /// Unit variant: `None`
     Unit,
@@ -165,7 +171,7 @@ pub enum VariantKind {
 }
 
 /// A trait definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Trait {
     pub name: Ident,
     pub generics: Option<Generics>,
@@ -176,14 +182,14 @@ pub struct Trait {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum TraitItem {
     Function(Function),
     TypeAlias(TypeAlias),
 }
 
 /// An impl block.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Impl {
     pub generics: Option<Generics>,
     pub trait_: Option<Type>,
@@ -194,14 +200,14 @@ pub struct Impl {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ImplItem {
     Function(Function),
     TypeAlias(TypeAlias),
 }
 
 /// A type alias: `type Meters = Int`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeAlias {
     pub name: Ident,
     pub generics: Option<Generics>,
@@ -210,13 +216,13 @@ pub struct TypeAlias {
 }
 
 /// A use/import statement.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Use {
     pub tree: UseTree,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum UseTree {
     /// `us std.io`
     Path(Vec<Ident>, Option<Box<UseTree>>),
@@ -229,7 +235,7 @@ pub enum UseTree {
 }
 
 /// A module definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Module {
     pub name: Ident,
     pub items: Option<Vec<Item>>,
@@ -238,7 +244,7 @@ pub struct Module {
 }
 
 /// A constant definition: `PI :: 3.14159`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Const {
     pub name: Ident,
     pub ty: Option<Type>,
@@ -251,45 +257,45 @@ pub struct Const {
 // Generics
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Generics {
     pub params: Vec<GenericParam>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum GenericParam {
     Type(TypeParam),
     Const(ConstParam),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeParam {
     pub name: Ident,
     pub bounds: Vec<TypeBound>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ConstParam {
     pub name: Ident,
     pub ty: Type,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeBound {
     pub path: TypePath,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct WhereClause {
     pub predicates: Vec<WherePredicate>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct WherePredicate {
     pub ty: Type,
     pub bounds: Vec<TypeBound>,
@@ -301,7 +307,7 @@ pub struct WherePredicate {
 // ============================================================================
 
 /// Linearity qualifier for types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub enum Linearity {
     /// Regular type (can be copied and dropped freely)
     #[default]
@@ -312,7 +318,7 @@ pub enum Linearity {
     Affine,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Type {
     pub kind: TypeKind,
     pub span: Span,
@@ -340,7 +346,7 @@ impl Type {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum TypeKind {
     /// Named type: `Int`, `Vec[T]`
     Path(TypePath),
@@ -370,36 +376,36 @@ pub enum TypeKind {
     Never,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypePath {
     pub segments: Vec<TypePathSegment>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypePathSegment {
     pub name: Ident,
     pub args: Option<GenericArgs>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GenericArgs {
     pub args: Vec<GenericArg>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum GenericArg {
     Type(Type),
-    Expr(Expr),
+    Expr(Box<Expr>),
 }
 
 // ============================================================================
 // Expressions
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Expr {
     pub kind: ExprKind,
     pub span: Span,
@@ -410,9 +416,80 @@ impl Expr {
     pub fn new(kind: ExprKind, span: Span) -> Self {
         Self { kind, span }
     }
+
+    /// Collect the arguments of every `old(...)` call in this expression.
+    pub fn collect_old_args<'a>(&'a self, out: &mut Vec<&'a Expr>) {
+        match &self.kind {
+            ExprKind::Call(callee, args) => {
+                if let ExprKind::Ident(name) = &callee.kind
+                    && name.name == "old"
+                    && args.len() == 1
+                {
+                    out.push(&args[0].value);
+                }
+                callee.collect_old_args(out);
+                for arg in args {
+                    arg.value.collect_old_args(out);
+                }
+            }
+            ExprKind::Binary(left, _, right)
+            | ExprKind::Coalesce(left, right)
+            | ExprKind::Pipeline(left, right)
+            | ExprKind::Index(left, right)
+            | ExprKind::Assign(left, right, _) => {
+                left.collect_old_args(out);
+                right.collect_old_args(out);
+            }
+            ExprKind::AssignOp(left, _, right) => {
+                left.collect_old_args(out);
+                right.collect_old_args(out);
+            }
+            ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleField(inner, _)
+            | ExprKind::Try(inner)
+            | ExprKind::Await(inner)
+            | ExprKind::Spawn(inner)
+            | ExprKind::Paren(inner)
+            | ExprKind::Cast(inner, _) => inner.collect_old_args(out),
+            ExprKind::MethodCall(receiver, _, args) => {
+                receiver.collect_old_args(out);
+                for arg in args {
+                    arg.value.collect_old_args(out);
+                }
+            }
+            ExprKind::Closure(closure) => closure.body.collect_old_args(out),
+            ExprKind::Tuple(items) | ExprKind::Array(items) => {
+                for item in items {
+                    item.collect_old_args(out);
+                }
+            }
+            ExprKind::ArrayRepeat(value, count) => {
+                value.collect_old_args(out);
+                count.collect_old_args(out);
+            }
+            ExprKind::MapOrSet(entries) => {
+                for entry in entries {
+                    entry.key.collect_old_args(out);
+                    if let Some(value) = &entry.value {
+                        value.collect_old_args(out);
+                    }
+                }
+            }
+            ExprKind::Range(start, end, _) => {
+                if let Some(start) = start {
+                    start.collect_old_args(out);
+                }
+                if let Some(end) = end {
+                    end.collect_old_args(out);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ExprKind {
     /// Literal: `42`, `"hello"`, `true`
     Literal(Literal),
@@ -448,10 +525,10 @@ pub enum ExprKind {
     If(Box<IfExpr>),
     /// Match expression
     Match(Box<Expr>, Vec<MatchArm>),
-    /// For loop with optional label: `'label: fo x in iter`
-    For(Option<Ident>, Pattern, Box<Expr>, Block),
-    /// While loop with optional label: `'label: wh cond`
-    While(Option<Ident>, Box<Expr>, Block),
+    /// For loop with optional label and `@invariant`s: `'label: fo x in iter`
+    For(Option<Ident>, Pattern, Box<Expr>, Block, Vec<Contract>),
+    /// While loop with optional label and `@invariant`s: `'label: wh cond`
+    While(Option<Ident>, Box<Expr>, Block, Vec<Contract>),
     /// While-let: `wh Some(x) = iter.next`
     WhileLet(Option<Ident>, Pattern, Box<Expr>, Block),
     /// Infinite loop with optional label: `'label: lp`
@@ -490,13 +567,13 @@ pub enum ExprKind {
     Assign(Box<Expr>, Box<Expr>, bool),
     /// Compound assignment: `x += 1`
     AssignOp(Box<Expr>, BinOp, Box<Expr>),
-    /// Cast: `x as T` (not in FORMA v2 grammar, placeholder)
+    /// Cast: `x as T` or `T(x)`
     Cast(Box<Expr>, Type),
     /// Unsafe block
     Unsafe(Block),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct IfExpr {
     pub condition: Expr,
     pub then_branch: IfBranch,
@@ -504,7 +581,7 @@ pub struct IfExpr {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum IfBranch {
     /// Inline: `if cond then expr`
     Expr(Box<Expr>),
@@ -512,7 +589,7 @@ pub enum IfBranch {
     Block(Block),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ElseBranch {
     /// `else expr`
     Expr(Box<Expr>),
@@ -522,7 +599,7 @@ pub enum ElseBranch {
     ElseIf(Box<IfExpr>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MatchArm {
     pub pattern: Pattern,
     pub guard: Option<Expr>,
@@ -530,7 +607,7 @@ pub struct MatchArm {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Closure {
     pub params: Vec<ClosureParam>,
     pub return_type: Option<Type>,
@@ -538,28 +615,28 @@ pub struct Closure {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ClosureParam {
     pub name: Ident,
     pub ty: Option<Type>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MapEntry {
     pub key: Expr,
     pub value: Option<Expr>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FieldInit {
     pub name: Ident,
     pub value: Option<Expr>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Arg {
     pub name: Option<Ident>,
     pub value: Expr,
@@ -567,11 +644,95 @@ pub struct Arg {
     pub span: Span,
 }
 
+/// Split a `print` format string into the literal text around its `{}`
+/// placeholders, so `n` placeholders give `n + 1` segments. `{{` and `}}`
+/// stand for literal braces.
+pub fn split_format_string(fmt: &str) -> Result<Vec<String>, String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                current.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                segments.push(std::mem::take(&mut current));
+            }
+            ('{', _) => {
+                return Err("invalid format string: expected '}' after '{'".to_string());
+            }
+            ('}', _) => return Err("invalid format string: unmatched '}'".to_string()),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    Ok(segments)
+}
+
+/// Match call arguments to parameters. Positional arguments fill parameters
+/// in order and named arguments fill the parameter with that name. Returns,
+/// for each parameter, the index of the argument bound to it, or `None` if
+/// the call omits it. Errors carry a message and the offending argument's
+/// span.
+pub fn bind_call_args(
+    fn_name: &str,
+    params: &[String],
+    args: &[Arg],
+) -> Result<Vec<Option<usize>>, (String, Span)> {
+    let mut slots = vec![None; params.len()];
+    let mut seen_named = false;
+    for (i, arg) in args.iter().enumerate() {
+        let slot = match &arg.name {
+            None if seen_named => {
+                return Err((
+                    "positional argument after named arguments".to_string(),
+                    arg.span,
+                ));
+            }
+            None if i >= params.len() => {
+                return Err((
+                    format!(
+                        "function '{}' takes at most {} argument(s), found {}",
+                        fn_name,
+                        params.len(),
+                        args.len()
+                    ),
+                    arg.span,
+                ));
+            }
+            None => i,
+            Some(name) => {
+                seen_named = true;
+                params.iter().position(|p| *p == name.name).ok_or_else(|| {
+                    (
+                        format!(
+                            "function '{}' has no parameter named '{}'",
+                            fn_name, name.name
+                        ),
+                        name.span,
+                    )
+                })?
+            }
+        };
+        if slots[slot].is_some() {
+            return Err((
+                format!("argument '{}' given more than once", params[slot]),
+                arg.span,
+            ));
+        }
+        slots[slot] = Some(i);
+    }
+    Ok(slots)
+}
+
 // ============================================================================
 // Operators
 // ============================================================================
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum BinOp {
     // Arithmetic
     Add,
@@ -597,8 +758,9 @@ pub enum BinOp {
     Shr,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum UnaryOp {
+    Plus,   // +
     Neg,    // -
     Not,    // !
     Ref,    // &
@@ -610,13 +772,13 @@ pub enum UnaryOp {
 // Patterns
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Pattern {
     pub kind: PatternKind,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum PatternKind {
     /// Wildcard: `_`
     Wildcard,
@@ -640,7 +802,7 @@ pub enum PatternKind {
     Rest,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PatternField {
     pub name: Ident,
     pub pattern: Option<Pattern>,
@@ -651,19 +813,19 @@ pub struct PatternField {
 // Statements
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Block {
     pub stmts: Vec<Stmt>,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Stmt {
     pub kind: StmtKind,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum StmtKind {
     /// Item declaration (function, struct, etc.)
     Item(Item),
@@ -675,7 +837,7 @@ pub enum StmtKind {
     Empty,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LetStmt {
     pub pattern: Pattern,
     pub ty: Option<Type>,
@@ -689,7 +851,7 @@ pub struct LetStmt {
 // ============================================================================
 
 /// An identifier.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Ident {
     pub name: String,
     pub span: Span,
@@ -705,14 +867,14 @@ impl Ident {
 }
 
 /// A path like `std.io.read`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Path {
     pub segments: Vec<Ident>,
     pub span: Span,
 }
 
 /// Visibility modifier.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub enum Visibility {
     #[default]
     Private,
@@ -721,18 +883,38 @@ pub enum Visibility {
 }
 
 /// A literal value.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Literal {
     pub kind: LiteralKind,
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum LiteralKind {
     Int(i128),
     Float(f64),
     String(String),
+    /// Byte string `b"..."`
+    Bytes(Vec<u8>),
     Char(char),
     Bool(bool),
     None,
 }
+
+/// Render bytes as a `b"..."` literal, escaping anything outside printable ASCII.
+pub fn byte_string_source(bytes: &[u8]) -> String {
+    let mut out = String::from("b\"");
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02X}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -5,6 +5,7 @@
 
 pub mod ast;
 pub mod parser;
+pub mod precedence;
 
 pub use ast::*;
 pub use parser::Parser;
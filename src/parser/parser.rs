@@ -6,12 +6,20 @@
 use crate::errors::{ParseError, Result};
 use crate::lexer::{FStringPart, Span, Token, TokenKind};
 use crate::parser::ast::*;
+use crate::parser::precedence::{Assoc, binary_op};
+
+/// Default limit on how deeply expressions may nest before parsing fails.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
 /// The parser for FORMA source code.
 pub struct Parser<'a> {
     tokens: &'a [Token],
     pos: usize,
     errors: Vec<crate::errors::CompileError>,
+    /// Current expression nesting depth
+    depth: usize,
+    /// Maximum expression nesting depth
+    max_depth: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -20,9 +28,17 @@ impl<'a> Parser<'a> {
             tokens,
             pos: 0,
             errors: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
+    /// Set the maximum expression nesting depth.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
     /// Parse a complete source file.
     /// Uses error recovery to continue parsing after errors and report multiple issues.
     pub fn parse(mut self) -> std::result::Result<SourceFile, Vec<crate::errors::CompileError>> {
@@ -68,9 +84,19 @@ impl<'a> Parser<'a> {
         // Check for visibility
         let vis = self.parse_visibility()?;
 
-        // Check for async/unsafe modifiers
+        // Check for const/async/unsafe modifiers; `const` is contextual and
+        // only a modifier when a function follows
+        let is_const = self.check_contextual("const")
+            && matches!(self.peek_kind(1), Some(TokenKind::Ident(name)) if name == "f");
+        if is_const {
+            self.advance();
+        }
         let is_async = self.match_token(TokenKind::As);
         let is_unsafe = self.match_token(TokenKind::Un);
+        let abi = self.parse_extern_abi()?;
+        if abi.is_some() && !self.is_function_keyword() {
+            return Err(self.error("expected `f` after `extern \"C\"`"));
+        }
 
         // Use contextual keyword checks for single-letter keywords (f, s, e, t, i)
         // These are now emitted as Ident tokens and distinguished by lookahead
@@ -96,6 +122,28 @@ impl<'a> Parser<'a> {
             return Err(self.error("expected item (f, s, e, t, i, type, us, md)"));
         };
 
+        if let ItemKind::Function(ref mut func) = kind {
+            match (&abi, &func.body) {
+                (Some(_), Some(_)) => {
+                    return Err(ParseError::new(
+                        format!("extern function `{}` cannot have a body", func.name.name),
+                        func.span,
+                    )
+                    .into());
+                }
+                (None, None) => {
+                    return Err(ParseError::new(
+                        format!("function `{}` has no body", func.name.name),
+                        func.span,
+                    )
+                    .with_help("declare foreign functions with `extern \"C\" f name(...)`")
+                    .into());
+                }
+                _ => func.abi = abi,
+            }
+            func.is_const = is_const;
+        }
+
         let remaining_attrs = if let ItemKind::Function(ref mut func) = kind {
             let mut keep = Vec::new();
             for attr in attrs {
@@ -177,7 +225,7 @@ impl<'a> Parser<'a> {
         let name = self.parse_ident()?;
 
         // Check for contract attributes that take expression arguments
-        let is_contract = name.name == "pre" || name.name == "post";
+        let is_contract = name.name == "pre" || name.name == "post" || name.name == "invariant";
 
         let args = if self.match_token(TokenKind::LParen) {
             if is_contract {
@@ -191,11 +239,115 @@ impl<'a> Parser<'a> {
             Vec::new()
         };
 
-        Ok(Attribute {
-            name,
-            args,
-            span: start.merge(self.previous_span()),
-        })
+        let span = start.merge(self.previous_span());
+        if name.name == "section" {
+            Self::check_section_args(&args, span)?;
+        }
+        if name.name == "visibility" {
+            Self::check_visibility_args(&args, span)?;
+        }
+        if name.name == "callconv" {
+            Self::check_callconv_args(&args, span)?;
+        }
+
+        Ok(Attribute { name, args, span })
+    }
+
+    /// `@section("name")` takes exactly one non-empty string naming an object
+    /// file section; whitespace and NUL would produce an unusable section.
+    fn check_section_args(args: &[AttrArg], span: Span) -> Result<()> {
+        let name = match args {
+            [
+                AttrArg {
+                    expr: Some(expr), ..
+                },
+            ] => match &expr.kind {
+                ExprKind::Literal(Literal {
+                    kind: LiteralKind::String(name),
+                    ..
+                }) => Some((name, expr.span)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match name {
+            Some((name, span))
+                if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c == '\0') =>
+            {
+                Err(ParseError::new(format!("invalid section name \"{}\"", name), span).into())
+            }
+            Some(_) => Ok(()),
+            None => {
+                Err(ParseError::new("@section requires a single string section name", span).into())
+            }
+        }
+    }
+
+    /// `@visibility("default")` or `@visibility("hidden")` sets the symbol
+    /// visibility of a function in native builds.
+    fn check_visibility_args(args: &[AttrArg], span: Span) -> Result<()> {
+        match args {
+            [
+                AttrArg {
+                    expr: Some(expr), ..
+                },
+            ] => match &expr.kind {
+                ExprKind::Literal(Literal {
+                    kind: LiteralKind::String(name),
+                    ..
+                }) if name == "default" || name == "hidden" => Ok(()),
+                ExprKind::Literal(Literal {
+                    kind: LiteralKind::String(name),
+                    ..
+                }) => Err(ParseError::new(
+                    format!(
+                        "unknown visibility \"{}\" (expected \"default\" or \"hidden\")",
+                        name
+                    ),
+                    expr.span,
+                )
+                .into()),
+                _ => Err(
+                    ParseError::new("@visibility requires \"default\" or \"hidden\"", span).into(),
+                ),
+            },
+            _ => {
+                Err(ParseError::new("@visibility requires \"default\" or \"hidden\"", span).into())
+            }
+        }
+    }
+
+    /// `@callconv("C")` or `@callconv("fast")` picks the calling convention of
+    /// a function in native builds.
+    fn check_callconv_args(args: &[AttrArg], span: Span) -> Result<()> {
+        let name = match args {
+            [
+                AttrArg {
+                    expr: Some(expr), ..
+                },
+            ] => match &expr.kind {
+                ExprKind::Literal(Literal {
+                    kind: LiteralKind::String(name),
+                    ..
+                }) => Some((name, expr.span)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match name {
+            Some((name, _)) if name == "C" || name == "fast" => Ok(()),
+            Some((name, span)) => Err(ParseError::new(
+                format!(
+                    "unknown calling convention \"{}\" (expected \"C\" or \"fast\")",
+                    name
+                ),
+                span,
+            )
+            .into()),
+            None => {
+                Err(ParseError::new("@callconv requires a calling convention name", span).into())
+            }
+        }
     }
 
     /// Parse contract attribute arguments: @pre(condition) or @pre(condition, "message")
@@ -914,6 +1066,7 @@ impl<'a> Parser<'a> {
             LiteralKind::Int(n) => n.to_string(),
             LiteralKind::Float(f) => f.to_string(),
             LiteralKind::String(s) => format!("{:?}", s),
+            LiteralKind::Bytes(bytes) => byte_string_source(bytes),
             LiteralKind::Char(c) => format!("{:?}", c),
             LiteralKind::Bool(b) => b.to_string(),
             LiteralKind::None => "None".to_string(),
@@ -979,13 +1132,36 @@ impl<'a> Parser<'a> {
             body,
             is_async,
             is_unsafe,
+            is_const: false,
             visibility: vis,
+            abi: None,
             preconditions: Vec::new(),
             postconditions: Vec::new(),
             span: start.merge(self.previous_span()),
         }))
     }
 
+    /// Parse an optional `extern "C"` prefix on a foreign function declaration.
+    fn parse_extern_abi(&mut self) -> Result<Option<String>> {
+        if !self.check_contextual("extern") {
+            return Ok(None);
+        }
+        let abi = match self.peek_kind(1) {
+            Some(TokenKind::String(abi)) => abi.clone(),
+            _ => return Ok(None),
+        };
+        if abi != "C" {
+            self.advance();
+            return Err(self.error(format!(
+                "unsupported ABI \"{}\"; only \"C\" is supported",
+                abi
+            )));
+        }
+        self.advance();
+        self.advance();
+        Ok(Some(abi))
+    }
+
     fn parse_fn_params(&mut self) -> Result<Vec<Param>> {
         if !self.match_token(TokenKind::LParen) {
             return Ok(Vec::new());
@@ -998,6 +1174,10 @@ impl<'a> Parser<'a> {
                 if !self.match_token(TokenKind::Comma) {
                     break;
                 }
+                // Allow trailing comma
+                if self.check(TokenKind::RParen) {
+                    break;
+                }
             }
         }
         self.expect(TokenKind::RParen)?;
@@ -1126,6 +1306,10 @@ impl<'a> Parser<'a> {
                         if !self.match_token(TokenKind::Comma) {
                             break;
                         }
+                        // Allow trailing comma
+                        if self.check(TokenKind::RParen) {
+                            break;
+                        }
                     }
                     self.expect(TokenKind::RParen)?;
                     StructKind::Tuple(types)
@@ -1308,6 +1492,10 @@ impl<'a> Parser<'a> {
                     if !self.match_token(TokenKind::Comma) {
                         break;
                     }
+                    // Allow trailing comma
+                    if self.check(TokenKind::RParen) {
+                        break;
+                    }
                 }
                 self.expect(TokenKind::RParen)?;
                 VariantKind::Tuple(types)
@@ -1681,6 +1869,10 @@ impl<'a> Parser<'a> {
                 if !self.match_token(TokenKind::Comma) {
                     break;
                 }
+                // Allow trailing comma
+                if self.check(TokenKind::RBracket) {
+                    break;
+                }
             }
         }
 
@@ -2006,6 +2198,10 @@ impl<'a> Parser<'a> {
                 if !self.match_token(TokenKind::Comma) {
                     break;
                 }
+                // Allow trailing comma
+                if self.check(TokenKind::RBracket) {
+                    break;
+                }
             }
         }
 
@@ -2034,7 +2230,23 @@ impl<'a> Parser<'a> {
     // ========================================================================
 
     pub fn parse_expr(&mut self) -> Result<Expr> {
-        self.parse_assignment()
+        self.parse_nested(Self::parse_assignment)
+    }
+
+    /// Run `parse` one nesting level deeper, failing once the limit is
+    /// exceeded so that pathological input is reported instead of
+    /// overflowing the stack.
+    fn parse_nested(&mut self, parse: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        if self.depth >= self.max_depth {
+            return Err(self.error(format!(
+                "maximum expression nesting depth exceeded (limit is {})",
+                self.max_depth
+            )));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
     fn parse_assignment(&mut self) -> Result<Expr> {
@@ -2101,10 +2313,10 @@ impl<'a> Parser<'a> {
 
     fn parse_coalesce(&mut self) -> Result<Expr> {
         let start = self.current_span();
-        let mut expr = self.parse_or()?;
+        let mut expr = self.parse_binary(0)?;
 
         while self.match_token(TokenKind::QuestionQuestion) {
-            let right = self.parse_or()?;
+            let right = self.parse_binary(0)?;
             expr = Expr {
                 kind: ExprKind::Coalesce(Box::new(expr), Box::new(right)),
                 span: start.merge(self.previous_span()),
@@ -2114,45 +2326,21 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn parse_or(&mut self) -> Result<Expr> {
+    /// Parse binary operators by precedence climbing over
+    /// [`binary_op`](super::precedence::binary_op), keeping only operators
+    /// that bind at least as tightly as `min_precedence`. Operands are casts
+    /// and prefix expressions.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
         let start = self.current_span();
-        let mut expr = self.parse_and()?;
+        let mut expr = self.parse_cast()?;
         let mut total_indent_count = 0;
 
-        while self.match_token(TokenKind::PipePipe) {
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_and()?;
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), BinOp::Or, Box::new(right)),
-                span: start.merge(self.previous_span()),
-            };
-        }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
+        while let Some(info) = self.current_kind().as_ref().and_then(binary_op) {
+            if info.precedence < min_precedence {
+                break;
             }
-        }
-
-        Ok(expr)
-    }
-
-    fn parse_and(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-        let mut expr = self.parse_comparison()?;
-        let mut total_indent_count = 0;
+            self.advance();
 
-        while self.match_token(TokenKind::AmpAmp) {
             // Skip newlines and indentation after binary operator to allow continuation
             while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
                 if self.check(TokenKind::Indent) {
@@ -2160,175 +2348,27 @@ impl<'a> Parser<'a> {
                 }
                 self.advance();
             }
-            let right = self.parse_comparison()?;
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), BinOp::And, Box::new(right)),
-                span: start.merge(self.previous_span()),
-            };
-        }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
-            }
-        }
-
-        Ok(expr)
-    }
-
-    fn parse_comparison(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-        let mut expr = self.parse_bitor()?;
-
-        let op = if self.match_token(TokenKind::EqEq) {
-            Some(BinOp::Eq)
-        } else if self.match_token(TokenKind::BangEq) {
-            Some(BinOp::Ne)
-        } else if self.match_token(TokenKind::Lt) {
-            Some(BinOp::Lt)
-        } else if self.match_token(TokenKind::LtEq) {
-            Some(BinOp::Le)
-        } else if self.match_token(TokenKind::Gt) {
-            Some(BinOp::Gt)
-        } else if self.match_token(TokenKind::GtEq) {
-            Some(BinOp::Ge)
-        } else {
-            None
-        };
-
-        if let Some(op) = op {
-            // Skip newlines and indentation after binary operator to allow continuation
-            let mut indent_count = 0;
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_bitor()?;
-            // Skip matching dedents and newlines
-            for _ in 0..indent_count {
-                while self.check(TokenKind::Newline) {
-                    self.advance();
-                }
-                if self.check(TokenKind::Dedent) {
-                    self.advance();
-                }
-            }
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), op, Box::new(right)),
-                span: start.merge(self.previous_span()),
+            let right_min = match info.assoc {
+                Assoc::Right => info.precedence,
+                Assoc::Left | Assoc::None => info.precedence + 1,
             };
-        }
-
-        Ok(expr)
-    }
-
-    fn parse_bitor(&mut self) -> Result<Expr> {
-        // Note: | is used for pipeline (higher in precedence chain).
-        // Consuming | here as BitOr would prevent pipeline from ever seeing it.
-        // BitOr requires a different syntax (e.g., |> for pipeline) to be enabled.
-        self.parse_bitxor()
-    }
-
-    fn parse_bitxor(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-        let mut expr = self.parse_bitand()?;
-        let mut total_indent_count = 0;
-
-        while self.match_token(TokenKind::Caret) {
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_bitand()?;
+            let right = self.parse_binary(right_min)?;
             expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), BinOp::BitXor, Box::new(right)),
+                kind: ExprKind::Binary(Box::new(expr), info.op, Box::new(right)),
                 span: start.merge(self.previous_span()),
             };
-        }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
-            }
-        }
 
-        Ok(expr)
-    }
-
-    fn parse_bitand(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-        let mut expr = self.parse_shift()?;
-        let mut total_indent_count = 0;
-
-        // & in binary position (after left operand) is BitAnd.
-        // & in unary/prefix position is Ref, handled in parse_unary.
-        while self.match_token(TokenKind::Amp) {
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_shift()?;
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), BinOp::BitAnd, Box::new(right)),
-                span: start.merge(self.previous_span()),
-            };
-        }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
-            }
-        }
-
-        Ok(expr)
-    }
-
-    fn parse_shift(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-        let mut expr = self.parse_additive()?;
-        let mut total_indent_count = 0;
-
-        loop {
-            let op = if self.match_token(TokenKind::LtLt) {
-                BinOp::Shl
-            } else if self.match_token(TokenKind::GtGt) {
-                BinOp::Shr
-            } else {
-                break;
-            };
-
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
+            if info.assoc == Assoc::None
+                && self
+                    .current_kind()
+                    .as_ref()
+                    .and_then(binary_op)
+                    .is_some_and(|next| next.precedence == info.precedence)
+            {
+                return Err(
+                    self.error("comparison operators cannot be chained; combine them with &&")
+                );
             }
-            let right = self.parse_additive()?;
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), op, Box::new(right)),
-                span: start.merge(self.previous_span()),
-            };
         }
 
         // Skip matching dedents at the end of expression
@@ -2344,95 +2384,34 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn parse_additive(&mut self) -> Result<Expr> {
+    /// Parse `expr as Type`, binding tighter than binary operators but
+    /// looser than unary ones.
+    fn parse_cast(&mut self) -> Result<Expr> {
         let start = self.current_span();
-        let mut expr = self.parse_multiplicative()?;
-        let mut total_indent_count = 0;
-
-        loop {
-            let op = if self.match_token(TokenKind::Plus) {
-                BinOp::Add
-            } else if self.match_token(TokenKind::Minus) {
-                BinOp::Sub
-            } else {
-                break;
-            };
-
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_multiplicative()?;
+        let mut expr = self.parse_unary()?;
+        while self.match_token(TokenKind::As) {
+            let target_ty = self.parse_type()?;
             expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), op, Box::new(right)),
+                kind: ExprKind::Cast(Box::new(expr), target_ty),
                 span: start.merge(self.previous_span()),
             };
         }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
-            }
-        }
-
         Ok(expr)
     }
 
-    fn parse_multiplicative(&mut self) -> Result<Expr> {
+    fn parse_unary(&mut self) -> Result<Expr> {
         let start = self.current_span();
-        let mut expr = self.parse_unary()?;
-        let mut total_indent_count = 0;
 
-        loop {
-            let op = if self.match_token(TokenKind::Star) {
-                BinOp::Mul
-            } else if self.match_token(TokenKind::Slash) {
-                BinOp::Div
-            } else if self.match_token(TokenKind::Percent) {
-                BinOp::Mod
-            } else {
-                break;
-            };
-
-            // Skip newlines and indentation after binary operator to allow continuation
-            while self.check(TokenKind::Newline) || self.check(TokenKind::Indent) {
-                if self.check(TokenKind::Indent) {
-                    total_indent_count += 1;
-                }
-                self.advance();
-            }
-            let right = self.parse_unary()?;
-            expr = Expr {
-                kind: ExprKind::Binary(Box::new(expr), op, Box::new(right)),
+        if self.match_token(TokenKind::Plus) {
+            let expr = self.parse_nested(Self::parse_unary)?;
+            return Ok(Expr {
+                kind: ExprKind::Unary(UnaryOp::Plus, Box::new(expr)),
                 span: start.merge(self.previous_span()),
-            };
-        }
-
-        // Skip matching dedents at the end of expression
-        for _ in 0..total_indent_count {
-            while self.check(TokenKind::Newline) {
-                self.advance();
-            }
-            if self.check(TokenKind::Dedent) {
-                self.advance();
-            }
+            });
         }
 
-        Ok(expr)
-    }
-
-    fn parse_unary(&mut self) -> Result<Expr> {
-        let start = self.current_span();
-
         if self.match_token(TokenKind::Minus) {
-            let expr = self.parse_unary()?;
+            let expr = self.parse_nested(Self::parse_unary)?;
             return Ok(Expr {
                 kind: ExprKind::Unary(UnaryOp::Neg, Box::new(expr)),
                 span: start.merge(self.previous_span()),
@@ -2440,7 +2419,7 @@ impl<'a> Parser<'a> {
         }
 
         if self.match_token(TokenKind::Bang) {
-            let expr = self.parse_unary()?;
+            let expr = self.parse_nested(Self::parse_unary)?;
             return Ok(Expr {
                 kind: ExprKind::Unary(UnaryOp::Not, Box::new(expr)),
                 span: start.merge(self.previous_span()),
@@ -2449,7 +2428,7 @@ impl<'a> Parser<'a> {
 
         if self.match_token(TokenKind::Amp) {
             let is_mut = self.match_token(TokenKind::Mut);
-            let expr = self.parse_unary()?;
+            let expr = self.parse_nested(Self::parse_unary)?;
             let op = if is_mut {
                 UnaryOp::RefMut
             } else {
@@ -2462,7 +2441,7 @@ impl<'a> Parser<'a> {
         }
 
         if self.match_token(TokenKind::Star) {
-            let expr = self.parse_unary()?;
+            let expr = self.parse_nested(Self::parse_unary)?;
             return Ok(Expr {
                 kind: ExprKind::Unary(UnaryOp::Deref, Box::new(expr)),
                 span: start.merge(self.previous_span()),
@@ -2614,6 +2593,10 @@ impl<'a> Parser<'a> {
                 if !self.match_token(TokenKind::Comma) {
                     break;
                 }
+                // Allow trailing comma
+                if self.check(TokenKind::RParen) {
+                    break;
+                }
             }
         }
 
@@ -3292,7 +3275,7 @@ impl<'a> Parser<'a> {
         let block = self.parse_block()?;
 
         Ok(Expr {
-            kind: ExprKind::For(label, pattern, Box::new(iter), block),
+            kind: ExprKind::For(label, pattern, Box::new(iter), block, Vec::new()),
             span: start.merge(self.previous_span()),
         })
     }
@@ -3330,7 +3313,7 @@ impl<'a> Parser<'a> {
             let condition = self.parse_expr()?;
             let block = self.parse_block()?;
             Ok(Expr {
-                kind: ExprKind::While(label, Box::new(condition), block),
+                kind: ExprKind::While(label, Box::new(condition), block, Vec::new()),
                 span: start.merge(self.previous_span()),
             })
         }
@@ -3376,6 +3359,10 @@ impl<'a> Parser<'a> {
                     if !self.match_token(TokenKind::Comma) {
                         break;
                     }
+                    // Allow trailing comma
+                    if self.check(TokenKind::Pipe) {
+                        break;
+                    }
                 }
             }
             self.expect(TokenKind::Pipe)?;
@@ -3775,6 +3762,9 @@ impl<'a> Parser<'a> {
             });
         }
 
+        if self.check_reserved_word() {
+            return Err(self.keyword_as_ident_error());
+        }
         Err(self.error("expected pattern"))
     }
 
@@ -3978,6 +3968,18 @@ impl<'a> Parser<'a> {
     fn parse_stmt(&mut self) -> Result<Stmt> {
         let start = self.current_span();
 
+        // Loop invariants: `@invariant(cond)` lines directly above `wh`/`fo`
+        if self.is_invariant_attr() {
+            return self.parse_loop_with_invariants(start);
+        }
+
+        // `ret := 1` would otherwise fail inside whatever the keyword starts
+        if self.check_reserved_word()
+            && matches!(self.peek_kind(1), Some(TokenKind::ColonEq | TokenKind::Eq))
+        {
+            return Err(self.keyword_as_ident_error());
+        }
+
         // Check for items. Single-letter keywords (f, s, e, t, i, m) can also be variable names,
         // so we need to distinguish:
         // - "s MyStruct" -> struct declaration (keyword followed by identifier = item name)
@@ -4003,6 +4005,17 @@ impl<'a> Parser<'a> {
             });
         }
 
+        if self.is_assert_stmt() {
+            return self.parse_assert_stmt(start);
+        }
+
+        // Annotated binding: `name: Type = expr` or `name: Type := expr`
+        if matches!(self.current_kind(), Some(TokenKind::Ident(_)))
+            && self.peek_is(TokenKind::Colon)
+        {
+            return self.parse_annotated_let(start);
+        }
+
         // Parse expression (which might be an assignment)
         let expr = self.parse_expr()?;
 
@@ -4031,6 +4044,123 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Check for the statement form `assert cond[, message]`. `assert(...)`
+    /// is an ordinary call, and `assert` followed by anything that cannot
+    /// start a condition is a variable.
+    fn is_assert_stmt(&self) -> bool {
+        self.check_contextual("assert")
+            && matches!(
+                self.peek_kind(1),
+                Some(
+                    TokenKind::Ident(_)
+                        | TokenKind::Int(_)
+                        | TokenKind::Float(_)
+                        | TokenKind::String(_)
+                        | TokenKind::ByteString(_)
+                        | TokenKind::True
+                        | TokenKind::False
+                        | TokenKind::Bang
+                        | TokenKind::None
+                        | TokenKind::Some
+                        | TokenKind::Ok
+                        | TokenKind::Err
+                        | TokenKind::LBracket
+                )
+            )
+    }
+
+    /// Parse `assert cond[, message]` as a call to the `assert` builtin.
+    fn parse_assert_stmt(&mut self, start: Span) -> Result<Stmt> {
+        let keyword = self.parse_ident()?;
+        let mut args = Vec::new();
+        loop {
+            let value = self.parse_expr()?;
+            args.push(Arg {
+                name: None,
+                span: value.span,
+                value,
+                pass_mode: PassMode::Owned,
+            });
+            if args.len() == 2 || !self.match_token(TokenKind::Comma) {
+                break;
+            }
+        }
+        let span = start.merge(self.previous_span());
+        let callee = Expr {
+            span: keyword.span,
+            kind: ExprKind::Ident(keyword),
+        };
+        Ok(Stmt {
+            kind: StmtKind::Expr(Expr {
+                kind: ExprKind::Call(Box::new(callee), args),
+                span,
+            }),
+            span,
+        })
+    }
+
+    fn is_invariant_attr(&self) -> bool {
+        self.check(TokenKind::At)
+            && matches!(self.peek_kind(1), Some(TokenKind::Ident(name)) if name == "invariant")
+    }
+
+    fn parse_loop_with_invariants(&mut self, start: Span) -> Result<Stmt> {
+        let mut invariants = Vec::new();
+        while self.is_invariant_attr() {
+            let attr = self.parse_attribute()?;
+            if let Some(contract) = Self::extract_contract(&attr) {
+                invariants.push(contract);
+            }
+            self.skip_newlines();
+        }
+
+        let mut stmt = self.parse_stmt()?;
+        match &mut stmt.kind {
+            StmtKind::Expr(Expr {
+                kind: ExprKind::While(_, _, _, loop_invariants),
+                ..
+            })
+            | StmtKind::Expr(Expr {
+                kind: ExprKind::For(_, _, _, _, loop_invariants),
+                ..
+            }) => {
+                *loop_invariants = invariants;
+                stmt.span = start.merge(stmt.span);
+                Ok(stmt)
+            }
+            _ => Err(
+                ParseError::new("@invariant must be followed by a `wh` or `fo` loop", start).into(),
+            ),
+        }
+    }
+
+    fn parse_annotated_let(&mut self, start: Span) -> Result<Stmt> {
+        let name = self.parse_ident()?;
+        let pattern = Pattern {
+            span: name.span,
+            kind: PatternKind::Ident(name, false, None),
+        };
+        self.expect(TokenKind::Colon)?;
+        let ty = self.parse_type()?;
+        let mutable = if self.match_token(TokenKind::ColonEq) {
+            true
+        } else {
+            self.expect(TokenKind::Eq)?;
+            false
+        };
+        let init = self.parse_expr()?;
+        Ok(Stmt {
+            kind: StmtKind::Let(LetStmt {
+                pattern,
+                ty: Some(ty),
+                init,
+                mutable,
+                span: start.merge(self.previous_span()),
+            }),
+            span: start.merge(self.previous_span()),
+        })
+    }
+
     fn expr_to_pattern(&self, expr: &Expr) -> Result<Pattern> {
         let span = expr.span;
         match &expr.kind {
@@ -4073,6 +4203,11 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Some(LiteralKind::String(s))
             }
+            Some(TokenKind::ByteString(ref bytes)) => {
+                let bytes = bytes.clone();
+                self.advance();
+                Some(LiteralKind::Bytes(bytes))
+            }
             Some(TokenKind::Char(c)) => {
                 self.advance();
                 Some(LiteralKind::Char(c))
@@ -4128,10 +4263,42 @@ impl<'a> Parser<'a> {
                     span,
                 })
             }
+            _ if self.check_reserved_word() => Err(self.keyword_as_ident_error()),
             _ => Err(self.error("expected identifier")),
         }
     }
 
+    /// Whether the current token is a keyword that cannot double as an
+    /// identifier, like `ret` or `for`.
+    fn check_reserved_word(&self) -> bool {
+        self.current().is_some_and(|t| {
+            t.kind.is_keyword()
+                && !matches!(
+                    t.kind,
+                    TokenKind::True
+                        | TokenKind::False
+                        | TokenKind::None
+                        | TokenKind::Some
+                        | TokenKind::Ok
+                        | TokenKind::Err
+                )
+        })
+    }
+
+    /// Error for a reserved keyword where an identifier was expected.
+    fn keyword_as_ident_error(&self) -> crate::errors::CompileError {
+        let keyword = self.current().map(|t| t.lexeme.clone()).unwrap_or_default();
+        ParseError::new(
+            format!("keyword `{}` used as identifier", keyword),
+            self.current_span(),
+        )
+        .with_help(format!(
+            "`{}` is reserved; rename it, e.g. `{}_`",
+            keyword, keyword
+        ))
+        .into()
+    }
+
     fn check_ident(&mut self) -> bool {
         // Single-letter keywords (f, s, e, t, i, m) are now emitted as Ident tokens
         // so they are handled by the Ident(_) case
@@ -4382,6 +4549,7 @@ impl<'a> Parser<'a> {
                 Some(TokenKind::Int(_))
                     | Some(TokenKind::Float(_))
                     | Some(TokenKind::String(_))
+                    | Some(TokenKind::ByteString(_))
                     | Some(TokenKind::Char(_))
                     | Some(TokenKind::True)
                     | Some(TokenKind::False)
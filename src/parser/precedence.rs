@@ -0,0 +1,62 @@
+//! Binary operator precedence and associativity.
+//!
+//! The expression parser climbs this table: [`binary_op`] says whether a
+//! token is a binary operator, how tightly it binds and which way it groups.
+//! Higher precedence binds tighter. Prefix operators (`-`, `!`, `&`, `*`) and
+//! `as` casts bind tighter than every binary operator; the pipeline `|` and
+//! coalescing `??` operators bind looser and are parsed separately.
+
+use super::ast::BinOp;
+use crate::lexer::TokenKind;
+
+/// How a chain of operators with the same precedence groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ** b ** c` would be `a ** (b ** c)`
+    Right,
+    /// `a < b < c` is an error
+    None,
+}
+
+/// Precedence and associativity of a binary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpInfo {
+    pub op: BinOp,
+    pub precedence: u8,
+    pub assoc: Assoc,
+}
+
+/// Precedence of prefix operators and casts, above every binary operator.
+pub const PREFIX_PRECEDENCE: u8 = 9;
+
+/// The binary operator `kind` stands for, if any.
+pub fn binary_op(kind: &TokenKind) -> Option<OpInfo> {
+    let (op, precedence, assoc) = match kind {
+        TokenKind::PipePipe => (BinOp::Or, 1, Assoc::Left),
+        TokenKind::AmpAmp => (BinOp::And, 2, Assoc::Left),
+        TokenKind::EqEq => (BinOp::Eq, 3, Assoc::None),
+        TokenKind::BangEq => (BinOp::Ne, 3, Assoc::None),
+        TokenKind::Lt => (BinOp::Lt, 3, Assoc::None),
+        TokenKind::LtEq => (BinOp::Le, 3, Assoc::None),
+        TokenKind::Gt => (BinOp::Gt, 3, Assoc::None),
+        TokenKind::GtEq => (BinOp::Ge, 3, Assoc::None),
+        // `|` is the pipeline operator, so there is no infix bitwise or
+        TokenKind::Caret => (BinOp::BitXor, 4, Assoc::Left),
+        TokenKind::Amp => (BinOp::BitAnd, 5, Assoc::Left),
+        TokenKind::LtLt => (BinOp::Shl, 6, Assoc::Left),
+        TokenKind::GtGt => (BinOp::Shr, 6, Assoc::Left),
+        TokenKind::Plus => (BinOp::Add, 7, Assoc::Left),
+        TokenKind::Minus => (BinOp::Sub, 7, Assoc::Left),
+        TokenKind::Star => (BinOp::Mul, 8, Assoc::Left),
+        TokenKind::Slash => (BinOp::Div, 8, Assoc::Left),
+        TokenKind::Percent => (BinOp::Mod, 8, Assoc::Left),
+        _ => return None,
+    };
+    Some(OpInfo {
+        op,
+        precedence,
+        assoc,
+    })
+}
@@ -5,8 +5,9 @@
 
 use std::collections::HashMap;
 
-use crate::lexer::Span;
-use crate::parser::{Item, ItemKind, SourceFile};
+use crate::errors::Diagnostic;
+use crate::lexer::{Scanner, Span};
+use crate::parser::{Item, ItemKind, Parser, SourceFile};
 
 use super::inference::{InferenceEngine, TypeEnv, TypeError};
 use super::types::{Ty, TypeId, TypeScheme};
@@ -30,10 +31,6 @@ pub struct TypeChecker {
     engine: InferenceEngine,
     /// Collected errors
     errors: Vec<TypeError>,
-    /// Inferred types for expressions (by span)
-    /// TODO: expose via API for IDE features (hover types, etc.)
-    #[allow(dead_code)]
-    expr_types: HashMap<Span, Ty>,
 }
 
 impl TypeChecker {
@@ -41,7 +38,6 @@ impl TypeChecker {
         Self {
             engine: InferenceEngine::new(),
             errors: Vec::new(),
-            expr_types: HashMap::new(),
         }
     }
 
@@ -162,6 +158,11 @@ impl TypeChecker {
         &self.errors
     }
 
+    /// Get lint warnings that need type information, such as narrowing casts.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        self.engine.warnings()
+    }
+
     /// Get the type environment.
     pub fn env(&self) -> &TypeEnv {
         self.engine.env()
@@ -170,6 +171,47 @@ impl TypeChecker {
     pub fn get_definition_location(&self, name: &str) -> Option<(Span, DefinitionKind)> {
         self.engine.get_symbol_location(name)
     }
+
+    /// Type of the innermost expression containing byte `offset`, after
+    /// [`check`](Self::check). Checking stops at the first type error, so
+    /// expressions after it have no type.
+    pub fn type_at(&self, offset: usize) -> Option<Ty> {
+        self.engine.type_at(offset)
+    }
+
+    /// Declaration span of the identifier used at byte `offset`, after
+    /// [`check`](Self::check).
+    pub fn definition_at(&self, offset: usize) -> Option<Span> {
+        self.engine.definition_at(offset)
+    }
+}
+
+/// Type of the innermost expression at byte `offset` in `source`, for editor
+/// hovers. Returns `None` if the source does not lex or parse, or if the
+/// offset is not inside a type-checked expression.
+pub fn type_at(source: &str, byte_offset: usize) -> Option<Ty> {
+    let (tokens, lex_errors) = Scanner::new(source).scan_all();
+    if !lex_errors.is_empty() {
+        return None;
+    }
+    let ast = Parser::new(&tokens).parse().ok()?;
+    let mut checker = TypeChecker::new();
+    let _ = checker.check(&ast); // keep what was inferred before any error
+    checker.type_at(byte_offset)
+}
+
+/// Declaration span (local binding, parameter, or function) of the identifier
+/// used at byte `offset` in `source`, for go-to-definition. Returns `None` if
+/// the source does not lex or parse, or if the identifier does not resolve.
+pub fn definition_of(source: &str, byte_offset: usize) -> Option<Span> {
+    let (tokens, lex_errors) = Scanner::new(source).scan_all();
+    if !lex_errors.is_empty() {
+        return None;
+    }
+    let ast = Parser::new(&tokens).parse().ok()?;
+    let mut checker = TypeChecker::new();
+    let _ = checker.check(&ast);
+    checker.definition_at(byte_offset)
 }
 
 impl Default for TypeChecker {
@@ -237,16 +279,14 @@ impl BoundsChecker {
     ) -> Result<(), TypeError> {
         for (trait_id, _args) in bounds {
             match ty {
-                Ty::Named(type_id, _) => {
-                    if !self.implements(type_id, trait_id) {
-                        return Err(TypeError::new(
-                            format!(
-                                "type {} does not implement trait {}",
-                                type_id.name, trait_id.name
-                            ),
-                            span,
-                        ));
-                    }
+                Ty::Named(type_id, _) if !self.implements(type_id, trait_id) => {
+                    return Err(TypeError::new(
+                        format!(
+                            "type {} does not implement trait {}",
+                            type_id.name, trait_id.name
+                        ),
+                        span,
+                    ));
                 }
                 _ => {
                     // Primitive types have built-in trait impls
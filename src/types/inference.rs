@@ -6,15 +6,18 @@
 //! - Unifier: Unification algorithm for type equality constraints
 //! - InferenceEngine: Walks AST and generates/solves constraints
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use crate::errors::Diagnostic;
 use crate::lexer::Span;
 use crate::parser::{
-    BinOp, Block, Expr, ExprKind, FnBody, GenericArg, GenericParam, Generics, Item, ItemKind,
-    LiteralKind, PassMode, Pattern, PatternKind, Stmt, StmtKind, Type as AstType,
-    TypeKind as AstTypeKind, UnaryOp, VariantKind,
+    Arg, BinOp, Block, Contract, Expr, ExprKind, FnBody, GenericArg, GenericParam, Generics, Ident,
+    Item, ItemKind, Literal, LiteralKind, PassMode, Pattern, PatternKind, Stmt, StmtKind,
+    Type as AstType, TypeKind as AstTypeKind, UnaryOp, VariantKind, bind_call_args,
+    split_format_string,
 };
 
+use super::lints::Lint;
 use super::types::{Mutability, Substitution, Ty, TypeId, TypeScheme, TypeVar};
 
 /// Reserved TypeVar IDs for method type substitution.
@@ -63,6 +66,10 @@ pub struct FunctionInfo {
     pub param_types: Vec<Ty>,
     /// Pass modes for each parameter (Owned, Ref, RefMut)
     pub param_pass_modes: Vec<PassMode>,
+    /// Parameter names, for binding named arguments
+    pub param_names: Vec<String>,
+    /// Whether each parameter has a default value
+    pub param_has_default: Vec<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -93,6 +100,8 @@ pub struct TypeEnv {
     traits: HashMap<String, TraitInfo>,
     /// Maps variant names to their parent enum names (e.g., "Some" -> "Option")
     variant_to_enum: HashMap<String, String>,
+    /// Where each user-declared binding was introduced
+    definitions: HashMap<String, Span>,
 }
 
 /// Definition of a named type.
@@ -234,14 +243,18 @@ impl TypeEnv {
 
         // I/O
         // print: ...Any -> Unit
-        let print_var = TypeVar::fresh();
-        env.bindings.insert(
-            "print".to_string(),
-            TypeScheme {
-                vars: vec![print_var],
-                ty: Ty::Fn(vec![Ty::Var(print_var)], Box::new(Ty::Unit)),
-            },
-        );
+        // With more than one argument, the first is a format string; see
+        // `check_format_call`.
+        for name in ["print", "println"] {
+            let print_var = TypeVar::fresh();
+            env.bindings.insert(
+                name.to_string(),
+                TypeScheme {
+                    vars: vec![print_var],
+                    ty: Ty::Fn(vec![Ty::Var(print_var)], Box::new(Ty::Unit)),
+                },
+            );
+        }
 
         // str: T -> Str (convert any value to string)
         let str_var = TypeVar::fresh();
@@ -433,6 +446,33 @@ impl TypeEnv {
             },
         );
 
+        // bytes_len: Bytes -> Int
+        env.bindings.insert(
+            "bytes_len".to_string(),
+            TypeScheme {
+                vars: vec![],
+                ty: Ty::Fn(vec![Ty::Bytes], Box::new(Ty::Int)),
+            },
+        );
+
+        // bytes_get: (Bytes, Int) -> u8 (panics when out of range, like b[i])
+        env.bindings.insert(
+            "bytes_get".to_string(),
+            TypeScheme {
+                vars: vec![],
+                ty: Ty::Fn(vec![Ty::Bytes, Ty::Int], Box::new(Ty::U8)),
+            },
+        );
+
+        // str_index: (Str, Int) -> Char (panics when out of range, like s[i])
+        env.bindings.insert(
+            "str_index".to_string(),
+            TypeScheme {
+                vars: vec![],
+                ty: Ty::Fn(vec![Ty::Str, Ty::Int], Box::new(Ty::Char)),
+            },
+        );
+
         // str_char_at: (Str, Int) -> Char?
         env.bindings.insert(
             "str_char_at".to_string(),
@@ -861,6 +901,19 @@ impl TypeEnv {
             },
         );
 
+        // select[T]: (Bool, T, T) -> T
+        let select_t = TypeVar::fresh();
+        env.bindings.insert(
+            "select".to_string(),
+            TypeScheme {
+                vars: vec![select_t],
+                ty: Ty::Fn(
+                    vec![Ty::Bool, Ty::Var(select_t), Ty::Var(select_t)],
+                    Box::new(Ty::Var(select_t)),
+                ),
+            },
+        );
+
         // is_some[T]: Option[T] -> Bool
         let is_some_t = TypeVar::fresh();
         env.bindings.insert(
@@ -4091,9 +4144,22 @@ impl TypeEnv {
 
     /// Insert a variable binding.
     pub fn insert(&mut self, name: String, scheme: TypeScheme) {
+        self.definitions.remove(&name);
         self.bindings.insert(name, scheme);
     }
 
+    /// Insert a binding declared in source at `span`, the target of
+    /// go-to-definition for its uses.
+    pub fn define(&mut self, name: &Ident, scheme: TypeScheme) {
+        self.bindings.insert(name.name.clone(), scheme);
+        self.definitions.insert(name.name.clone(), name.span);
+    }
+
+    /// Span of the declaration that `name` currently resolves to.
+    pub fn definition(&self, name: &str) -> Option<Span> {
+        self.definitions.get(name).copied()
+    }
+
     /// Insert a type definition.
     pub fn insert_type(&mut self, name: String, def: TypeDef) {
         // Auto-register variant-to-enum mappings for enum types
@@ -4152,6 +4218,7 @@ impl TypeEnv {
             fn_info: self.fn_info.clone(),
             traits: self.traits.clone(),
             variant_to_enum: self.variant_to_enum.clone(),
+            definitions: self.definitions.clone(),
         }
     }
 
@@ -4231,6 +4298,7 @@ impl Unifier {
             | (Ty::Bool, Ty::Bool)
             | (Ty::Char, Ty::Char)
             | (Ty::Str, Ty::Str)
+            | (Ty::Bytes, Ty::Bytes)
             | (Ty::Unit, Ty::Unit)
             | (Ty::Never, Ty::Never)
             | (Ty::Json, Ty::Json) => Ok(()),
@@ -4475,6 +4543,27 @@ struct LinearVarInfo {
     last_use_span: Option<Span>,
 }
 
+/// An un-annotated `let` bound to an empty container literal (`[]` or `{}`).
+///
+/// Nothing in the initializer constrains the element type, so if the type is
+/// still unresolved when the enclosing function finishes checking, whether or
+/// not the binding was read, inference reports it as ambiguous.
+#[derive(Debug, Clone)]
+struct EmptyContainerLet {
+    name: String,
+    ty: Ty,
+    span: Span,
+}
+
+/// Whether `expr` is an empty list, map, or set literal.
+fn is_empty_container(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Array(elems) => elems.is_empty(),
+        ExprKind::MapOrSet(entries) => entries.is_empty(),
+        _ => false,
+    }
+}
+
 pub struct InferenceEngine {
     env: TypeEnv,
     unifier: Unifier,
@@ -4491,6 +4580,16 @@ pub struct InferenceEngine {
     symbol_locations: HashMap<String, (Span, super::checker::DefinitionKind)>,
     /// Linear/affine variable tracking: maps variable name to tracking info
     linear_tracking: HashMap<String, LinearVarInfo>,
+    /// Empty-container bindings in the current function awaiting an inferred type
+    empty_container_lets: Vec<EmptyContainerLet>,
+    /// Number of loops enclosing the expression being checked
+    loop_depth: usize,
+    /// Lint warnings that need resolved types, such as narrowing casts
+    warnings: Vec<Diagnostic>,
+    /// Type inferred for each expression, by span, for editor queries
+    expr_types: Vec<(Span, Ty)>,
+    /// Each identifier use paired with the declaration it resolved to
+    references: Vec<(Span, Span)>,
 }
 
 impl InferenceEngine {
@@ -4504,6 +4603,11 @@ impl InferenceEngine {
             impl_self_type: None,
             symbol_locations: HashMap::new(),
             linear_tracking: HashMap::new(),
+            empty_container_lets: Vec::new(),
+            loop_depth: 0,
+            warnings: Vec::new(),
+            expr_types: Vec::new(),
+            references: Vec::new(),
         };
         engine.register_builtin_methods();
         engine
@@ -4519,6 +4623,11 @@ impl InferenceEngine {
             impl_self_type: None,
             symbol_locations: HashMap::new(),
             linear_tracking: HashMap::new(),
+            empty_container_lets: Vec::new(),
+            loop_depth: 0,
+            warnings: Vec::new(),
+            expr_types: Vec::new(),
+            references: Vec::new(),
         };
         engine.register_builtin_methods();
         engine
@@ -5075,7 +5184,7 @@ impl InferenceEngine {
                                 .zip(type_args.iter())
                                 .map(|(p, a)| (p.clone(), a.clone()))
                                 .collect();
-                            return Ok(self.substitute_type_params(field_ty, &subst));
+                            return Ok(field_ty.substitute_named(&subst));
                         }
                         return Ok(field_ty.clone());
                     } else {
@@ -5160,68 +5269,23 @@ impl InferenceEngine {
         params
     }
 
-    /// Substitute named type parameters with concrete types.
-    /// Used when instantiating generic struct types.
-    fn substitute_type_params(&self, ty: &Ty, subst: &HashMap<String, Ty>) -> Ty {
-        match ty {
-            Ty::Var(tv) => {
-                // Type variables from struct field definitions might be Named("T", [])
-                // since they were created before we had fresh vars
-                Ty::Var(*tv)
-            }
-            Ty::Named(id, args) => {
-                // Check if this is a type parameter reference
-                if args.is_empty()
-                    && let Some(replacement) = subst.get(&id.name)
-                {
-                    return replacement.clone();
-                }
-                // Otherwise, recursively substitute in args
-                let new_args = args
-                    .iter()
-                    .map(|a| self.substitute_type_params(a, subst))
-                    .collect();
-                Ty::Named(id.clone(), new_args)
-            }
-            Ty::List(elem) => Ty::List(Box::new(self.substitute_type_params(elem, subst))),
-            Ty::Tuple(elems) => Ty::Tuple(
-                elems
-                    .iter()
-                    .map(|e| self.substitute_type_params(e, subst))
-                    .collect(),
-            ),
-            Ty::Fn(params, ret) => Ty::Fn(
-                params
-                    .iter()
-                    .map(|p| self.substitute_type_params(p, subst))
-                    .collect(),
-                Box::new(self.substitute_type_params(ret, subst)),
-            ),
-            Ty::Option(inner) => Ty::Option(Box::new(self.substitute_type_params(inner, subst))),
-            Ty::Result(ok, err) => Ty::Result(
-                Box::new(self.substitute_type_params(ok, subst)),
-                Box::new(self.substitute_type_params(err, subst)),
-            ),
-            Ty::Ref(inner, m) => Ty::Ref(Box::new(self.substitute_type_params(inner, subst)), *m),
-            Ty::Ptr(inner, m) => Ty::Ptr(Box::new(self.substitute_type_params(inner, subst)), *m),
-            Ty::Map(k, v) => Ty::Map(
-                Box::new(self.substitute_type_params(k, subst)),
-                Box::new(self.substitute_type_params(v, subst)),
-            ),
-            Ty::Set(elem) => Ty::Set(Box::new(self.substitute_type_params(elem, subst))),
-            // Primitive types don't need substitution
-            _ => ty.clone(),
-        }
-    }
-
     /// Infer types for a list of items (a module/file).
     pub fn infer_items(&mut self, items: &[Item]) -> Result<(), TypeError> {
-        // First pass: collect type definitions
-        for item in items {
+        // First pass: collect type definitions, aliases first so that any
+        // type written afterwards can name them
+        let is_alias = |item: &&Item| matches!(item.kind, ItemKind::TypeAlias(_));
+        for item in items.iter().filter(is_alias) {
+            self.collect_type_def(item)?;
+        }
+        for item in items.iter().filter(is_alias) {
+            self.check_alias_not_recursive(item)?;
+        }
+        for item in items.iter().filter(|item| !is_alias(item)) {
             self.collect_type_def(item)?;
         }
 
         // Second pass: collect function signatures
+        Self::check_duplicate_functions(items)?;
         for item in items {
             self.collect_function_sig(item)?;
         }
@@ -5234,6 +5298,138 @@ impl InferenceEngine {
         Ok(())
     }
 
+    /// Reject a second top-level function, or a second method in one impl
+    /// block, with a name already taken. Local bindings may shadow each
+    /// other; function definitions may not.
+    fn check_duplicate_functions(items: &[Item]) -> Result<(), TypeError> {
+        fn check<'a>(
+            functions: impl Iterator<Item = &'a crate::parser::Function>,
+            what: &str,
+        ) -> Result<(), TypeError> {
+            let mut seen = HashSet::new();
+            for f in functions {
+                if !seen.insert(f.name.name.as_str()) {
+                    return Err(TypeError::new(
+                        format!("{} `{}` is already defined", what, f.name.name),
+                        f.name.span,
+                    ));
+                }
+            }
+            Ok(())
+        }
+
+        check(
+            items.iter().filter_map(|item| match &item.kind {
+                ItemKind::Function(f) => Some(f),
+                _ => None,
+            }),
+            "function",
+        )?;
+        for item in items {
+            if let ItemKind::Impl(i) = &item.kind {
+                check(
+                    i.items.iter().filter_map(|impl_item| match impl_item {
+                        crate::parser::ImplItem::Function(f) => Some(f),
+                        _ => None,
+                    }),
+                    "method",
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject a function that names two parameters the same.
+    fn check_duplicate_params(f: &crate::parser::Function) -> Result<(), TypeError> {
+        let mut seen = HashSet::new();
+        for param in &f.params {
+            if !seen.insert(param.name.name.as_str()) {
+                return Err(TypeError::new(
+                    format!(
+                        "duplicate parameter `{}` in `{}`",
+                        param.name.name, f.name.name
+                    ),
+                    param.name.span,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject an alias whose expansion never ends, like `type Loop = [Loop]`.
+    fn check_alias_not_recursive(&self, item: &Item) -> Result<(), TypeError> {
+        let ItemKind::TypeAlias(t) = &item.kind else {
+            return Ok(());
+        };
+        let name = &t.name.name;
+        let params: Vec<Ty> = self
+            .get_type_params(&t.generics)
+            .into_iter()
+            .map(|p| Ty::Named(TypeId::new(p), vec![]))
+            .collect();
+        match self.expand_alias(name, &params, &mut Vec::new()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TypeError::new(
+                format!("recursive type alias `{}`", name),
+                t.name.span,
+            )),
+        }
+    }
+
+    /// Expand alias `name` applied to `args` into its target type, along
+    /// with any aliases the target names. `visiting` holds the aliases being
+    /// expanded; meeting one of them again fails with its name.
+    fn expand_alias(
+        &self,
+        name: &str,
+        args: &[Ty],
+        visiting: &mut Vec<String>,
+    ) -> Result<Ty, String> {
+        let Some(TypeDef::Alias {
+            type_params,
+            target,
+        }) = self.env.get_type(name)
+        else {
+            return Ok(Ty::Named(TypeId::new(name), args.to_vec()));
+        };
+        if visiting.iter().any(|v| v == name) {
+            return Err(name.to_string());
+        }
+        let subst: HashMap<String, Ty> = type_params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        let target = target.substitute_named(&subst);
+
+        visiting.push(name.to_string());
+        let expanded = self.expand_aliases_in(&target, visiting);
+        visiting.pop();
+        expanded
+    }
+
+    /// Expand every alias named inside `ty`.
+    fn expand_aliases_in(&self, ty: &Ty, visiting: &mut Vec<String>) -> Result<Ty, String> {
+        if let Ty::Named(id, args) = ty
+            && matches!(self.env.get_type(&id.name), Some(TypeDef::Alias { .. }))
+        {
+            let args = args
+                .iter()
+                .map(|arg| self.expand_aliases_in(arg, visiting))
+                .collect::<Result<Vec<_>, _>>()?;
+            return self.expand_alias(&id.name, &args, visiting);
+        }
+        let mut cycle = None;
+        let expanded = ty.map_children(|child| {
+            self.expand_aliases_in(child, visiting)
+                .unwrap_or_else(|name| {
+                    cycle.get_or_insert(name);
+                    Ty::Error
+                })
+        });
+        cycle.map_or(Ok(expanded), Err)
+    }
+
     /// Extract type parameter names from generics.
     fn get_type_params(&self, generics: &Option<crate::parser::Generics>) -> Vec<String> {
         generics
@@ -5467,6 +5663,8 @@ impl InferenceEngine {
     fn collect_function_sig(&mut self, item: &Item) -> Result<(), TypeError> {
         match &item.kind {
             ItemKind::Function(f) => {
+                Self::check_duplicate_params(f)?;
+
                 // Set up type parameters for generic functions
                 let old_type_params = std::mem::take(&mut self.type_params);
                 self.type_params = self.setup_type_params(&f.generics);
@@ -5495,7 +5693,7 @@ impl InferenceEngine {
                     TypeScheme::generalize(fn_type, &env_vars)
                 };
 
-                self.env.insert(f.name.name.clone(), scheme);
+                self.env.define(&f.name, scheme);
                 self.symbol_locations.insert(
                     f.name.name.clone(),
                     (f.name.span, super::checker::DefinitionKind::Function),
@@ -5513,6 +5711,8 @@ impl InferenceEngine {
                         total_params,
                         param_types,
                         param_pass_modes,
+                        param_names: f.params.iter().map(|p| p.name.name.clone()).collect(),
+                        param_has_default: f.params.iter().map(|p| p.default.is_some()).collect(),
                     },
                 );
 
@@ -5526,6 +5726,8 @@ impl InferenceEngine {
                 // Collect method signatures from impl block
                 for impl_item in &i.items {
                     if let crate::parser::ImplItem::Function(f) = impl_item {
+                        Self::check_duplicate_params(f)?;
+
                         // Skip the self parameter for method signature
                         let param_types: Vec<Ty> = f
                             .params
@@ -5553,6 +5755,21 @@ impl InferenceEngine {
                     }
                 }
             }
+            ItemKind::Const(c) => {
+                // Constants are collected in source order, so an initializer
+                // may only refer to constants declared above it
+                let ty = self.infer_expr(&c.value)?;
+                if let Some(annotation) = &c.ty {
+                    let expected = self.ast_type_to_ty(annotation)?;
+                    self.unifier.unify(&ty, &expected, c.value.span)?;
+                }
+                let ty = ty.apply(self.unifier.substitution());
+                self.env.define(&c.name, TypeScheme::mono(ty));
+                self.symbol_locations.insert(
+                    c.name.name.clone(),
+                    (c.name.span, super::checker::DefinitionKind::Variable),
+                );
+            }
             _ => {}
         }
         Ok(())
@@ -5590,7 +5807,7 @@ impl InferenceEngine {
                 // Add parameters to the scope
                 for param in &f.params {
                     let ty = self.ast_type_to_ty(&param.ty)?;
-                    body_env.insert(param.name.name.clone(), TypeScheme::mono(ty));
+                    body_env.define(&param.name, TypeScheme::mono(ty));
                 }
 
                 // Set up return type
@@ -5605,10 +5822,14 @@ impl InferenceEngine {
 
                 // Infer body type
                 let old_env = std::mem::replace(&mut self.env, body_env);
+                let old_empty_lets = std::mem::take(&mut self.empty_container_lets);
+                let old_loop_depth = std::mem::take(&mut self.loop_depth);
                 let body_type = match body {
-                    FnBody::Expr(expr) => self.infer_expr(expr)?,
-                    FnBody::Block(block) => self.infer_block(block)?,
+                    FnBody::Expr(expr) => self.infer_expr(expr),
+                    FnBody::Block(block) => self.infer_block(block),
                 };
+                self.loop_depth = old_loop_depth;
+                let body_type = body_type?;
                 self.env = old_env;
                 self.return_type = old_return;
 
@@ -5617,6 +5838,9 @@ impl InferenceEngine {
 
                 // Unify body type with return type
                 self.unifier.unify(&body_type, &return_type, item.span)?;
+
+                let empty_lets = std::mem::replace(&mut self.empty_container_lets, old_empty_lets);
+                self.check_empty_container_lets(&empty_lets)?;
             }
             ItemKind::Impl(i) => {
                 // Convert the impl target type and set it for Self resolution
@@ -5704,6 +5928,248 @@ impl InferenceEngine {
         Ok(())
     }
 
+    /// Report empty-container bindings whose type nothing ever determined.
+    fn check_empty_container_lets(&self, lets: &[EmptyContainerLet]) -> Result<(), TypeError> {
+        for pending in lets {
+            // Bindings are recorded one variable at a time, so a later use
+            // may have resolved the element type through a chain of variables
+            let mut ty = pending.ty.clone();
+            loop {
+                let next = ty.apply(self.unifier.substitution());
+                if next == ty {
+                    break;
+                }
+                ty = next;
+            }
+            if ty.has_vars() {
+                return Err(TypeError::new(
+                    format!(
+                        "cannot infer type for `{}`: add a type annotation",
+                        pending.name
+                    ),
+                    pending.span,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a match over an enum covers every variant, a match over a
+    /// `Bool` covers `true` and `false`, and a match over an integer has a
+    /// catch-all arm.
+    ///
+    /// Guarded arms never count toward coverage, and a variant pattern only
+    /// covers its variant when all of its fields are irrefutable.
+    fn check_match_exhaustive(
+        &self,
+        scrutinee_ty: &Ty,
+        arms: &[crate::parser::MatchArm],
+        span: Span,
+    ) -> Result<(), TypeError> {
+        let scrutinee_ty = scrutinee_ty.apply(self.unifier.substitution());
+        let enum_name = match &scrutinee_ty {
+            Ty::Named(id, _) => id.name.clone(),
+            Ty::Option(_) => "Option".to_string(),
+            Ty::Result(_, _) => "Result".to_string(),
+            Ty::Bool => "Bool".to_string(),
+            ty if ty.is_integer() => {
+                let mut covered = HashSet::new();
+                if arms
+                    .iter()
+                    .filter(|arm| arm.guard.is_none())
+                    .any(|arm| Self::pattern_covers(&arm.pattern, &[], &mut covered))
+                {
+                    return Ok(());
+                }
+                return Err(TypeError::new(
+                    format!(
+                        "non-exhaustive match on `{}`: add a `_` arm for the remaining values",
+                        scrutinee_ty
+                    ),
+                    span,
+                ));
+            }
+            _ => return Ok(()),
+        };
+        let variant_names: Vec<&str> = if scrutinee_ty == Ty::Bool {
+            vec!["true", "false"]
+        } else {
+            match self.env.get_type(&enum_name) {
+                Some(TypeDef::Enum { variants, .. }) => {
+                    variants.iter().map(|(name, _)| name.as_str()).collect()
+                }
+                _ => return Ok(()),
+            }
+        };
+
+        let mut covered = HashSet::new();
+        for arm in arms.iter().filter(|arm| arm.guard.is_none()) {
+            if Self::pattern_covers(&arm.pattern, &variant_names, &mut covered) {
+                return Ok(());
+            }
+        }
+
+        let missing: Vec<&str> = variant_names
+            .iter()
+            .filter(|name| !covered.contains(**name))
+            .copied()
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(TypeError::new(
+            format!(
+                "non-exhaustive match on `{}`: missing {}",
+                enum_name,
+                missing.join(", ")
+            ),
+            span,
+        ))
+    }
+
+    /// Record the variants `pattern` fully covers, counting `true` and `false`
+    /// literals as the variants of `Bool`. Returns true if it matches any
+    /// value (a wildcard or plain binding).
+    fn pattern_covers<'a>(
+        pattern: &'a Pattern,
+        variants: &[&str],
+        covered: &mut HashSet<&'a str>,
+    ) -> bool {
+        let irrefutable = |p: &Pattern| match &p.kind {
+            PatternKind::Wildcard | PatternKind::Rest => true,
+            PatternKind::Ident(ident, _, None) => !variants.contains(&ident.name.as_str()),
+            _ => false,
+        };
+        match &pattern.kind {
+            PatternKind::Wildcard => true,
+            PatternKind::Ident(ident, _, _) if variants.contains(&ident.name.as_str()) => {
+                covered.insert(ident.name.as_str());
+                false
+            }
+            PatternKind::Ident(_, _, None) => true,
+            PatternKind::Ident(_, _, Some(sub)) | PatternKind::Ref(sub, _) => {
+                Self::pattern_covers(sub, variants, covered)
+            }
+            PatternKind::Struct(path, fields, _) => {
+                if let Some(seg) = path.segments.last()
+                    && fields
+                        .iter()
+                        .all(|f| f.pattern.as_ref().is_none_or(irrefutable))
+                {
+                    covered.insert(seg.name.name.as_str());
+                }
+                false
+            }
+            PatternKind::Literal(Literal {
+                kind: LiteralKind::Bool(value),
+                ..
+            }) => {
+                covered.insert(if *value { "true" } else { "false" });
+                false
+            }
+            PatternKind::Or(alternatives) => alternatives
+                .iter()
+                .any(|alt| Self::pattern_covers(alt, variants, covered)),
+            _ => false,
+        }
+    }
+
+    /// Check that a branch condition is `Bool`. Integers are not truthy, so
+    /// `if n` must be written `if n != 0`.
+    fn check_condition(&mut self, cond: &Expr, context: &str) -> Result<(), TypeError> {
+        let cond_ty = self.infer_expr(cond)?;
+        if self.unifier.unify(&cond_ty, &Ty::Bool, cond.span).is_err() {
+            let found = cond_ty.apply(self.unifier.substitution());
+            let hint = if found.is_integer() {
+                " (compare it explicitly, e.g. `x != 0`)"
+            } else {
+                ""
+            };
+            return Err(TypeError::new(
+                format!(
+                    "{} condition must be Bool, found {}{}",
+                    context, found, hint
+                ),
+                cond.span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a loop's `@invariant`s, each of which must be a `Bool`.
+    fn check_loop_invariants(&mut self, invariants: &[Contract]) -> Result<(), TypeError> {
+        for invariant in invariants {
+            self.check_invariant_condition(&invariant.condition)?;
+        }
+        Ok(())
+    }
+
+    /// Check one invariant condition, seeing through the `__forall`/`__exists`
+    /// calls quantifiers desugar to and the `||` an implication becomes.
+    fn check_invariant_condition(&mut self, cond: &Expr) -> Result<(), TypeError> {
+        match &cond.kind {
+            ExprKind::Binary(left, BinOp::And | BinOp::Or, right) => {
+                self.check_invariant_condition(left)?;
+                self.check_invariant_condition(right)
+            }
+            ExprKind::Unary(UnaryOp::Not, operand) => self.check_invariant_condition(operand),
+            // `x in xs` becomes `xs.contains(x)`, which lists do not have as a method
+            ExprKind::MethodCall(collection, method, args)
+                if method.name == "contains" && args.len() == 1 =>
+            {
+                let collection_ty = self.infer_expr(collection)?;
+                match collection_ty.apply(self.unifier.substitution()) {
+                    Ty::List(elem) | Ty::Set(elem) | Ty::Array(elem, _) => {
+                        let item_ty = self.infer_expr(&args[0].value)?;
+                        self.unifier.unify(&item_ty, &elem, args[0].span)
+                    }
+                    _ => self.check_condition(cond, "loop invariant"),
+                }
+            }
+            ExprKind::Call(callee, args)
+                if matches!(&callee.kind, ExprKind::Ident(name)
+                    if name.name == "__forall" || name.name == "__exists") =>
+            {
+                let [iter, predicate] = args.as_slice() else {
+                    return self.check_condition(cond, "loop invariant");
+                };
+                let ExprKind::Closure(closure) = &predicate.value.kind else {
+                    return self.check_condition(cond, "loop invariant");
+                };
+                let [param] = closure.params.as_slice() else {
+                    return self.check_condition(cond, "loop invariant");
+                };
+
+                // Quantifiers range over a list or a range, like `for`
+                let iter_ty = self.infer_expr(&iter.value)?;
+                let elem_ty = Ty::fresh_var();
+                let collection_ty = if matches!(&iter.value.kind, ExprKind::Range(_, _, _)) {
+                    Ty::Named(TypeId::new("Range"), vec![elem_ty.clone()])
+                } else {
+                    Ty::List(Box::new(elem_ty.clone()))
+                };
+                self.unifier
+                    .unify(&iter_ty, &collection_ty, iter.value.span)?;
+
+                let mut quant_env = self.env.child();
+                quant_env.define(&param.name, TypeScheme::mono(elem_ty));
+                let old_env = std::mem::replace(&mut self.env, quant_env);
+                let result = self.check_invariant_condition(&closure.body);
+                self.env = old_env;
+                result
+            }
+            _ => self.check_condition(cond, "loop invariant"),
+        }
+    }
+
+    /// Infer a loop body, where `br` and `ct` are allowed.
+    fn infer_loop_body(&mut self, body: &Block) -> Result<Ty, TypeError> {
+        self.loop_depth += 1;
+        let result = self.infer_block(body);
+        self.loop_depth -= 1;
+        result
+    }
+
     /// Infer the type of a block.
     pub fn infer_block(&mut self, block: &Block) -> Result<Ty, TypeError> {
         let mut result_type = Ty::Unit;
@@ -5734,6 +6200,17 @@ impl InferenceEngine {
                 // Bind the pattern
                 self.bind_pattern(&l.pattern, &var_type)?;
 
+                if l.ty.is_none()
+                    && is_empty_container(&l.init)
+                    && let PatternKind::Ident(ident, _, _) = &l.pattern.kind
+                {
+                    self.empty_container_lets.push(EmptyContainerLet {
+                        name: ident.name.clone(),
+                        ty: var_type,
+                        span: stmt.span,
+                    });
+                }
+
                 Ok(Ty::Unit)
             }
             StmtKind::Expr(e) => self.infer_expr(e),
@@ -5747,10 +6224,19 @@ impl InferenceEngine {
 
     /// Infer the type of an expression.
     pub fn infer_expr(&mut self, expr: &Expr) -> Result<Ty, TypeError> {
+        let ty = self.infer_expr_kind(expr)?;
+        self.expr_types.push((expr.span, ty.clone()));
+        Ok(ty)
+    }
+
+    fn infer_expr_kind(&mut self, expr: &Expr) -> Result<Ty, TypeError> {
         match &expr.kind {
             ExprKind::Literal(lit) => self.infer_literal(&lit.kind, expr.span),
 
             ExprKind::Ident(name) => {
+                if let Some(def) = self.env.definition(&name.name) {
+                    self.references.push((name.span, def));
+                }
                 if let Some(scheme) = self.env.get(&name.name) {
                     Ok(scheme.instantiate())
                 } else {
@@ -5811,7 +6297,7 @@ impl InferenceEngine {
                 let operand_ty = self.infer_expr(operand)?;
 
                 match op {
-                    UnaryOp::Neg => Ok(operand_ty),
+                    UnaryOp::Plus | UnaryOp::Neg => Ok(operand_ty),
                     UnaryOp::Not => {
                         self.unifier.unify(&operand_ty, &Ty::Bool, expr.span)?;
                         Ok(Ty::Bool)
@@ -5828,12 +6314,46 @@ impl InferenceEngine {
             }
 
             ExprKind::Call(callee, args) => {
+                // old(...) is contract syntax, not a function
+                if let ExprKind::Ident(name) = &callee.kind
+                    && name.name == "old"
+                    && self.env.get("old").is_none()
+                {
+                    return Err(TypeError::new(
+                        "old(...) is only valid in @post contracts",
+                        expr.span,
+                    ));
+                }
+
                 // Infer argument types first
                 let arg_types: Vec<Ty> = args
                     .iter()
                     .map(|a| self.infer_expr(&a.value))
                     .collect::<Result<Vec<_>, _>>()?;
 
+                // Formatted print: print("x = {}", x)
+                if let ExprKind::Ident(name) = &callee.kind
+                    && matches!(name.name.as_str(), "print" | "println")
+                    && args.len() > 1
+                    && self.env.get_fn_info(&name.name).is_none()
+                {
+                    self.check_format_call(args, &arg_types)?;
+                    return Ok(Ty::Unit);
+                }
+
+                // assert(cond, message): the message is optional
+                if let ExprKind::Ident(name) = &callee.kind
+                    && name.name == "assert"
+                    && args.len() == 2
+                    && self.env.get_fn_info(&name.name).is_none()
+                {
+                    self.unifier
+                        .unify(&arg_types[0], &Ty::Bool, args[0].value.span)?;
+                    self.unifier
+                        .unify(&arg_types[1], &Ty::Str, args[1].value.span)?;
+                    return Ok(Ty::Unit);
+                }
+
                 // Check if callee is an identifier with function info (for default params)
                 if let ExprKind::Ident(name) = &callee.kind
                     && let Some(fn_info) = self.env.get_fn_info(&name.name)
@@ -5863,9 +6383,31 @@ impl InferenceEngine {
                         ));
                     }
 
+                    // Bind positional and named arguments to parameters
+                    let slots = bind_call_args(&name.name, &fn_info.param_names, args)
+                        .map_err(|(message, span)| TypeError::new(message, span))?;
+                    if let Some(missing) = slots
+                        .iter()
+                        .zip(&fn_info.param_has_default)
+                        .position(|(slot, has_default)| slot.is_none() && !has_default)
+                    {
+                        return Err(TypeError::new(
+                            format!(
+                                "missing argument for parameter '{}' of '{}'",
+                                fn_info.param_names[missing], name.name
+                            ),
+                            expr.span,
+                        ));
+                    }
+                    let bound: Vec<(usize, &Arg)> = slots
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(param, slot)| slot.map(|a| (param, &args[a])))
+                        .collect();
+
                     // Validate pass modes match between call site and declaration
                     let param_modes = &fn_info.param_pass_modes;
-                    for (i, arg) in args.iter().enumerate().take(provided) {
+                    for &(i, arg) in &bound {
                         if i < param_modes.len() {
                             let expected = param_modes[i];
                             let actual = arg.pass_mode;
@@ -5950,7 +6492,7 @@ impl InferenceEngine {
                     // Basic borrow check: reject same variable as ref mut in multiple args
                     {
                         let mut ref_mut_vars: Vec<(String, usize)> = Vec::new();
-                        for (i, arg) in args.iter().enumerate().take(provided) {
+                        for &(i, arg) in &bound {
                             if arg.pass_mode == PassMode::RefMut
                                 && let ExprKind::Ident(ident) = &arg.value.kind
                             {
@@ -5978,14 +6520,11 @@ impl InferenceEngine {
                         .param_types
                         .iter()
                         .enumerate()
-                        .map(|(i, param_ty)| {
-                            if i < provided {
-                                // Use provided argument type
-                                arg_types[i].clone()
-                            } else {
-                                // Use parameter type (from default)
-                                param_ty.clone()
-                            }
+                        .map(|(i, param_ty)| match slots[i] {
+                            // Use provided argument type
+                            Some(a) => arg_types[a].clone(),
+                            // Use parameter type (from default)
+                            None => param_ty.clone(),
                         })
                         .collect();
 
@@ -6086,6 +6625,18 @@ impl InferenceEngine {
                 let base_ty = self.infer_expr(base)?;
                 let index_ty = self.infer_expr(index)?;
 
+                match base_ty.apply(&self.unifier.subst) {
+                    Ty::Bytes => {
+                        self.unifier.unify(&index_ty, &Ty::Int, expr.span)?;
+                        return Ok(Ty::U8);
+                    }
+                    Ty::Str => {
+                        self.unifier.unify(&index_ty, &Ty::Int, expr.span)?;
+                        return Ok(Ty::Char);
+                    }
+                    _ => {}
+                }
+
                 // For list/array indexing — use checkpoint to avoid corrupting
                 // substitution state if this speculative unification fails
                 let elem_ty = Ty::fresh_var();
@@ -6161,8 +6712,7 @@ impl InferenceEngine {
             }
 
             ExprKind::If(if_expr) => {
-                let cond_ty = self.infer_expr(&if_expr.condition)?;
-                self.unifier.unify(&cond_ty, &Ty::Bool, expr.span)?;
+                self.check_condition(&if_expr.condition, "if")?;
 
                 let then_ty = match &if_expr.then_branch {
                     crate::parser::IfBranch::Expr(e) => self.infer_expr(e)?,
@@ -6178,7 +6728,17 @@ impl InferenceEngine {
                             self.infer_expr(&elif_expr)?
                         }
                     };
-                    self.unifier.unify(&then_ty, &else_ty, expr.span)?;
+                    if self.unifier.unify(&then_ty, &else_ty, expr.span).is_err() {
+                        let subst = self.unifier.substitution();
+                        return Err(TypeError::new(
+                            format!(
+                                "if and else branches have incompatible types: then is {}, else is {}",
+                                then_ty.apply(subst),
+                                else_ty.apply(subst)
+                            ),
+                            expr.span,
+                        ));
+                    }
                     Ok(then_ty)
                 } else {
                     self.unifier.unify(&then_ty, &Ty::Unit, expr.span)?;
@@ -6198,9 +6758,9 @@ impl InferenceEngine {
 
                     if let Some(guard) = &arm.guard {
                         let old_env = std::mem::replace(&mut self.env, arm_env.clone());
-                        let guard_ty = self.infer_expr(guard)?;
+                        let guard_ok = self.check_condition(guard, "match guard");
                         self.env = old_env;
-                        self.unifier.unify(&guard_ty, &Ty::Bool, expr.span)?;
+                        guard_ok?;
                     }
 
                     let old_env = std::mem::replace(&mut self.env, arm_env);
@@ -6210,6 +6770,8 @@ impl InferenceEngine {
                     self.unifier.unify(&body_ty, &result_ty, expr.span)?;
                 }
 
+                self.check_match_exhaustive(&scrutinee_ty, arms, expr.span)?;
+
                 Ok(result_ty)
             }
 
@@ -6233,11 +6795,14 @@ impl InferenceEngine {
 
                 let mut closure_env = self.env.child();
                 for (param, ty) in c.params.iter().zip(param_types.iter()) {
-                    closure_env.insert(param.name.name.clone(), TypeScheme::mono(ty.clone()));
+                    closure_env.define(&param.name, TypeScheme::mono(ty.clone()));
                 }
 
                 let old_env = std::mem::replace(&mut self.env, closure_env);
-                let body_ty = self.infer_expr(&c.body)?;
+                let old_loop_depth = std::mem::take(&mut self.loop_depth);
+                let body_ty = self.infer_expr(&c.body);
+                self.loop_depth = old_loop_depth;
+                let body_ty = body_ty?;
                 self.env = old_env;
 
                 let return_ty = if let Some(ty) = &c.return_type {
@@ -6266,15 +6831,23 @@ impl InferenceEngine {
             }
 
             ExprKind::Break(_, value) => {
+                if self.loop_depth == 0 {
+                    return Err(TypeError::new("`br` outside of a loop", expr.span));
+                }
                 if let Some(v) = value {
                     self.infer_expr(v)?;
                 }
                 Ok(Ty::Never)
             }
 
-            ExprKind::Continue(_) => Ok(Ty::Never),
+            ExprKind::Continue(_) => {
+                if self.loop_depth == 0 {
+                    return Err(TypeError::new("`ct` outside of a loop", expr.span));
+                }
+                Ok(Ty::Never)
+            }
 
-            ExprKind::For(_label, pattern, iter, body) => {
+            ExprKind::For(_label, pattern, iter, body, invariants) => {
                 let iter_ty = self.infer_expr(iter)?;
 
                 // For loops can iterate over:
@@ -6298,17 +6871,20 @@ impl InferenceEngine {
                 let mut loop_env = self.env.child();
                 self.collect_pattern_bindings(pattern, &elem_ty, &mut loop_env)?;
 
+                // Invariants are checked once the loop variable is bound, so
+                // they may refer to it
                 let old_env = std::mem::replace(&mut self.env, loop_env);
-                self.infer_block(body)?;
+                self.check_loop_invariants(invariants)?;
+                self.infer_loop_body(body)?;
                 self.env = old_env;
 
                 Ok(Ty::Unit)
             }
 
-            ExprKind::While(_label, cond, body) => {
-                let cond_ty = self.infer_expr(cond)?;
-                self.unifier.unify(&cond_ty, &Ty::Bool, expr.span)?;
-                self.infer_block(body)?;
+            ExprKind::While(_label, cond, body, invariants) => {
+                self.check_loop_invariants(invariants)?;
+                self.check_condition(cond, "while")?;
+                self.infer_loop_body(body)?;
                 Ok(Ty::Unit)
             }
 
@@ -6320,14 +6896,14 @@ impl InferenceEngine {
                 self.collect_pattern_bindings(pattern, &expr_ty, &mut loop_env)?;
 
                 let old_env = std::mem::replace(&mut self.env, loop_env);
-                self.infer_block(body)?;
+                self.infer_loop_body(body)?;
                 self.env = old_env;
 
                 Ok(Ty::Unit)
             }
 
             ExprKind::Loop(_label, body) => {
-                self.infer_block(body)?;
+                self.infer_loop_body(body)?;
                 Ok(Ty::Never)
             }
 
@@ -6365,7 +6941,7 @@ impl InferenceEngine {
                                 def_fields.iter().find(|(n, _)| n == &field.name.name)
                             {
                                 // Apply type parameter substitution to the definition type
-                                let expected_ty = self.substitute_type_params(def_field_ty, &subst);
+                                let expected_ty = def_field_ty.substitute_named(&subst);
                                 self.unifier.unify(&value_ty, &expected_ty, expr.span)?;
                             }
                         }
@@ -6424,6 +7000,26 @@ impl InferenceEngine {
                         expr.span,
                     ));
                 }
+                // A literal that fits the target loses nothing
+                let literal_fits = match &e.kind {
+                    ExprKind::Literal(Literal {
+                        kind: LiteralKind::Int(n),
+                        ..
+                    }) => Self::int_fits(*n, &target_ty),
+                    _ => false,
+                };
+                if !literal_fits && Self::is_narrowing_cast(&resolved_source, &target_ty) {
+                    self.warnings.push(
+                        Diagnostic::warning(
+                            format!(
+                                "cast from {} to {} may lose data",
+                                resolved_source, target_ty
+                            ),
+                            expr.span,
+                        )
+                        .with_code(Lint::NarrowingCast.code()),
+                    );
+                }
 
                 Ok(target_ty)
             }
@@ -6581,6 +7177,7 @@ impl InferenceEngine {
             LiteralKind::Int(_) => Ok(Ty::Int),
             LiteralKind::Float(_) => Ok(Ty::Float),
             LiteralKind::String(_) => Ok(Ty::Str),
+            LiteralKind::Bytes(_) => Ok(Ty::Bytes),
             LiteralKind::Char(_) => Ok(Ty::Char),
             LiteralKind::Bool(_) => Ok(Ty::Bool),
             LiteralKind::None => Ok(Ty::Option(Box::new(Ty::fresh_var()))),
@@ -6848,7 +7445,7 @@ impl InferenceEngine {
                 // These are constructor patterns, not variable bindings, and binding them
                 // would shadow the constructor in the arm body causing type conflicts.
                 if self.env.get_enum_for_variant(&ident.name).is_none() {
-                    env.insert(ident.name.clone(), TypeScheme::mono(ty.clone()));
+                    env.define(ident, TypeScheme::mono(ty.clone()));
                 }
                 if let Some(sub) = subpattern {
                     self.collect_pattern_bindings(sub, ty, env)?;
@@ -6918,7 +7515,7 @@ impl InferenceEngine {
                                 self.collect_pattern_bindings(p, &field_ty, env)?;
                             } else {
                                 // Shorthand: field name is also the binding
-                                env.insert(field.name.name.clone(), TypeScheme::mono(field_ty));
+                                env.define(&field.name, TypeScheme::mono(field_ty));
                             }
                         }
                     }
@@ -6968,10 +7565,7 @@ impl InferenceEngine {
                                 self.collect_pattern_bindings(p, concrete_ty, env)?;
                             } else {
                                 // The field name is the binding
-                                env.insert(
-                                    field.name.name.clone(),
-                                    TypeScheme::mono(concrete_ty.clone()),
-                                );
+                                env.define(&field.name, TypeScheme::mono(concrete_ty.clone()));
                             }
                         }
                     }
@@ -7002,8 +7596,7 @@ impl InferenceEngine {
     fn bind_pattern(&mut self, pattern: &Pattern, ty: &Ty) -> Result<(), TypeError> {
         match &pattern.kind {
             PatternKind::Ident(ident, _mutable, _subpattern) => {
-                self.env
-                    .insert(ident.name.clone(), TypeScheme::mono(ty.clone()));
+                self.env.define(ident, TypeScheme::mono(ty.clone()));
             }
             PatternKind::Tuple(elems) => {
                 if let Ty::Tuple(elem_tys) = ty.apply(&self.unifier.subst) {
@@ -7070,6 +7663,12 @@ impl InferenceEngine {
                     "Str" | "String" => Ok(Ty::Str),
                     "Unit" => Ok(Ty::Unit),
                     "Json" => Ok(Ty::Json),
+                    "Bytes" => Ok(Ty::Bytes),
+                    // Aliases are transparent; recursive ones were rejected
+                    // where they were declared
+                    _ if matches!(self.env.get_type(name), Some(TypeDef::Alias { .. })) => Ok(self
+                        .expand_alias(name, &args, &mut Vec::new())
+                        .unwrap_or(Ty::Error)),
                     _ => Ok(Ty::Named(TypeId::new(name), args)),
                 }
             }
@@ -7153,6 +7752,35 @@ impl InferenceEngine {
 
     /// Check if a type can be cast to another type.
     /// Returns true if the cast is valid.
+    /// Whether a numeric cast can drop bits: float to integer, or to a
+    /// narrower type of the same kind.
+    fn is_narrowing_cast(from: &Ty, to: &Ty) -> bool {
+        if from.is_float() && to.is_integer() {
+            return true;
+        }
+        let same_kind =
+            (from.is_integer() && to.is_integer()) || (from.is_float() && to.is_float());
+        match (from.numeric_bits(), to.numeric_bits()) {
+            (Some(src), Some(dst)) => same_kind && dst < src,
+            _ => false,
+        }
+    }
+
+    /// Whether `n` is representable in the integer type `ty`.
+    fn int_fits(n: i128, ty: &Ty) -> bool {
+        let Some(bits) = ty.numeric_bits().filter(|_| ty.is_integer()) else {
+            return false;
+        };
+        if bits >= 128 {
+            return n >= 0 || !ty.is_unsigned_integer();
+        }
+        if ty.is_unsigned_integer() {
+            n >= 0 && n < (1i128 << bits)
+        } else {
+            n >= -(1i128 << (bits - 1)) && n < (1i128 << (bits - 1))
+        }
+    }
+
     fn can_cast(&self, from: &Ty, to: &Ty) -> bool {
         // Same type is always valid
         if from == to {
@@ -7168,10 +7796,13 @@ impl InferenceEngine {
             return true;
         }
 
-        // Bool to int is allowed
+        // Bool to int (0 or 1) and int to Bool (`!= 0`) are allowed
         if matches!(from, Ty::Bool) && to.is_integer() {
             return true;
         }
+        if from.is_integer() && matches!(to, Ty::Bool) {
+            return true;
+        }
 
         // Char to int is allowed
         if matches!(from, Ty::Char) && to.is_integer() {
@@ -7196,6 +7827,32 @@ impl InferenceEngine {
         &self.env
     }
 
+    /// Lint warnings collected while checking.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Span of the declaration that the identifier use at `offset` resolved
+    /// to: a function, constant, parameter or local binding.
+    pub fn definition_at(&self, offset: usize) -> Option<Span> {
+        self.references
+            .iter()
+            .rev()
+            .find(|(span, _)| span.start <= offset && offset < span.end)
+            .map(|(_, def)| *def)
+    }
+
+    /// The innermost checked expression whose span contains `offset`, with
+    /// its type after the final substitution.
+    pub fn type_at(&self, offset: usize) -> Option<Ty> {
+        self.expr_types
+            .iter()
+            .rev()
+            .filter(|(span, _)| span.start <= offset && offset < span.end)
+            .min_by_key(|(span, _)| span.end - span.start)
+            .map(|(_, ty)| self.finalize_type(ty))
+    }
+
     pub fn get_symbol_location(
         &self,
         name: &str,
@@ -7203,6 +7860,48 @@ impl InferenceEngine {
         self.symbol_locations.get(name).copied()
     }
 
+    /// Check a formatted `print`: the format string must be a literal with
+    /// one `{}` per remaining argument, and each argument must be an
+    /// integer, float, boolean or string.
+    fn check_format_call(&mut self, args: &[Arg], arg_types: &[Ty]) -> Result<(), TypeError> {
+        let ExprKind::Literal(Literal {
+            kind: LiteralKind::String(fmt),
+            ..
+        }) = &args[0].value.kind
+        else {
+            return Err(TypeError::new(
+                "the format string must be a string literal",
+                args[0].span,
+            ));
+        };
+        let segments =
+            split_format_string(fmt).map_err(|message| TypeError::new(message, args[0].span))?;
+        let placeholders = segments.len() - 1;
+        if placeholders != args.len() - 1 {
+            return Err(TypeError::new(
+                format!(
+                    "format string has {} placeholder(s) but {} argument(s) were given",
+                    placeholders,
+                    args.len() - 1
+                ),
+                args[0].span,
+            ));
+        }
+        for (arg, ty) in args.iter().zip(arg_types).skip(1) {
+            let ty = ty.apply(&self.unifier.subst);
+            if !matches!(
+                ty,
+                Ty::Int | Ty::Float | Ty::Bool | Ty::Str | Ty::Var(_) | Ty::Error
+            ) {
+                return Err(TypeError::new(
+                    format!("cannot format a value of type {}", ty),
+                    arg.span,
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Check whether an expression is a "place" (lvalue) that can be passed by reference.
     fn is_place_expr(expr: &Expr) -> bool {
         matches!(
@@ -0,0 +1,469 @@
+//! Lint-style warnings that do not stop compilation.
+//!
+//! Runs over the parsed AST after type checking and reports:
+//! - `unused_variable` (`UNUSED`): a local bound with `=`/`:=` whose value is
+//!   never read
+//! - `dead_code` (`UNREACHABLE`): a statement following `ret`, `br` or `ct` in
+//!   the same block
+//!
+//! The type checker adds `narrowing_cast` (`NARROWING`) for an `as` cast to a
+//! smaller numeric type. It needs resolved types, so it is reported by
+//! [`crate::types::TypeChecker::warnings`] rather than [`lint`], and it is
+//! allowed unless enabled.
+//!
+//! Each lint can be allowed, left as a warning or denied with [`LintLevels`].
+//!
+//! Locals are tracked by name within a function, so a name read anywhere in
+//! the function counts as used. Names starting with `_` are never reported.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::Diagnostic;
+use crate::parser::{
+    Block, ElseBranch, Expr, ExprKind, FnBody, Function, IfBranch, IfExpr, ImplItem, Item,
+    ItemKind, Pattern, PatternKind, SourceFile, Stmt, StmtKind,
+};
+
+/// A named lint that `check` can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    DeadCode,
+    NarrowingCast,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 3] = [Lint::UnusedVariable, Lint::DeadCode, Lint::NarrowingCast];
+
+    /// Name used by `--allow`, `--warn` and `--deny`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::DeadCode => "dead_code",
+            Lint::NarrowingCast => "narrowing_cast",
+        }
+    }
+
+    /// Diagnostic code attached to the lint's warnings.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "UNUSED",
+            Lint::DeadCode => "UNREACHABLE",
+            Lint::NarrowingCast => "NARROWING",
+        }
+    }
+
+    /// Level used when no flag names the lint.
+    pub fn default_level(self) -> LintLevel {
+        match self {
+            Lint::NarrowingCast => LintLevel::Allow,
+            Lint::UnusedVariable | Lint::DeadCode => LintLevel::Warn,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    pub fn from_code(code: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.code() == code)
+    }
+}
+
+/// What to do with a lint's diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Per-lint levels; each lint uses [`Lint::default_level`] unless configured
+/// otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, LintLevel>,
+    deny_warnings: bool,
+}
+
+impl LintLevels {
+    /// Set the level of the lint called `name`. Returns `false` if there is
+    /// no such lint.
+    pub fn set(&mut self, name: &str, level: LintLevel) -> bool {
+        match Lint::from_name(name) {
+            Some(lint) => {
+                self.levels.insert(lint, level);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Treat every lint left at `Warn` as `Deny`.
+    pub fn deny_warnings(&mut self) {
+        self.deny_warnings = true;
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        match self
+            .levels
+            .get(&lint)
+            .copied()
+            .unwrap_or(lint.default_level())
+        {
+            LintLevel::Warn if self.deny_warnings => LintLevel::Deny,
+            level => level,
+        }
+    }
+}
+
+/// Collect warnings for every function in `ast`.
+pub fn lint(ast: &SourceFile) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    for item in &ast.items {
+        lint_item(item, &mut warnings);
+    }
+    warnings.sort_by_key(|d| (d.span.line, d.span.column));
+    warnings
+}
+
+fn lint_item(item: &Item, warnings: &mut Vec<Diagnostic>) {
+    match &item.kind {
+        ItemKind::Function(f) => lint_function(f, warnings),
+        ItemKind::Impl(impl_block) => {
+            for impl_item in &impl_block.items {
+                if let ImplItem::Function(f) = impl_item {
+                    lint_function(f, warnings);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn lint_function(f: &Function, warnings: &mut Vec<Diagnostic>) {
+    let Some(body) = &f.body else {
+        return;
+    };
+    let mut lint = FunctionLint {
+        bindings: Vec::new(),
+        reads: HashSet::new(),
+        warnings,
+    };
+    for contract in f.preconditions.iter().chain(&f.postconditions) {
+        lint.visit_expr(&contract.condition);
+    }
+    match body {
+        FnBody::Expr(expr) => lint.visit_expr(expr),
+        FnBody::Block(block) => lint.visit_block(block),
+    }
+
+    let FunctionLint {
+        bindings,
+        reads,
+        warnings,
+    } = lint;
+    for (name, span) in bindings {
+        if !reads.contains(&name) {
+            warnings.push(
+                Diagnostic::warning(format!("unused variable `{}`", name), span)
+                    .with_code(Lint::UnusedVariable.code())
+                    .with_help(format!("prefix it with an underscore: `_{}`", name)),
+            );
+        }
+    }
+}
+
+struct FunctionLint<'a> {
+    /// First binding site of each local, in source order
+    bindings: Vec<(String, crate::lexer::Span)>,
+    /// Names read anywhere in the function
+    reads: HashSet<String>,
+    warnings: &'a mut Vec<Diagnostic>,
+}
+
+impl FunctionLint<'_> {
+    fn visit_block(&mut self, block: &Block) {
+        let mut diverged = false;
+        let mut reported = false;
+        for stmt in &block.stmts {
+            if matches!(stmt.kind, StmtKind::Empty) {
+                continue;
+            }
+            if diverged && !reported {
+                self.warnings.push(
+                    Diagnostic::warning("unreachable statement", stmt.span)
+                        .with_code(Lint::DeadCode.code()),
+                );
+                reported = true;
+            }
+            self.visit_stmt(stmt);
+            if let StmtKind::Expr(expr) = &stmt.kind
+                && matches!(
+                    expr.kind,
+                    ExprKind::Return(_) | ExprKind::Break(..) | ExprKind::Continue(_)
+                )
+            {
+                diverged = true;
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Item(item) => lint_item(item, self.warnings),
+            StmtKind::Let(let_stmt) => {
+                self.visit_expr(&let_stmt.init);
+                self.bind_pattern(&let_stmt.pattern);
+            }
+            StmtKind::Expr(expr) => self.visit_expr(expr),
+            StmtKind::Empty => {}
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Ident(ident, _, sub) => {
+                if !ident.name.starts_with('_')
+                    && !self.bindings.iter().any(|(name, _)| *name == ident.name)
+                {
+                    self.bindings.push((ident.name.clone(), ident.span));
+                }
+                if let Some(sub) = sub {
+                    self.bind_pattern(sub);
+                }
+            }
+            PatternKind::Tuple(patterns) | PatternKind::Or(patterns) => {
+                for p in patterns {
+                    self.bind_pattern(p);
+                }
+            }
+            PatternKind::List(patterns, rest) => {
+                for p in patterns {
+                    self.bind_pattern(p);
+                }
+                if let Some(rest) = rest {
+                    self.bind_pattern(rest);
+                }
+            }
+            PatternKind::Struct(_, fields, _) => {
+                for field in fields {
+                    if let Some(p) = &field.pattern {
+                        self.bind_pattern(p);
+                    }
+                }
+            }
+            PatternKind::Ref(inner, _) => self.bind_pattern(inner),
+            PatternKind::Wildcard
+            | PatternKind::Literal(_)
+            | PatternKind::Range(..)
+            | PatternKind::Rest => {}
+        }
+    }
+
+    fn visit_if(&mut self, if_expr: &IfExpr) {
+        self.visit_expr(&if_expr.condition);
+        match &if_expr.then_branch {
+            IfBranch::Expr(expr) => self.visit_expr(expr),
+            IfBranch::Block(block) => self.visit_block(block),
+        }
+        match &if_expr.else_branch {
+            Some(ElseBranch::Expr(expr)) => self.visit_expr(expr),
+            Some(ElseBranch::Block(block)) => self.visit_block(block),
+            Some(ElseBranch::ElseIf(inner)) => self.visit_if(inner),
+            None => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(ident) => {
+                self.reads.insert(ident.name.clone());
+            }
+            ExprKind::Path(path) => {
+                if let Some(first) = path.segments.first() {
+                    self.reads.insert(first.name.clone());
+                }
+            }
+            ExprKind::FieldShorthand(_) | ExprKind::Literal(_) | ExprKind::Continue(_) => {}
+            ExprKind::Binary(lhs, _, rhs)
+            | ExprKind::Index(lhs, rhs)
+            | ExprKind::ArrayRepeat(lhs, rhs)
+            | ExprKind::Coalesce(lhs, rhs)
+            | ExprKind::Pipeline(lhs, rhs)
+            | ExprKind::AssignOp(lhs, _, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleField(inner, _)
+            | ExprKind::OpShorthand(_, inner, _)
+            | ExprKind::Await(inner)
+            | ExprKind::Spawn(inner)
+            | ExprKind::Try(inner)
+            | ExprKind::Paren(inner)
+            | ExprKind::Cast(inner, _) => self.visit_expr(inner),
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(&arg.value);
+                }
+            }
+            ExprKind::MethodCall(receiver, _, args) => {
+                self.visit_expr(receiver);
+                for arg in args {
+                    self.visit_expr(&arg.value);
+                }
+            }
+            ExprKind::Tuple(items) | ExprKind::Array(items) => {
+                for item in items {
+                    self.visit_expr(item);
+                }
+            }
+            ExprKind::MapOrSet(entries) => {
+                for entry in entries {
+                    self.visit_expr(&entry.key);
+                    if let Some(value) = &entry.value {
+                        self.visit_expr(value);
+                    }
+                }
+            }
+            ExprKind::Struct(_, fields, base) => {
+                for field in fields {
+                    match &field.value {
+                        Some(value) => self.visit_expr(value),
+                        // Shorthand `Point { x }` reads the local `x`
+                        None => {
+                            self.reads.insert(field.name.name.clone());
+                        }
+                    }
+                }
+                if let Some(base) = base {
+                    self.visit_expr(base);
+                }
+            }
+            ExprKind::If(if_expr) => self.visit_if(if_expr),
+            ExprKind::Match(scrutinee, arms) => {
+                self.visit_expr(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&arm.body);
+                }
+            }
+            ExprKind::For(_, _, iter, body, invariants) => {
+                self.visit_expr(iter);
+                for contract in invariants {
+                    self.visit_expr(&contract.condition);
+                }
+                self.visit_block(body);
+            }
+            ExprKind::While(_, cond, body, invariants) => {
+                self.visit_expr(cond);
+                for contract in invariants {
+                    self.visit_expr(&contract.condition);
+                }
+                self.visit_block(body);
+            }
+            ExprKind::WhileLet(_, _, scrutinee, body) => {
+                self.visit_expr(scrutinee);
+                self.visit_block(body);
+            }
+            ExprKind::Loop(_, block)
+            | ExprKind::Block(block)
+            | ExprKind::Async(block)
+            | ExprKind::Unsafe(block) => self.visit_block(block),
+            ExprKind::Closure(closure) => self.visit_expr(&closure.body),
+            ExprKind::Return(value) | ExprKind::Break(_, value) => {
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+            }
+            ExprKind::Range(start, end, _) => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.visit_expr(bound);
+                }
+            }
+            ExprKind::Assign(target, value, _) => {
+                // Overwriting a plain local is not a read of it
+                if !matches!(target.kind, ExprKind::Ident(_)) {
+                    self.visit_expr(target);
+                }
+                self.visit_expr(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_source(source: &str) -> Vec<Diagnostic> {
+        let (tokens, _) = crate::Scanner::new(source).scan_all();
+        let ast = crate::Parser::new(&tokens)
+            .parse()
+            .expect("parse should succeed");
+        lint(&ast)
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let mut levels = LintLevels::default();
+        assert_eq!(levels.level(Lint::UnusedVariable), LintLevel::Warn);
+        assert!(levels.set("unused_variable", LintLevel::Allow));
+        assert!(!levels.set("unused_variables", LintLevel::Deny));
+        levels.deny_warnings();
+        assert_eq!(levels.level(Lint::UnusedVariable), LintLevel::Allow);
+        assert_eq!(levels.level(Lint::DeadCode), LintLevel::Deny);
+        assert_eq!(Lint::from_code("UNREACHABLE"), Some(Lint::DeadCode));
+        // Opt-in lints stay allowed under --deny-warnings
+        assert_eq!(levels.level(Lint::NarrowingCast), LintLevel::Allow);
+        assert!(levels.set("narrowing_cast", LintLevel::Warn));
+        assert_eq!(levels.level(Lint::NarrowingCast), LintLevel::Deny);
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        let warnings = lint_source("f main() -> Int\n    x := 1\n    y := 2\n    y\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "unused variable `x`");
+        assert_eq!(warnings[0].code.as_deref(), Some("UNUSED"));
+        assert_eq!((warnings[0].span.line, warnings[0].span.column), (2, 5));
+    }
+
+    #[test]
+    fn test_used_variables_are_not_reported() {
+        let warnings = lint_source(
+            "f main() -> Int\n    a := 1\n    b := 2\n    c := [a]\n    _d := 4\n    n := 0\n    n = n + b\n    for x in c\n        print(x)\n    n\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_reassigned_but_never_read() {
+        let warnings = lint_source("f main() -> Int\n    x := 1\n    x = 2\n    0\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "unused variable `x`");
+    }
+
+    #[test]
+    fn test_unreachable_after_return() {
+        let warnings = lint_source("f main() -> Int\n    ret 1\n    print(2)\n    print(3)\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "unreachable statement");
+        assert_eq!(warnings[0].span.line, 3);
+    }
+
+    #[test]
+    fn test_unreachable_after_break_in_loop() {
+        let warnings = lint_source(
+            "f main() -> Int\n    i := 0\n    wh i < 3\n        br\n        i = i + 1\n    i\n",
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "unreachable statement");
+        assert_eq!(warnings[0].span.line, 5);
+    }
+}
@@ -4,6 +4,7 @@
 //! - Type representations (`types`)
 //! - Hindley-Milner type inference (`inference`)
 //! - Type checking and validation (`checker`)
+//! - Lint warnings for unused locals and unreachable code (`lints`)
 //!
 //! # Architecture
 //!
@@ -26,11 +27,16 @@
 
 pub mod checker;
 pub mod inference;
+pub mod lints;
 pub mod types;
 
 // Re-export main types
-pub use checker::{BoundsChecker, TypeChecker, TypeRelations, TypedAst, TypedItem, TypedItemKind};
+pub use checker::{
+    BoundsChecker, TypeChecker, TypeRelations, TypedAst, TypedItem, TypedItemKind, definition_of,
+    type_at,
+};
 pub use inference::{InferenceEngine, TypeDef, TypeEnv, TypeError, Unifier};
+pub use lints::{Lint, LintLevel, LintLevels, lint};
 pub use types::{
     Capability, EnumInfo, EnvCapability, FileCapability, FunctionInfo, LinearityKind, MethodInfo,
     Mutability, NetworkCapability, StructInfo, Substitution, TraitBound, TraitInfo, Ty, TypeId,
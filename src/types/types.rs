@@ -50,6 +50,8 @@ pub enum Ty {
     Bool,
     Char,
     Str,
+    /// Immutable byte buffer (`b"..."` literals)
+    Bytes,
     /// JSON value type (dynamic)
     Json,
 
@@ -379,10 +381,8 @@ impl Ty {
 
     fn collect_vars(&self, vars: &mut Vec<TypeVar>) {
         match self {
-            Ty::Var(v) => {
-                if !vars.contains(v) {
-                    vars.push(*v);
-                }
+            Ty::Var(v) if !vars.contains(v) => {
+                vars.push(*v);
             }
             Ty::Tuple(tys) => {
                 for ty in tys {
@@ -468,6 +468,54 @@ impl Ty {
         }
     }
 
+    /// Rebuild this type with `f` applied to each type directly inside it.
+    pub fn map_children(&self, mut f: impl FnMut(&Ty) -> Ty) -> Ty {
+        match self {
+            Ty::Tuple(tys) => Ty::Tuple(tys.iter().map(&mut f).collect()),
+            Ty::Array(ty, n) => Ty::Array(Box::new(f(ty)), *n),
+            Ty::List(ty) => Ty::List(Box::new(f(ty))),
+            Ty::Map(k, v) => {
+                let k = f(k);
+                Ty::Map(Box::new(k), Box::new(f(v)))
+            }
+            Ty::Set(ty) => Ty::Set(Box::new(f(ty))),
+            Ty::Task(ty) => Ty::Task(Box::new(f(ty))),
+            Ty::Future(ty) => Ty::Future(Box::new(f(ty))),
+            Ty::Sender(ty) => Ty::Sender(Box::new(f(ty))),
+            Ty::Receiver(ty) => Ty::Receiver(Box::new(f(ty))),
+            Ty::Mutex(ty) => Ty::Mutex(Box::new(f(ty))),
+            Ty::MutexGuard(ty) => Ty::MutexGuard(Box::new(f(ty))),
+            Ty::RawPtr(ty) => Ty::RawPtr(Box::new(f(ty))),
+            Ty::Option(ty) => Ty::Option(Box::new(f(ty))),
+            Ty::Result(ok, err) => {
+                let ok = f(ok);
+                Ty::Result(Box::new(ok), Box::new(f(err)))
+            }
+            Ty::Fn(params, ret) => {
+                let params = params.iter().map(&mut f).collect();
+                Ty::Fn(params, Box::new(f(ret)))
+            }
+            Ty::Ref(ty, m) => Ty::Ref(Box::new(f(ty)), *m),
+            Ty::Ptr(ty, m) => Ty::Ptr(Box::new(f(ty)), *m),
+            Ty::Named(id, args) => Ty::Named(id.clone(), args.iter().map(&mut f).collect()),
+            Ty::Alias(id, args) => Ty::Alias(id.clone(), args.iter().map(&mut f).collect()),
+            Ty::Associated(ty, name) => Ty::Associated(Box::new(f(ty)), name.clone()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Replace argument-less named types whose name is in `subst`, such as
+    /// the `T` in a generic struct field or alias target.
+    pub fn substitute_named(&self, subst: &HashMap<String, Ty>) -> Ty {
+        if let Ty::Named(id, args) = self
+            && args.is_empty()
+            && let Some(replacement) = subst.get(&id.name)
+        {
+            return replacement.clone();
+        }
+        self.map_children(|ty| ty.substitute_named(subst))
+    }
+
     /// Check if this type is a numeric type.
     pub fn is_numeric(&self) -> bool {
         matches!(
@@ -513,6 +561,34 @@ impl Ty {
         )
     }
 
+    /// Width in bits of an integer or float type; `Int`, `UInt`, `isize`,
+    /// `usize` and `Float` are 64-bit.
+    pub fn numeric_bits(&self) -> Option<u32> {
+        match self {
+            Ty::I8 | Ty::U8 => Some(8),
+            Ty::I16 | Ty::U16 => Some(16),
+            Ty::I32 | Ty::U32 | Ty::F32 => Some(32),
+            Ty::Int
+            | Ty::I64
+            | Ty::UInt
+            | Ty::U64
+            | Ty::Isize
+            | Ty::Usize
+            | Ty::Float
+            | Ty::F64 => Some(64),
+            Ty::I128 | Ty::U128 => Some(128),
+            _ => None,
+        }
+    }
+
+    /// Check if this type is an unsigned integer type.
+    pub fn is_unsigned_integer(&self) -> bool {
+        matches!(
+            self,
+            Ty::UInt | Ty::U8 | Ty::U16 | Ty::U32 | Ty::U64 | Ty::U128 | Ty::Usize
+        )
+    }
+
     /// Check if this type is a floating point type.
     pub fn is_float(&self) -> bool {
         matches!(self, Ty::Float | Ty::F32 | Ty::F64)
@@ -575,6 +651,7 @@ impl fmt::Display for Ty {
             Ty::Bool => write!(f, "Bool"),
             Ty::Char => write!(f, "Char"),
             Ty::Str => write!(f, "Str"),
+            Ty::Bytes => write!(f, "Bytes"),
             Ty::Unit => write!(f, "()"),
             Ty::Never => write!(f, "!"),
             Ty::Tuple(tys) => {
@@ -38,6 +38,270 @@ fn test_cli_run_hello() {
     assert!(stdout.contains("hello"), "stdout should contain 'hello'");
 }
 
+#[test]
+fn test_cli_run_print_format() {
+    let output = Command::new(forma_bin())
+        .args(["run", "--allow-all"])
+        .arg(fixture("print_format.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma run print_format.forma should exit 0"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "42 is the answer\n{} and 1.5 done\n");
+}
+
+#[test]
+fn test_cli_run_coverage() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = dir.path().join("branch.coverage");
+    let output = Command::new(forma_bin())
+        .args(["run", "--allow-all", "--coverage"])
+        .arg(fixture("coverage_branch.forma"))
+        .env("FORMA_COVERAGE_FILE", &report)
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma run --coverage should exit 0"
+    );
+
+    let counts = std::fs::read_to_string(&report).unwrap();
+    let lines: Vec<&str> = counts.lines().collect();
+    // bb_1 is the `x < 0` arm, which `sign(5)` never takes
+    assert!(lines.contains(&"sign bb_0 1"), "report: {}", counts);
+    assert!(lines.contains(&"sign bb_1 0"), "report: {}", counts);
+    assert!(lines.contains(&"sign bb_2 1"), "report: {}", counts);
+    assert!(lines.contains(&"main bb_0 1"), "report: {}", counts);
+}
+
+#[test]
+fn test_cli_run_program_args() {
+    let output = Command::new(forma_bin())
+        .args(["run"])
+        .arg(fixture("print_args.forma"))
+        .args(["--", "first", "--second"])
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success(), "forma run -- args should exit 0");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // argv[0] is the program, so two arguments make a count of three
+    assert_eq!(stdout, "3\nfirst\ntrue\n");
+}
+
+#[test]
+fn test_cli_run_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let cases = [
+        ("f main() -> Int\n    0\n", 0),
+        ("f main() -> Int\n    3\n", 3),
+        ("f main()\n    print(\"unit\")\n", 0),
+        ("f main() -> Int\n    300\n", 255),
+        ("f main() -> Int\n    0 - 1\n", 255),
+        ("f main() -> Int\n    0 - 256\n", 255),
+    ];
+    for (i, (source, expected)) in cases.into_iter().enumerate() {
+        let file = dir.path().join(format!("exit_{}.forma", i));
+        std::fs::write(&file, source).unwrap();
+        let output = Command::new(forma_bin())
+            .args(["run"])
+            .arg(&file)
+            .output()
+            .expect("failed to execute forma");
+        assert_eq!(
+            output.status.code(),
+            Some(expected),
+            "wrong exit code for {:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_cli_run_stack_size() {
+    // Each FORMA call recurses in the interpreter; unoptimized builds need
+    // more than the default 256 MiB for this depth
+    if cfg!(debug_assertions) {
+        let output = Command::new(forma_bin())
+            .args(["run"])
+            .arg(fixture("deep_recursion.forma"))
+            .output()
+            .expect("failed to execute forma");
+        assert!(!output.status.success(), "expected a stack overflow");
+    }
+
+    let output = Command::new(forma_bin())
+        .args(["run", "--stack-size", "1073741824"])
+        .arg(fixture("deep_recursion.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "20000\n");
+
+    let output = Command::new(forma_bin())
+        .args(["run", "--stack-size", "0"])
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("stack size must be at least"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_run_int_casts() {
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(fixture("int_casts.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "70000\n44\n255\n3.5\n3\ntrue\n0\ntrue\nfalse\n"
+    );
+
+    // Narrowing casts only warn when the lint is enabled
+    let check = |flags: &[&str]| {
+        Command::new(forma_bin())
+            .arg("check")
+            .args(flags)
+            .arg(fixture("int_casts.forma"))
+            .output()
+            .expect("failed to execute forma")
+    };
+    let stdout = String::from_utf8_lossy(&check(&[]).stdout).to_string();
+    assert!(!stdout.contains("may lose data"), "got: {}", stdout);
+    let output = check(&["--warn", "narrowing_cast"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("cast from Float to Int may lose data"),
+        "got: {}",
+        stdout
+    );
+    // Literals that fit the target are not reported
+    assert!(!stdout.contains("Int to i32"), "got: {}", stdout);
+}
+
+#[test]
+fn test_cli_run_byte_strings() {
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(fixture("byte_strings.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "3\n0\n1\n255\n66\n"
+    );
+}
+
+#[test]
+fn test_cli_run_string_constants() {
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(fixture("string_constants.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "5\n6\n'é'\n'c'\n'a'\n"
+    );
+
+    // Out-of-range constant indexes are compile errors
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bad_index.forma");
+    std::fs::write(&path, "LAST :: \"ab\"[2]\nf main()\n    print(LAST)\n").unwrap();
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(&path)
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let report = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        report.contains("string index 2 is out of bounds for length 2"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_cli_run_match_guards() {
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(fixture("match_guards.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "positive\nnegative\nzero\nabsent\nsmall\nmedium\nlarge\n"
+    );
+}
+
+#[test]
+fn test_cli_run_assert_statement() {
+    let dir = tempfile::tempdir().unwrap();
+    let run = |x: i64, flags: &[&str]| {
+        let path = dir.path().join(format!("assert_{}.forma", x.abs()));
+        let source = format!(
+            "f check(x: Int)\n    assert x > 0, \"x must be positive\"\n    print(x)\n\nf main()\n    check({})\n",
+            x
+        );
+        std::fs::write(&path, source).unwrap();
+        Command::new(forma_bin())
+            .arg("run")
+            .args(flags)
+            .arg(&path)
+            .output()
+            .expect("failed to execute forma")
+    };
+
+    let output = run(1, &[]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+
+    let output = run(-1, &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("x must be positive (at 2:5)"),
+        "got: {}",
+        stderr
+    );
+
+    // --no-assertions elides the check but keeps contracts on
+    let output = run(-1, &["--no-assertions"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "-1\n");
+}
+
 #[test]
 fn test_cli_run_syntax_error() {
     let output = Command::new(forma_bin())
@@ -113,6 +377,67 @@ fn test_cli_lex_hello() {
     );
 }
 
+#[test]
+fn test_cli_lex_hello_json() {
+    let output = Command::new(forma_bin())
+        .args(["lex", "--format", "json"])
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma lex --format json hello.forma should exit 0"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let tokens: serde_json::Value =
+        serde_json::from_str(&stdout).expect("lex JSON output should parse");
+    let tokens = tokens.as_array().expect("lex JSON should be an array");
+    let first = &tokens[0];
+    assert!(first["kind"].is_string(), "token should have a kind");
+    assert_eq!(first["text"], "f");
+    assert_eq!(first["start"], 0);
+    assert_eq!(first["end"], 1);
+    assert_eq!(first["byte_start"], 0);
+    assert_eq!(first["byte_end"], 1);
+    assert_eq!(first["line"], 1);
+    assert_eq!(first["column"], 1);
+    assert_eq!(
+        tokens.last().map(|t| t["kind"].clone()),
+        Some(serde_json::Value::from("Eof")),
+        "token stream should end with EOF"
+    );
+}
+
+#[test]
+fn test_cli_lex_error_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("unterminated.forma");
+    std::fs::write(&path, "f main() -> Int\n    x := \"unterminated\n").unwrap();
+
+    for format in ["text", "json"] {
+        let output = Command::new(forma_bin())
+            .args(["lex", "--error-format", "json", "--format", format])
+            .arg(&path)
+            .output()
+            .expect("failed to execute forma");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            !stderr.contains("error[LEX]"),
+            "lexer errors should not be printed as text: {}",
+            stderr
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let report: serde_json::Value =
+            serde_json::from_str(&stdout).expect("lex error output should be JSON");
+        assert_eq!(report["success"], false);
+        let error = &report["errors"][0];
+        assert_eq!(error["code"], "LEX");
+        assert_eq!(error["line"], 2);
+        assert_eq!(error["message"], "unterminated string");
+    }
+}
+
 #[test]
 fn test_cli_parse_hello() {
     let output = Command::new(forma_bin())
@@ -133,6 +458,28 @@ fn test_cli_parse_hello() {
     );
 }
 
+#[test]
+fn test_cli_parse_hello_json() {
+    let output = Command::new(forma_bin())
+        .args(["parse", "--format", "json"])
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma parse --format json hello.forma should exit 0"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let ast: serde_json::Value =
+        serde_json::from_str(&stdout).expect("parse JSON output should parse");
+    let function = &ast["items"][0]["kind"]["Function"];
+    assert_eq!(function["name"]["name"], "main");
+    assert!(
+        function["span"]["start"].is_u64(),
+        "nodes should carry spans"
+    );
+}
+
 #[test]
 fn test_cli_fmt_hello() {
     let output = Command::new(forma_bin())
@@ -151,6 +498,87 @@ fn test_cli_fmt_hello() {
     );
 }
 
+#[test]
+fn test_cli_fmt_preserves_comments() {
+    let path = fixture("comments.forma");
+    let source = std::fs::read_to_string(&path).unwrap();
+    let output = Command::new(forma_bin())
+        .args(["fmt"])
+        .arg(&path)
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in source.lines() {
+        if let Some(pos) = line.find('#') {
+            let comment = line[pos..].trim_end();
+            assert!(
+                stdout.contains(comment),
+                "comment `{}` was lost:\n{}",
+                comment,
+                stdout
+            );
+        }
+    }
+    // Trailing comments stay on their line
+    assert!(
+        stdout.contains("f main() -> Int  # entry point\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("y: Int  # vertical position\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_fmt_line_endings() {
+    let fmt = |path: &std::path::Path, line_ending: &str| {
+        let output = Command::new(forma_bin())
+            .args(["fmt", "--line-ending", line_ending])
+            .arg(path)
+            .output()
+            .expect("failed to execute forma");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // CRLF input to LF output, with exactly one trailing newline
+    let crlf = fixture("crlf.forma");
+    let lf_output = fmt(&crlf, "lf");
+    assert!(!lf_output.contains('\r'), "{:?}", lf_output);
+    assert!(
+        lf_output.ends_with("print(add(1, 2))  # 3\n"),
+        "{:?}",
+        lf_output
+    );
+
+    // `auto` keeps the dominant style of the input
+    assert_eq!(fmt(&crlf, "auto"), lf_output.replace('\n', "\r\n"));
+
+    // LF input to CRLF output
+    let dir = tempfile::tempdir().unwrap();
+    let lf = dir.path().join("lf.forma");
+    std::fs::write(&lf, &lf_output).unwrap();
+    let crlf_output = fmt(&lf, "crlf");
+    assert_eq!(
+        crlf_output.matches('\n').count(),
+        lf_output.matches('\n').count()
+    );
+    assert_eq!(
+        crlf_output.matches("\r\n").count(),
+        crlf_output.matches('\n').count()
+    );
+    assert!(crlf_output.ends_with("# 3\r\n"), "{:?}", crlf_output);
+    assert_eq!(fmt(&lf, "auto"), lf_output);
+}
+
 #[test]
 fn test_cli_fmt_json_error() {
     let output = Command::new(forma_bin())
@@ -202,6 +630,105 @@ fn test_cli_run_env_allowed() {
     );
 }
 
+#[test]
+fn test_cli_check_env_requires_capability() {
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(fixture("env_usage.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("capability 'env' required for operation 'env_get' (pass --allow-env)"),
+        "stdout: {}",
+        stdout
+    );
+
+    let output = Command::new(forma_bin())
+        .args(["check", "--allow-env"])
+        .arg(fixture("env_usage.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma check --allow-env env_usage.forma should exit 0"
+    );
+}
+
+#[test]
+fn test_cli_capabilities_report() {
+    let output = Command::new(forma_bin())
+        .args(["capabilities"])
+        .arg(fixture("env_usage.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("env (--allow-env)\n"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("env_usage.forma:2:5: env_get in main"),
+        "stdout: {}",
+        stdout
+    );
+
+    let output = Command::new(forma_bin())
+        .args(["capabilities", "--format", "json"])
+        .arg(fixture("env_usage.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("report should be JSON");
+    let capabilities = report["capabilities"].as_array().unwrap();
+    assert_eq!(capabilities.len(), 1, "{}", report);
+    assert_eq!(capabilities[0]["capability"], "env");
+    assert_eq!(capabilities[0]["uses"][0]["builtin"], "env_get");
+    assert_eq!(capabilities[0]["uses"][0]["line"], 2);
+}
+
+#[test]
+fn test_cli_run_tcp_connect_denied() {
+    let output = Command::new(forma_bin())
+        .args(["run"])
+        .arg(fixture("tcp_usage.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        !output.status.success(),
+        "forma run tcp_usage.forma without --allow-net should exit nonzero"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("capability 'network' required for operation 'tcp_connect to 127.0.0.1:9'"),
+        "error should mention capability, got: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_run_tcp_connect_restricted_to_allowed_hosts() {
+    let run = |flag: &str| {
+        Command::new(forma_bin())
+            .args(["run", flag])
+            .arg(fixture("tcp_usage.forma"))
+            .output()
+            .expect("failed to execute forma")
+    };
+    let output = run("--allow-net=127.0.0.1:10,localhost:9");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("capability"), "stderr: {}", stderr);
+
+    // Allowed, so a refused connection is just an Err result
+    assert!(run("--allow-net=127.0.0.1:9").status.success());
+    assert!(run("--allow-net").status.success());
+}
+
 #[test]
 fn test_cli_run_allow_all() {
     let output = Command::new(forma_bin())
@@ -241,6 +768,114 @@ fn test_cli_run_contract_violation() {
     );
 }
 
+#[test]
+fn test_cli_run_division_by_zero() {
+    let output = Command::new(forma_bin())
+        .args(["run"])
+        .arg(fixture("div_by_zero.forma"))
+        .output()
+        .expect("failed to execute forma");
+    // Exits with an error code rather than being killed by SIGFPE
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("division by zero"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_cli_check_deep_nesting() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("nested.forma");
+    let depth = 10_000;
+    let source = format!(
+        "f main() -> Int\n    {}1{}\n",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    );
+    std::fs::write(&file, source).unwrap();
+
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(&file)
+        .output()
+        .expect("failed to execute forma");
+    // Reported as a parse error rather than crashing on stack overflow
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("maximum expression nesting depth exceeded"),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_run_imported_functions() {
+    let output = Command::new(forma_bin())
+        .args(["run", "--allow-all"])
+        .arg(fixture("imports/main.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "forma run imports/main.forma should exit 0, stdout: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "hello, forma!\n49\n");
+}
+
+#[test]
+fn test_cli_check_pub_and_private_imports() {
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(fixture("imports/main.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success(), "importing pub items should check");
+
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(fixture("imports/private_import.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'shout' is private to module 'lib'"),
+        "stderr: {}",
+        stderr
+    );
+    // The diagnostic points at the `us` line of the importing file
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("private_import.forma:1:1"),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_check_circular_import() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.forma"), "us b\nf fa() -> Int = 1\n").unwrap();
+    std::fs::write(dir.path().join("b.forma"), "us a\nf fb() -> Int = 2\n").unwrap();
+    let main = dir.path().join("main.forma");
+    std::fs::write(&main, "us a\nf main() -> Int = fa()\n").unwrap();
+
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(&main)
+        .output()
+        .expect("failed to execute forma");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("circular module dependency detected"),
+        "stderr: {}",
+        stderr
+    );
+}
+
 #[test]
 fn test_cli_check_missing_import() {
     let output = Command::new(forma_bin())
@@ -274,15 +909,159 @@ fn test_cli_check_missing_import_json() {
         !output.status.success(),
         "forma --error-format json check missing_import.forma should exit nonzero"
     );
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("check output should be JSON");
+    let diagnostics = json.as_array().expect("check output should be an array");
     assert!(
-        stdout.contains("\"MODULE\""),
+        diagnostics.iter().any(|d| d["code"] == "MODULE"),
         "JSON output should contain MODULE error category, got: {}",
+        json
+    );
+}
+
+#[test]
+fn test_cli_check_unused_variable_warning() {
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(fixture("unused_variable.forma"))
+        .output()
+        .expect("failed to execute forma");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "warnings alone should not fail check, got: {}",
         stdout
     );
     assert!(
-        stdout.contains("\"errors\""),
-        "JSON output should contain errors key, got: {}",
+        stdout.contains("Warning") && stdout.contains("unused variable `unused`"),
+        "expected an unused variable warning, got: {}",
+        stdout
+    );
+    assert!(!stdout.contains("`total`"), "got: {}", stdout);
+
+    let output = Command::new(forma_bin())
+        .args(["check", "--deny-warnings"])
+        .arg(fixture("unused_variable.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        !output.status.success(),
+        "--deny-warnings should turn warnings into a failure"
+    );
+
+    let output = Command::new(forma_bin())
+        .args(["--error-format", "json", "check"])
+        .arg(fixture("unused_variable.forma"))
+        .output()
+        .expect("failed to execute forma");
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("check output should be JSON");
+    assert!(output.status.success());
+    assert_eq!(json[0]["level"], "warning");
+    assert_eq!(json[0]["code"], "UNUSED");
+    assert_eq!(json[0]["line"], 2);
+}
+
+#[test]
+fn test_cli_check_lint_levels() {
+    let check = |flags: &[&str]| {
+        Command::new(forma_bin())
+            .arg("check")
+            .args(flags)
+            .arg(fixture("unused_variable.forma"))
+            .output()
+            .expect("failed to execute forma")
+    };
+
+    let output = check(&["--deny", "unused_variable"]);
+    assert!(
+        !output.status.success(),
+        "--deny unused_variable should fail on an unused variable"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("unused variable `unused`"),
+        "got: {}",
+        stdout
+    );
+
+    // Denying a lint that does not fire still passes
+    assert!(check(&["-D", "dead_code"]).status.success());
+
+    let output = check(&["--allow", "unused_variable", "--deny-warnings"]);
+    assert!(output.status.success(), "allowed lints are not denied");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("unused variable"), "got: {}", stdout);
+
+    let output = check(&["--warn", "unused_variables"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown lint 'unused_variables'"),
+        "got: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_check_json_array() {
+    let output = Command::new(forma_bin())
+        .args(["--error-format", "json", "check"])
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success(), "clean check should exit 0");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json, serde_json::json!([]));
+
+    let output = Command::new(forma_bin())
+        .args(["--error-format", "json", "check"])
+        .arg(fixture("type_error.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = json.as_array().expect("check output should be an array");
+    let error = diagnostics
+        .iter()
+        .find(|d| d["level"] == "error")
+        .expect("expected an error diagnostic");
+    assert!(
+        error["file"]
+            .as_str()
+            .unwrap()
+            .ends_with("type_error.forma")
+    );
+    assert!(error["message"].as_str().unwrap().contains("type mismatch"));
+    for field in ["line", "column", "end_line", "end_column"] {
+        assert!(error[field].is_u64(), "missing {}: {}", field, error);
+    }
+}
+
+#[test]
+fn test_cli_check_columns_after_emoji() {
+    // `missing` follows a two-emoji string literal; columns count chars, not bytes
+    let output = Command::new(forma_bin())
+        .args(["--error-format", "json", "check"])
+        .arg(fixture("emoji_error.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let error = &json[0];
+    assert_eq!(error["line"], 2);
+    assert_eq!(error["column"], 17);
+    assert_eq!(error["end_column"], 24);
+
+    let output = Command::new(forma_bin())
+        .args(["check"])
+        .arg(fixture("emoji_error.forma"))
+        .output()
+        .expect("failed to execute forma");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("emoji_error.forma:2:17"),
+        "stdout: {}",
         stdout
     );
 }
@@ -535,23 +1314,30 @@ fn assert_json_error(cmd_args: &[&str], fixture_name: &str, expected_code: &str)
     );
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(
-        stdout.contains("\"success\":false") || stdout.contains("\"success\": false"),
-        "{:?} {} JSON should contain success:false, got: {}",
-        cmd_args,
-        fixture_name,
-        stdout
-    );
-    assert!(
-        stdout.contains("\"errors\""),
-        "{:?} {} JSON should contain errors key, got: {}",
-        cmd_args,
-        fixture_name,
-        stdout
-    );
-    assert!(
-        stdout.contains(&format!("\"{}\"", expected_code)),
-        "{:?} {} JSON should contain code \"{}\", got: {}",
+    let json: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|_| panic!("{:?} {} should emit JSON", cmd_args, fixture_name));
+    // `check` emits a bare array of diagnostics; other commands wrap it
+    let diagnostics = if cmd_args[0] == "check" {
+        &json
+    } else {
+        assert_eq!(
+            json["success"], false,
+            "{:?} {} JSON should contain success:false, got: {}",
+            cmd_args, fixture_name, stdout
+        );
+        &json["errors"]
+    };
+    let diagnostics = diagnostics.as_array().unwrap_or_else(|| {
+        panic!(
+            "{:?} {} JSON should contain a diagnostics array, got: {}",
+            cmd_args, fixture_name, stdout
+        )
+    });
+    assert!(
+        diagnostics
+            .iter()
+            .any(|d| d["code"] == expected_code && d["level"] == "error"),
+        "{:?} {} JSON should contain an error with code \"{}\", got: {}",
         cmd_args,
         fixture_name,
         expected_code,
@@ -662,16 +1448,13 @@ fn test_cli_check_partial_missing_import_json() {
         !output.status.success(),
         "check --partial --error-format json missing_import.forma should exit nonzero"
     );
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("check output should be JSON");
+    let diagnostics = json.as_array().expect("check output should be an array");
     assert!(
-        stdout.contains("\"MODULE\""),
+        diagnostics.iter().any(|d| d["code"] == "MODULE"),
         "JSON should contain MODULE error code, got: {}",
-        stdout
-    );
-    assert!(
-        stdout.contains("\"errors\""),
-        "JSON should contain errors key, got: {}",
-        stdout
+        json
     );
 }
 
@@ -875,3 +1658,468 @@ fn test_cli_option_chaining() {
         stdout
     );
 }
+
+// ---- LSP integration ----
+
+/// Frame a JSON-RPC message with the LSP `Content-Length` header.
+fn lsp_frame(message: &serde_json::Value) -> Vec<u8> {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+/// Read one framed JSON-RPC message from the server.
+fn read_lsp_message(reader: &mut impl std::io::BufRead) -> Option<serde_json::Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(len) = line.strip_prefix("Content-Length: ") {
+            content_length = len.parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0u8; content_length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+#[test]
+fn test_cli_lsp_publishes_diagnostics() {
+    use std::io::{BufReader, Write};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let mut child = Command::new(forma_bin())
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start forma lsp");
+
+    let stdout = child.stdout.take().expect("lsp stdout");
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        while let Some(message) = read_lsp_message(&mut reader) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let source = std::fs::read_to_string(fixture("type_error.forma")).unwrap();
+    let mut stdin = child.stdin.take().expect("lsp stdin");
+    stdin
+        .write_all(&lsp_frame(&serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "capabilities": {} }
+        })))
+        .unwrap();
+    stdin.flush().unwrap();
+
+    // Notifications sent before the initialize response are dropped by the server.
+    while let Ok(message) = rx.recv_timeout(Duration::from_secs(10)) {
+        if message["id"] == 1 {
+            break;
+        }
+    }
+
+    for message in [
+        serde_json::json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        serde_json::json!({
+            "jsonrpc": "2.0", "method": "textDocument/didOpen",
+            "params": { "textDocument": {
+                "uri": "file:///type_error.forma", "languageId": "forma",
+                "version": 1, "text": source
+            }}
+        }),
+    ] {
+        stdin.write_all(&lsp_frame(&message)).unwrap();
+    }
+    stdin.flush().unwrap();
+
+    let mut diagnostics = None;
+    while let Ok(message) = rx.recv_timeout(Duration::from_secs(10)) {
+        if message["method"] == "textDocument/publishDiagnostics" {
+            diagnostics = Some(message["params"]["diagnostics"].clone());
+            break;
+        }
+    }
+
+    let _ = stdin.write_all(&lsp_frame(&serde_json::json!({
+        "jsonrpc": "2.0", "id": 2, "method": "shutdown"
+    })));
+    let _ = stdin.write_all(&lsp_frame(&serde_json::json!({
+        "jsonrpc": "2.0", "method": "exit"
+    })));
+    drop(stdin);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    let diagnostics = diagnostics.expect("server should publish diagnostics after didOpen");
+    let diagnostics = diagnostics
+        .as_array()
+        .expect("diagnostics should be an array");
+    assert!(
+        diagnostics.iter().any(|d| d["code"] == "TYPE"),
+        "type error document should produce a TYPE diagnostic, got: {:?}",
+        diagnostics
+    );
+}
+
+#[test]
+fn test_cli_check_watch_rechecks_on_change() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("watched.forma");
+    std::fs::write(&file, "f main() -> Int\n    42\n").unwrap();
+
+    let mut child = Command::new(forma_bin())
+        .args(["check", "--watch"])
+        .arg(&file)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start forma check --watch");
+
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let tx_err = tx.clone();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = tx.send(line);
+        }
+    });
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            let _ = tx_err.send(line);
+        }
+    });
+
+    let wait_for = |needle: &str| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match rx.recv_timeout(remaining) {
+                Ok(line) if line.contains(needle) => return true,
+                Ok(_) => continue,
+                Err(_) => return false,
+            }
+        }
+        false
+    };
+
+    let first_check = wait_for("No errors found");
+    // Make sure the rewrite lands on a different modification time
+    std::thread::sleep(Duration::from_millis(50));
+    std::fs::write(&file, "f main() -> Int\n    \"not an int\"\n").unwrap();
+    let recheck = wait_for("type mismatch");
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(first_check, "initial check should succeed");
+    assert!(
+        recheck,
+        "modifying the watched file should trigger a re-check"
+    );
+}
+
+#[test]
+fn test_cli_check_multiple_files_json() {
+    // Pass the failing file in the middle to show ordering is by file name
+    let output = Command::new(forma_bin())
+        .args(["--error-format", "json", "check"])
+        .arg(fixture("with_contracts.forma"))
+        .arg(fixture("type_error.forma"))
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        !output.status.success(),
+        "check should exit nonzero when any file has errors"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let json: serde_json::Value =
+        serde_json::from_str(&stdout).expect("output should be valid JSON");
+    let errors = json.as_array().expect("errors array");
+    assert!(!errors.is_empty(), "expected diagnostics, got: {}", stdout);
+    for error in errors {
+        assert!(
+            error["file"]
+                .as_str()
+                .unwrap()
+                .ends_with("type_error.forma"),
+            "error attributed to the wrong file: {}",
+            error
+        );
+        assert_eq!(error["code"], "TYPE");
+    }
+}
+
+#[test]
+fn test_cli_check_multiple_files_human() {
+    let run = || {
+        Command::new(forma_bin())
+            .args(["check"])
+            .arg(fixture("type_error.forma"))
+            .arg(fixture("hello.forma"))
+            .arg(fixture("with_contracts.forma"))
+            .output()
+            .expect("failed to execute forma")
+    };
+    let output = run();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Checked 3 file(s): 1 with errors"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("type_error.forma:1:1"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(!stdout.contains("hello.forma:"), "stdout: {}", stdout);
+    // Output is stable across runs regardless of thread scheduling
+    assert_eq!(stdout, String::from_utf8_lossy(&run().stdout));
+}
+
+#[test]
+fn test_cli_check_more_files_than_workers() {
+    // Far more files than cores, so workers must each take several
+    let dir = tempfile::tempdir().unwrap();
+    let mut files = Vec::new();
+    for i in 0..200 {
+        let path = dir.path().join(format!("file_{:03}.forma", i));
+        let body = if i == 150 { "\"oops\"" } else { "0" };
+        std::fs::write(&path, format!("f main() -> Int\n    {}\n", body)).unwrap();
+        files.push(path);
+    }
+    let output = Command::new(forma_bin())
+        .arg("check")
+        .args(&files)
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Checked 200 file(s): 1 with errors"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(stdout.contains("file_150.forma:1:1"), "stdout: {}", stdout);
+}
+
+#[test]
+fn test_cli_run_timings() {
+    let output = Command::new(forma_bin())
+        .args(["run", "--timings"])
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for phase in [
+        "lex",
+        "parse",
+        "imports",
+        "typecheck",
+        "borrowck",
+        "lower",
+        "optimize",
+        "interpret",
+        "total",
+    ] {
+        let line = stderr
+            .lines()
+            .find(|line| line.split_whitespace().next() == Some(phase))
+            .unwrap_or_else(|| panic!("missing phase '{}' in: {}", phase, stderr));
+        let ms: f64 = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+        assert!(ms >= 0.0, "negative time for '{}': {}", phase, line);
+    }
+    // Timings go to stderr and leave program output untouched
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+}
+
+#[test]
+fn test_cli_run_timings_json_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = dir.path().join("timings.json");
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(format!("--timings={}", report.display()))
+        .arg(fixture("hello.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(output.status.success());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let phases: Vec<&str> = json["phases"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| {
+            assert!(p["ms"].as_f64().unwrap() >= 0.0);
+            p["phase"].as_str().unwrap()
+        })
+        .collect();
+    assert_eq!(
+        phases,
+        [
+            "lex",
+            "parse",
+            "imports",
+            "typecheck",
+            "borrowck",
+            "lower",
+            "optimize",
+            "interpret"
+        ]
+    );
+    assert!(json["total_ms"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn test_cli_build_timings_reports_phases_before_failure() {
+    // Type errors stop the build, but the phases that ran are still reported
+    let output = Command::new(forma_bin())
+        .args(["build", "--timings"])
+        .arg(fixture("type_error.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Timings for"), "stderr: {}", stderr);
+    assert!(stderr.contains("typecheck"), "stderr: {}", stderr);
+    assert!(!stderr.contains("  lower "), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_cli_build_code_model_option() {
+    let build = |flags: &[&str]| {
+        Command::new(forma_bin())
+            .arg("build")
+            .args(flags)
+            .arg(fixture("type_error.forma"))
+            .output()
+            .expect("failed to execute forma")
+    };
+    // Accepted options get as far as the type error
+    for model in ["small", "kernel", "medium", "large"] {
+        let output = build(&["--code-model", model, "--relocation-model", "pic"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!stderr.contains("invalid value"), "{}: {}", model, stderr);
+        assert_eq!(output.status.code(), Some(1), "{}: {}", model, stderr);
+    }
+    let output = build(&["--code-model", "tiny"]);
+    assert_eq!(
+        output.status.code(),
+        Some(2),
+        "clap should reject the value"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid value 'tiny'"), "{}", stderr);
+}
+
+#[test]
+#[cfg(feature = "llvm")]
+fn test_cli_build_incremental_reuses_object() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("main.forma");
+    let cache = dir.path().join("cache");
+    std::fs::write(&src, "f main() -> Int = 0\n").unwrap();
+
+    let build = || {
+        Command::new(forma_bin())
+            .arg("build")
+            .arg("--incremental")
+            .arg(&cache)
+            .arg(&src)
+            .output()
+            .expect("failed to execute forma");
+    };
+    // The object is cached before linking, so this works without the runtime
+    let cached_objects = || {
+        let mut objects = vec![];
+        for entry in std::fs::read_dir(&cache).unwrap().flatten() {
+            for object in std::fs::read_dir(entry.path()).unwrap().flatten() {
+                let path = object.path();
+                let mtime = object.metadata().unwrap().modified().unwrap();
+                objects.push((path, mtime));
+            }
+        }
+        objects
+    };
+
+    build();
+    let first = cached_objects();
+    assert_eq!(first.len(), 1, "one object should be cached: {:?}", first);
+
+    // An unchanged source reuses the cached object untouched
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    build();
+    assert_eq!(cached_objects(), first);
+
+    // Editing the source recompiles and replaces the entry
+    std::fs::write(&src, "f main() -> Int = 1\n").unwrap();
+    build();
+    let edited = cached_objects();
+    assert_eq!(edited.len(), 1, "{:?}", edited);
+    assert_ne!(edited[0].0, first[0].0);
+}
+
+#[test]
+fn test_cli_run_index_out_of_bounds() {
+    let output = Command::new(forma_bin())
+        .arg("run")
+        .arg(fixture("index_oob.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("index 5 out of bounds for length 3"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+#[cfg(feature = "llvm")]
+fn test_cli_build_bounds_checks_abort_on_out_of_bounds_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = dir.path().join("index_oob");
+    let output = Command::new(forma_bin())
+        .args(["build", "--bounds-checks", "-o"])
+        .arg(&exe)
+        .arg(fixture("index_oob.forma"))
+        .output()
+        .expect("failed to execute forma");
+    assert!(
+        output.status.success(),
+        "build failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(&exe).output().expect("failed to run program");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("panic: index 5 out of bounds for length 3 at"),
+        "stderr: {}",
+        stderr
+    );
+}
@@ -1,7 +1,7 @@
 //! Tests for FORMA error diagnostic types.
 
 use forma::Span;
-use forma::errors::{Diagnostic, Label, LabelStyle, Severity};
+use forma::errors::{Diagnostic, Label, LabelStyle, Severity, span_end};
 use forma::errors::{LexError, ParseError, TypeError};
 
 // ============================================================================
@@ -93,3 +93,26 @@ fn test_compile_error_from_parse() {
     assert!(matches!(ce, forma::CompileError::Parse(_)));
     assert_eq!(ce.help(), Some("add closing brace"));
 }
+
+// ============================================================================
+// Column Reporting
+// ============================================================================
+
+#[test]
+fn test_span_end_counts_chars_after_multibyte() {
+    let source = "x := \"😀\" + yy";
+    let start = source.find("yy").unwrap();
+    let span = Span::new(start, start + 2, 1, 13);
+    assert_eq!(span_end(source, span), (1, 15));
+
+    let span = Span::new(5, 11, 1, 6);
+    assert_eq!(span_end(source, span), (1, 9));
+}
+
+#[test]
+fn test_span_end_multiline_and_out_of_range() {
+    let source = "a\n😀b";
+    assert_eq!(span_end(source, Span::new(0, 7, 1, 1)), (2, 3));
+    // Spans from another file fall back to byte arithmetic
+    assert_eq!(span_end(source, Span::new(40, 44, 3, 2)), (3, 6));
+}
@@ -90,3 +90,45 @@ fn test_format_idempotence() {
     let second = format_source(&first);
     assert_eq!(first, second, "formatting should be idempotent");
 }
+
+#[test]
+fn test_format_loop_invariant() {
+    let source = "f count() -> Int\n    i := 0\n    @invariant(i <= 3, \"bounded\")\n    wh i < 3\n        i = i + 1\n    i";
+    let formatted = format_source(source);
+    assert!(
+        formatted.contains("    @invariant(i <= 3, \"bounded\")\n    wh i < 3"),
+        "{}",
+        formatted
+    );
+}
+
+#[test]
+fn test_format_extern_function() {
+    let source = "extern \"C\" f labs(x: Int) -> Int\n";
+    assert_eq!(format_source(source), source);
+}
+
+fn format_with_comments(source: &str) -> String {
+    let scanner = Scanner::new(source);
+    let (tokens, _, comments) = scanner.scan_all_with_comments();
+    let ast = Parser::new(&tokens).parse().expect("parse should succeed");
+    Formatter::new().with_comments(comments).format(&ast)
+}
+
+#[test]
+fn test_format_keeps_comments() {
+    let source =
+        "# about add\nf add(a: Int, b: Int) -> Int\n    # sum them\n    a + b   # result   \n";
+    let formatted = format_with_comments(source);
+    assert_eq!(
+        formatted,
+        "# about add\nf add(a: Int, b: Int) -> Int\n    # sum them\n    a + b  # result\n"
+    );
+    assert_eq!(format_with_comments(&formatted), formatted);
+}
+
+#[test]
+fn test_format_comment_after_last_item() {
+    let formatted = format_with_comments("f a() -> Int = 1\n# done\n");
+    assert_eq!(formatted, "f a() -> Int = 1\n\n# done\n");
+}
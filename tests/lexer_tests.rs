@@ -1,5 +1,6 @@
 //! Integration tests for the FORMA lexer.
 
+use forma::lexer::IndentPolicy;
 use forma::{Scanner, TokenKind};
 
 fn tokens(source: &str) -> Vec<TokenKind> {
@@ -236,12 +237,89 @@ fn test_raw_strings() {
     );
 }
 
+#[test]
+fn test_quoted_raw_string_keeps_backslashes() {
+    assert_eq!(
+        tokens(r#"r"C:\path\to\file""#)[0],
+        TokenKind::String(r"C:\path\to\file".to_string())
+    );
+    assert_eq!(
+        tokens(r#"r"\d+\.\d+""#)[0],
+        TokenKind::String(r"\d+\.\d+".to_string())
+    );
+}
+
+#[test]
+fn test_hash_delimited_raw_string_with_quotes() {
+    assert_eq!(
+        tokens(r##"r#"quote " inside"#"##)[0],
+        TokenKind::String(r#"quote " inside"#.to_string())
+    );
+    assert_eq!(
+        tokens(r###"r##"ends with "# still"##"###)[0],
+        TokenKind::String(r##"ends with "# still"##.to_string())
+    );
+}
+
+#[test]
+fn test_multiline_string_preserves_newlines() {
+    let toks = tokens("x := \"\"\"first\n  second\n\"third\" line\"\"\"\ny");
+    assert_eq!(
+        toks[2],
+        TokenKind::String("first\n  second\n\"third\" line".to_string())
+    );
+    assert_eq!(
+        tokens(r#""""tab\there""""#)[0],
+        TokenKind::String("tab\there".to_string())
+    );
+    assert_eq!(tokens(r#""""#)[0], TokenKind::String(String::new()));
+}
+
+#[test]
+fn test_unterminated_raw_string_points_at_opening() {
+    for (source, message) in [
+        ("x := r\"open\nmore", "unterminated raw string"),
+        ("x := r#\"open\"\nmore", "unterminated delimited raw string"),
+        ("x := \"\"\"open\nmore", "unterminated multi-line string"),
+    ] {
+        let (_, errors) = Scanner::new(source).scan_all();
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert_eq!(errors[0].message, message);
+        assert_eq!(errors[0].span.line, 1);
+        assert_eq!(errors[0].span.column, 6);
+        assert_eq!(errors[0].span.start, 5);
+    }
+}
+
+#[test]
+fn test_token_positions() {
+    // "é" is two bytes but one column
+    let source = "x := \"é\" + y\nz := 1\n";
+    let (toks, errors) = Scanner::new(source).scan_all();
+    assert!(errors.is_empty());
+
+    let plus = toks.iter().find(|t| t.kind == TokenKind::Plus).unwrap();
+    assert_eq!((plus.line(), plus.column()), (1, 10));
+    assert_eq!((plus.byte_start(), plus.byte_end()), (10, 11));
+
+    let z = toks
+        .iter()
+        .find(|t| t.kind == TokenKind::Ident("z".to_string()))
+        .unwrap();
+    assert_eq!(z.line(), 2);
+    assert_eq!(z.column(), 1);
+    assert_eq!(z.byte_start(), source.find('z').unwrap());
+    assert_eq!(z.byte_end(), z.byte_start() + 1);
+}
+
 #[test]
 fn test_char_literals() {
     assert_eq!(tokens("'a'")[0], TokenKind::Char('a'));
     assert_eq!(tokens("'\\n'")[0], TokenKind::Char('\n'));
     assert_eq!(tokens("'\\t'")[0], TokenKind::Char('\t'));
     assert_eq!(tokens("'\\''")[0], TokenKind::Char('\''));
+    assert_eq!(tokens("'\\u{1F600}'")[0], TokenKind::Char('\u{1F600}'));
+    assert_eq!(tokens("'é'")[0], TokenKind::Char('é'));
 }
 
 #[test]
@@ -345,6 +423,32 @@ fn test_unterminated_char_error() {
     assert!(has_errors("'ab"));
 }
 
+#[test]
+fn test_multi_char_literal_error() {
+    let (tokens, errors) = Scanner::new("'ab' + 1").scan_all();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("exactly one"));
+    // Scanning resumes after the closing quote
+    assert!(tokens.iter().any(|t| t.kind == TokenKind::Int(1)));
+}
+
+#[test]
+fn test_two_char_literals_on_one_line() {
+    let (tokens, errors) = Scanner::new("'a' < 'b'").scan_all();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+    assert_eq!(
+        kinds[..3],
+        [TokenKind::Char('a'), TokenKind::Lt, TokenKind::Char('b')]
+    );
+
+    // An unterminated literal does not swallow a later one on the same line
+    let (tokens, errors) = Scanner::new("'a + f('b')").scan_all();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("unterminated"));
+    assert!(tokens.iter().any(|t| t.kind == TokenKind::Char('b')));
+}
+
 #[test]
 fn test_invalid_escape_error() {
     assert!(has_errors("\"\\z\""));
@@ -396,3 +500,59 @@ fn test_contextual_keywords_as_variables() {
         );
     }
 }
+
+#[test]
+fn test_mixed_tabs_and_spaces_across_lines() {
+    let source = "f a() -> Int\n    1\n\nf b() -> Int\n\t2\n";
+    let (_, errors) = Scanner::new(source).scan_all();
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0]
+            .message
+            .contains("line 5 is indented with tabs, but line 2 uses spaces"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(errors[0].span.line, 5);
+}
+
+#[test]
+fn test_mixed_tabs_and_spaces_within_line() {
+    let (_, errors) = Scanner::new("f a() -> Int\n \t1\n").scan_all();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "inconsistent use of tabs and spaces in indentation"
+    );
+    assert_eq!(errors[0].span.line, 2);
+}
+
+#[test]
+fn test_consistent_indentation_passes() {
+    assert!(!has_errors(
+        "f a() -> Int\n    x := 1\n    if x > 0\n        x\n    else\n        0\n"
+    ));
+    assert!(!has_errors(
+        "f a() -> Int\n\tx := 1\n\tif x > 0\n\t\tx\n\telse\n\t\t0\n"
+    ));
+}
+
+#[test]
+fn test_indent_policy() {
+    let tabs = "f a() -> Int\n\t1\n";
+    let spaces = "f a() -> Int\n    1\n";
+    let errors = |source: &str, policy| {
+        let (_, errors) = Scanner::new(source).with_indent_policy(policy).scan_all();
+        errors
+    };
+    assert_eq!(
+        errors(tabs, IndentPolicy::SpacesOnly)[0].message,
+        "indentation must use spaces, found tabs"
+    );
+    assert!(errors(spaces, IndentPolicy::SpacesOnly).is_empty());
+    assert_eq!(
+        errors(spaces, IndentPolicy::TabsOnly)[0].message,
+        "indentation must use tabs, found spaces"
+    );
+    assert!(errors(tabs, IndentPolicy::TabsOnly).is_empty());
+}
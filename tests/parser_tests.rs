@@ -259,6 +259,95 @@ fn test_logical_expressions() {
     }
 }
 
+/// Render an expression with every binary and unary operation parenthesized.
+fn grouping(e: &Expr) -> String {
+    match &e.kind {
+        ExprKind::Binary(l, op, r) => format!("({} {:?} {})", grouping(l), op, grouping(r)),
+        ExprKind::Unary(op, inner) => format!("({:?} {})", op, grouping(inner)),
+        ExprKind::Cast(inner, _) => format!("({} as _)", grouping(inner)),
+        ExprKind::Ident(name) => name.name.clone(),
+        ExprKind::Field(base, field) => format!("{}.{}", grouping(base), field.name),
+        ExprKind::Literal(Literal {
+            kind: LiteralKind::Int(n),
+            ..
+        }) => n.to_string(),
+        other => panic!("unexpected expression {:?}", other),
+    }
+}
+
+fn parse_grouping(expr: &str) -> String {
+    let ast = parse_ok(&format!("f test -> Int = {}", expr));
+    match &ast.items[0].kind {
+        ItemKind::Function(f) => match &f.body {
+            Some(FnBody::Expr(e)) => grouping(e),
+            _ => panic!("expected expression body"),
+        },
+        _ => panic!("expected function"),
+    }
+}
+
+#[test]
+fn test_operator_precedence_and_associativity() {
+    assert_eq!(parse_grouping("2 + 3 * 4"), "(2 Add (3 Mul 4))");
+    assert_eq!(parse_grouping("a - b - c"), "((a Sub b) Sub c)");
+    assert_eq!(parse_grouping("a / b * c % d"), "(((a Div b) Mul c) Mod d)");
+    assert_eq!(parse_grouping("a << 1 + b"), "(a Shl (1 Add b))");
+    assert_eq!(
+        parse_grouping("a & b ^ c & d"),
+        "((a BitAnd b) BitXor (c BitAnd d))"
+    );
+    assert_eq!(
+        parse_grouping("a ^ b == c || d && e < f"),
+        "(((a BitXor b) Eq c) Or (d And (e Lt f)))"
+    );
+    assert_eq!(parse_grouping("-a * b"), "((Neg a) Mul b)");
+    assert_eq!(parse_grouping("a * b as Int"), "(a Mul (b as _))");
+
+    // Comparisons do not chain
+    assert!(parse_err("f test -> Bool = 1 < a < 3"));
+    assert!(parse_err("f test -> Bool = a && b == c != d"));
+}
+
+#[test]
+fn test_unary_operator_precedence() {
+    // Prefix operators nest and bind tighter than any binary operator
+    assert_eq!(parse_grouping("--x"), "(Neg (Neg x))");
+    assert_eq!(parse_grouping("!!b"), "(Not (Not b))");
+    assert_eq!(parse_grouping("+x"), "(Plus x)");
+    assert_eq!(parse_grouping("-+x"), "(Neg (Plus x))");
+    assert_eq!(parse_grouping("-2 * 3"), "((Neg 2) Mul 3)");
+    assert_eq!(parse_grouping("!a == b"), "((Not a) Eq b)");
+    assert_eq!(parse_grouping("+x * -y"), "((Plus x) Mul (Neg y))");
+    assert_eq!(parse_grouping("a - -b"), "(a Sub (Neg b))");
+    assert_eq!(parse_grouping("a + +b"), "(a Add (Plus b))");
+    assert_eq!(parse_grouping("-x.len"), "(Neg x.len)");
+    // Casts apply to the whole prefix expression
+    assert_eq!(parse_grouping("-x as Int"), "((Neg x) as _)");
+}
+
+#[test]
+fn test_precedence_table() {
+    use forma::lexer::TokenKind;
+    use forma::parser::precedence::{Assoc, PREFIX_PRECEDENCE, binary_op};
+
+    let info = |kind: TokenKind| binary_op(&kind).expect("binary operator");
+    assert!(info(TokenKind::PipePipe).precedence < info(TokenKind::AmpAmp).precedence);
+    assert!(info(TokenKind::AmpAmp).precedence < info(TokenKind::Lt).precedence);
+    assert!(info(TokenKind::Lt).precedence < info(TokenKind::Plus).precedence);
+    assert!(info(TokenKind::Plus).precedence < info(TokenKind::Star).precedence);
+    assert_eq!(
+        info(TokenKind::Star).precedence,
+        info(TokenKind::Percent).precedence
+    );
+    assert!(info(TokenKind::Star).precedence < PREFIX_PRECEDENCE);
+    assert_eq!(info(TokenKind::Minus).assoc, Assoc::Left);
+    assert_eq!(info(TokenKind::EqEq).assoc, Assoc::None);
+    assert_eq!(info(TokenKind::Minus).op, BinOp::Sub);
+    // `|` is the pipeline operator, not bitwise or
+    assert!(binary_op(&TokenKind::Pipe).is_none());
+    assert!(binary_op(&TokenKind::Bang).is_none());
+}
+
 #[test]
 fn test_unary_expressions() {
     let ast = parse_ok("f test -> Int = -x");
@@ -394,6 +483,44 @@ fn test_cast_expression() {
     }
 }
 
+#[test]
+fn test_cast_as_binds_tighter_than_binary() {
+    let ast = parse_ok("f test(a: Int, b: i32) -> Int = a + b as Int");
+    if let ItemKind::Function(f) = &ast.items[0].kind
+        && let Some(FnBody::Expr(e)) = &f.body
+    {
+        let ExprKind::Binary(_, _, rhs) = &e.kind else {
+            panic!("expected a binary expression, got {:?}", e.kind);
+        };
+        assert!(matches!(rhs.kind, ExprKind::Cast(_, _)));
+    } else {
+        panic!("expected an expression-bodied function");
+    }
+}
+
+#[test]
+fn test_assert_statement() {
+    let ast =
+        parse_ok("f check(x: Int)\n    assert x > 0, \"x must be positive\"\n    assert(x < 10)\n");
+    let ItemKind::Function(f) = &ast.items[0].kind else {
+        panic!("expected a function");
+    };
+    let Some(FnBody::Block(block)) = &f.body else {
+        panic!("expected a block body");
+    };
+    for (stmt, arity) in block.stmts.iter().zip([2, 1]) {
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::Call(callee, args),
+            ..
+        }) = &stmt.kind
+        else {
+            panic!("expected an assert call, got {:?}", stmt.kind);
+        };
+        assert!(matches!(&callee.kind, ExprKind::Ident(id) if id.name == "assert"));
+        assert_eq!(args.len(), arity);
+    }
+}
+
 #[test]
 fn test_cast_float_to_int() {
     let ast = parse_ok("f test -> i32 = i32(3.14)");
@@ -404,6 +531,46 @@ fn test_cast_float_to_int() {
     }
 }
 
+#[test]
+fn test_trailing_commas() {
+    // Each pair parses to the same tree, so it formats the same
+    let cases = [
+        (
+            "f add(a: Int, b: Int,) -> Int = a + b",
+            "f add(a: Int, b: Int) -> Int = a + b",
+        ),
+        ("f t -> Int = add(1, 2,)", "f t -> Int = add(1, 2)"),
+        ("f t -> Int = p.add(1,)", "f t -> Int = p.add(1)"),
+        (
+            "f t -> Int = add(\n    1,\n    2,\n)",
+            "f t -> Int = add(1, 2)",
+        ),
+        ("f t -> [Int] = [1, 2,]", "f t -> [Int] = [1, 2]"),
+        ("f t -> (Int, Int) = (1, 2,)", "f t -> (Int, Int) = (1, 2)"),
+        ("s P { x: Int, y: Int, }", "s P { x: Int, y: Int }"),
+        ("s P(Int, Int,)", "s P(Int, Int)"),
+        (
+            "f t -> P = P { x: 1, y: 2, }",
+            "f t -> P = P { x: 1, y: 2 }",
+        ),
+        ("e E\n    A(Int, Int,)", "e E\n    A(Int, Int)"),
+        ("f id[T,](x: T) -> T = x", "f id[T](x: T) -> T = x"),
+        ("f t = map(xs, |a, b,| a)", "f t = map(xs, |a, b| a)"),
+    ];
+    for (with_comma, without) in cases {
+        let format = |source: &str| forma::Formatter::new().format(&parse_ok(source));
+        assert_eq!(format(with_comma), format(without), "{}", with_comma);
+    }
+
+    // A comma needs an element before it
+    assert!(parse_err("f t -> Int = add(,a)"));
+    assert!(parse_err("f t -> Int = add(a,,)"));
+    assert!(parse_err("f t -> Int = add(,)"));
+    assert!(parse_err("f add(, a: Int) -> Int = a"));
+    assert!(parse_err("f t -> [Int] = [,]"));
+    assert!(parse_err("f t -> (Int, Int) = (,)"));
+}
+
 // ============================================================================
 // Pattern Parsing
 // ============================================================================
@@ -632,6 +799,43 @@ f bad(result: [Int]) -> [Int] = result
     ));
 }
 
+#[test]
+fn test_section_attribute() {
+    let ast = parse_ok("@section(\".text.boot\")\nf boot() -> Int = 0\n");
+    assert_eq!(ast.items[0].attrs[0].name.name, "section");
+    assert!(parse_err("@section(\"\")\nf boot() -> Int = 0\n"));
+    assert!(parse_err("@section(\".text boot\")\nf boot() -> Int = 0\n"));
+    assert!(parse_err("@section(boot)\nf boot() -> Int = 0\n"));
+    assert!(parse_err("@section\nf boot() -> Int = 0\n"));
+    assert!(parse_err("@section(\".a\", \".b\")\nf boot() -> Int = 0\n"));
+}
+
+#[test]
+fn test_visibility_attribute() {
+    let ast = parse_ok("@visibility(\"hidden\")\npub f helper() -> Int = 0\n");
+    assert_eq!(ast.items[0].attrs[0].name.name, "visibility");
+    parse_ok("@visibility(\"default\")\nf helper() -> Int = 0\n");
+    assert!(parse_err(
+        "@visibility(\"protected\")\nf helper() -> Int = 0\n"
+    ));
+    assert!(parse_err("@visibility(hidden)\nf helper() -> Int = 0\n"));
+    assert!(parse_err("@visibility\nf helper() -> Int = 0\n"));
+}
+
+#[test]
+fn test_callconv_attribute() {
+    let ast = parse_ok("@callconv(\"fast\")\nf step(x: Int) -> Int = x + 1\n");
+    assert_eq!(ast.items[0].attrs[0].name.name, "callconv");
+    parse_ok("@callconv(\"C\")\nextern \"C\" f labs(x: Int) -> Int\n");
+    assert!(parse_err(
+        "@callconv(\"stdcall\")\nf step(x: Int) -> Int = x + 1\n"
+    ));
+    assert!(parse_err(
+        "@callconv(fast)\nf step(x: Int) -> Int = x + 1\n"
+    ));
+    assert!(parse_err("@callconv\nf step(x: Int) -> Int = x + 1\n"));
+}
+
 // ============================================================================
 // Error Cases
 // ============================================================================
@@ -683,7 +887,8 @@ fn test_double_arrow() {
 
 #[test]
 fn test_invalid_operator_sequence() {
-    parse_should_fail("f test -> Int\n    1 + + 2");
+    // `1 + + 2` is valid now that `+` is also a prefix operator
+    parse_should_fail("f test -> Int\n    1 * / 2");
 }
 
 #[test]
@@ -705,3 +910,140 @@ fn test_struct_missing_field_type() {
 fn test_duplicate_arrow_in_fn_type() {
     parse_should_fail("f test -> (Int) -> -> Int\n    42");
 }
+
+#[test]
+fn test_loop_invariants() {
+    let ast = parse(
+        "f test() -> Int\n    i := 0\n    @invariant(i >= 0)\n    @invariant(i <= 10, \"bounded\")\n    wh i < 10\n        i = i + 1\n    i",
+    )
+    .expect("parse should succeed");
+    let ItemKind::Function(func) = &ast.items[0].kind else {
+        panic!("expected function");
+    };
+    let Some(FnBody::Block(body)) = &func.body else {
+        panic!("expected block body");
+    };
+    let StmtKind::Expr(expr) = &body.stmts[1].kind else {
+        panic!("expected loop statement");
+    };
+    let ExprKind::While(_, _, _, invariants) = &expr.kind else {
+        panic!("expected while loop");
+    };
+    assert_eq!(invariants.len(), 2);
+    assert_eq!(invariants[1].message.as_deref(), Some("bounded"));
+}
+
+#[test]
+fn test_invariant_requires_loop() {
+    parse_should_fail("f test() -> Int\n    x := 0\n    @invariant(x >= 0)\n    x");
+}
+
+#[test]
+fn test_extern_function_declaration() {
+    let ast = parse_ok("extern \"C\" f forma_map_get(m: *Int, key: Str) -> Str\n");
+    let ItemKind::Function(func) = &ast.items[0].kind else {
+        panic!("expected function");
+    };
+    assert_eq!(func.name.name, "forma_map_get");
+    assert_eq!(func.abi.as_deref(), Some("C"));
+    assert_eq!(func.params.len(), 2);
+    assert!(func.body.is_none());
+}
+
+#[test]
+fn test_extern_function_rejects_body_and_other_abis() {
+    parse_should_fail("extern \"C\" f ext(x: Int) -> Int = x");
+    parse_should_fail("extern \"Rust\" f ext(x: Int) -> Int\n");
+    parse_should_fail("extern \"C\" s Point\n    x: Int\n");
+}
+
+#[test]
+fn test_bodyless_function_requires_extern() {
+    parse_should_fail("f ext(x: Int) -> Int\n");
+}
+
+/// Parse on a thread with a large stack. Unoptimized parser frames are
+/// large, so deep nesting needs more than the default test-thread stack to
+/// reach the parser's limit, as the CLI provides.
+fn parse_with_large_stack(source: String) -> Result<SourceFile, Vec<forma::CompileError>> {
+    std::thread::Builder::new()
+        .stack_size(128 * 1024 * 1024)
+        .spawn(move || parse(&source))
+        .unwrap()
+        .join()
+        .expect("parser should not overflow the stack")
+}
+
+#[test]
+fn test_deeply_nested_parens_report_error() {
+    let depth = 10_000;
+    let source = format!(
+        "f main() -> Int\n    {}1{}\n",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    );
+    let errors = parse_with_large_stack(source).expect_err("deep nesting should be rejected");
+    let message = errors[0].to_string();
+    assert!(
+        message.contains("maximum expression nesting depth exceeded"),
+        "{}",
+        message
+    );
+    match &errors[0] {
+        forma::CompileError::Parse(e) => assert_eq!(e.span.line, 2),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_nesting_within_limit() {
+    let source = format!(
+        "f main() -> Int\n    {}1{}\n",
+        "(".repeat(200),
+        ")".repeat(200)
+    );
+    assert!(parse_with_large_stack(source).is_ok());
+}
+
+#[test]
+fn test_custom_max_depth() {
+    let source = "f main() -> Int\n    ((((1))))\n";
+    let scanner = Scanner::new(source);
+    let (tokens, _) = scanner.scan_all();
+    assert!(Parser::new(&tokens).with_max_depth(3).parse().is_err());
+    assert!(Parser::new(&tokens).with_max_depth(16).parse().is_ok());
+}
+
+#[test]
+fn test_keyword_used_as_identifier() {
+    let keyword_error = |source: &str| match parse(source) {
+        Err(errors) => match &errors[0] {
+            forma::CompileError::Parse(e) => e.clone(),
+            other => panic!("expected a parse error, got {:?}", other),
+        },
+        Ok(_) => panic!("`{}` should not parse", source),
+    };
+
+    let e = keyword_error("f main()\n    ret := 1");
+    assert_eq!(e.message, "keyword `ret` used as identifier");
+    assert_eq!((e.span.line, e.span.column, e.span.len()), (2, 5, 3));
+    assert!(e.help.unwrap().contains("rename it"));
+
+    for source in [
+        "f main()\n    return = 1",
+        "f take(for: Int) -> Int = 0",
+        "f main()\n    for in in [1]\n        print(1)",
+        "s P\n    type: Int",
+    ] {
+        assert!(
+            keyword_error(source)
+                .message
+                .ends_with("used as identifier"),
+            "{}",
+            source
+        );
+    }
+
+    // Single-letter keywords are contextual and stay usable as names
+    parse_ok("f main()\n    m := 1\n    e := 2\n    print(m + e)");
+}
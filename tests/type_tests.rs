@@ -1,6 +1,6 @@
 //! Integration tests for the FORMA type system.
 
-use forma::types::{Ty, TypeId};
+use forma::types::{Ty, TypeId, definition_of, type_at};
 use forma::{Parser, Scanner, TypeChecker};
 
 fn check_source(source: &str) -> Result<forma::types::TypedAst, Vec<forma::types::TypeError>> {
@@ -607,6 +607,34 @@ f test(b: Bool) -> Int
     );
 }
 
+#[test]
+fn test_if_branch_type_mismatch_message() {
+    let errors = check_source(
+        r#"
+f test(b: Bool) -> Int
+    m := if b then 1 else "no"
+    0
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "if and else branches have incompatible types: then is Int, else is Str"
+    );
+}
+
+#[test]
+fn test_if_expression_value() {
+    let result = check_source(
+        r#"
+f max(a: Int, b: Int) -> Int
+    m := if a > b then a else b
+    m
+"#,
+    );
+    assert!(result.is_ok());
+}
+
 #[test]
 fn test_wrong_arg_type() {
     check_should_fail(
@@ -679,6 +707,61 @@ f cast_test() -> i32 = i32(3.14)
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_narrowing_cast_warnings() {
+    let source = r#"
+f widen(x: i32) -> i64 = x as i64
+f narrow(x: i64) -> i8 = x as i8
+f truncate(x: Float) -> Int = x as Int
+f small() -> u8 = 200 as u8
+"#;
+    let (tokens, _) = Scanner::new(source).scan_all();
+    let ast = Parser::new(&tokens).parse().expect("parse should succeed");
+    let mut checker = TypeChecker::new();
+    checker.check(&ast).expect("casts should type check");
+    let messages: Vec<&str> = checker
+        .warnings()
+        .iter()
+        .map(|w| w.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            "cast from i64 to i8 may lose data",
+            "cast from Float to Int may lose data",
+        ]
+    );
+    assert!(
+        checker
+            .warnings()
+            .iter()
+            .all(|w| w.code.as_deref() == Some("NARROWING"))
+    );
+}
+
+#[test]
+fn test_bool_to_int_cast() {
+    check_source("f one() -> Bool = true as i64 == 1 as i64").expect("bool casts to i64");
+    check_source("f truthy(n: Int) -> Bool = n as Bool").expect("ints cast to Bool");
+}
+
+#[test]
+fn test_int_condition_is_type_error() {
+    let errors = check_source("f test(n: Int) -> Int = if 5 then n else 0").unwrap_err();
+    assert!(
+        errors[0]
+            .message
+            .contains("if condition must be Bool, found Int"),
+        "got: {}",
+        errors[0].message
+    );
+    check_should_fail("f test(n: Int)\n    wh n\n        print(n)\n");
+    check_should_fail("f test(n: Int) -> Int\n    m n\n        x if x -> 1\n        _ -> 0\n");
+    // An explicit cast makes the test against zero visible
+    check_source("f test(n: Int) -> Int = if n as Bool then 1 else 0")
+        .expect("cast condition should type check");
+}
+
 #[test]
 fn test_integer_type_i8() {
     // Test that i8 type annotation is accepted in return type
@@ -700,3 +783,647 @@ f test() -> u8 = u8(255)
     );
     assert!(result.is_ok());
 }
+
+// ============================================================================
+// Let Binding Inference
+// ============================================================================
+
+#[test]
+fn test_unannotated_let_inference() {
+    let result = check_source(
+        r#"
+f pick(a: Int, b: Int) -> Int
+    x := a + b
+    flag := a < b
+    if flag then x else 0
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_annotated_let() {
+    let result = check_source(
+        r#"
+f total() -> Int
+    n: Int = 40
+    items: [Int] := []
+    n + vec_len(items)
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_annotated_let_mismatch() {
+    check_should_fail(
+        r#"
+f test() -> Int
+    n: Int = true
+    0
+"#,
+    );
+}
+
+#[test]
+fn test_cannot_infer_empty_list() {
+    let errors = check_source(
+        r#"
+f test() -> Int
+    items := []
+    0
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("cannot infer type for `items`"));
+    assert_eq!(errors[0].span.line, 3);
+}
+
+#[test]
+fn test_cannot_infer_empty_list_that_is_read() {
+    // Reading the list does not constrain its element type
+    let errors = check_source(
+        r#"
+f test() -> Int
+    items := []
+    len(items)
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("cannot infer type for `items`"));
+    assert_eq!(errors[0].span.line, 3);
+
+    let result = check_source(
+        r#"
+f test() -> Int
+    items := []
+    items := vec_push(items, 1)
+    len(items)
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_empty_list_element_inferred_from_later_use() {
+    // The element type is fixed by how the elements are used further down
+    for body in [
+        "    first := items[0] + 1\n    first",
+        "    total := 0\n    for x in items\n        total = total + x\n    total",
+    ] {
+        let source = format!("f test() -> Int\n    items := []\n{}\n", body);
+        let result = check_source(&source);
+        assert!(result.is_ok(), "{}: {:?}", source, result.err());
+    }
+}
+
+// ============================================================================
+// Shadowing and Redefinition
+// ============================================================================
+
+#[test]
+fn test_let_shadowing_allowed() {
+    let result = check_source(
+        r#"
+f describe(n: Int) -> Str
+    x := n
+    x := x * 2
+    x := f"{x}"
+    if n > 0
+        x := "positive"
+        print(x)
+    x
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_duplicate_function_error() {
+    let errors = check_source(
+        r#"
+f add(a: Int, b: Int) -> Int = a + b
+f add(a: Int) -> Int = a
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(errors[0].message, "function `add` is already defined");
+    assert_eq!((errors[0].span.line, errors[0].span.column), (3, 3));
+}
+
+#[test]
+fn test_duplicate_method_error() {
+    let errors = check_source(
+        r#"
+s Counter
+    n: Int
+
+i Counter
+    f get(self) -> Int = self.n
+    f get(self) -> Int = 0
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(errors[0].message, "method `get` is already defined");
+}
+
+#[test]
+fn test_duplicate_parameter_error() {
+    let errors = check_source(
+        r#"
+f add(a: Int, a: Int) -> Int = a + a
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(errors[0].message, "duplicate parameter `a` in `add`");
+    assert_eq!((errors[0].span.line, errors[0].span.column), (2, 15));
+}
+
+// ============================================================================
+// For Loops
+// ============================================================================
+
+#[test]
+fn test_for_range_loop() {
+    let result = check_source(
+        r#"
+f sum_to(n: Int) -> Int
+    total := 0
+    for i in 1..=n
+        total = total + i
+    total
+"#,
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_for_range_variable_scoped_to_body() {
+    check_should_fail(
+        r#"
+f last() -> Int
+    for i in 0..3
+        x := i
+    i
+"#,
+    );
+}
+
+#[test]
+fn test_break_outside_loop() {
+    let errors = check_source(
+        r#"
+f test() -> Int
+    br
+    0
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("outside of a loop"));
+}
+
+#[test]
+fn test_continue_in_closure_inside_loop() {
+    // A closure body is not part of the enclosing loop
+    let errors = check_source(
+        r#"
+f test() -> Int
+    wh true
+        g := |x: Int| -> Int ct
+        br
+    0
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("`ct` outside of a loop"));
+}
+
+#[test]
+fn test_break_and_continue_in_loops() {
+    let result = check_source(
+        r#"
+f test() -> Int
+    i := 0
+    wh true
+        i = i + 1
+        if i % 2 == 0 then ct
+        if i > 5 then br
+    i
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_loop_invariants_are_type_checked() {
+    let errors = check_source(
+        r#"
+f test(n: Int) -> Int
+    i := 0
+    @invariant(i + 1)
+    wh i < n
+        i = i + 1
+    i
+"#,
+    )
+    .unwrap_err();
+    assert!(
+        errors[0]
+            .message
+            .contains("loop invariant condition must be Bool, found Int")
+    );
+
+    let errors = check_source(
+        r#"
+f test(xs: [Int]) -> Int
+    total := 0
+    @invariant(missing >= 0)
+    for x in xs
+        total = total + x
+    total
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("undefined variable: missing"));
+
+    // The loop variable, quantifiers and implications are all in scope
+    let result = check_source(
+        r#"
+f test(xs: [Int]) -> Int
+    total := 0
+    @invariant(forall y in xs: y == y)
+    @invariant(x >= 0 => total >= 0)
+    for x in xs
+        total = total + x
+    total
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+// ============================================================================
+// Match Exhaustiveness
+// ============================================================================
+
+#[test]
+fn test_match_missing_enum_arm() {
+    let errors = check_source(
+        r#"
+e Dir
+    North
+    South
+    East
+
+f code(d: Dir) -> Int
+    m d
+        North -> 1
+        South -> 2
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "non-exhaustive match on `Dir`: missing East"
+    );
+}
+
+#[test]
+fn test_match_missing_option_arm() {
+    let errors = check_source(
+        r#"
+f get(o: Int?) -> Int
+    m o
+        Some(x) -> x
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("missing None"));
+}
+
+#[test]
+fn test_match_exhaustive_enum() {
+    let result = check_source(
+        r#"
+e Shape
+    Circle(Int)
+    Rect(Int, Int)
+    Empty
+
+f area(s: Shape) -> Int
+    m s
+        Circle(r) -> r * r
+        Rect(w, h) -> w * h
+        Empty -> 0
+
+f radius(s: Shape) -> Int
+    m s
+        Circle(r) -> r
+        _ -> 0
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_match_missing_bool_arm() {
+    let errors = check_source(
+        r#"
+f flag(b: Bool) -> Int
+    m b
+        true -> 1
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "non-exhaustive match on `Bool`: missing false"
+    );
+}
+
+#[test]
+fn test_match_exhaustive_bool() {
+    let result = check_source(
+        r#"
+f flag(b: Bool) -> Int
+    m b
+        true -> 1
+        false -> 0
+
+f either(b: Bool) -> Int
+    m b
+        true | false -> 1
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_match_int_needs_wildcard() {
+    let errors = check_source(
+        r#"
+f digit(n: Int) -> Str
+    m n
+        0 -> "zero"
+        1 -> "one"
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("non-exhaustive match on `Int`"));
+}
+
+#[test]
+fn test_match_guarded_arm_not_exhaustive() {
+    check_should_fail(
+        r#"
+f get(o: Int?) -> Int
+    m o
+        Some(x) if x > 0 -> x
+        None -> 0
+"#,
+    );
+}
+
+#[test]
+fn test_match_guard_must_be_bool() {
+    let errors = check_source(
+        r#"
+f pick(x: Int) -> Int
+    m x
+        n if n -> 1
+        _ -> 0
+"#,
+    )
+    .unwrap_err();
+    assert!(
+        errors[0]
+            .message
+            .contains("match guard condition must be Bool")
+    );
+}
+
+#[test]
+fn test_old_outside_postcondition() {
+    let errors = check_source(
+        r#"
+f bump(x: Int) -> Int
+    old(x) + 1
+"#,
+    )
+    .unwrap_err();
+    assert!(errors[0].message.contains("only valid in @post"));
+}
+
+#[test]
+fn test_old_in_postcondition() {
+    let result = check_source(
+        r#"
+@post(result == old(x) + 1)
+f bump(x: Int) -> Int = x + 1
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_extern_function_call() {
+    let result = check_source(
+        r#"
+extern "C" f labs(x: Int) -> Int
+
+f main() -> Int = labs(-3)
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_extern_function_arg_mismatch() {
+    check_should_fail("extern \"C\" f labs(x: Int) -> Int\n\nf main() -> Int = labs(\"x\")\n");
+}
+
+#[test]
+fn test_undeclared_extern_call() {
+    check_should_fail("f main() -> Int = labs(-3)\n");
+}
+
+#[test]
+fn test_function_value_call() {
+    let result = check_source(
+        r#"
+f double(x: Int) -> Int = x * 2
+
+f main() -> Int
+    g := double
+    g(21)
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_function_value_call_mismatch() {
+    check_should_fail(
+        "f double(x: Int) -> Int = x * 2\n\nf main() -> Int\n    g := double\n    g(\"x\")\n",
+    );
+    check_should_fail(
+        "f double(x: Int) -> Int = x * 2\n\nf main() -> Int\n    g := double\n    g(1, 2)\n",
+    );
+}
+
+#[test]
+fn test_named_arguments() {
+    let result = check_source(
+        r#"
+f step(x: Int, by: Int = 1) -> Int = x + by
+
+f main() -> Int = step(by: 2, x: 3) + step(4)
+"#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_named_argument_errors() {
+    let decl = "f step(x: Int, by: Int = 1) -> Int = x + by\n\n";
+    // Missing required argument
+    check_should_fail(&format!("{decl}f main() -> Int = step(by: 2)\n"));
+    // Unknown parameter name
+    check_should_fail(&format!("{decl}f main() -> Int = step(1, size: 2)\n"));
+    // Same parameter given twice
+    check_should_fail(&format!("{decl}f main() -> Int = step(1, x: 2)\n"));
+    // Positional after named
+    check_should_fail(&format!("{decl}f main() -> Int = step(x: 1, 2)\n"));
+    // Named argument of the wrong type
+    check_should_fail(&format!("{decl}f main() -> Int = step(1, by: \"two\")\n"));
+}
+
+#[test]
+fn test_print_format_arguments() {
+    check_source(
+        "f main()\n    print(\"{} and {}\", 1, \"two\")\n    println(\"{{}} {}\", true)\n",
+    )
+    .expect("formatted print should type check");
+    // Placeholder count must match the argument count
+    check_should_fail("f main()\n    print(\"{} and {}\", 1)\n");
+    check_should_fail("f main()\n    print(\"no placeholders\", 1)\n");
+    // The format string must be a literal
+    check_should_fail("f main()\n    fmt := \"{}\"\n    print(fmt, 1)\n");
+    // Only scalars and strings can be formatted
+    check_should_fail("f main()\n    print(\"{}\", [1, 2])\n");
+    // Unbalanced braces
+    check_should_fail("f main()\n    print(\"{ oops\", 1)\n");
+}
+
+#[test]
+fn test_type_alias_in_signature() {
+    let source = "type Celsius = Float\ntype Pair[T] = (T, T)\n\nf warm(c: Celsius) -> Celsius = c + 1.5\n\nf first(p: Pair[Int]) -> Int = p.0\n";
+    check_source(source).expect("aliases should type check");
+    // Aliases are transparent: the signature uses the target types
+    assert_eq!(
+        get_fn_type(source, "warm"),
+        Some(Ty::Fn(vec![Ty::Float], Box::new(Ty::Float)))
+    );
+    assert_eq!(
+        get_fn_type(source, "first"),
+        Some(Ty::Fn(
+            vec![Ty::Tuple(vec![Ty::Int, Ty::Int])],
+            Box::new(Ty::Int)
+        ))
+    );
+    // An alias may name one declared after it
+    check_source("type Grid = [Row]\ntype Row = [Int]\n\nf width(g: Grid) -> Int = len(g[0])\n")
+        .expect("forward alias reference should type check");
+}
+
+#[test]
+fn test_recursive_type_alias() {
+    let errors = check_source("type Loop = [Loop]\n\nf main() -> Int = 0\n").unwrap_err();
+    assert!(
+        errors[0].message.contains("recursive type alias `Loop`"),
+        "got: {}",
+        errors[0].message
+    );
+    check_should_fail("type A = B\ntype B = {Str: A}\n\nf main() -> Int = 0\n");
+}
+
+#[test]
+fn test_type_at_offset() {
+    let source = "f add(a: Int, b: Int) -> Int = a + b\nf scale(x: Float) -> Float = x * 2.0\n";
+    let offset = |needle: &str| source.find(needle).unwrap();
+    assert_eq!(type_at(source, offset("+ b")), Some(Ty::Int));
+    assert_eq!(type_at(source, offset("* 2.0")), Some(Ty::Float));
+    assert_eq!(type_at(source, offset("2.0")), Some(Ty::Float));
+    // Keywords and signatures are not expressions
+    assert_eq!(type_at(source, 0), None);
+    assert_eq!(type_at(source, offset("Int)")), None);
+
+    let source = "f main()\n    count := [1, 2]\n    print(count)\n";
+    let count = source.rfind("count").unwrap();
+    assert_eq!(type_at(source, count), Some(Ty::List(Box::new(Ty::Int))));
+    assert_eq!(type_at("f main(\n", 0), None);
+}
+
+#[test]
+fn test_definition_of() {
+    let source = "f double(n: Int) -> Int = n * 2\nf main()\n    total := double(3)\n    print(total + undefined_name)\n";
+    let span_text = |span: forma::lexer::Span| &source[span.start..span.end];
+
+    // A call resolves to the function's name in its declaration
+    let call = source.find("double(3)").unwrap();
+    let def = definition_of(source, call).unwrap();
+    assert_eq!(def.start, source.find("double").unwrap());
+    assert_eq!(span_text(def), "double");
+
+    // Parameters and locals resolve to their binding sites
+    let param_use = source.find("n * 2").unwrap();
+    assert_eq!(
+        definition_of(source, param_use).unwrap().start,
+        source.find("n: Int").unwrap()
+    );
+    let local_use = source.find("total +").unwrap();
+    assert_eq!(
+        definition_of(source, local_use).unwrap().start,
+        source.find("total :=").unwrap()
+    );
+
+    // Builtins and unknown names do not resolve
+    assert_eq!(definition_of(source, source.find("print").unwrap()), None);
+    assert_eq!(
+        definition_of(source, source.find("undefined_name").unwrap()),
+        None
+    );
+}
+
+#[test]
+fn test_string_indexing() {
+    let result = check_source("f second(s: Str) -> Char = s[1]\nf first() -> Char = \"abc\"[0]");
+    assert!(
+        result.is_ok(),
+        "string indexing should type check: {:?}",
+        result
+    );
+    check_should_fail("f bad(s: Str) -> Str = s[0]");
+    check_should_fail("f bad(s: Str) -> Char = s[\"0\"]");
+}
+
+#[test]
+fn test_byte_strings() {
+    let result = check_source(
+        "f first(data: Bytes) -> u8 = data[0]\nf size() -> Int = bytes_len(b\"\\x00\\x01\\xFF\")",
+    );
+    assert!(
+        result.is_ok(),
+        "byte strings should type check: {:?}",
+        result
+    );
+    check_should_fail("f bad(data: Bytes) -> Int = data[0]");
+    check_should_fail("f bad(data: Bytes) -> Str = data");
+}
+
+#[test]
+fn test_select_builtin() {
+    check_source("f pick(c: Bool, x: Int, y: Int) -> Int = select(c, x, y)")
+        .expect("select should type check");
+    check_should_fail("f bad(c: Bool) -> Int = select(c, 1, \"one\")");
+    check_should_fail("f bad(n: Int) -> Int = select(n, 1, 2)");
+}